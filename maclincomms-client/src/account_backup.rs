@@ -0,0 +1,75 @@
+use std::{fmt, fs};
+
+use crate::{crypto::{account_backup::{open_account_backup, seal_account_backup}, store_seal::StoreKdfParams}, user_model::{AccountBackup, DmChats_Warehouse}};
+
+
+#[derive(Debug)]
+pub enum BackupError {
+    NoAccount, //Nothing logged in on this machine
+    NoLocalChats, //The TUI hasn't saved the DM chats on this machine yet
+    NoPassphrase,
+    Io(std::io::Error),
+    Sealing,
+    WrongPassphrase //Or not a maclincomms backup
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupError::NoAccount => write!(f, "no account on this machine, log in first"),
+            BackupError::NoLocalChats => write!(f, "no DM chats saved on this machine yet, open maclincomms, log in and exit with Ctrl+Q first"),
+            BackupError::NoPassphrase => write!(f, "no backup passphrase given"),
+            BackupError::Io(err) => write!(f, "{}", err),
+            BackupError::Sealing => write!(f, "couldn't seal the backup"),
+            BackupError::WrongPassphrase => write!(f, "wrong passphrase, or not a maclincomms backup")
+        }
+    }
+}
+
+impl From<std::io::Error> for BackupError {
+    fn from(err: std::io::Error) -> Self {
+        BackupError::Io(err)
+    }
+}
+
+
+//`maclincomms export --out backup.mcb`, works offline from what this machine has saved
+pub fn export_account(out_path: &str) -> Result<(), BackupError> {
+    let dmchats_warehouse = DmChats_Warehouse::load_local().ok_or(BackupError::NoLocalChats)?;
+
+    let backup = AccountBackup::collect(dmchats_warehouse).ok_or(BackupError::NoAccount)?;
+    let passphrase = prompt_new_backup_passphrase().ok_or(BackupError::NoPassphrase)?;
+    let contents = seal_account_backup(&passphrase, StoreKdfParams::recommended(), &backup).ok_or(BackupError::Sealing)?;
+
+    fs::write(out_path, contents)?;
+    return Ok(());
+}
+
+//`maclincomms import --in backup.mcb`, replaces the account on this machine
+pub fn import_account(in_path: &str) -> Result<String, BackupError> {
+    let contents = fs::read_to_string(in_path)?;
+    let passphrase = rpassword::prompt_password("Backup passphrase: ").map_err(|_| BackupError::NoPassphrase)?;
+    let backup = open_account_backup(&passphrase, &contents).ok_or(BackupError::WrongPassphrase)?;
+
+    let username = backup.username.clone();
+    backup.restore();
+    return Ok(username);
+}
+
+
+//Its own passphrase, the backup is meant to be opened on another machine
+fn prompt_new_backup_passphrase() -> Option<String> {
+    loop {
+        let passphrase = rpassword::prompt_password("Backup passphrase: ").ok()?;
+        if passphrase.is_empty() {
+            println!("The passphrase can't be empty");
+            continue;
+        }
+        let confirmation = rpassword::prompt_password("Confirm backup passphrase: ").ok()?;
+        if passphrase!=confirmation {
+            println!("Passphrases don't match");
+            continue;
+        }
+        return Some(passphrase);
+    }
+}
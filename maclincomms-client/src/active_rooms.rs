@@ -0,0 +1,206 @@
+use std::{collections::VecDeque, mem, sync::mpsc, time::Instant};
+
+use crate::{
+    screens_model::Screens,
+    tui_main::MaclincommsApp,
+    tui_widgets::roomchat_panel::RoomChatComponents,
+    user_model::{DisconnectType, MessageType, RoomHistoryMessage, RoomMessageType, RoomSenderMessage, RoomSettings, Room_Keys, SharedRoomHistory, SocketMessage, WhisperMode}
+};
+
+
+//Everything the client holds for one joined room
+//The room on screen lives in the app's own room fields, the others wait here with their sockets still open
+pub struct ActiveRoom {
+    pub roomchat_comps: RoomChatComponents,
+    pub is_roomchat_joined: bool,
+    pub outgoing_roomchat_msg_tx: Option<mpsc::Sender<SocketMessage>>,
+    pub room_keys: Room_Keys,
+    pub room_token: String,
+    pub is_current_room_owner: bool,
+    pub current_room_owner: String,
+    pub current_room_settings: RoomSettings,
+    pub last_room_message_at: Option<Instant>,
+    pub room_history: VecDeque<RoomHistoryMessage>,
+    pub room_history_shared: Option<SharedRoomHistory>,
+    pub unread_count: usize //Messages that came in while in the background
+}
+
+impl ActiveRoom {
+    pub fn new() -> Self {
+        Self {
+            roomchat_comps: RoomChatComponents::new(),
+            is_roomchat_joined: false,
+            outgoing_roomchat_msg_tx: None,
+            room_keys: Room_Keys::new(),
+            room_token: "".to_string(),
+            is_current_room_owner: false,
+            current_room_owner: "".to_string(),
+            current_room_settings: RoomSettings::default(),
+            last_room_message_at: None,
+            room_history: VecDeque::new(),
+            room_history_shared: None,
+            unread_count: 0
+        }
+    }
+}
+
+
+//A row of the room list
+pub struct RoomListEntry {
+    pub room_name: String,
+    pub unread_count: usize,
+    pub is_on_screen: bool
+}
+
+
+//Where an event from a room socket goes
+pub enum RoomEventRoute {
+    ON_SCREEN,
+    BACKGROUND(BackgroundRoomEvent),
+    GONE //Left already, leftovers from its socket are dropped
+}
+
+//What to put back once a background room's event is handled
+pub struct BackgroundRoomEvent {
+    on_screen_room: ActiveRoom,
+    screen: Screens,
+    history_len: usize
+}
+
+
+impl MaclincommsApp {
+
+    //Trades the room on screen for the given one
+    fn swap_room(&mut self, room: &mut ActiveRoom) {
+        mem::swap(&mut self.roomchat_comps, &mut room.roomchat_comps);
+        mem::swap(&mut self.is_roomchat_joined, &mut room.is_roomchat_joined);
+        mem::swap(&mut self.outgoing_roomchat_msg_tx, &mut room.outgoing_roomchat_msg_tx);
+        mem::swap(&mut self.room_keys, &mut room.room_keys);
+        mem::swap(&mut self.room_token, &mut room.room_token);
+        mem::swap(&mut self.is_current_room_owner, &mut room.is_current_room_owner);
+        mem::swap(&mut self.current_room_owner, &mut room.current_room_owner);
+        mem::swap(&mut self.current_room_settings, &mut room.current_room_settings);
+        mem::swap(&mut self.last_room_message_at, &mut room.last_room_message_at);
+        mem::swap(&mut self.room_history, &mut room.room_history);
+        mem::swap(&mut self.room_history_shared, &mut room.room_history_shared);
+    }
+
+    //Moves the room on screen to the background, leaving the app's room fields fresh for another room
+    pub fn park_current_room(&mut self) {
+        if !self.is_roomchat_joined {
+            return;
+        }
+        let mut room = ActiveRoom::new();
+        self.swap_room(&mut room);
+        self.active_rooms.insert(room.roomchat_comps.room_name.clone(), room);
+    }
+
+    pub fn is_room_active(&self, room_name: &str) -> bool {
+        (self.is_roomchat_joined && self.roomchat_comps.room_name==room_name) || self.active_rooms.contains_key(room_name)
+    }
+
+    //Brings a background room on screen
+    pub fn switch_to_room(&mut self, room_name: &str) {
+        if let Some(mut room) = self.active_rooms.remove(room_name) {
+            self.park_current_room();
+            self.swap_room(&mut room);
+        }
+        if self.is_roomchat_joined && self.roomchat_comps.room_name==room_name {
+            self.current_screen = Screens::ROOM_CHAT_SCREEN;
+        }
+    }
+
+    //Rooms for the room list, the one on screen first
+    pub fn room_list_entries(&self) -> Vec<RoomListEntry> {
+        let mut entries: Vec<RoomListEntry> = self.active_rooms.iter()
+            .map(|(room_name, room)| RoomListEntry {
+                room_name: room_name.clone(),
+                unread_count: room.unread_count,
+                is_on_screen: false
+            })
+            .collect();
+        entries.sort_by(|a, b| a.room_name.cmp(&b.room_name));
+        if self.is_roomchat_joined {
+            entries.insert(0, RoomListEntry {
+                room_name: self.roomchat_comps.room_name.clone(),
+                unread_count: 0,
+                is_on_screen: true
+            });
+        }
+        entries
+    }
+
+    //Room socket events are handled as if their room were on screen, so a background room is swapped in for one
+    pub fn route_room_event(&mut self, room_name: &str) -> RoomEventRoute {
+        if self.is_roomchat_joined && self.roomchat_comps.room_name==room_name {
+            return RoomEventRoute::ON_SCREEN;
+        }
+        match self.active_rooms.remove(room_name) {
+            Some(mut room) => {
+                self.swap_room(&mut room);
+                let history_len = self.roomchat_comps.chat_history.lock().map(|history| history.len()).unwrap_or_default();
+                RoomEventRoute::BACKGROUND(BackgroundRoomEvent {
+                    on_screen_room: room,
+                    screen: self.current_screen.clone(),
+                    history_len
+                })
+            }
+            None => RoomEventRoute::GONE
+        }
+    }
+
+    //Swaps the background room back out after its event, unless the event closed it
+    pub fn finish_background_room_event(&mut self, background: BackgroundRoomEvent) {
+        let BackgroundRoomEvent { mut on_screen_room, screen, history_len } = background;
+        let new_messages = self.roomchat_comps.chat_history.lock().map(|history| history.len().saturating_sub(history_len)).unwrap_or_default();
+        self.swap_room(&mut on_screen_room);
+        let mut room = on_screen_room;
+        self.current_screen = screen;
+        if room.is_roomchat_joined {
+            room.unread_count += new_messages;
+            self.active_rooms.insert(room.roomchat_comps.room_name.clone(), room);
+        }
+    }
+
+    //Leaves the room on screen, its socket is closed
+    pub fn leave_current_room(&mut self) {
+        if let Some(roomchat_sender) = &self.outgoing_roomchat_msg_tx {
+            send_room_leave(roomchat_sender, &self.username, self.is_current_room_owner);
+        }
+        let mut left_room = ActiveRoom::new();
+        self.swap_room(&mut left_room);
+    }
+
+    //Leaves every room, for exiting
+    pub fn leave_all_rooms(&mut self) {
+        self.leave_current_room();
+        for (_, room) in self.active_rooms.drain() {
+            if let Some(roomchat_sender) = &room.outgoing_roomchat_msg_tx {
+                send_room_leave(roomchat_sender, &self.username, room.is_current_room_owner);
+            }
+        }
+    }
+}
+
+
+fn send_room_leave(roomchat_sender: &mpsc::Sender<SocketMessage>, username: &str, is_room_owner: bool) {
+    //Sending Leave Message only if user is NOT room owner (cuz when owner leaves, room anyways is deleted)
+    if !is_room_owner {
+        if let Err(_) = roomchat_sender.send(SocketMessage::Leave(MessageType::ROOM(RoomMessageType::SENDER(RoomSenderMessage{
+            username: username.to_string(),
+            content: format!("{} left", username),
+            users: Vec::new(),
+            whisper_mode: WhisperMode::NONE,
+            is_join_leave_msg: true
+        }))))
+        {
+            println!("Couldnt send leave message");
+        }
+    }
+
+    if let Err(e) = roomchat_sender.send(
+        SocketMessage::Disconnect(DisconnectType::ROOM)
+    ) {
+        eprintln!("Failed to send message to WebSocket: {}", e);
+    }
+}
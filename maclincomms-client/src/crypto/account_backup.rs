@@ -0,0 +1,98 @@
+use base64::{engine::general_purpose, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::user_model::AccountBackup;
+
+use super::store_seal::{derive_store_key, generate_store_salt, open_store_bytes, seal_store_bytes, StoreKdfParams};
+
+
+pub const ACCOUNT_BACKUP_VERSION: u8 = 1;
+
+//Associated data of the sealed account, a backup can't be passed off as any other sealed blob
+const ACCOUNT_BACKUP_LABEL: &str = "maclincomms-account-backup";
+
+
+//What a .mcb file holds: how to get the key back from the passphrase, and the sealed account
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountBackupFile{
+    version: u8,
+    salt: String,
+    kdf_params: StoreKdfParams,
+    sealed_account: String //Base64 [Nonce][Ciphertext]
+}
+
+
+//The file contents, with a salt of its own so the backup doesn't share a key with the local store
+pub fn seal_account_backup(passphrase: &str, kdf_params: StoreKdfParams, backup: &AccountBackup) -> Option<String> {
+    let salt = generate_store_salt();
+    let backup_key = derive_store_key(passphrase, &salt, kdf_params)?;
+    let plaintext = serde_json::to_vec(backup).ok()?;
+
+    let backup_file = AccountBackupFile {
+        version: ACCOUNT_BACKUP_VERSION,
+        salt: general_purpose::STANDARD.encode(salt),
+        kdf_params,
+        sealed_account: general_purpose::STANDARD.encode(seal_store_bytes(backup_key, ACCOUNT_BACKUP_LABEL, &plaintext))
+    };
+    return serde_json::to_string(&backup_file).ok();
+}
+
+//None if it isn't a backup, or the passphrase is wrong
+pub fn open_account_backup(passphrase: &str, contents: &str) -> Option<AccountBackup> {
+    let backup_file: AccountBackupFile = serde_json::from_str(contents).ok()?;
+    if backup_file.version!=ACCOUNT_BACKUP_VERSION {
+        return None;
+    }
+    let salt = general_purpose::STANDARD.decode(backup_file.salt).ok()?;
+    let sealed_account = general_purpose::STANDARD.decode(backup_file.sealed_account).ok()?;
+
+    //The cost comes from whoever wrote the file
+    let backup_key = derive_store_key(passphrase, &salt, backup_file.kdf_params.clamped())?;
+    let plaintext = open_store_bytes(backup_key, ACCOUNT_BACKUP_LABEL, &sealed_account)?;
+    return serde_json::from_slice(&plaintext).ok();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{crypto::double_ratchet::DoubleRatchet, user_model::{DmChats_Warehouse, DmE2EEncryption_Data, DmSessionEncryption_Key, UserIdentityKeys, VerifiedContacts}};
+
+    use super::*;
+
+    fn test_params() -> StoreKdfParams {
+        StoreKdfParams { m_cost: 256, t_cost: 1, p_cost: 1 }
+    }
+
+    fn backup() -> AccountBackup {
+        let mut dmchats_warehouse = DmChats_Warehouse::new();
+        dmchats_warehouse.dms_session_key.insert("bob".to_string(), DmSessionEncryption_Key { key: [5u8;32], timestamp: "2026-01-01 00:00:00+0000".to_string(), nonce: [1u8;12] });
+        dmchats_warehouse.dms_data.insert("bob".to_string(), vec![("bob".to_string(), "hi alice".to_string(), "12:00 PM".to_string(), "".to_string(), false, ">>".to_string())]);
+
+        AccountBackup {
+            username: "alice".to_string(),
+            identity_keys: Some(UserIdentityKeys { public_identity_key: "cHVi".to_string(), private_identity_key: "cHJpdg==".to_string() }),
+            account_identity_keys: None,
+            dm_ratchets: DmE2EEncryption_Data { dms: HashMap::from([("bob".to_string(), DoubleRatchet::new([9u8;32]))]) },
+            dms_list: Vec::new(),
+            verified_contacts: VerifiedContacts::default(),
+            dmchats_warehouse
+        }
+    }
+
+    #[test]
+    fn a_backup_opens_with_its_passphrase_only() {
+        let original = backup();
+        let contents = seal_account_backup("moving day", test_params(), &original).unwrap();
+        assert!(!contents.contains("hi alice") && !contents.contains("cHJpdg=="));
+
+        let restored = open_account_backup("moving day", &contents).unwrap();
+        assert_eq!(restored.username, "alice");
+        assert_eq!(restored.dmchats_warehouse.dms_data["bob"][0].1, "hi alice");
+        assert_eq!(restored.dm_ratchets.dms["bob"], original.dm_ratchets.dms["bob"]);
+
+        assert!(open_account_backup("moving night", &contents).is_none());
+        assert!(open_account_backup("moving day", "not a backup").is_none());
+    }
+}
@@ -1,22 +1,27 @@
 use std::collections::HashMap;
-use aes_gcm::{aead::Aead, Aes256Gcm, Key, KeyInit, Nonce};
+use aes_gcm::{aead::{Aead, Payload}, Aes256Gcm, Key, KeyInit, Nonce};
 use base64::{engine::general_purpose, Engine};
 use crate::user_model::{DmSessionEncryption_Key};
 
+use super::dm_envelope::{DmEnvelope, LEGACY_DM_ENVELOPE_VERSION};
 
 
 
-pub fn decrypt_dm_message(receiving_msg_key: [u8;32], ciphertext: &str) -> String {
 
-    let nonce_slice = [0u8;12]; //Zeroes Nonce
+pub fn decrypt_dm_message(receiving_msg_key: [u8;32], envelope: &DmEnvelope) -> String {
+
     let key = Key::<Aes256Gcm>::from_slice(&receiving_msg_key);
     let cipher = Aes256Gcm::new(&key);
-    let nonce: &sha2::digest::generic_array::GenericArray<u8, sha2::digest::typenum::UInt<sha2::digest::typenum::UInt<sha2::digest::typenum::UInt<sha2::digest::typenum::UInt<sha2::digest::typenum::UTerm, sha2::digest::consts::B1>, sha2::digest::consts::B1>, sha2::digest::consts::B0>, sha2::digest::consts::B0>> = Nonce::from_slice(&nonce_slice);
+    let nonce = Nonce::from_slice(&envelope.nonce); //Zeroes Nonce for legacy messages
 
-    //Decoding base 64 ciphertext to bytes
-    let ciphertext_bytes = general_purpose::STANDARD.decode(ciphertext).unwrap();
-    //Decrypting to plaintext
-    let plaintext_res = cipher.decrypt(&nonce, ciphertext_bytes.as_ref());
+    //Legacy messages were encrypted without a header
+    let plaintext_res = if envelope.version==LEGACY_DM_ENVELOPE_VERSION {
+        cipher.decrypt(&nonce, envelope.ciphertext.as_ref())
+    }
+    else{
+        let aad = envelope.associated_data();
+        cipher.decrypt(&nonce, Payload{ msg: envelope.ciphertext.as_ref(), aad: &aad })
+    };
     match plaintext_res{
         Ok(plaintext_bytes) => {
             let plaintext_str_res = String::from_utf8(plaintext_bytes);
@@ -0,0 +1,151 @@
+use aes_gcm::{aead::{Aead, Payload}, AeadCore, Aes256Gcm, Key, KeyInit, Nonce};
+use base64::{engine::general_purpose, Engine};
+use hkdf::Hkdf;
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+
+use crate::user_model::DeviceLinkBundle;
+
+
+//Crockford base32, no I, L, O or U so the code survives being read out or retyped
+const LINK_CODE_ALPHABET: &[u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+const LINK_CODE_GROUPS: usize = 5;
+const LINK_CODE_GROUP_LEN: usize = 4;
+
+//The first group names the link on the server, the other four (80 bits) never leave the two devices
+const LINK_ID_LEN: usize = LINK_CODE_GROUP_LEN;
+
+
+//A fresh code like 7K2M-Q9XD-4FRT-B0NW-HC3P
+pub fn generate_link_code() -> String {
+    let mut random_bytes = [0u8; LINK_CODE_GROUPS*LINK_CODE_GROUP_LEN];
+    OsRng.fill_bytes(&mut random_bytes);
+
+    let chars: Vec<char> = random_bytes.iter()
+        .map(|byte| LINK_CODE_ALPHABET[(*byte % 32) as usize] as char)
+        .collect();
+
+    return chars.chunks(LINK_CODE_GROUP_LEN)
+        .map(|group| group.iter().collect::<String>())
+        .collect::<Vec<String>>()
+        .join("-");
+}
+
+//Link id and secret of a typed in code, forgiving case, spacing and the usual look-alikes
+pub fn parse_link_code(code: &str) -> Option<(String, String)> {
+    let normalized: String = code.chars()
+        .filter(|c| !c.is_whitespace() && *c!='-')
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            other => other
+        })
+        .collect();
+
+    if normalized.len()!=LINK_CODE_GROUPS*LINK_CODE_GROUP_LEN || !normalized.bytes().all(|byte| LINK_CODE_ALPHABET.contains(&byte)) {
+        return None;
+    }
+
+    let (link_id, secret) = normalized.split_at(LINK_ID_LEN);
+    return Some((link_id.to_string(), secret.to_string()));
+}
+
+//The key only the code can give, bound to the account and the link so a bundle can't be replayed under another
+fn derive_link_key(username: &str, link_id: &str, secret: &str) -> [u8;32] {
+    let hk = Hkdf::<Sha256>::new(Some(link_id.as_bytes()), secret.as_bytes());
+    let mut link_key = [0u8;32];
+    hk.expand(format!("maclincomms-device-link:{}", username).as_bytes(), &mut link_key).unwrap();
+    return link_key;
+}
+
+fn link_associated_data(username: &str, link_id: &str) -> Vec<u8> {
+    return [username.as_bytes(), b"#", link_id.as_bytes()].concat();
+}
+
+//Base64 of [Nonce][Ciphertext], all the server ever gets to hold
+pub fn seal_link_bundle(username: &str, link_id: &str, secret: &str, bundle: &DeviceLinkBundle) -> String {
+    let link_key = derive_link_key(username, link_id, secret);
+    let key = Key::<Aes256Gcm>::from_slice(&link_key);
+    let cipher = Aes256Gcm::new(&key);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let plaintext = serde_json::to_vec(bundle).unwrap();
+    let aad = link_associated_data(username, link_id);
+
+    match cipher.encrypt(&nonce, Payload{ msg: &plaintext, aad: &aad }) {
+        Ok(ciphertext) => general_purpose::STANDARD.encode([nonce.to_vec(), ciphertext].concat()),
+        Err(_) => "".to_string()
+    }
+}
+
+//None if the code is wrong or the bundle was tampered with
+pub fn open_link_bundle(username: &str, link_id: &str, secret: &str, sealed: &str) -> Option<DeviceLinkBundle> {
+    let sealed_bytes = general_purpose::STANDARD.decode(sealed).ok()?;
+    if sealed_bytes.len() <= 12 {
+        return None;
+    }
+    let (nonce_bytes, ciphertext) = sealed_bytes.split_at(12);
+
+    let link_key = derive_link_key(username, link_id, secret);
+    let key = Key::<Aes256Gcm>::from_slice(&link_key);
+    let cipher = Aes256Gcm::new(&key);
+    let aad = link_associated_data(username, link_id);
+
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce_bytes), Payload{ msg: ciphertext, aad: &aad }).ok()?;
+    return serde_json::from_slice(&plaintext).ok();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::user_model::{DmSessionEncryption_Key, DmUser_Data, UserIdentityKeys, VerifiedContacts};
+
+    use super::*;
+
+    fn bundle() -> DeviceLinkBundle {
+        let mut dms_session_keys = HashMap::new();
+        dms_session_keys.insert("bob".to_string(), DmSessionEncryption_Key { key: [7u8;32], timestamp: "2026-01-01 00:00:00+0000".to_string(), nonce: [3u8;12] });
+        DeviceLinkBundle {
+            account_identity_keys: UserIdentityKeys { public_identity_key: "cHVi".to_string(), private_identity_key: "cHJpdg==".to_string() },
+            dms_session_keys,
+            dms_list: vec![DmUser_Data { username: "bob".to_string(), public_identity_key: "Ym9i".to_string(), devices: Vec::new() }],
+            verified_contacts: VerifiedContacts::default()
+        }
+    }
+
+    #[test]
+    fn generated_codes_parse_back() {
+        let code = generate_link_code();
+        assert_eq!(code.len(), 24);
+
+        let (link_id, secret) = parse_link_code(&code).unwrap();
+        assert_eq!(link_id.len(), 4);
+        assert_eq!(secret.len(), 16);
+        assert_eq!(format!("{}{}", link_id, secret), code.replace('-', ""));
+    }
+
+    #[test]
+    fn retyped_codes_are_forgiven_but_garbage_is_not() {
+        assert_eq!(parse_link_code(" 7k2m-q9xd 4frt-b0nw-hc3p "), parse_link_code("7K2M-Q9XD-4FRT-B0NW-HC3P"));
+        assert_eq!(parse_link_code("7K2M-Q9XD-4FRT-BONW-HC3P"), parse_link_code("7K2M-Q9XD-4FRT-B0NW-HC3P"));
+        assert_eq!(parse_link_code("7K2M-Q9XD-4FRT-B0NW"), None);
+        assert_eq!(parse_link_code("7K2M-Q9XD-4FRT-B0NW-HC3U"), None);
+    }
+
+    #[test]
+    fn only_the_right_code_and_account_open_the_bundle() {
+        let (link_id, secret) = parse_link_code(&generate_link_code()).unwrap();
+        let sealed = seal_link_bundle("alice", &link_id, &secret, &bundle());
+
+        let opened = open_link_bundle("alice", &link_id, &secret, &sealed).unwrap();
+        assert_eq!(opened.dms_session_keys["bob"].key, [7u8;32]);
+        assert_eq!(opened.account_identity_keys.private_identity_key, "cHJpdg==");
+
+        let (_, wrong_secret) = parse_link_code(&generate_link_code()).unwrap();
+        assert!(open_link_bundle("alice", &link_id, &wrong_secret, &sealed).is_none());
+        assert!(open_link_bundle("mallory", &link_id, &secret, &sealed).is_none());
+    }
+}
@@ -0,0 +1,112 @@
+use base64::{engine::general_purpose, Engine};
+
+
+//Current DM envelope version, sent as "v3" in front of the envelope
+pub const DM_ENVELOPE_VERSION: u8 = 3;
+//First version whose sender chains the root key through KDF_RK (v2 and v3 share a layout)
+pub const ROOT_KEY_CHAINING_DM_ENVELOPE_VERSION: u8 = 3;
+//First versioned envelope, without the previous chain length
+pub const V1_DM_ENVELOPE_VERSION: u8 = 1;
+//Version given to bare [Ciphertext].[DH Pub Key] messages from older clients (zeroes nonce, no header)
+pub const LEGACY_DM_ENVELOPE_VERSION: u8 = 0;
+
+
+// Versioned DM envelope carried in DmMessage.content
+// v3.[Ratchet Pub Key].[Previous Chain Length].[Message Counter].[Nonce].[Ciphertext]  (keys, nonce and ciphertext Base64 encoded)
+// v2 as v3, v1.[Ratchet Pub Key].[Message Counter].[Nonce].[Ciphertext]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmEnvelope {
+    pub version: u8,
+    pub ratchet_pub_key: [u8;32],
+    pub previous_counter: u32, //Messages sent on the sender's previous sending chain (PN)
+    pub counter: u32, //Message number on the current sending chain (N)
+    pub nonce: [u8;12],
+    pub ciphertext: Vec<u8>
+}
+
+impl DmEnvelope {
+
+    pub fn encode(&self) -> String {
+        let ciphertext = general_purpose::STANDARD.encode(&self.ciphertext);
+        let ratchet_pub_key = general_purpose::STANDARD.encode(self.ratchet_pub_key);
+
+        if self.version==LEGACY_DM_ENVELOPE_VERSION {
+            return ciphertext + "." + &ratchet_pub_key;
+        }
+
+        let nonce = general_purpose::STANDARD.encode(self.nonce);
+
+        if self.version==V1_DM_ENVELOPE_VERSION {
+            return format!("v{}.{}.{}.{}.{}", self.version, ratchet_pub_key, self.counter, nonce, ciphertext);
+        }
+
+        format!(
+            "v{}.{}.{}.{}.{}.{}",
+            self.version,
+            ratchet_pub_key,
+            self.previous_counter,
+            self.counter,
+            nonce,
+            ciphertext
+        )
+    }
+
+    // Parses the versioned envelopes and the legacy [Ciphertext].[DH Pub Key] format
+    pub fn parse(content: &str) -> Option<Self> {
+        let parts: Vec<&str> = content.split('.').collect();
+
+        match parts.as_slice() {
+            [ciphertext, ratchet_pub_key] => {
+                Some(DmEnvelope {
+                    version: LEGACY_DM_ENVELOPE_VERSION,
+                    ratchet_pub_key: general_purpose::STANDARD.decode(ratchet_pub_key).ok()?.try_into().ok()?,
+                    previous_counter: 0,
+                    counter: 0,
+                    nonce: [0u8;12],
+                    ciphertext: general_purpose::STANDARD.decode(ciphertext).ok()?
+                })
+            }
+            [version, ratchet_pub_key, counter, nonce, ciphertext] => {
+                let version: u8 = version.strip_prefix('v')?.parse().ok()?;
+                if version!=V1_DM_ENVELOPE_VERSION {
+                    return None;
+                }
+                Some(DmEnvelope {
+                    version,
+                    ratchet_pub_key: general_purpose::STANDARD.decode(ratchet_pub_key).ok()?.try_into().ok()?,
+                    previous_counter: 0,
+                    counter: counter.parse().ok()?,
+                    nonce: general_purpose::STANDARD.decode(nonce).ok()?.try_into().ok()?,
+                    ciphertext: general_purpose::STANDARD.decode(ciphertext).ok()?
+                })
+            }
+            [version, ratchet_pub_key, previous_counter, counter, nonce, ciphertext] => {
+                let version: u8 = version.strip_prefix('v')?.parse().ok()?;
+                if version<2 || version>DM_ENVELOPE_VERSION {
+                    return None;
+                }
+                Some(DmEnvelope {
+                    version,
+                    ratchet_pub_key: general_purpose::STANDARD.decode(ratchet_pub_key).ok()?.try_into().ok()?,
+                    previous_counter: previous_counter.parse().ok()?,
+                    counter: counter.parse().ok()?,
+                    nonce: general_purpose::STANDARD.decode(nonce).ok()?.try_into().ok()?,
+                    ciphertext: general_purpose::STANDARD.decode(ciphertext).ok()?
+                })
+            }
+            _ => None
+        }
+    }
+
+    // Header authenticated as AES-GCM associated data, so the ratchet key and counters can't be swapped
+    pub fn associated_data(&self) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(41);
+        aad.push(self.version);
+        aad.extend_from_slice(&self.ratchet_pub_key);
+        if self.version!=V1_DM_ENVELOPE_VERSION {
+            aad.extend_from_slice(&self.previous_counter.to_be_bytes());
+        }
+        aad.extend_from_slice(&self.counter.to_be_bytes());
+        aad
+    }
+}
@@ -3,8 +3,6 @@ use rand_core::OsRng;
 use sha2::Sha256;
 use x25519_dalek::{PublicKey, StaticSecret};
 
-use crate::user_model::DmDoubleRatchet_Keys;
-
 
 
 pub fn generate_shared_rootkey(their_pub_key: [u8;32], my_priv_key: [u8;32]) -> [u8;32] {
@@ -44,18 +42,3 @@ pub fn derive_message_key(chain_key: [u8;32]) -> [u8;32] {
     hk.expand(&[0x01], &mut derived_message_key).unwrap();
     return derived_message_key;
 }
-
-//FORWARD SECRECY
-pub fn update_sending_chainkey(keys: &mut DmDoubleRatchet_Keys) {
-    let hk = Hkdf::<Sha256>::new(None, &keys.sending_chain_key);
-    let mut derived_sending_chainkey: [u8; 32] = [0u8;32];
-    hk.expand(&[0x02], &mut derived_sending_chainkey).unwrap();
-    keys.sending_chain_key = derived_sending_chainkey;
-}
-
-pub fn update_receiving_chainkey(keys: &mut DmDoubleRatchet_Keys) {
-    let hk = Hkdf::<Sha256>::new(None, &keys.receiving_chain_key);
-    let mut derived_receiving_chainkey: [u8; 32] = [0u8;32];
-    hk.expand(&[0x02], &mut derived_receiving_chainkey).unwrap();
-    keys.receiving_chain_key = derived_receiving_chainkey;
-}
\ No newline at end of file
@@ -0,0 +1,411 @@
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+use super::{
+    decrypt_msg::decrypt_dm_message,
+    dm_envelope::{DmEnvelope, DM_ENVELOPE_VERSION, LEGACY_DM_ENVELOPE_VERSION, ROOT_KEY_CHAINING_DM_ENVELOPE_VERSION},
+    dm_keys::{derive_message_key, generate_dh_keypair, generate_receiver_chainkey, generate_shared_rootkey},
+    encrypt_msg::encrypt_dm_message
+};
+
+
+//Most messages a single chain may skip ahead by
+pub const MAX_SKIPPED_MESSAGES: u32 = 1000;
+//Most skipped message keys kept per conversation
+pub const MAX_STORED_SKIPPED_KEYS: usize = 2000;
+
+const KDF_RK_INFO: &[u8] = b"maclincomms-dm-ratchet";
+
+
+// Signal style double ratchet for one DM conversation.
+// Both users start it from an X3DH shared secret when a DM request is accepted,
+// or from the other's public identity key when they have no prekey bundle.
+// Serialised as part of DmE2EEncryption_Data ("persistent-dms-e2e-keys").
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DoubleRatchet{
+    pub root_key: [u8;32],
+    pub dh_pub_key: [u8;32],
+    pub dh_priv_key: [u8;32],
+    pub their_dh_pub_key: [u8;32], //Zeroes until their first message
+    pub sending_chain_key: [u8;32],
+    pub receiving_chain_key: [u8;32],
+    pub sending_counter: u32, //Messages sent on the current sending chain (N)
+    pub previous_sending_counter: u32, //Length of the previous sending chain (PN)
+    pub receiving_counter: u32, //Next expected message number on the receiving chain
+    pub skipped_message_keys: Vec<SkippedMessageKey>, //Keys of messages not received yet, oldest first
+    pub first_contact_priv_key: Option<[u8;32]>, //X3DH key their first ratchet key is combined with (our ephemeral key or signed prekey), identity key when None
+    pub first_contact_root_key: [u8;32] //X3DH shared secret, zeroes for sessions started from identity keys. Both are wiped on their first message
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SkippedMessageKey{
+    pub ratchet_pub_key: [u8;32],
+    pub counter: u32,
+    pub message_key: [u8;32]
+}
+
+
+//KDF_RK: chains the previous root key (as HKDF salt) into a DH output, giving the next root key and a chain key
+pub fn kdf_rk(root_key: [u8;32], dh_output: [u8;32]) -> ([u8;32], [u8;32]) {
+    let hk = Hkdf::<Sha256>::new(Some(&root_key), &dh_output);
+    let mut okm = [0u8;64];
+    hk.expand(KDF_RK_INFO, &mut okm).unwrap();
+    return (okm[..32].try_into().unwrap(), okm[32..].try_into().unwrap());
+}
+
+//KDF_CK: next chain key (the message key comes from derive_message_key)
+pub fn kdf_ck(chain_key: [u8;32]) -> [u8;32] {
+    let hk = Hkdf::<Sha256>::new(None, &chain_key);
+    let mut next_chain_key = [0u8;32];
+    hk.expand(&[0x02], &mut next_chain_key).unwrap();
+    return next_chain_key;
+}
+
+pub fn dh(my_priv_key: [u8;32], their_pub_key: [u8;32]) -> [u8;32] {
+    StaticSecret::from(my_priv_key).diffie_hellman(&PublicKey::from(their_pub_key)).to_bytes()
+}
+
+
+impl DoubleRatchet{
+
+    pub fn new(their_identity_pub_key: [u8;32]) -> Self {
+        Self::with_dh_keypair(their_identity_pub_key, generate_dh_keypair())
+    }
+
+    //First sending chain comes from our fresh DH key and their identity key
+    pub fn with_dh_keypair(their_identity_pub_key: [u8;32], dh_keypair: ([u8;32], [u8;32])) -> Self {
+        Self::start([0u8;32], their_identity_pub_key, None, dh_keypair)
+    }
+
+    //From the X3DH shared secret, with their signed prekey (as initiator) or ephemeral key (as responder)
+    //standing in for their first ratchet key
+    pub fn from_x3dh(shared_key: [u8;32], their_ratchet_pub_key: [u8;32], first_contact_priv_key: [u8;32]) -> Self {
+        Self::start(shared_key, their_ratchet_pub_key, Some(first_contact_priv_key), generate_dh_keypair())
+    }
+
+    fn start(root_key: [u8;32], their_ratchet_pub_key: [u8;32], first_contact_priv_key: Option<[u8;32]>, (dh_pub_key, dh_priv_key): ([u8;32], [u8;32])) -> Self {
+        let first_contact_root_key = root_key;
+        let (root_key, sending_chain_key) = kdf_rk(root_key, dh(dh_priv_key, their_ratchet_pub_key));
+        DoubleRatchet{
+            root_key,
+            dh_pub_key,
+            dh_priv_key,
+            their_dh_pub_key: [0u8;32],
+            sending_chain_key,
+            receiving_chain_key: [0u8;32],
+            sending_counter: 0,
+            previous_sending_counter: 0,
+            receiving_counter: 0,
+            skipped_message_keys: Vec::new(),
+            first_contact_priv_key,
+            first_contact_root_key
+        }
+    }
+
+    //Returns the encoded DM envelope and rotates the sending chain
+    pub fn encrypt(&mut self, plaintext: &str) -> String {
+        let header = DmEnvelope{
+            version: DM_ENVELOPE_VERSION,
+            ratchet_pub_key: self.dh_pub_key,
+            previous_counter: self.previous_sending_counter,
+            counter: self.sending_counter,
+            nonce: [0u8;12],
+            ciphertext: Vec::new()
+        };
+        let envelope = encrypt_dm_message(derive_message_key(self.sending_chain_key), header, plaintext);
+        self.sending_chain_key = kdf_ck(self.sending_chain_key);
+        self.sending_counter += 1;
+        return envelope;
+    }
+
+    //Handles DH ratchet steps, out of order and skipped messages.
+    //Works on a copy of the state and only keeps it if the message decrypts.
+    pub fn decrypt(&mut self, envelope: &DmEnvelope, identity_priv_key: [u8;32]) -> Option<String> {
+        self.decrypt_with(envelope, identity_priv_key, generate_dh_keypair)
+    }
+
+    fn decrypt_with(&mut self, envelope: &DmEnvelope, identity_priv_key: [u8;32], mut next_dh_keypair: impl FnMut() -> ([u8;32], [u8;32])) -> Option<String> {
+
+        //Delayed message whose key was stored while skipping ahead
+        if envelope.version!=LEGACY_DM_ENVELOPE_VERSION {
+            let skipped_res = self.skipped_message_keys.iter()
+                .position(|skipped| skipped.ratchet_pub_key==envelope.ratchet_pub_key && skipped.counter==envelope.counter);
+            if let Some(pos) = skipped_res {
+                let plaintext = non_empty(decrypt_dm_message(self.skipped_message_keys[pos].message_key, envelope))?;
+                self.skipped_message_keys.remove(pos);
+                return Some(plaintext);
+            }
+        }
+
+        //Same DH for now
+        if envelope.ratchet_pub_key==self.their_dh_pub_key {
+            let mut state = self.clone();
+            let plaintext = state.decrypt_on_receiving_chain(envelope)?;
+            *self = state;
+            return Some(plaintext);
+        }
+
+        //They DH-RATCHETED and sent a new pub key.
+        //Before we receive anything, they either started from our X3DH or identity key (root starts over)
+        //or, when replying to us, from our current DH key
+        let candidates = if self.their_dh_pub_key==[0u8;32] {
+            vec![(self.first_contact_priv_key.unwrap_or(identity_priv_key), self.first_contact_root_key), (self.dh_priv_key, self.root_key)]
+        }
+        else {
+            vec![(self.dh_priv_key, self.root_key)]
+        };
+
+        for (my_priv_key, root_key) in candidates {
+            let mut state = self.clone();
+            state.dh_ratchet(envelope, my_priv_key, root_key, next_dh_keypair())?;
+            if let Some(plaintext) = state.decrypt_on_receiving_chain(envelope) {
+                *self = state;
+                return Some(plaintext);
+            }
+        }
+        return None;
+    }
+
+    fn dh_ratchet(&mut self, envelope: &DmEnvelope, my_priv_key: [u8;32], root_key: [u8;32], (new_dh_pub, new_dh_priv): ([u8;32], [u8;32])) -> Option<()> {
+        //Keeping the keys of messages still missing on their previous chain
+        if self.their_dh_pub_key!=[0u8;32] && envelope.version!=LEGACY_DM_ENVELOPE_VERSION {
+            self.skip_message_keys(envelope.previous_counter)?;
+        }
+
+        let their_pub_key = envelope.ratchet_pub_key;
+        if envelope.version < ROOT_KEY_CHAINING_DM_ENVELOPE_VERSION {
+            //Older senders derived each chain from the DH output alone
+            self.root_key = generate_shared_rootkey(their_pub_key, my_priv_key);
+            self.receiving_chain_key = generate_receiver_chainkey(self.root_key);
+        }
+        else {
+            (self.root_key, self.receiving_chain_key) = kdf_rk(root_key, dh(my_priv_key, their_pub_key));
+        }
+        self.their_dh_pub_key = their_pub_key;
+        self.receiving_counter = 0;
+        self.first_contact_priv_key = None;
+        self.first_contact_root_key = [0u8;32];
+
+        //New DH pair and sending chain
+        (self.root_key, self.sending_chain_key) = kdf_rk(self.root_key, dh(new_dh_priv, their_pub_key));
+        self.dh_pub_key = new_dh_pub;
+        self.dh_priv_key = new_dh_priv;
+        self.previous_sending_counter = self.sending_counter;
+        self.sending_counter = 0;
+        return Some(());
+    }
+
+    fn decrypt_on_receiving_chain(&mut self, envelope: &DmEnvelope) -> Option<String> {
+        //Legacy senders carry no counter and only advance their chain on our receiver ack,
+        //so their message key is either the current one or the next
+        if envelope.version==LEGACY_DM_ENVELOPE_VERSION {
+            for _ in 0..2 {
+                if let Some(plaintext) = non_empty(decrypt_dm_message(derive_message_key(self.receiving_chain_key), envelope)) {
+                    return Some(plaintext);
+                }
+                self.advance_receiving_chain();
+            }
+            return None;
+        }
+
+        //Already received (and not in the skipped keys)
+        if envelope.counter < self.receiving_counter {
+            return None;
+        }
+        self.skip_message_keys(envelope.counter)?;
+
+        let plaintext = non_empty(decrypt_dm_message(derive_message_key(self.receiving_chain_key), envelope))?;
+        self.advance_receiving_chain();
+        return Some(plaintext);
+    }
+
+    //Stores the message keys of the receiving chain up to (not including) `until`
+    fn skip_message_keys(&mut self, until: u32) -> Option<()> {
+        if until.saturating_sub(self.receiving_counter) > MAX_SKIPPED_MESSAGES {
+            return None;
+        }
+        while self.receiving_counter < until {
+            self.skipped_message_keys.push(SkippedMessageKey{
+                ratchet_pub_key: self.their_dh_pub_key,
+                counter: self.receiving_counter,
+                message_key: derive_message_key(self.receiving_chain_key)
+            });
+            self.advance_receiving_chain();
+        }
+        //Dropping the oldest keys beyond the cache bound
+        let stored = self.skipped_message_keys.len();
+        if stored > MAX_STORED_SKIPPED_KEYS {
+            self.skipped_message_keys.drain(..stored - MAX_STORED_SKIPPED_KEYS);
+        }
+        return Some(());
+    }
+
+    fn advance_receiving_chain(&mut self) {
+        self.receiving_chain_key = kdf_ck(self.receiving_chain_key);
+        self.receiving_counter += 1;
+    }
+}
+
+fn non_empty(plaintext: String) -> Option<String> {
+    if plaintext.is_empty() { None } else { Some(plaintext) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use aes_gcm::{aead::Aead, Aes256Gcm, Key, KeyInit, Nonce};
+
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn keypair(seed: u8) -> ([u8;32], [u8;32]) {
+        let priv_key = [seed;32];
+        (PublicKey::from(&StaticSecret::from(priv_key)).to_bytes(), priv_key)
+    }
+
+    // Alice and Bob as after an accepted DM request, with fixed keys
+    fn session() -> (DoubleRatchet, [u8;32], DoubleRatchet, [u8;32]) {
+        let (alice_id_pub, alice_id_priv) = keypair(0x01);
+        let (bob_id_pub, bob_id_priv) = keypair(0x02);
+        let alice = DoubleRatchet::with_dh_keypair(bob_id_pub, keypair(0x03));
+        let bob = DoubleRatchet::with_dh_keypair(alice_id_pub, keypair(0x04));
+        (alice, alice_id_priv, bob, bob_id_priv)
+    }
+
+    fn receive(ratchet: &mut DoubleRatchet, identity_priv: [u8;32], envelope: &str, next_dh_seed: u8) -> Option<String> {
+        ratchet.decrypt_with(&DmEnvelope::parse(envelope).unwrap(), identity_priv, || keypair(next_dh_seed))
+    }
+
+    // Vectors cross-checked against an independent HKDF-SHA256 / X25519 implementation
+    #[test]
+    fn kdf_vectors() {
+        let (root_key, chain_key) = kdf_rk([0x11;32], [0x22;32]);
+        assert_eq!(hex(&root_key), "84329eaaba8a674bd91b0ac6f194c03d3198962ce94224c22f2c95268861168b");
+        assert_eq!(hex(&chain_key), "5b3d3bb2796b96c0e0e906d0cf8d38fc65d4ebde0d97932aedd3b05657cf0ece");
+        assert_eq!(hex(&kdf_ck([0x33;32])), "7c867cc34207b3a2de992658c445752f710c1dcb38f10b349fd2882bf909e468");
+        assert_eq!(hex(&derive_message_key([0x33;32])), "042d24ec0e21d3e454bf566e091ff0ce6b61f1446814b98c0a420d71e474b4ec");
+    }
+
+    #[test]
+    fn session_vectors() {
+        let (mut alice, alice_id_priv, bob, _) = session();
+        assert_eq!(hex(&bob.root_key), "bf8893df44b9758e202bf05c06d023a9dcdc3ffb1632406fbf4a1c723fb6b403");
+        assert_eq!(hex(&bob.sending_chain_key), "dc58804e9deb36c22acf8d5619e11b858718a4ba3032c3404de578adf38e0d2b");
+
+        // Receiving bob's first message restarts alice's root from her identity key, then chains it
+        let mut sender = bob.clone();
+        assert_eq!(receive(&mut alice, alice_id_priv, &sender.encrypt("hi"), 0x05).as_deref(), Some("hi"));
+        assert_eq!(alice.their_dh_pub_key, bob.dh_pub_key);
+        assert_eq!(hex(&alice.receiving_chain_key), "a87b01c2f288c3398842317ddfb741264641029baa1a0d1d27bfb53125031279");
+        assert_eq!(hex(&alice.root_key), "5d18b9b80bbde76a0d347bb9123862f972ae76a527eee3e39a86c24147a0eef2");
+        assert_eq!(hex(&alice.sending_chain_key), "5841e86732e062ce62d55621341140c1693cafe861d608178c1ee057d9b5e699");
+    }
+
+    #[test]
+    fn ping_pong_with_dh_ratchet_steps() {
+        let (mut alice, alice_id_priv, mut bob, bob_id_priv) = session();
+
+        assert_eq!(receive(&mut alice, alice_id_priv, &bob.encrypt("hello alice"), 0x05).as_deref(), Some("hello alice"));
+        assert_eq!(receive(&mut bob, bob_id_priv, &alice.encrypt("hi bob"), 0x06).as_deref(), Some("hi bob"));
+        assert_eq!(receive(&mut alice, alice_id_priv, &bob.encrypt("how are you?"), 0x07).as_deref(), Some("how are you?"));
+        assert_eq!(receive(&mut bob, bob_id_priv, &alice.encrypt("good"), 0x08).as_deref(), Some("good"));
+    }
+
+    #[test]
+    fn out_of_order_and_replayed_messages() {
+        let (mut alice, alice_id_priv, mut bob, bob_id_priv) = session();
+        assert!(receive(&mut alice, alice_id_priv, &bob.encrypt("1"), 0x05).is_some());
+        assert!(receive(&mut bob, bob_id_priv, &alice.encrypt("2"), 0x06).is_some());
+
+        let m3 = bob.encrypt("3");
+        let m4 = bob.encrypt("4");
+        let m5 = bob.encrypt("5");
+
+        assert_eq!(receive(&mut alice, alice_id_priv, &m5, 0x07).as_deref(), Some("5"));
+        assert_eq!(alice.skipped_message_keys.len(), 2);
+        assert_eq!(receive(&mut alice, alice_id_priv, &m3, 0x08).as_deref(), Some("3"));
+        assert_eq!(receive(&mut alice, alice_id_priv, &m4, 0x09).as_deref(), Some("4"));
+        assert!(alice.skipped_message_keys.is_empty());
+
+        let before = alice.clone();
+        assert_eq!(receive(&mut alice, alice_id_priv, &m4, 0x0a), None);
+        assert_eq!(alice, before);
+    }
+
+    #[test]
+    fn skipped_keys_survive_a_dh_ratchet_step() {
+        let (mut alice, alice_id_priv, mut bob, bob_id_priv) = session();
+        assert!(receive(&mut alice, alice_id_priv, &bob.encrypt("1"), 0x05).is_some());
+        assert!(receive(&mut bob, bob_id_priv, &alice.encrypt("2"), 0x06).is_some());
+
+        assert!(receive(&mut alice, alice_id_priv, &bob.encrypt("3"), 0x07).is_some());
+        let delayed = bob.encrypt("delayed");
+        assert!(receive(&mut bob, bob_id_priv, &alice.encrypt("4"), 0x08).is_some());
+
+        // Bob's new chain says his previous one had two messages, so alice keeps the key of the one she missed
+        let m5 = bob.encrypt("5");
+        assert_eq!(DmEnvelope::parse(&m5).unwrap().previous_counter, 2);
+        assert_eq!(receive(&mut alice, alice_id_priv, &m5, 0x09).as_deref(), Some("5"));
+        assert_eq!(alice.skipped_message_keys.len(), 1);
+        assert_eq!(receive(&mut alice, alice_id_priv, &delayed, 0x0a).as_deref(), Some("delayed"));
+    }
+
+    #[test]
+    fn too_many_skipped_messages_are_refused() {
+        let (mut alice, alice_id_priv, mut bob, _) = session();
+        for _ in 0..=MAX_SKIPPED_MESSAGES {
+            bob.encrypt("lost");
+        }
+        let before = alice.clone();
+        assert_eq!(receive(&mut alice, alice_id_priv, &bob.encrypt("too far"), 0x05), None);
+        assert_eq!(alice, before);
+    }
+
+    #[test]
+    fn tampered_header_is_rejected() {
+        let (mut alice, alice_id_priv, mut bob, _) = session();
+        let mut envelope = DmEnvelope::parse(&bob.encrypt("hello")).unwrap();
+        envelope.previous_counter = 7;
+        assert_eq!(alice.decrypt_with(&envelope, alice_id_priv, || keypair(0x05)), None);
+    }
+
+    #[test]
+    fn legacy_envelopes_still_decrypt() {
+        let (mut alice, alice_id_priv, _, _) = session();
+        let (legacy_pub, legacy_priv) = keypair(0x04);
+
+        // Pre-envelope clients: unchained root, zeroes nonce, bare [Ciphertext].[DH Pub Key]
+        let chain_key = generate_receiver_chainkey(generate_shared_rootkey(keypair(0x01).0, legacy_priv));
+        let legacy_encrypt = |chain_key: [u8;32], plaintext: &str| {
+            let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&derive_message_key(chain_key)));
+            let ciphertext = cipher.encrypt(Nonce::from_slice(&[0u8;12]), plaintext.as_bytes()).unwrap();
+            DmEnvelope{
+                version: LEGACY_DM_ENVELOPE_VERSION,
+                ratchet_pub_key: legacy_pub,
+                previous_counter: 0,
+                counter: 0,
+                nonce: [0u8;12],
+                ciphertext
+            }.encode()
+        };
+
+        assert_eq!(receive(&mut alice, alice_id_priv, &legacy_encrypt(chain_key, "old client"), 0x05).as_deref(), Some("old client"));
+        // Legacy sender advanced its chain after our receiver ack
+        assert_eq!(receive(&mut alice, alice_id_priv, &legacy_encrypt(kdf_ck(chain_key), "acked"), 0x06).as_deref(), Some("acked"));
+        // ... but not for offline messages, which reuse the same key
+        assert_eq!(receive(&mut alice, alice_id_priv, &legacy_encrypt(kdf_ck(chain_key), "offline"), 0x07).as_deref(), Some("offline"));
+    }
+
+    #[test]
+    fn state_round_trips_through_serde() {
+        let (_, _, mut bob, _) = session();
+        bob.encrypt("advance");
+        let json = serde_json::to_string(&bob).unwrap();
+        assert_eq!(serde_json::from_str::<DoubleRatchet>(&json).unwrap(), bob);
+    }
+}
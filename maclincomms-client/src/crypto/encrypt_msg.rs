@@ -1,8 +1,8 @@
 use std::collections::HashMap;
-use aes_gcm::{aead::Aead, AeadCore, Aes256Gcm, Key, KeyInit, Nonce};
+use aes_gcm::{aead::{Aead, Payload}, AeadCore, Aes256Gcm, Key, KeyInit, Nonce};
 use base64::{engine::general_purpose, Engine};
 use chrono::{DateTime, Duration, Utc};
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use ed25519_dalek::{ed25519::signature::SignerMut, Signature, SignatureError, SigningKey, VerifyingKey};
 use hkdf::Hkdf;
 use rand_core::OsRng;
@@ -11,25 +11,26 @@ use x25519_dalek::StaticSecret;
 
 use crate::{crypto::room_keys::derive_roommessage_key, user_model::DmSessionEncryption_Key};
 
-use super::dm_keys::derive_message_key;
+use super::{dm_envelope::DmEnvelope, dm_keys::derive_message_key};
 
 
-pub fn encrypt_dm_message(sending_chain_key: [u8;32], plaintext: &str) -> String {
-    let msg_key_slice = derive_message_key(sending_chain_key);
-    let key = Key::<Aes256Gcm>::from_slice(&msg_key_slice);
-    let nonce_slice = [0u8;12];
+//Fills the envelope header with a random nonce and the ciphertext, and encodes it
+pub fn encrypt_dm_message(message_key: [u8;32], mut envelope: DmEnvelope, plaintext: &str) -> String {
+    let key = Key::<Aes256Gcm>::from_slice(&message_key);
     let cipher = Aes256Gcm::new(&key);
-    let nonce = Nonce::from_slice(&nonce_slice);
-    let ciphertext_res = cipher.encrypt(&nonce, plaintext.as_bytes());
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng); //96 Bits Random Nonce Per Message
+    envelope.nonce = nonce.into();
+    let aad = envelope.associated_data();
+
+    let ciphertext_res = cipher.encrypt(&nonce, Payload{ msg: plaintext.as_bytes(), aad: &aad });
     match ciphertext_res{
         Ok(ciphertext_bytes) => {
-            //Encoding ciphertext bytes to base 64
-            let ciphertext = general_purpose::STANDARD.encode(ciphertext_bytes);
-            //RETURNING ENCRYPTED MESSAGE (Base64 Encoded)
-            return ciphertext;
+            envelope.ciphertext = ciphertext_bytes;
+            //RETURNING ENCRYPTED MESSAGE ENVELOPE (Base64 Encoded Parts)
+            return envelope.encode();
         }
         Err(err) => {
-            //decryption error to be handled
+            //encryption error to be handled
             return "".to_string();
         }
     } 
@@ -174,7 +175,7 @@ pub fn encrypt_dm_chats_session(
     }
 
     //Rewriting updated session_keys_list to persistent storage
-    let persistent_dm_session_keys: DiskPersist<HashMap<String, DmSessionEncryption_Key>> = DiskPersist::init("persistent-dms-session-keys").unwrap();
+    let persistent_dm_session_keys: SealedPersist<HashMap<String, DmSessionEncryption_Key>> = SealedPersist::init("persistent-dms-session-keys").unwrap();
     persistent_dm_session_keys.write(&dm_session_keys).unwrap();
 
 
@@ -1,7 +1,10 @@
 use base64::{engine::general_purpose, Engine};
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
+use curve25519_dalek::{edwards::EdwardsPoint, montgomery::MontgomeryPoint, scalar::{clamp_integer, Scalar}};
+use ed25519_dalek::{Signature, VerifyingKey};
+use sha2::{Digest, Sha512};
 use x25519_dalek::{PublicKey, StaticSecret};
-use rand_core::{OsRng};
+use rand_core::{OsRng, RngCore};
 
 use crate::user_model::UserIdentityKeys;
 
@@ -18,7 +21,7 @@ pub fn generate_identity_keypair() -> String {
     let public_key_b64 = general_purpose::STANDARD.encode(public_key.as_bytes());
 
     //Storing private key in persistent storage
-    let persistent_storage: DiskPersist<UserIdentityKeys> = DiskPersist::init("persistent-user-identity-keypair").unwrap();
+    let persistent_storage: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-user-identity-keypair").unwrap();
 
     let id_keypair_data = UserIdentityKeys {
         public_identity_key: public_key_b64.clone(),
@@ -28,4 +31,117 @@ pub fn generate_identity_keypair() -> String {
     persistent_storage.write(&id_keypair_data).unwrap();
 
     return public_key_b64;
-}
\ No newline at end of file
+}
+
+
+//Signed along with both keys so the signature can't stand for anything else
+const IDENTITY_ROTATION_CONTEXT: &[u8] = b"maclincomms-identity-rotation";
+
+pub fn identity_rotation_message(old_identity_pub_key: [u8;32], new_identity_pub_key: [u8;32]) -> Vec<u8> {
+    [IDENTITY_ROTATION_CONTEXT, &old_identity_pub_key, &new_identity_pub_key].concat()
+}
+
+//XEdDSA (as in Signal) so the X25519 identity key itself vouches for its successor
+pub fn sign_identity_rotation(old_identity_priv_key: [u8;32], new_identity_pub_key: [u8;32]) -> [u8;64] {
+    let old_identity_pub_key = PublicKey::from(&StaticSecret::from(old_identity_priv_key)).to_bytes();
+    let message = identity_rotation_message(old_identity_pub_key, new_identity_pub_key);
+
+    let mut random = [0u8;64];
+    OsRng.fill_bytes(&mut random);
+    return xeddsa_sign(old_identity_priv_key, &message, random);
+}
+
+pub fn verify_identity_rotation(old_identity_pub_key: [u8;32], new_identity_pub_key: [u8;32], signature: &[u8]) -> bool {
+    //The Edwards form of the key, with the sign bit XEdDSA signs with
+    let Some(edwards_key) = MontgomeryPoint(old_identity_pub_key).to_edwards(0) else {
+        return false;
+    };
+    let (Ok(verifying_key), Ok(signature)) = (VerifyingKey::from_bytes(&edwards_key.compress().to_bytes()), Signature::from_slice(signature)) else {
+        return false;
+    };
+    return verifying_key.verify_strict(&identity_rotation_message(old_identity_pub_key, new_identity_pub_key), &signature).is_ok();
+}
+
+fn xeddsa_sign(identity_priv_key: [u8;32], message: &[u8], random: [u8;64]) -> [u8;64] {
+    //Key pair whose public key has the sign bit cleared
+    let k = Scalar::from_bytes_mod_order(clamp_integer(identity_priv_key));
+    let mut public_key = EdwardsPoint::mul_base(&k).compress().to_bytes();
+    let a = match public_key[31] & 0x80 {
+        0 => k,
+        _ => -k
+    };
+    public_key[31] &= 0x7F;
+
+    //r = hash1(a || M || Z), hash1 being SHA-512 prefixed with 2^256 - 2
+    let mut nonce_hash = Sha512::new();
+    nonce_hash.update([0xFEu8]);
+    nonce_hash.update([0xFFu8; 31]);
+    nonce_hash.update(a.as_bytes());
+    nonce_hash.update(message);
+    nonce_hash.update(random);
+    let r = Scalar::from_bytes_mod_order_wide(&nonce_hash.finalize().into());
+    let big_r = EdwardsPoint::mul_base(&r).compress().to_bytes();
+
+    let mut challenge_hash = Sha512::new();
+    challenge_hash.update(big_r);
+    challenge_hash.update(public_key);
+    challenge_hash.update(message);
+    let h = Scalar::from_bytes_mod_order_wide(&challenge_hash.finalize().into());
+    let s = r + h*a;
+
+    let mut signature = [0u8;64];
+    signature[..32].copy_from_slice(&big_r);
+    signature[32..].copy_from_slice(s.as_bytes());
+    return signature;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::dm_keys::generate_dh_keypair;
+
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    //The server's identity_signature tests check the same vector
+    #[test]
+    fn rotation_vector_matches_the_server() {
+        let old_priv = [0x11;32];
+        let old_pub = PublicKey::from(&StaticSecret::from(old_priv)).to_bytes();
+        let new_pub = PublicKey::from(&StaticSecret::from([0x22;32])).to_bytes();
+        assert_eq!(hex(&old_pub), "7b4e909bbe7ffe44c465a220037d608ee35897d31ef972f07f74892cb0f73f13");
+        assert_eq!(hex(&new_pub), "0faa684ed28867b97f4a6a2dee5df8ce974e76b7018e3f22a1c4cf2678570f20");
+
+        let signature = xeddsa_sign(old_priv, &identity_rotation_message(old_pub, new_pub), [0;64]);
+        assert_eq!(hex(&signature), "2d4d37e7ca9bec4556391ca8769cd03c3bedd6715af2a92ce91f6b9774df4f1e2a73abf640bef101f37e55a18aa1c1821cc0806eb22ce1c659de16563e666300");
+        assert!(verify_identity_rotation(old_pub, new_pub, &signature));
+    }
+
+    #[test]
+    fn the_old_key_vouches_for_the_new_one() {
+        //Enough key pairs that both signs of the Edwards key come up
+        for _ in 0..16 {
+            let (old_pub, old_priv) = generate_dh_keypair();
+            let (new_pub, _) = generate_dh_keypair();
+
+            let signature = sign_identity_rotation(old_priv, new_pub);
+            assert!(verify_identity_rotation(old_pub, new_pub, &signature));
+        }
+    }
+
+    #[test]
+    fn rotations_dont_verify_under_other_keys() {
+        let (old_pub, old_priv) = generate_dh_keypair();
+        let (new_pub, _) = generate_dh_keypair();
+        let (other_pub, other_priv) = generate_dh_keypair();
+
+        let signature = sign_identity_rotation(old_priv, new_pub);
+        assert!(!verify_identity_rotation(other_pub, new_pub, &signature));
+        assert!(!verify_identity_rotation(old_pub, other_pub, &signature));
+        assert!(!verify_identity_rotation(old_pub, new_pub, &sign_identity_rotation(other_priv, new_pub)));
+        assert!(!verify_identity_rotation(old_pub, new_pub, &signature[..63]));
+    }
+}
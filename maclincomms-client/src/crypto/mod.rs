@@ -1,6 +1,14 @@
 pub mod encrypt_msg;
 pub mod decrypt_msg;
+pub mod dm_envelope;
 pub mod identity_pair;
 pub mod dm_keys;
+pub mod double_ratchet;
 pub mod room_keys;
-pub mod signature_keypair;
\ No newline at end of file
+pub mod signature_keypair;pub mod x3dh;
+pub mod safety_number;
+pub mod room_whisper;
+pub mod room_history;
+pub mod device_link;
+pub mod store_seal;
+pub mod account_backup;
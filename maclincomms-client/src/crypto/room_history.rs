@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+
+use ed25519_dalek::{ed25519::signature::SignerMut, Signature, SigningKey, VerifyingKey};
+
+use crate::user_model::{RoomHistoryMessage, ROOM_HISTORY_BYTE, ROOM_HISTORY_LIMIT};
+
+use super::{decrypt_msg::decrypt_senderkey_message, dm_keys::{derive_message_key, generate_dh_keypair, generate_receiver_chainkey, generate_sender_chainkey, generate_shared_rootkey}, encrypt_msg::encrypt_senderkey_message};
+
+
+//Room frames are held to 64KiB by the server, the sealed history has to fit in one
+const MAX_SEALED_HISTORY_BYTES: usize = 48 * 1024;
+
+
+// History for a newcomer goes the way sender keys do, over a pairwise chain from a fresh DH pair and their identity key.
+// The DH pair is never reused, so the zeroes nonce of encrypt_senderkey_message stays safe.
+// Signed with the sharer's room signature key like their live messages. [DH Public Key][Signature][Ciphertext]
+pub fn seal_room_history(their_idpub_key: [u8;32], my_sigpriv_key: [u8;32], history: &[RoomHistoryMessage]) -> Vec<u8> {
+    //Oldest messages give way first when the history is too big for one frame
+    let mut start = 0;
+    let mut plaintext = serde_json::to_vec(history).unwrap();
+    while plaintext.len() > MAX_SEALED_HISTORY_BYTES && start < history.len() {
+        start += 1;
+        plaintext = serde_json::to_vec(&history[start..]).unwrap();
+    }
+
+    let (public_dh_key, private_dh_key) = generate_dh_keypair();
+    let rootkey = generate_shared_rootkey(their_idpub_key, private_dh_key);
+    let sending_chainkey = generate_sender_chainkey(rootkey);
+    let ciphertext = encrypt_senderkey_message(sending_chainkey, &plaintext);
+
+    let signature = SigningKey::from_bytes(&my_sigpriv_key).sign(&[public_dh_key.to_vec(), ciphertext.clone()].concat());
+    return [public_dh_key.to_vec(), signature.to_bytes().to_vec(), ciphertext].concat();
+}
+
+//None if it wasn't sealed for us, or the sharer's signature key didn't sign it
+pub fn open_room_history(my_idpriv_key: [u8;32], their_sigpub_key: [u8;32], sealed: &[u8]) -> Option<Vec<RoomHistoryMessage>> {
+    if sealed.len() <= 32 + 64 {
+        return None;
+    }
+    let their_dh_pub_key: [u8;32] = sealed[..32].try_into().ok()?;
+    let signature = Signature::from_bytes(&sealed[32..96].try_into().ok()?);
+    let ciphertext = &sealed[96..];
+    VerifyingKey::from_bytes(&their_sigpub_key).ok()?
+        .verify_strict(&[&their_dh_pub_key[..], ciphertext].concat(), &signature).ok()?;
+
+    let rootkey = generate_shared_rootkey(their_dh_pub_key, my_idpriv_key);
+    let receiving_chainkey = generate_receiver_chainkey(rootkey);
+    let plaintext = decrypt_senderkey_message(derive_message_key(receiving_chainkey), ciphertext);
+    return serde_json::from_slice(&plaintext).ok();
+}
+
+//Shared messages go in front of those seen since joining. Like keep_room_history, the oldest give way past the limit
+pub fn merge_room_history(room_history: &mut VecDeque<RoomHistoryMessage>, earlier: Vec<RoomHistoryMessage>) {
+    for message in earlier.into_iter().rev() {
+        room_history.push_front(message);
+    }
+    while room_history.len() > ROOM_HISTORY_LIMIT {
+        room_history.pop_front();
+    }
+}
+
+pub fn compose_room_history_frame(username: &str, sealed: Vec<u8>) -> Vec<u8> {
+    return [vec![ROOM_HISTORY_BYTE, username.len() as u8], username.as_bytes().to_vec(), sealed].concat();
+}
+
+//Username and sealed history
+pub fn parse_room_history_frame(frame: &[u8]) -> Option<(String, Vec<u8>)> {
+    if frame.len() < 2 || frame[0]!=ROOM_HISTORY_BYTE {
+        return None;
+    }
+    let username_end = 2 + frame[1] as usize;
+    let username = String::from_utf8(frame.get(2..username_end)?.to_vec()).ok()?;
+    return Some((username, frame[username_end..].to_vec()));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::signature_keypair::generate_signature_keypair;
+
+    use super::*;
+
+    fn message(username: &str, content: &str) -> RoomHistoryMessage {
+        RoomHistoryMessage { username: username.to_string(), content: content.to_string(), time: "12:00".to_string() }
+    }
+
+    #[test]
+    fn only_the_newcomer_opens_the_history() {
+        let (bob_pub, bob_priv) = generate_dh_keypair();
+        let (_, carol_priv) = generate_dh_keypair();
+        let (alice_sigpub, alice_sigpriv) = generate_signature_keypair();
+        let history = vec![message("alice", "hi"), message("carol", "hey")];
+
+        let frame = compose_room_history_frame("bob", seal_room_history(bob_pub, alice_sigpriv, &history));
+        let (username, sealed) = parse_room_history_frame(&frame).unwrap();
+        assert_eq!(username, "bob");
+
+        assert_eq!(open_room_history(bob_priv, alice_sigpub, &sealed), Some(history));
+        assert_eq!(open_room_history(carol_priv, alice_sigpub, &sealed), None);
+    }
+
+    #[test]
+    fn only_the_sharers_signature_key_opens_the_history() {
+        let (bob_pub, bob_priv) = generate_dh_keypair();
+        let (alice_sigpub, alice_sigpriv) = generate_signature_keypair();
+        let (mallory_sigpub, mallory_sigpriv) = generate_signature_keypair();
+        let history = vec![message("alice", "hi")];
+
+        //Passed off as alice's, but signed by someone else
+        let forged = seal_room_history(bob_pub, mallory_sigpriv, &history);
+        assert_eq!(open_room_history(bob_priv, alice_sigpub, &forged), None);
+        assert_eq!(open_room_history(bob_priv, mallory_sigpub, &forged), Some(history.clone()));
+
+        let mut tampered = seal_room_history(bob_pub, alice_sigpriv, &history);
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert_eq!(open_room_history(bob_priv, alice_sigpub, &tampered), None);
+    }
+
+    #[test]
+    fn merging_a_full_history_keeps_the_newest_messages() {
+        let mut room_history: VecDeque<RoomHistoryMessage> = (0..5).map(|i| message("bob", &format!("since joining {}", i))).collect();
+        let earlier: Vec<RoomHistoryMessage> = (0..ROOM_HISTORY_LIMIT).map(|i| message("alice", &format!("earlier {}", i))).collect();
+
+        merge_room_history(&mut room_history, earlier);
+        assert_eq!(room_history.len(), ROOM_HISTORY_LIMIT);
+        assert_eq!(room_history.front(), Some(&message("alice", "earlier 5")));
+        assert_eq!(room_history.back(), Some(&message("bob", "since joining 4")));
+    }
+
+    #[test]
+    fn oversized_history_drops_its_oldest_messages() {
+        let (bob_pub, bob_priv) = generate_dh_keypair();
+        let (alice_sigpub, alice_sigpriv) = generate_signature_keypair();
+        let history: Vec<RoomHistoryMessage> = (0..10).map(|i| message("alice", &format!("{}{}", i, "x".repeat(8 * 1024)))).collect();
+
+        let opened = open_room_history(bob_priv, alice_sigpub, &seal_room_history(bob_pub, alice_sigpriv, &history)).unwrap();
+        assert!(opened.len() < history.len());
+        assert_eq!(opened.last(), history.last());
+    }
+}
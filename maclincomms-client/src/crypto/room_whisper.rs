@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use aes_gcm::{aead::{Aead, Payload}, AeadCore, Aes256Gcm, Key, KeyInit, Nonce};
+use base64::{engine::general_purpose, Engine};
+use hkdf::Hkdf;
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use super::{double_ratchet::dh, dm_keys::generate_dh_keypair, encrypt_msg::generate_session_key};
+
+
+//Prefix of whisper payloads in room messages, "w1:[Base64 JSON RoomWhisper].[Signature]"
+pub const ROOM_WHISPER_PREFIX: &str = "w1:";
+const ROOM_WHISPER_INFO: &[u8] = b"maclincomms-room-whisper";
+
+
+// Whispers (--hf / --sw) are not encrypted with the room chain key, which every member holds.
+// The message is sealed with a fresh content key, and that key is wrapped for each recipient with
+// HKDF(DH(Ephemeral, Their ID) || DH(My ID, Their ID)), so only the recipients can read it whoever gets the frame.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RoomWhisper {
+    pub ephemeral_key: String,
+    pub nonce: String,
+    pub ciphertext: String,
+    pub wrapped_keys: HashMap<String, String> // Recipient username -> content key encrypted for them (Base64)
+}
+
+
+//Wrapping key for one recipient, the username is bound in so wrapped keys can't be moved between recipients
+fn derive_wrapping_key(ephemeral_dh: [u8;32], identity_dh: [u8;32], recipient: &str) -> [u8;32] {
+    let hk = Hkdf::<Sha256>::new(None, &[ephemeral_dh, identity_dh].concat());
+    let mut wrapping_key = [0u8;32];
+    hk.expand(&[ROOM_WHISPER_INFO, recipient.as_bytes()].concat(), &mut wrapping_key).unwrap();
+    return wrapping_key;
+}
+
+//Every wrapping key is used once (fresh ephemeral per whisper), so a zeroes nonce is fine for it
+fn wrap_content_key(wrapping_key: [u8;32], content_key: &[u8]) -> Option<Vec<u8>> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&wrapping_key));
+    cipher.encrypt(Nonce::from_slice(&[0u8;12]), content_key).ok()
+}
+
+fn unwrap_content_key(wrapping_key: [u8;32], wrapped_key: &[u8]) -> Option<[u8;32]> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&wrapping_key));
+    cipher.decrypt(Nonce::from_slice(&[0u8;12]), wrapped_key).ok()?.try_into().ok()
+}
+
+
+//Whisper payload for the given recipients (username, identity public key), without the signature
+pub fn encrypt_room_whisper(my_idpriv_key: [u8;32], recipients: &[(String, [u8;32])], plaintext: &str) -> String {
+    let content_key = generate_session_key();
+    let (ephemeral_pub_key, ephemeral_priv_key) = generate_dh_keypair();
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&content_key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let Ok(ciphertext) = cipher.encrypt(&nonce, Payload { msg: plaintext.as_bytes(), aad: &ephemeral_pub_key }) else {
+        return "".to_string();
+    };
+
+    let mut wrapped_keys = HashMap::new();
+    for (recipient, their_idpub_key) in recipients {
+        let wrapping_key = derive_wrapping_key(dh(ephemeral_priv_key, *their_idpub_key), dh(my_idpriv_key, *their_idpub_key), recipient);
+        if let Some(wrapped_key) = wrap_content_key(wrapping_key, &content_key) {
+            wrapped_keys.insert(recipient.clone(), general_purpose::STANDARD.encode(wrapped_key));
+        }
+    }
+
+    let whisper = RoomWhisper {
+        ephemeral_key: general_purpose::STANDARD.encode(ephemeral_pub_key),
+        nonce: general_purpose::STANDARD.encode(nonce),
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        wrapped_keys
+    };
+    return ROOM_WHISPER_PREFIX.to_string() + &general_purpose::STANDARD.encode(serde_json::to_vec(&whisper).unwrap());
+}
+
+pub fn is_room_whisper(payload: &str) -> bool {
+    payload.starts_with(ROOM_WHISPER_PREFIX)
+}
+
+//None if the whisper wasn't meant for us or doesn't decrypt
+pub fn decrypt_room_whisper(my_idpriv_key: [u8;32], my_username: &str, their_idpub_key: [u8;32], payload: &str) -> Option<String> {
+    let encoded = payload.strip_prefix(ROOM_WHISPER_PREFIX)?;
+    let whisper: RoomWhisper = serde_json::from_slice(&general_purpose::STANDARD.decode(encoded).ok()?).ok()?;
+
+    let wrapped_key = general_purpose::STANDARD.decode(whisper.wrapped_keys.get(my_username)?).ok()?;
+    let ephemeral_pub_key: [u8;32] = general_purpose::STANDARD.decode(&whisper.ephemeral_key).ok()?.try_into().ok()?;
+    let nonce: [u8;12] = general_purpose::STANDARD.decode(&whisper.nonce).ok()?.try_into().ok()?;
+    let ciphertext = general_purpose::STANDARD.decode(&whisper.ciphertext).ok()?;
+
+    let wrapping_key = derive_wrapping_key(dh(my_idpriv_key, ephemeral_pub_key), dh(my_idpriv_key, their_idpub_key), my_username);
+    let content_key = unwrap_content_key(wrapping_key, &wrapped_key)?;
+
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&content_key));
+    let plaintext = cipher.decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &ephemeral_pub_key }).ok()?;
+    return String::from_utf8(plaintext).ok();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_recipients_can_read_a_whisper() {
+        let (alice_pub, alice_priv) = generate_dh_keypair();
+        let (bob_pub, bob_priv) = generate_dh_keypair();
+        let (carol_pub, carol_priv) = generate_dh_keypair();
+        let (_, dave_priv) = generate_dh_keypair();
+
+        let payload = encrypt_room_whisper(alice_priv, &[("bob".to_string(), bob_pub), ("carol".to_string(), carol_pub)], "psst");
+        assert!(is_room_whisper(&payload));
+
+        assert_eq!(decrypt_room_whisper(bob_priv, "bob", alice_pub, &payload).as_deref(), Some("psst"));
+        assert_eq!(decrypt_room_whisper(carol_priv, "carol", alice_pub, &payload).as_deref(), Some("psst"));
+
+        //A hidden member holding the whole frame gets nothing, even by claiming a recipient's name
+        assert_eq!(decrypt_room_whisper(dave_priv, "dave", alice_pub, &payload), None);
+        assert_eq!(decrypt_room_whisper(dave_priv, "bob", alice_pub, &payload), None);
+    }
+
+    #[test]
+    fn wrapped_keys_are_bound_to_sender_and_recipient() {
+        let (alice_pub, alice_priv) = generate_dh_keypair();
+        let (bob_pub, bob_priv) = generate_dh_keypair();
+        let (mallory_pub, _) = generate_dh_keypair();
+
+        let payload = encrypt_room_whisper(alice_priv, &[("bob".to_string(), bob_pub)], "psst");
+
+        //Someone else's identity as sender doesn't unwrap
+        assert_eq!(decrypt_room_whisper(bob_priv, "bob", mallory_pub, &payload), None);
+
+        //Bob's wrapped key moved under another name doesn't unwrap either
+        let encoded = payload.strip_prefix(ROOM_WHISPER_PREFIX).unwrap();
+        let mut whisper: RoomWhisper = serde_json::from_slice(&general_purpose::STANDARD.decode(encoded).unwrap()).unwrap();
+        let bobs_key = whisper.wrapped_keys.remove("bob").unwrap();
+        whisper.wrapped_keys.insert("b0b".to_string(), bobs_key);
+        let moved = ROOM_WHISPER_PREFIX.to_string() + &general_purpose::STANDARD.encode(serde_json::to_vec(&whisper).unwrap());
+        assert_eq!(decrypt_room_whisper(bob_priv, "b0b", alice_pub, &moved), None);
+
+        assert_eq!(decrypt_room_whisper(bob_priv, "bob", alice_pub, &payload).as_deref(), Some("psst"));
+    }
+}
@@ -0,0 +1,93 @@
+use sha2::{Digest, Sha512};
+
+
+const FINGERPRINT_VERSION: [u8;2] = [0, 0];
+const FINGERPRINT_ITERATIONS: usize = 5200;
+
+
+// Safety numbers (as in Signal) for comparing identity keys with a DM contact out of band.
+// Each side gets a 30 digit fingerprint of their username and identity key, the safety number is both
+// fingerprints in sorted order so it reads the same on either side.
+
+
+//SHA-512 of (version || identity key || username), re-hashed with the key 5200 times, first 30 bytes as 6 groups of 5 digits
+fn fingerprint(username: &str, identity_pub_key: [u8;32]) -> String {
+    let mut hash = Sha512::new()
+        .chain_update(FINGERPRINT_VERSION)
+        .chain_update(identity_pub_key)
+        .chain_update(username.as_bytes())
+        .finalize();
+
+    for _ in 1..FINGERPRINT_ITERATIONS {
+        hash = Sha512::new()
+            .chain_update(hash)
+            .chain_update(identity_pub_key)
+            .finalize();
+    }
+
+    return hash[..30].chunks(5)
+        .map(|chunk| {
+            let value = chunk.iter().fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+            format!("{:05}", value % 100000)
+        })
+        .collect();
+}
+
+//60 digit safety number
+pub fn safety_number(my_username: &str, my_identity_pub_key: [u8;32], their_username: &str, their_identity_pub_key: [u8;32]) -> String {
+    let mut fingerprints = [
+        fingerprint(my_username, my_identity_pub_key),
+        fingerprint(their_username, their_identity_pub_key)
+    ];
+    fingerprints.sort();
+    return fingerprints.concat();
+}
+
+//Groups of 5 digits, 4 groups per line
+pub fn format_safety_number(safety_number: &str) -> Vec<String> {
+    let groups: Vec<&str> = safety_number.as_bytes()
+        .chunks(5)
+        .map(|group| std::str::from_utf8(group).unwrap())
+        .collect();
+
+    return groups.chunks(4)
+        .map(|line| line.join(" "))
+        .collect();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_sides_see_the_same_number() {
+        let alice_key = [1u8;32];
+        let bob_key = [2u8;32];
+
+        let alices_view = safety_number("alice", alice_key, "bob", bob_key);
+        assert_eq!(alices_view, safety_number("bob", bob_key, "alice", alice_key));
+        assert_eq!(alices_view.len(), 60);
+        assert!(alices_view.chars().all(|c| c.is_ascii_digit()));
+
+        //A swapped key or username changes the number
+        assert_ne!(alices_view, safety_number("alice", alice_key, "bob", [3u8;32]));
+        assert_ne!(alices_view, safety_number("alice", alice_key, "mallory", bob_key));
+    }
+
+    // Vector cross-checked against an independent SHA-512 implementation
+    #[test]
+    fn fingerprint_vector() {
+        assert_eq!(fingerprint("alice", [1u8;32]), "962065022068759816662896432951");
+    }
+
+    #[test]
+    fn formats_into_lines_of_four_groups() {
+        let lines = format_safety_number(&"0123456789".repeat(6));
+        assert_eq!(lines, vec![
+            "01234 56789 01234 56789",
+            "01234 56789 01234 56789",
+            "01234 56789 01234 56789"
+        ]);
+    }
+}
@@ -0,0 +1,126 @@
+use aes_gcm::{aead::{Aead, Payload}, AeadCore, Aes256Gcm, Key, KeyInit, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+
+pub const STORE_SALT_LEN: usize = 16;
+
+//Ceiling for parameters read back from disk, a tampered file shouldn't be able to exhaust memory or hang the unlock
+const MAX_M_COST: u32 = 1024*1024; //KiB
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 8;
+
+
+//Argon2id cost of the local store key, kept next to the salt so it can be raised later without locking anyone out
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct StoreKdfParams{
+    pub m_cost: u32, //KiB
+    pub t_cost: u32,
+    pub p_cost: u32
+}
+
+impl StoreKdfParams{
+    pub fn recommended() -> Self{
+        Self {
+            m_cost: 64*1024,
+            t_cost: 3,
+            p_cost: 1
+        }
+    }
+
+    //Anything read from a file goes through this before it reaches Argon2
+    pub fn clamped(self) -> Self{
+        Self {
+            m_cost: self.m_cost.min(MAX_M_COST),
+            t_cost: self.t_cost.min(MAX_T_COST),
+            p_cost: self.p_cost.min(MAX_P_COST)
+        }
+    }
+}
+
+
+pub fn generate_store_salt() -> [u8; STORE_SALT_LEN] {
+    let mut salt = [0u8; STORE_SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    return salt;
+}
+
+//The key every persistent file is sealed with, None if the stored parameters are unusable
+pub fn derive_store_key(passphrase: &str, salt: &[u8], kdf_params: StoreKdfParams) -> Option<[u8;32]> {
+    let params = Params::new(kdf_params.m_cost, kdf_params.t_cost, kdf_params.p_cost, Some(32)).ok()?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+    let mut store_key = [0u8;32];
+    argon2.hash_password_into(passphrase.as_bytes(), salt, &mut store_key).ok()?;
+    return Some(store_key);
+}
+
+//[Nonce][Ciphertext], the file name goes in as associated data so sealed files can't be swapped for one another
+pub fn seal_store_bytes(store_key: [u8;32], file_name: &str, plaintext: &[u8]) -> Vec<u8> {
+    let key = Key::<Aes256Gcm>::from_slice(&store_key);
+    let cipher = Aes256Gcm::new(&key);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    match cipher.encrypt(&nonce, Payload{ msg: plaintext, aad: file_name.as_bytes() }) {
+        Ok(ciphertext) => [nonce.to_vec(), ciphertext].concat(),
+        Err(_) => Vec::new()
+    }
+}
+
+//None with the wrong key, or if the file was tampered with or renamed
+pub fn open_store_bytes(store_key: [u8;32], file_name: &str, sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() <= 12 {
+        return None;
+    }
+    let (nonce_bytes, ciphertext) = sealed.split_at(12);
+
+    let key = Key::<Aes256Gcm>::from_slice(&store_key);
+    let cipher = Aes256Gcm::new(&key);
+
+    return cipher.decrypt(Nonce::from_slice(nonce_bytes), Payload{ msg: ciphertext, aad: file_name.as_bytes() }).ok();
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Cheap enough for debug builds
+    fn test_params() -> StoreKdfParams {
+        StoreKdfParams { m_cost: 256, t_cost: 1, p_cost: 1 }
+    }
+
+    #[test]
+    fn the_same_passphrase_and_salt_give_the_same_key() {
+        let salt = generate_store_salt();
+        let store_key = derive_store_key("correct horse", &salt, test_params()).unwrap();
+
+        assert_eq!(derive_store_key("correct horse", &salt, test_params()), Some(store_key));
+        assert_ne!(derive_store_key("correct horse!", &salt, test_params()), Some(store_key));
+        assert_ne!(derive_store_key("correct horse", &generate_store_salt(), test_params()), Some(store_key));
+    }
+
+    #[test]
+    fn sealed_files_open_only_with_their_key_and_name() {
+        let salt = generate_store_salt();
+        let store_key = derive_store_key("correct horse", &salt, test_params()).unwrap();
+        let wrong_key = derive_store_key("battery staple", &salt, test_params()).unwrap();
+
+        let sealed = seal_store_bytes(store_key, "persistent-user-data", b"tokens");
+        assert_eq!(open_store_bytes(store_key, "persistent-user-data", &sealed), Some(b"tokens".to_vec()));
+        assert_eq!(open_store_bytes(wrong_key, "persistent-user-data", &sealed), None);
+        assert_eq!(open_store_bytes(store_key, "persistent-user-prekeys", &sealed), None);
+    }
+
+    #[test]
+    fn oversized_parameters_are_clamped() {
+        assert_eq!(StoreKdfParams::recommended().clamped(), StoreKdfParams::recommended());
+        assert_eq!(StoreKdfParams { m_cost: u32::MAX, t_cost: u32::MAX, p_cost: u32::MAX }.clamped(), StoreKdfParams { m_cost: MAX_M_COST, t_cost: MAX_T_COST, p_cost: MAX_P_COST });
+    }
+
+    #[test]
+    fn unusable_parameters_give_no_key() {
+        assert_eq!(derive_store_key("correct horse", &generate_store_salt(), StoreKdfParams { m_cost: 0, t_cost: 0, p_cost: 0 }), None);
+    }
+}
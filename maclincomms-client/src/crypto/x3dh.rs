@@ -0,0 +1,210 @@
+use base64::{engine::general_purpose, Engine};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
+use crate::user_model::{PrekeyBundle, UserIdentityKeys, UserPrekeys, X3dhHandshake};
+
+use super::{double_ratchet::{dh, DoubleRatchet}, dm_keys::generate_dh_keypair};
+
+
+const X3DH_INFO: &[u8] = b"maclincomms-x3dh";
+
+
+// X3DH (Signal's Extended Triple Diffie-Hellman) for starting a DM session with a user who may be offline.
+// The user accepting an add request initiates with the requester's prekey bundle from /prekey_bundle,
+// and the requester responds from the handshake carried in the ACCEPTED notification.
+
+
+//Signature over our identity key and signed prekey, made with the Ed25519 identity signing key
+pub fn sign_prekey(signing_priv_key: [u8;32], identity_pub_key: [u8;32], signed_prekey: [u8;32]) -> [u8;64] {
+    let signing_key = SigningKey::from_bytes(&signing_priv_key);
+    return signing_key.sign(&[identity_pub_key, signed_prekey].concat()).to_bytes();
+}
+
+pub fn verify_prekey_signature(signing_pub_key: [u8;32], identity_pub_key: [u8;32], signed_prekey: [u8;32], signature: &[u8]) -> bool {
+    let (Ok(verifying_key), Ok(signature)) = (VerifyingKey::from_bytes(&signing_pub_key), Signature::from_slice(signature)) else {
+        return false;
+    };
+    return verifying_key.verify_strict(&[identity_pub_key, signed_prekey].concat(), &signature).is_ok();
+}
+
+//SK = HKDF(F || DH1 || DH2 || DH3 [|| DH4]), F being 32 0xFF bytes as in the X3DH spec for X25519
+fn kdf_x3dh(dh_outputs: &[[u8;32]]) -> [u8;32] {
+    let mut ikm = vec![0xFFu8; 32];
+    for dh_output in dh_outputs {
+        ikm.extend_from_slice(dh_output);
+    }
+    let hk = Hkdf::<Sha256>::new(Some(&[0u8;32]), &ikm);
+    let mut shared_key = [0u8;32];
+    hk.expand(X3DH_INFO, &mut shared_key).unwrap();
+    return shared_key;
+}
+
+//Initiator: DH1 = DH(IK_A, SPK_B), DH2 = DH(EK_A, IK_B), DH3 = DH(EK_A, SPK_B), DH4 = DH(EK_A, OPK_B)
+pub fn x3dh_initiate(
+    identity_priv_key: [u8;32],
+    ephemeral_priv_key: [u8;32],
+    their_identity_pub_key: [u8;32],
+    their_signed_prekey: [u8;32],
+    their_one_time_prekey: Option<[u8;32]>
+) -> [u8;32] {
+    let mut dh_outputs = vec![
+        dh(identity_priv_key, their_signed_prekey),
+        dh(ephemeral_priv_key, their_identity_pub_key),
+        dh(ephemeral_priv_key, their_signed_prekey)
+    ];
+    if let Some(one_time_prekey) = their_one_time_prekey {
+        dh_outputs.push(dh(ephemeral_priv_key, one_time_prekey));
+    }
+    return kdf_x3dh(&dh_outputs);
+}
+
+//Responder: same DH outputs from the other side
+pub fn x3dh_respond(
+    identity_priv_key: [u8;32],
+    signed_prekey_priv_key: [u8;32],
+    one_time_prekey_priv_key: Option<[u8;32]>,
+    their_identity_pub_key: [u8;32],
+    their_ephemeral_key: [u8;32]
+) -> [u8;32] {
+    let mut dh_outputs = vec![
+        dh(signed_prekey_priv_key, their_identity_pub_key),
+        dh(identity_priv_key, their_ephemeral_key),
+        dh(signed_prekey_priv_key, their_ephemeral_key)
+    ];
+    if let Some(one_time_prekey_priv) = one_time_prekey_priv_key {
+        dh_outputs.push(dh(one_time_prekey_priv, their_ephemeral_key));
+    }
+    return kdf_x3dh(&dh_outputs);
+}
+
+
+//Initiator side for accept_user: DM ratchet and the handshake to send along.
+//None if the bundle doesn't verify, the ratchet then starts from identity keys as before
+pub fn initiate_x3dh_handshake(bundle: &PrekeyBundle) -> Option<(DoubleRatchet, X3dhHandshake)> {
+    let identity_priv_key = UserIdentityKeys::load_private_key()?;
+
+    let decode = |key: &str| -> Option<[u8;32]> { general_purpose::STANDARD.decode(key).ok()?.try_into().ok() };
+    let their_identity_pub_key = decode(&bundle.public_identity_key)?;
+    let signing_pub_key = decode(&bundle.identity_signing_key)?;
+    let signed_prekey = decode(&bundle.signed_prekey)?;
+    let signature = general_purpose::STANDARD.decode(&bundle.signed_prekey_signature).ok()?;
+
+    if !verify_prekey_signature(signing_pub_key, their_identity_pub_key, signed_prekey, &signature) {
+        return None;
+    }
+
+    let one_time_prekey = match &bundle.one_time_prekey {
+        Some(one_time_prekey) => Some((one_time_prekey.key_id, decode(&one_time_prekey.prekey)?)),
+        None => None
+    };
+
+    let (ephemeral_pub_key, ephemeral_priv_key) = generate_dh_keypair();
+    let shared_key = x3dh_initiate(
+        identity_priv_key,
+        ephemeral_priv_key,
+        their_identity_pub_key,
+        signed_prekey,
+        one_time_prekey.map(|(_, prekey)| prekey)
+    );
+
+    let handshake = X3dhHandshake {
+        ephemeral_key: general_purpose::STANDARD.encode(ephemeral_pub_key),
+        signed_prekey_id: bundle.signed_prekey_id,
+        one_time_prekey_id: one_time_prekey.map(|(key_id, _)| key_id)
+    };
+
+    return Some((DoubleRatchet::from_x3dh(shared_key, signed_prekey, ephemeral_priv_key), handshake));
+}
+
+//Responder side for ACCEPTED notifications, using up the one time prekey the initiator was given
+pub fn accept_x3dh_handshake(their_identity_pub_key: [u8;32], handshake: &X3dhHandshake) -> Option<DoubleRatchet> {
+    let identity_priv_key = UserIdentityKeys::load_private_key()?;
+    let mut prekeys = UserPrekeys::load()?;
+
+    if handshake.signed_prekey_id!=prekeys.signed_prekey_id {
+        return None;
+    }
+    let their_ephemeral_key: [u8;32] = general_purpose::STANDARD.decode(&handshake.ephemeral_key).ok()?.try_into().ok()?;
+
+    let one_time_prekey_priv = match handshake.one_time_prekey_id {
+        Some(key_id) => Some(prekeys.take_one_time_prekey(key_id)?),
+        None => None
+    };
+    prekeys.save();
+
+    let shared_key = x3dh_respond(
+        identity_priv_key,
+        prekeys.signed_prekey_priv,
+        one_time_prekey_priv,
+        their_identity_pub_key,
+        their_ephemeral_key
+    );
+
+    return Some(DoubleRatchet::from_x3dh(shared_key, their_ephemeral_key, prekeys.signed_prekey_priv));
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::{dm_envelope::DmEnvelope, signature_keypair::generate_signature_keypair};
+
+    use super::*;
+
+    #[test]
+    fn both_sides_derive_the_same_shared_key() {
+        let (alice_id_pub, alice_id_priv) = generate_dh_keypair();
+        let (bob_id_pub, bob_id_priv) = generate_dh_keypair();
+        let (bob_spk_pub, bob_spk_priv) = generate_dh_keypair();
+        let (bob_opk_pub, bob_opk_priv) = generate_dh_keypair();
+        let (eph_pub, eph_priv) = generate_dh_keypair();
+
+        let with_opk = x3dh_initiate(alice_id_priv, eph_priv, bob_id_pub, bob_spk_pub, Some(bob_opk_pub));
+        assert_eq!(with_opk, x3dh_respond(bob_id_priv, bob_spk_priv, Some(bob_opk_priv), alice_id_pub, eph_pub));
+
+        let without_opk = x3dh_initiate(alice_id_priv, eph_priv, bob_id_pub, bob_spk_pub, None);
+        assert_eq!(without_opk, x3dh_respond(bob_id_priv, bob_spk_priv, None, alice_id_pub, eph_pub));
+        assert_ne!(with_opk, without_opk);
+
+        //A different identity on either side gives a different key
+        let (_, mallory_id_priv) = generate_dh_keypair();
+        assert_ne!(with_opk, x3dh_initiate(mallory_id_priv, eph_priv, bob_id_pub, bob_spk_pub, Some(bob_opk_pub)));
+    }
+
+    #[test]
+    fn signed_prekey_signature_binds_identity_and_prekey() {
+        let (signing_pub, signing_priv) = generate_signature_keypair();
+        let (id_pub, _) = generate_dh_keypair();
+        let (spk_pub, _) = generate_dh_keypair();
+        let (other_pub, _) = generate_dh_keypair();
+
+        let signature = sign_prekey(signing_priv, id_pub, spk_pub);
+        assert!(verify_prekey_signature(signing_pub, id_pub, spk_pub, &signature));
+        assert!(!verify_prekey_signature(signing_pub, id_pub, other_pub, &signature));
+        assert!(!verify_prekey_signature(signing_pub, other_pub, spk_pub, &signature));
+        assert!(!verify_prekey_signature(signing_pub, id_pub, spk_pub, &signature[..63]));
+    }
+
+    #[test]
+    fn ratchets_from_x3dh_talk_in_both_directions() {
+        let (alice_id_pub, alice_id_priv) = generate_dh_keypair();
+        let (bob_id_pub, bob_id_priv) = generate_dh_keypair();
+        let (bob_spk_pub, bob_spk_priv) = generate_dh_keypair();
+        let (eph_pub, eph_priv) = generate_dh_keypair();
+
+        let shared_key = x3dh_initiate(alice_id_priv, eph_priv, bob_id_pub, bob_spk_pub, None);
+        let alice = DoubleRatchet::from_x3dh(shared_key, bob_spk_pub, eph_priv);
+        let bob = DoubleRatchet::from_x3dh(x3dh_respond(bob_id_priv, bob_spk_priv, None, alice_id_pub, eph_pub), eph_pub, bob_spk_priv);
+
+        //Either side may send first
+        for (mut sender, mut receiver, receiver_id_priv) in [(alice.clone(), bob.clone(), bob_id_priv), (bob, alice, alice_id_priv)] {
+            let first = DmEnvelope::parse(&sender.encrypt("first")).unwrap();
+            assert_eq!(receiver.decrypt(&first, receiver_id_priv).as_deref(), Some("first"));
+            assert_eq!(receiver.first_contact_priv_key, None);
+
+            let reply = DmEnvelope::parse(&receiver.encrypt("reply")).unwrap();
+            assert_eq!(sender.decrypt(&reply, [0u8;32]).as_deref(), Some("reply"));
+        }
+    }
+}
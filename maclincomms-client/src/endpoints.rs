@@ -1,5 +1,14 @@
+use std::{collections::HashMap, sync::{Mutex, OnceLock}};
 
+pub const DEFAULT_SERVER_URL: &str = "https://maclincomms-server-v2-prvj.shuttle.app";
 
+static ENDPOINTS: OnceLock<Endpoints> = OnceLock::new();
+
+// Endpoints already built per server, so each server's URLs are only leaked once
+static SERVER_ENDPOINTS: OnceLock<Mutex<HashMap<ServerUrl, Endpoints>>> = OnceLock::new();
+
+
+#[derive(Debug, Clone, Copy)]
 pub struct Endpoints {
     pub register: &'static str,
     pub login: &'static str,
@@ -8,13 +17,23 @@ pub struct Endpoints {
     pub world_chat: &'static str,
     pub create_room: &'static str,
     pub join_room: &'static str,
+    pub create_room_invite: &'static str,
+    pub respond_join_request: &'static str,
     pub get_room_data: &'static str,
+    pub room_directory: &'static str,
+    pub devices: &'static str,
+    pub revoke_device: &'static str,
+    pub create_device_link: &'static str,
+    pub claim_device_link: &'static str,
+    pub rotate_identity_key: &'static str,
     pub room_chat: &'static str,
     pub add_user: &'static str,
     pub accept_user: &'static str,
     pub get_dms_data: &'static str,
     pub get_dm_chats_data: &'static str,
     pub upload_dm_chats_data: &'static str,
+    pub upload_prekeys: &'static str,
+    pub prekey_bundle: &'static str,
     pub dm_chat: &'static str,
     pub block_user: &'static str,
     pub unblock_user: &'static str,
@@ -24,102 +43,244 @@ pub struct Endpoints {
 
 impl Endpoints {
     pub fn new() -> Self {
+        *ENDPOINTS.get_or_init(|| Self::from_server_url(&ServerUrl::resolve()))
+    }
+
+    pub fn from_server_url(server: &ServerUrl) -> Self {
+        let built = SERVER_ENDPOINTS.get_or_init(|| Mutex::new(HashMap::new()));
+        *built.lock().unwrap().entry(server.clone()).or_insert_with(|| Self::build(server))
+    }
+
+    fn build(server: &ServerUrl) -> Self {
         Self {
-            register: Self::get_register_endpoint(),
-            login: Self::get_login_endpoint(),
-            authN: Self::get_authenticate_user_endpoint(),
-            new_token: Self::get_new_token_endpoint(),
-            world_chat: Self::get_world_chat_endpoint(),
-            create_room: Self::get_create_room_endpoint(),
-            join_room: Self::get_join_room_endpoint(),
-            get_room_data: Self::get_room_data_endpoint(),
-            room_chat: Self::get_room_chat_endpoint(),
-            add_user: Self::get_add_user_endpoint(),
-            accept_user: Self::get_accept_user_endpoint(),
-            get_dms_data: Self::get_dms_data_endpoint(),
-            get_dm_chats_data: Self::get_dm_chats_data_endpoint(),
-            upload_dm_chats_data: Self::upload_dm_chats_data_endpoint(),
-            dm_chat: Self::get_dm_chat_endpoint(),
-            block_user: Self::get_block_user_endpoint(),
-            unblock_user: Self::get_unblock_user_endpoint(),
-            queued_notifications: Self::get_queued_notifications_endpoint(),
-            realtime_notifications: Self::get_realtime_notifications_endpoint()
+            register: Self::get_register_endpoint(server),
+            login: Self::get_login_endpoint(server),
+            authN: Self::get_authenticate_user_endpoint(server),
+            new_token: Self::get_new_token_endpoint(server),
+            world_chat: Self::get_world_chat_endpoint(server),
+            create_room: Self::get_create_room_endpoint(server),
+            join_room: Self::get_join_room_endpoint(server),
+            create_room_invite: Self::get_create_room_invite_endpoint(server),
+            respond_join_request: Self::get_respond_join_request_endpoint(server),
+            get_room_data: Self::get_room_data_endpoint(server),
+            room_directory: Self::get_room_directory_endpoint(server),
+            devices: Self::get_devices_endpoint(server),
+            revoke_device: Self::get_revoke_device_endpoint(server),
+            create_device_link: Self::get_create_device_link_endpoint(server),
+            claim_device_link: Self::get_claim_device_link_endpoint(server),
+            rotate_identity_key: Self::get_rotate_identity_key_endpoint(server),
+            room_chat: Self::get_room_chat_endpoint(server),
+            add_user: Self::get_add_user_endpoint(server),
+            accept_user: Self::get_accept_user_endpoint(server),
+            get_dms_data: Self::get_dms_data_endpoint(server),
+            get_dm_chats_data: Self::get_dm_chats_data_endpoint(server),
+            upload_dm_chats_data: Self::upload_dm_chats_data_endpoint(server),
+            upload_prekeys: Self::upload_prekeys_endpoint(server),
+            prekey_bundle: Self::get_prekey_bundle_endpoint(server),
+            dm_chat: Self::get_dm_chat_endpoint(server),
+            block_user: Self::get_block_user_endpoint(server),
+            unblock_user: Self::get_unblock_user_endpoint(server),
+            queued_notifications: Self::get_queued_notifications_endpoint(server),
+            realtime_notifications: Self::get_realtime_notifications_endpoint(server)
         }
     }
 
-    pub fn get_register_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/register_user";
+    pub fn get_register_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("register_user");
+    }
+
+    pub fn get_login_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("login_user");
+    }
+
+    pub fn get_authenticate_user_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("authN_user");
+    }
+
+    pub fn get_new_token_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("new_token");
+    }
+
+    pub fn get_world_chat_endpoint(server: &ServerUrl) -> &'static str {
+        return server.socket("world_chat");
+    }
+
+    pub fn get_create_room_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("create_room");
+    }
+
+    pub fn get_join_room_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("join_room");
+    }
+
+    pub fn get_create_room_invite_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("create_room_invite");
     }
 
-    pub fn get_login_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/login_user";
+    pub fn get_respond_join_request_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("respond_join_request");
     }
 
-    pub fn get_authenticate_user_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/authN_user";
+    pub fn get_room_data_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("room_data");
     }
 
-    pub fn get_new_token_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/new_token";
+    pub fn get_room_directory_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("room_directory");
     }
 
-    pub fn get_world_chat_endpoint() -> &'static str {
-        return "wss://maclincomms-server-v2-prvj.shuttle.app/world_chat";
+    pub fn get_devices_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("devices");
     }
 
-    pub fn get_create_room_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/create_room";
+    pub fn get_revoke_device_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("revoke_device");
     }
 
-    pub fn get_join_room_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/join_room";
+    pub fn get_create_device_link_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("create_device_link");
     }
 
-    pub fn get_room_data_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/room_data";
+    pub fn get_claim_device_link_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("claim_device_link");
     }
 
-    pub fn get_room_chat_endpoint() -> &'static str {
-        return "wss://maclincomms-server-v2-prvj.shuttle.app/room_chat";
+    pub fn get_rotate_identity_key_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("rotate_identity_key");
     }
 
-    pub fn get_add_user_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/add_user";
+    pub fn get_room_chat_endpoint(server: &ServerUrl) -> &'static str {
+        return server.socket("room_chat");
     }
 
-    pub fn get_accept_user_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/accept_user";
+    pub fn get_add_user_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("add_user");
     }
 
-    pub fn get_dms_data_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/get_dms";
+    pub fn get_accept_user_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("accept_user");
     }
 
-    pub fn get_dm_chats_data_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/get_dm_chats";
+    pub fn get_dms_data_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("get_dms");
     }
 
-    pub fn upload_dm_chats_data_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/upload_dm_chats";
+    pub fn get_dm_chats_data_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("get_dm_chats");
     }
 
-    pub fn get_dm_chat_endpoint() -> &'static str {
-        return "wss://maclincomms-server-v2-prvj.shuttle.app/dm_chat";
+    pub fn upload_dm_chats_data_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("upload_dm_chats");
     }
 
-    pub fn get_block_user_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/block_user";
+    pub fn upload_prekeys_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("upload_prekeys");
     }
 
-    pub fn get_unblock_user_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/unblock_user";
+    pub fn get_prekey_bundle_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("prekey_bundle");
     }
 
-    pub fn get_realtime_notifications_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/realtime_notifications";
+    pub fn get_dm_chat_endpoint(server: &ServerUrl) -> &'static str {
+        return server.socket("dm_chat");
     }
 
-    pub fn get_queued_notifications_endpoint() -> &'static str {
-        return "https://maclincomms-server-v2-prvj.shuttle.app/queued_notifications";
+    pub fn get_block_user_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("block_user");
     }
-}
\ No newline at end of file
+
+    pub fn get_unblock_user_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("unblock_user");
+    }
+
+    pub fn get_realtime_notifications_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("realtime_notifications");
+    }
+
+    pub fn get_queued_notifications_endpoint(server: &ServerUrl) -> &'static str {
+        return server.rest("queued_notifications");
+    }
+}
+
+
+/* Base URL of the maclincomms server.
+   Resolved once at startup from (in order) the `--server` flag, the MACLINCOMMS_SERVER
+   env var, the `server` key of ~/.config/maclincomms/config.toml, or the hosted default.
+   http(s):// and ws(s):// are both accepted; REST and websocket URLs are derived from it. */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ServerUrl {
+    pub secure: bool,
+    pub host: String
+}
+
+impl ServerUrl {
+    pub fn parse(url: &str) -> Self {
+        let url = url.trim().trim_end_matches('/');
+
+        let (secure, host) = if let Some(host) = url.strip_prefix("https://") {
+            (true, host)
+        } else if let Some(host) = url.strip_prefix("wss://") {
+            (true, host)
+        } else if let Some(host) = url.strip_prefix("http://") {
+            (false, host)
+        } else if let Some(host) = url.strip_prefix("ws://") {
+            (false, host)
+        } else {
+            (true, url)
+        };
+
+        Self {
+            secure,
+            host: host.to_string()
+        }
+    }
+
+    pub fn resolve() -> Self {
+        let url = Self::from_args()
+            .or_else(|| std::env::var("MACLINCOMMS_SERVER").ok())
+            .or_else(Self::from_config_file)
+            .unwrap_or(DEFAULT_SERVER_URL.to_string());
+
+        Self::parse(&url)
+    }
+
+    fn from_args() -> Option<String> {
+        let args: Vec<String> = std::env::args().collect();
+
+        for (i, arg) in args.iter().enumerate() {
+            if let Some(url) = arg.strip_prefix("--server=") {
+                return Some(url.to_string());
+            }
+            if arg == "--server" {
+                return args.get(i + 1).cloned();
+            }
+        }
+        None
+    }
+
+    pub fn config_file_path() -> Option<std::path::PathBuf> {
+        let config_dir = std::env::var("XDG_CONFIG_HOME").map(std::path::PathBuf::from)
+            .or_else(|_| std::env::var("HOME").map(|home| std::path::PathBuf::from(home).join(".config")))
+            .ok()?;
+
+        Some(config_dir.join("maclincomms").join("config.toml"))
+    }
+
+    fn from_config_file() -> Option<String> {
+        let contents = std::fs::read_to_string(Self::config_file_path()?).ok()?;
+        let table: toml::Table = contents.parse().ok()?;
+
+        table.get("server")?.as_str().map(|s| s.to_string())
+    }
+
+    // Endpoint strings live for the whole program, so they are leaked once per server, when its Endpoints are first built
+    fn rest(&self, path: &str) -> &'static str {
+        let scheme = if self.secure { "https" } else { "http" };
+        Box::leak(format!("{}://{}/{}", scheme, self.host, path).into_boxed_str())
+    }
+
+    fn socket(&self, path: &str) -> &'static str {
+        let scheme = if self.secure { "wss" } else { "ws" };
+        Box::leak(format!("{}://{}/{}", scheme, self.host, path).into_boxed_str())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::user_model::{AckType, ChatData, DmMessage, NotificationData, RoomReceiverMessage, WorldChatMessage};
+use crate::user_model::{AcceptanceStatus, AckType, ModerationAction, ChatData, DmMessage, NotificationData, RoomReceiverMessage, WorldChatMessage};
 
 
 
@@ -8,7 +8,11 @@ pub enum Event {
     IncomingRoomMessageEvent(RoomReceiverMessage),
     IncomingRoomMessageAckEvent(AckType),
     IncomingRoomSenderKeyMessageEvent(Vec<u8>),
+    IncomingRoomHistoryEvent(Vec<u8>),
     UnknownRotateRoomChainKeyEvent(String),
+    RoomOwnershipTransferredEvent(String),
+    RoomModerationEvent(ModerationAction, String),
+    RoomEvent(String, Box<Event>), //Any of the room socket events above, with the room it came from
     LoadDmsDataEvent,
     IncomingDMMessageEvent(DmMessage),
     IncomingDMMessageAckEvent(AckType),
@@ -18,6 +22,13 @@ pub enum Event {
     LoginEvent,
     RoomCreationEvent,
     RoomJoinEvent,
+    LoadRoomDirectoryEvent(usize), //With the page to load
+    LoadDevicesEvent,
+    RevokeDeviceEvent(String), //With the device id
+    CreateDeviceLinkEvent,
+    ClaimDeviceLinkEvent(String), //With the link code as typed
+    RotateIdentityKeyEvent,
+    RoomInviteEvent(i64, i64, Option<String>), //With max uses, minutes until expiry and DM contact to send it to
     RoomChatEvent(String), //With room token,
     DmChatEvent(String), //With dm token
     UploadDmChatsEvent(ChatData),
@@ -27,5 +38,7 @@ pub enum Event {
     BlockEvent,
     UnblockEvent,
     AddUserEvent,
-    AcceptUserEvent(String) //With username to accept
+    AcceptUserEvent(String), //With username to accept
+    RespondJoinRequestEvent(String, String, AcceptanceStatus), //With room name, username who knocked and the answer
+    RoomJoinApprovedEvent(String, String) //With room name and room token
 }
\ No newline at end of file
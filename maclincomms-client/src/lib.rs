@@ -0,0 +1,37 @@
+use chrono::{DateTime, Local};
+
+pub mod tui_main;
+pub mod register_user;
+pub mod user_model;
+pub mod login_user;
+pub mod tui_widgets;
+pub mod screens_model;
+pub mod screen_inputs;
+pub mod websockets;
+pub mod crypto;
+pub mod endpoints;
+pub mod event_model;
+pub mod network_jobs;
+pub mod persistent_login;
+pub mod active_rooms;
+pub mod local_store;
+pub mod account_backup;
+
+
+pub fn get_current_time() -> String {
+    let now = Local::now();
+    now.format("%I:%M %p").to_string()
+}
+
+pub fn get_current_date() -> String {
+    let now = Local::now();
+    now.format("%d %B, %Y").to_string()
+}
+
+//Unix timestamps from the server in the same "time on date" form as notifications
+pub fn format_local_timestamp(timestamp: i64) -> String {
+    match DateTime::from_timestamp(timestamp, 0) {
+        Some(time) => time.with_timezone(&Local).format("%I:%M %p on %d %B, %Y").to_string(),
+        None => "".to_string()
+    }
+}
@@ -0,0 +1,316 @@
+use std::{fmt, fs, marker::PhantomData, sync::OnceLock};
+
+use disk_persist::{DataError, DiskPersist};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{crypto::store_seal::{derive_store_key, generate_store_salt, open_store_bytes, seal_store_bytes, StoreKdfParams, STORE_SALT_LEN}, endpoints::ServerUrl};
+
+
+//Set once the passphrase is checked, nothing persistent can be read or written before that
+static STORE_KEY: OnceLock<[u8;32]> = OnceLock::new();
+
+//Salt, Argon2 cost and a check value, the one file left in the clear
+const STORE_LOCK_FILE: &str = "persistent-store-lock";
+const STORE_CHECK: &[u8] = b"maclincomms local store";
+
+const SEALED_FILE_SUFFIX: &str = ".sealed";
+
+//Every file the client persists. Plain ones left by older clients are sealed on the first unlock
+pub const PERSISTENT_FILES: [&str; 11] = [
+    "persistent-user-data",
+    "persistent-user-identity-keypair",
+    "persistent-account-identity-keypair",
+    "persistent-user-device",
+    "persistent-user-prekeys",
+    "persistent-user-dms-list",
+    "persistent-user-verified-contacts",
+    "persistent-dms-session-keys",
+    "persistent-dms-e2e-keys",
+    "persistent-user-dm-chats",
+    "persistent-restored-dm-chats"
+];
+
+const MAX_UNLOCK_ATTEMPTS: usize = 3;
+
+
+#[derive(Debug)]
+pub enum StoreError {
+    Disk(DataError),
+    Serialization(bincode::Error),
+    Locked, //Read or written before the passphrase was entered
+    Tampered //Wrong key, or the file was changed
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Disk(err) => write!(f, "{}", err),
+            StoreError::Serialization(err) => write!(f, "{}", err),
+            StoreError::Locked => write!(f, "local store is locked"),
+            StoreError::Tampered => write!(f, "local store file can't be opened with this passphrase")
+        }
+    }
+}
+
+impl From<DataError> for StoreError {
+    fn from(err: DataError) -> Self {
+        StoreError::Disk(err)
+    }
+}
+
+impl From<bincode::Error> for StoreError {
+    fn from(err: bincode::Error) -> Self {
+        StoreError::Serialization(err)
+    }
+}
+
+
+#[derive(Serialize, Deserialize, Debug)]
+struct StoreLock{
+    salt: [u8; STORE_SALT_LEN],
+    kdf_params: StoreKdfParams,
+    check: Vec<u8> //STORE_CHECK sealed with the store key, tells a wrong passphrase apart
+}
+
+
+/// Drop-in for `DiskPersist` that seals the data with the store key.
+/// The value is bincode encoded exactly as `DiskPersist` would, then encrypted under the file's name.
+pub struct SealedPersist<D> {
+    name: String,
+    disk: DiskPersist<Vec<u8>>,
+    _type: PhantomData<D>
+}
+
+impl<D: Serialize + DeserializeOwned> SealedPersist<D> {
+
+    pub fn init(name: impl AsRef<str>) -> Result<Self, StoreError> {
+        Ok(Self {
+            name: name.as_ref().to_string(),
+            disk: DiskPersist::init(format!("{}{}", name.as_ref(), SEALED_FILE_SUFFIX))?,
+            _type: PhantomData
+        })
+    }
+
+    pub fn write(&self, data: &D) -> Result<(), StoreError> {
+        let store_key = STORE_KEY.get().ok_or(StoreError::Locked)?;
+        let plaintext = bincode::serialize(data)?;
+        self.disk.write(&seal_store_bytes(*store_key, &self.name, &plaintext))?;
+        Ok(())
+    }
+
+    pub fn read(&self) -> Result<Option<D>, StoreError> {
+        let store_key = STORE_KEY.get().ok_or(StoreError::Locked)?;
+        let Some(sealed) = self.disk.read()? else {
+            return Ok(None);
+        };
+        let plaintext = open_store_bytes(*store_key, &self.name, &sealed).ok_or(StoreError::Tampered)?;
+        Ok(Some(bincode::deserialize(&plaintext)?))
+    }
+
+    pub fn remove(&self) {
+        let _ = fs::remove_file(self.disk.path());
+    }
+}
+
+
+pub fn is_store_created() -> bool {
+    matches!(read_store_lock(), Some(_))
+}
+
+fn read_store_lock() -> Option<StoreLock> {
+    let disk: DiskPersist<StoreLock> = DiskPersist::init(STORE_LOCK_FILE).ok()?;
+    let mut store_lock = disk.read().ok()??;
+    //The lock file is the one left in the clear, its cost can't be trusted as is
+    store_lock.kdf_params = store_lock.kdf_params.clamped();
+    Some(store_lock)
+}
+
+//First run: a fresh salt and the check value for the chosen passphrase
+pub fn create_store(passphrase: &str) -> Result<[u8;32], StoreError> {
+    let salt = generate_store_salt();
+    let kdf_params = StoreKdfParams::recommended();
+    let store_key = derive_store_key(passphrase, &salt, kdf_params).ok_or(StoreError::Tampered)?;
+
+    let disk: DiskPersist<StoreLock> = DiskPersist::init(STORE_LOCK_FILE)?;
+    disk.write(&StoreLock {
+        salt,
+        kdf_params,
+        check: seal_store_bytes(store_key, STORE_LOCK_FILE, STORE_CHECK)
+    })?;
+
+    Ok(store_key)
+}
+
+//The store key if the passphrase is right
+pub fn unlock_store(passphrase: &str) -> Result<[u8;32], StoreError> {
+    let store_lock = read_store_lock().ok_or(StoreError::Locked)?;
+    let store_key = derive_store_key(passphrase, &store_lock.salt, store_lock.kdf_params).ok_or(StoreError::Tampered)?;
+    match verify_store_key(&store_lock, store_key) {
+        true => Ok(store_key),
+        false => Err(StoreError::Tampered)
+    }
+}
+
+fn verify_store_key(store_lock: &StoreLock, store_key: [u8;32]) -> bool {
+    open_store_bytes(store_key, STORE_LOCK_FILE, &store_lock.check).as_deref()==Some(STORE_CHECK)
+}
+
+//Hands the key to every SealedPersist and seals whatever plain files an older client left behind
+pub fn open_store(store_key: [u8;32]) {
+    if STORE_KEY.set(store_key).is_err() {
+        return;
+    }
+    seal_plain_files(store_key);
+}
+
+//Plain DiskPersist files hold the same bincode bytes SealedPersist encrypts, so they are sealed as they are
+fn seal_plain_files(store_key: [u8;32]) {
+    for name in PERSISTENT_FILES {
+        let Ok(plain_disk) = DiskPersist::<Vec<u8>>::init(name) else {
+            continue;
+        };
+        let Ok(plaintext) = fs::read(plain_disk.path()) else {
+            continue;
+        };
+        let Ok(sealed_disk) = DiskPersist::<Vec<u8>>::init(format!("{}{}", name, SEALED_FILE_SUFFIX)) else {
+            continue;
+        };
+        if sealed_disk.write(&seal_store_bytes(store_key, name, &plaintext)).is_ok() {
+            let _ = fs::remove_file(plain_disk.path());
+        }
+    }
+}
+
+
+//Keeping the unlocked key in the kernel keyring is opted into with --cache-unlock-key, MACLINCOMMS_CACHE_UNLOCK_KEY=1
+//or `cache_unlock_key = true` in the config file
+pub fn cache_unlock_key_enabled() -> bool {
+    if std::env::args().any(|arg| arg=="--cache-unlock-key") {
+        return true;
+    }
+    if let Ok(value) = std::env::var("MACLINCOMMS_CACHE_UNLOCK_KEY") {
+        return value=="1" || value.eq_ignore_ascii_case("true");
+    }
+    ServerUrl::config_file_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| contents.parse::<toml::Table>().ok())
+        .and_then(|table| table.get("cache_unlock_key")?.as_bool())
+        .unwrap_or(false)
+}
+
+//Prompts on the terminal before the TUI starts, false if the store stays locked
+pub fn unlock_store_interactive() -> bool {
+    let cache_key = cache_unlock_key_enabled();
+
+    let Some(store_lock) = read_store_lock() else {
+        return create_store_interactive(cache_key);
+    };
+
+    if cache_key {
+        if let Some(cached_key) = keyring_cache::load().filter(|key| verify_store_key(&store_lock, *key)) {
+            open_store(cached_key);
+            return true;
+        }
+    }
+    else {
+        //Turning the option off drops what an earlier run cached
+        keyring_cache::forget();
+    }
+
+    for _ in 0..MAX_UNLOCK_ATTEMPTS {
+        let Ok(passphrase) = rpassword::prompt_password("Unlock passphrase: ") else {
+            return false;
+        };
+        match unlock_store(&passphrase) {
+            Ok(store_key) => {
+                if cache_key {
+                    keyring_cache::store(&store_key);
+                }
+                open_store(store_key);
+                return true;
+            }
+            Err(_) => println!("Wrong passphrase")
+        }
+    }
+
+    return false;
+}
+
+fn create_store_interactive(cache_key: bool) -> bool {
+    println!("Choose a passphrase, it seals your keys and session on this machine and can't be recovered");
+
+    loop {
+        let Ok(passphrase) = rpassword::prompt_password("New passphrase: ") else {
+            return false;
+        };
+        if passphrase.is_empty() {
+            println!("The passphrase can't be empty");
+            continue;
+        }
+        let Ok(confirmation) = rpassword::prompt_password("Confirm passphrase: ") else {
+            return false;
+        };
+        if passphrase!=confirmation {
+            println!("Passphrases don't match");
+            continue;
+        }
+
+        return match create_store(&passphrase) {
+            Ok(store_key) => {
+                if cache_key {
+                    keyring_cache::store(&store_key);
+                }
+                open_store(store_key);
+                true
+            }
+            Err(err) => {
+                println!("Couldn't set up the local store: {}", err);
+                false
+            }
+        };
+    }
+}
+
+
+//The key sits in the user keyring for a while so the passphrase isn't asked on every start
+#[cfg(target_os = "linux")]
+mod keyring_cache {
+    use linux_keyutils::{KeyRing, KeyRingIdentifier};
+
+    const KEY_DESCRIPTION: &str = "maclincomms:local-store-key";
+    const CACHE_SECONDS: usize = 8*60*60;
+
+    pub fn load() -> Option<[u8;32]> {
+        let keyring = KeyRing::from_special_id(KeyRingIdentifier::User, false).ok()?;
+        let key = keyring.search(KEY_DESCRIPTION).ok()?;
+        key.read_to_vec().ok()?.try_into().ok()
+    }
+
+    pub fn store(store_key: &[u8;32]) {
+        if let Ok(keyring) = KeyRing::from_special_id(KeyRingIdentifier::User, true) {
+            if let Ok(key) = keyring.add_key(KEY_DESCRIPTION, store_key) {
+                let _ = key.set_timeout(CACHE_SECONDS);
+            }
+        }
+    }
+
+    pub fn forget() {
+        if let Ok(keyring) = KeyRing::from_special_id(KeyRingIdentifier::User, false) {
+            if let Ok(key) = keyring.search(KEY_DESCRIPTION) {
+                let _ = key.invalidate();
+            }
+        }
+    }
+}
+
+//No kernel keyring elsewhere, the passphrase is asked every time
+#[cfg(not(target_os = "linux"))]
+mod keyring_cache {
+    pub fn load() -> Option<[u8;32]> {
+        None
+    }
+
+    pub fn store(_store_key: &[u8;32]) {}
+
+    pub fn forget() {}
+}
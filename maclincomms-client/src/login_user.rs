@@ -1,10 +1,12 @@
 use futures_util::{StreamExt, SinkExt, stream::{SplitSink, SplitStream}};
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::io::{self, AsyncBufReadExt};
 use tokio_tungstenite::{connect_async, tungstenite::{client::{self, IntoClientRequest}, http::Request, ClientRequestBuilder, Message}, WebSocketStream};
 
-use crate::{endpoints::Endpoints, user_model::{LoginResponse, RegisterResponse, StatusTypes, UserLoginPayload}};
+use base64::{engine::general_purpose, Engine};
+
+use crate::{crypto::identity_pair::generate_identity_keypair, endpoints::Endpoints, network_jobs::login::login_account, user_model::{LoginResponse, RegisterResponse, StatusTypes, UserDevice, UserIdentityKeys, UserLoginPayload}};
 
 
 #[derive(Debug)]
@@ -14,7 +16,8 @@ pub enum LoginResponseResult{
     USER_NOT_FOUND,
     DATABASE_ERROR,
     UNKNOWN_ERROR,
-    REQUEST_ERROR
+    REQUEST_ERROR,
+    DEVICE_REVOKED
 }
 
 
@@ -44,6 +47,11 @@ pub async fn get_user(username: String, password: String) -> (LoginResponseResul
                             let refresh_token = status.refresh_token;
                             let expiry = status.exp;
 
+                            //Only the device holding the account key publishes prekeys and runs X3DH
+                            let mut device = UserDevice::load_or_create();
+                            device.account_device = status.account_device;
+                            device.save();
+
                             return (LoginResponseResult::TOKEN(token), refresh_token, expiry) ;
 
                     },
@@ -61,6 +69,14 @@ pub async fn get_user(username: String, password: String) -> (LoginResponseResul
 
                         return (LoginResponseResult::DATABASE_ERROR, "".to_string(), 0);
 
+                    },
+                    StatusTypes::DEVICE_REVOKED => {
+
+                        //Revoked ids are never taken back, this install comes back as a new device
+                        UserDevice::reset();
+
+                        return (LoginResponseResult::DEVICE_REVOKED, "".to_string(), 0);
+
                     },
                     _ => {
                             
@@ -79,21 +95,21 @@ pub async fn get_user(username: String, password: String) -> (LoginResponseResul
 pub async fn login_into_backend(user_name: String, pass:String) -> Result< LoginResponse, reqwest::Error > {
     
     let endpoints = Endpoints::new();
-    
-    let url = endpoints.login.to_owned();
+
+    //Each device signs in with its own identity key, made the first time it logs in
+    let device = UserDevice::load_or_create();
+    let pub_id_key = match UserIdentityKeys::load_public_key() {
+        Some(key) => general_purpose::STANDARD.encode(key),
+        None => generate_identity_keypair()
+    };
 
     let user = UserLoginPayload{
         username: user_name,
-        password: pass
+        password: pass,
+        device_id: device.device_id,
+        device_name: device.device_name,
+        public_identity_key: pub_id_key
     };
 
-    let client = Client::new();
-    let res = client
-        .post(url)
-        .json(&user)
-        .send()
-        .await?;    
-
-    let data = res.json::<LoginResponse>().await?;
-    Ok(data)
+    login_account(user, endpoints.login).await
 }
\ No newline at end of file
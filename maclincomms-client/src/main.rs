@@ -1,34 +1,83 @@
-use chrono::Local;
-
-mod tui_main;
-mod register_user;
-mod user_model;
-mod login_user;
-mod tui_widgets;
-mod screens_model;
-mod screen_inputs;
-mod websockets;
-mod crypto;
-mod endpoints;
-mod event_model;
-mod network_jobs;
-mod persistent_login;
-
-
-pub fn get_current_time() -> String {
-    let now = Local::now();
-    now.format("%I:%M %p").to_string()
-}
-
-pub fn get_current_date() -> String {
-    let now = Local::now();
-    now.format("%d %B, %Y").to_string()
-}
+use maclincomms::{account_backup, endpoints, local_store, tui_main};
 
 //Main Function
 #[tokio::main]
 async fn main() {
 
-    tui_main::start_tui().await;
+    // Resolve server URL (--server flag, MACLINCOMMS_SERVER, config file) before the TUI starts
+    endpoints::Endpoints::new();
+
+    // Everything persistent is sealed with the passphrase, so it is asked on the plain terminal first
+    if !local_store::unlock_store_interactive() {
+        eprintln!("Local store stays locked, exiting");
+        std::process::exit(1);
+    }
+
+    let args: Vec<String> = std::env::args().collect();
+
+    // `maclincomms export --out backup.mcb` and `maclincomms import --in backup.mcb` run without the TUI
+    // Global flags like `--server host` may come before the subcommand
+    match plain_args(&args).first().map(|arg| arg.as_str()) {
+        Some("export") => {
+            let Some(out_path) = path_arg(&args, "--out") else {
+                eprintln!("Usage: maclincomms export --out <backup.mcb>");
+                std::process::exit(2);
+            };
+            match account_backup::export_account(&out_path) {
+                Ok(()) => println!("Account exported to {}", out_path),
+                Err(err) => {
+                    eprintln!("Export failed: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Some("import") => {
+            let Some(in_path) = path_arg(&args, "--in") else {
+                eprintln!("Usage: maclincomms import --in <backup.mcb>");
+                std::process::exit(2);
+            };
+            match account_backup::import_account(&in_path) {
+                Ok(username) => println!("Restored {} on this machine, log in to register it as a new device, chats are merged after that", username),
+                Err(err) => {
+                    eprintln!("Import failed: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => {
+            let _ = tui_main::start_tui().await;
+        }
+    }
 
-}
\ No newline at end of file
+}
+
+// Flags given their value as the next argument, `--server` is read by ServerUrl::from_args
+const VALUE_FLAGS: [&str; 3] = ["--server", "--out", "--in"];
+
+// Arguments that are neither flags nor a flag's value, the subcommand first
+fn plain_args(args: &[String]) -> Vec<String> {
+    let mut plain = Vec::new();
+    let mut args = args.iter().skip(1);
+    while let Some(arg) = args.next() {
+        if VALUE_FLAGS.contains(&arg.as_str()) {
+            args.next();
+        }
+        else if !arg.starts_with("--") {
+            plain.push(arg.clone());
+        }
+    }
+    plain
+}
+
+// `--out path`, `--out=path`, or the first plain argument after the subcommand
+fn path_arg(args: &[String], flag: &str) -> Option<String> {
+    for (i, arg) in args.iter().enumerate().skip(1) {
+        if let Some(path) = arg.strip_prefix(&format!("{}=", flag)) {
+            return Some(path.to_string());
+        }
+        if arg == flag {
+            return args.get(i + 1).cloned();
+        }
+    }
+    plain_args(args).get(1).cloned()
+}
@@ -2,7 +2,7 @@ use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::user_model::{AcceptanceStatus, StatusTypes};
+use crate::user_model::{AcceptanceStatus, StatusTypes, X3dhHandshake};
 
 #[derive(Debug)]
 pub enum AcceptUserResponseResult {
@@ -27,6 +27,7 @@ pub async fn accept_user(
     token: String,
     username: String,
     status: AcceptanceStatus,
+    x3dh_handshake: Option<X3dhHandshake>,
     accept_user_endpoint: &'static str
 ) -> AcceptUserResponseResult {
 
@@ -35,7 +36,8 @@ pub async fn accept_user(
 
     let data = json!({
         "username_to_accept": username,
-        "acceptance_status": status
+        "acceptance_status": status,
+        "x3dh_handshake": x3dh_handshake
     });
 
     let response = client
@@ -1,10 +1,10 @@
 use base64::{engine::general_purpose, Engine};
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use ratatui::{style::{ Style, Stylize}, text::{Line, Text}, widgets::{Block, Borders, Paragraph}};
 
-use crate::{crypto::dm_keys::{generate_dh_keypair, generate_sender_chainkey, generate_shared_rootkey}, event_model::Event, tui_main::MaclincommsApp, user_model::{AcceptanceStatus, DmDoubleRatchet_Keys, DmUser_Data}};
+use crate::{crypto::{double_ratchet::DoubleRatchet, x3dh::initiate_x3dh_handshake}, event_model::Event, tui_main::MaclincommsApp, user_model::{AcceptanceStatus, DmUser_Data, UserDevice}};
 
-use super::{accept_user::{accept_user, AcceptUserResponseResult}, getdms_thread::start_getdms_thread};
+use super::{accept_user::{accept_user, AcceptUserResponseResult}, get_prekey_bundle::{get_prekey_bundle, GetPrekeyBundleResponseResult}, getdms_thread::start_getdms_thread};
 
 
 
@@ -15,7 +15,18 @@ pub async fn start_acceptuser_thread(app: &mut MaclincommsApp, user_to_accept: S
 
     let endpoint = app.endpoints.accept_user;
 
-    let accept_user_result = accept_user(accept_user_token, user_to_accept.clone(), AcceptanceStatus::ACCEPTED, endpoint).await;
+    //X3DH with their prekey bundle, so the DM session is set up even if they are offline
+    //Made with the account's identity key, so only from the account device
+    let x3dh_session = match UserDevice::load_or_create().account_device {
+        true => match get_prekey_bundle(app.access_token.clone(), user_to_accept.clone(), app.endpoints.prekey_bundle).await {
+            GetPrekeyBundleResponseResult::PREKEY_BUNDLE_FETCHED(bundle) => initiate_x3dh_handshake(&bundle),
+            _ => None
+        },
+        false => None
+    };
+    let x3dh_handshake = x3dh_session.as_ref().map(|(_, handshake)| handshake.clone());
+
+    let accept_user_result = accept_user(accept_user_token, user_to_accept.clone(), AcceptanceStatus::ACCEPTED, x3dh_handshake, endpoint).await;
     
 
 
@@ -98,7 +109,7 @@ pub async fn start_acceptuser_thread(app: &mut MaclincommsApp, user_to_accept: S
             start_getdms_thread(app).await;
 
             //Retrieve user public key
-            let dms_list: DiskPersist<Vec<DmUser_Data>> = DiskPersist::init("persistent-user-dms-list").unwrap();
+            let dms_list: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
 
             if let Ok(dmslist_res) = dms_list.read(){
                 match dmslist_res{
@@ -108,20 +119,10 @@ pub async fn start_acceptuser_thread(app: &mut MaclincommsApp, user_to_accept: S
                                 .map(|info| info.public_identity_key)
                                 .unwrap();
                         let their_pub_key_bytes: [u8;32] = general_purpose::STANDARD.decode(their_pub_key).unwrap().try_into().unwrap();
-                        //Generate dh pair
-                        let (pub_dh_key, priv_dh_key) = generate_dh_keypair();
-                        //Generate root key
-                        let rootkey = generate_shared_rootkey(their_pub_key_bytes, priv_dh_key);
-                        //Generate chain key
-                        let sending_chainkey = generate_sender_chainkey(rootkey);
-                        //Initialise first keys with user and store in dme2ee data
-                        let keys_data = DmDoubleRatchet_Keys{
-                            root_key: rootkey,
-                            their_old_dh_pub_key: [0u8;32],
-                            dh_pub_key: pub_dh_key,
-                            dh_priv_key: priv_dh_key,
-                            sending_chain_key: sending_chainkey,
-                            receiving_chain_key: [0u8;32],
+                        //Initialise the DM ratchet from X3DH, or from their identity key if they had no prekeys, and store in dme2ee data
+                        let keys_data = match x3dh_session {
+                            Some((ratchet, _)) => ratchet,
+                            None => DoubleRatchet::new(their_pub_key_bytes)
                         };
 
                         app.dme2ee_data.dms.insert(user_to_accept, keys_data);
@@ -1,4 +1,4 @@
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use ratatui::{style::{ Style, Stylize}, text::{Line, Text}, widgets::{Block, Borders, Paragraph}};
 
 use crate::{event_model::Event, tui_main::MaclincommsApp, tui_widgets::blockuser_textarea::BlockUnblockUserTaskStatus, user_model::DmUser_Data};
@@ -123,7 +123,7 @@ pub async fn start_blockuser_task(app: &mut MaclincommsApp) {
             app.blockunblock_textarea.task_status = BlockUnblockUserTaskStatus::NOT_INITIATED;
 
             //REMOVING USER'S DETAILS LOCALLY SAVED...
-            let persistent_storage: DiskPersist<Vec<DmUser_Data>> = DiskPersist::init("persistent-user-dms-list").unwrap();
+            let persistent_storage: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
 
             if let Err(e) = persistent_storage.read() {
                 //error handling
@@ -145,7 +145,7 @@ pub async fn start_blockuser_task(app: &mut MaclincommsApp) {
             //FETCH your dm list again
             start_getdms_thread(app).await;
             //Remove user's keys and chats
-            app.dme2ee_data.dms.remove(&username_to_block);
+            app.dme2ee_data.remove_contact(&username_to_block);
             app.dmchats_warehouse.dms_session_key.remove(&username_to_block);
             app.dmchats_warehouse.dms_data.remove(&username_to_block);
         }
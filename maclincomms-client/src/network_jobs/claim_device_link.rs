@@ -0,0 +1,66 @@
+use reqwest::Client;
+use serde_json::json;
+
+use crate::user_model::StatusTypes;
+
+use super::create_device_link::DeviceLinkResponseData;
+
+#[derive(Debug)]
+pub enum ClaimDeviceLinkResponseResult {
+    DEVICE_LINK_CLAIMED(String), //Sealed bundle
+    INVALID_DEVICE_LINK,
+    DATABASE_ERROR,
+    REQUEST_ERROR,
+    UNKNOWN_ERROR
+}
+
+
+
+
+pub async fn claim_device_link(
+    token: String,
+    link_id: String,
+    claim_device_link_endpoint: &'static str
+) -> ClaimDeviceLinkResponseResult {
+
+    let url = claim_device_link_endpoint.to_string();
+    let client = Client::new();
+
+    let data = json!({
+        "link_id": link_id,
+    });
+
+    let response = client
+        .post(url)
+        .json(&data)
+        .header("Authorization", token)
+        .send()
+        .await;
+
+
+    match response {
+        Ok(data) => {
+
+            let res_data = data.json::<DeviceLinkResponseData>().await.unwrap();
+
+            match res_data.status_type {
+                StatusTypes::DEVICE_LINK_CLAIMED => {
+                    return ClaimDeviceLinkResponseResult::DEVICE_LINK_CLAIMED(res_data.sealed_bundle);
+                }
+                StatusTypes::INVALID_DEVICE_LINK => {
+                    return ClaimDeviceLinkResponseResult::INVALID_DEVICE_LINK;
+                }
+                StatusTypes::DATABASE_ERROR => {
+                    return ClaimDeviceLinkResponseResult::DATABASE_ERROR;
+                }
+                _ => {
+                    return ClaimDeviceLinkResponseResult::UNKNOWN_ERROR;
+                }
+            }
+
+        },
+        Err(_) => {
+            return ClaimDeviceLinkResponseResult::REQUEST_ERROR;
+        }
+    }
+}
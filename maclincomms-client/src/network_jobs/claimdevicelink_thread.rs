@@ -0,0 +1,60 @@
+use ratatui::{style::{Style, Stylize}, widgets::{Block, Borders, Paragraph}};
+
+use crate::{crypto::device_link::{open_link_bundle, parse_link_code}, tui_main::MaclincommsApp};
+
+use super::{claim_device_link::{claim_device_link, ClaimDeviceLinkResponseResult}, getdmchats_thread::start_getdmchats_thread};
+
+
+
+
+pub async fn start_claimdevicelink_thread(app: &mut MaclincommsApp, link_code: String) {
+
+    let claim_device_link_token = app.access_token.clone();
+
+    let endpoint = app.endpoints.claim_device_link;
+
+    let (text, color) = match parse_link_code(&link_code) {
+
+        None => ("That isn't a link code".to_string(), ratatui::style::Color::LightYellow),
+
+        Some((link_id, secret)) => {
+
+            match claim_device_link(claim_device_link_token, link_id.clone(), endpoint).await {
+
+                ClaimDeviceLinkResponseResult::REQUEST_ERROR => ("Network error or bad request".to_string(), ratatui::style::Color::LightRed),
+
+                ClaimDeviceLinkResponseResult::DATABASE_ERROR => ("Database Error".to_string(), ratatui::style::Color::LightRed),
+
+                ClaimDeviceLinkResponseResult::UNKNOWN_ERROR => ("Unknown Server Error".to_string(), ratatui::style::Color::LightRed),
+
+                ClaimDeviceLinkResponseResult::INVALID_DEVICE_LINK => ("Code is unknown, used or expired".to_string(), ratatui::style::Color::LightYellow),
+
+                ClaimDeviceLinkResponseResult::DEVICE_LINK_CLAIMED(sealed_bundle) => {
+
+                    //The link is used up once claimed, a mistyped secret half means making a new one
+                    match open_link_bundle(&app.username, &link_id, &secret, &sealed_bundle) {
+                        Some(bundle) => {
+                            //In memory too, chat uploads write the warehouse keys back to disk
+                            app.dmchats_warehouse.dms_session_key = bundle.import();
+
+                            //Backed up chats can be decrypted with the session keys handed over
+                            start_getdmchats_thread(app).await;
+
+                            ("Device linked, keys and chats imported".to_string(), ratatui::style::Color::LightGreen)
+                        }
+                        None => ("Code didn't match, make a new one".to_string(), ratatui::style::Color::LightRed)
+                    }
+                }
+            }
+        }
+    };
+
+    let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(color));
+
+    app.devices_comps.action_status_block = Paragraph::new(text.fg(color))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+}
@@ -0,0 +1,75 @@
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::user_model::StatusTypes;
+
+#[derive(Debug)]
+pub enum CreateDeviceLinkResponseResult {
+    DEVICE_LINK_CREATED(i64), //Expiry timestamp
+    INVALID_DEVICE_LINK,
+    DATABASE_ERROR,
+    REQUEST_ERROR,
+    UNKNOWN_ERROR
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceLinkResponseData {
+    pub status_type: StatusTypes,
+    pub sealed_bundle: String,
+    pub expires_at: i64,
+    pub message: String
+}
+
+
+
+
+pub async fn create_device_link(
+    token: String,
+    link_id: String,
+    sealed_bundle: String,
+    create_device_link_endpoint: &'static str
+) -> CreateDeviceLinkResponseResult {
+
+    let url = create_device_link_endpoint.to_string();
+    let client = Client::new();
+
+    let data = json!({
+        "link_id": link_id,
+        "sealed_bundle": sealed_bundle
+    });
+
+    let response = client
+        .post(url)
+        .json(&data)
+        .header("Authorization", token)
+        .send()
+        .await;
+
+
+    match response {
+        Ok(data) => {
+
+            let res_data = data.json::<DeviceLinkResponseData>().await.unwrap();
+
+            match res_data.status_type {
+                StatusTypes::DEVICE_LINK_CREATED => {
+                    return CreateDeviceLinkResponseResult::DEVICE_LINK_CREATED(res_data.expires_at);
+                }
+                StatusTypes::INVALID_DEVICE_LINK => {
+                    return CreateDeviceLinkResponseResult::INVALID_DEVICE_LINK;
+                }
+                StatusTypes::DATABASE_ERROR => {
+                    return CreateDeviceLinkResponseResult::DATABASE_ERROR;
+                }
+                _ => {
+                    return CreateDeviceLinkResponseResult::UNKNOWN_ERROR;
+                }
+            }
+
+        },
+        Err(_) => {
+            return CreateDeviceLinkResponseResult::REQUEST_ERROR;
+        }
+    }
+}
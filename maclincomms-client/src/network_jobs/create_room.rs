@@ -2,7 +2,7 @@ use reqwest::{Client, Response, Error};
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::user_model::StatusTypes;
+use crate::user_model::{RoomSettings, StatusTypes};
 
 #[derive(Debug)]
 pub enum CreateRoomResponseResult {
@@ -30,6 +30,8 @@ pub async fn create_room(
     token: String,
     room_name: String,
     public_key: String,
+    persistent: bool,
+    settings: RoomSettings,
     create_room_endpoint: &'static str
 ) -> CreateRoomResponseResult {
 
@@ -38,7 +40,9 @@ pub async fn create_room(
 
     let room = json!({
         "room_name": room_name,
-        "owner_key": public_key
+        "owner_key": public_key,
+        "persistent": persistent,
+        "settings": settings
     });
 
     let response = client
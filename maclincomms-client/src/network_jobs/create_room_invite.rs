@@ -0,0 +1,96 @@
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::user_model::StatusTypes;
+
+#[derive(Debug)]
+pub enum CreateRoomInviteResponseResult {
+    INVITE(CreateRoomInviteResponseData),
+    INVITE_NOT_SENT(CreateRoomInviteResponseData), //Created, but the notification to the DM contact failed
+    ROOM_NOT_FOUND,
+    NOT_ROOM_OWNER,
+    INVALID_INVITE,
+    USER_NOT_FOUND,
+    DATABASE_ERROR,
+    REQUEST_ERROR,
+    UNKNOWN_ERROR
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateRoomInviteResponseData {
+    pub status_type: StatusTypes,
+    pub invite_token: String,
+    pub expires_at: i64,
+    pub message: String
+}
+
+
+pub async fn create_room_invite(
+    token: String,
+    room_name: String,
+    max_uses: i64,
+    expires_in_minutes: i64,
+    send_to: Option<String>,
+    create_room_invite_endpoint: &'static str
+) -> CreateRoomInviteResponseResult {
+
+    let url = create_room_invite_endpoint.to_string();
+    let client = Client::new();
+
+    let invite = json!({
+        "room_name": room_name,
+        "max_uses": max_uses,
+        "expires_in_minutes": expires_in_minutes,
+        "send_to": send_to
+    });
+
+    let response = client
+        .post(url)
+        .json(&invite)
+        .header("Authorization", token)
+        .send()
+        .await;
+
+    match response {
+        Ok(data) => {
+            let res_data = match data.json::<CreateRoomInviteResponseData>().await {
+                Ok(res_data) => res_data,
+                Err(_) => {
+                    return CreateRoomInviteResponseResult::UNKNOWN_ERROR;
+                }
+            };
+    
+            match res_data.status_type {
+                StatusTypes::ROOM_INVITE_CREATED => {
+                    return CreateRoomInviteResponseResult::INVITE(res_data);
+                }
+                StatusTypes::NOTIFICATIONS_ERROR => {
+                    return CreateRoomInviteResponseResult::INVITE_NOT_SENT(res_data);
+                }
+                StatusTypes::ROOM_NOT_FOUND => {
+                    return CreateRoomInviteResponseResult::ROOM_NOT_FOUND;
+                }
+                StatusTypes::INVALID_CREDENTIALS => {
+                    return CreateRoomInviteResponseResult::NOT_ROOM_OWNER;
+                }
+                StatusTypes::INVALID_INVITE => {
+                    return CreateRoomInviteResponseResult::INVALID_INVITE;
+                }
+                StatusTypes::USER_NOT_FOUND => {
+                    return CreateRoomInviteResponseResult::USER_NOT_FOUND;
+                }
+                StatusTypes::DATABASE_ERROR => {
+                    return CreateRoomInviteResponseResult::DATABASE_ERROR;
+                }
+                _ => {
+                    return CreateRoomInviteResponseResult::UNKNOWN_ERROR;
+                }
+            }
+    
+        },
+        Err(_) => {
+            return CreateRoomInviteResponseResult::REQUEST_ERROR;
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use chrono::Utc;
+use ratatui::{style::{Style, Stylize}, widgets::{Block, Borders, Paragraph}};
+
+use crate::{crypto::device_link::{generate_link_code, parse_link_code, seal_link_bundle}, tui_main::MaclincommsApp, user_model::DeviceLinkBundle};
+
+use super::create_device_link::{create_device_link, CreateDeviceLinkResponseResult};
+
+
+
+
+pub async fn start_createdevicelink_thread(app: &mut MaclincommsApp) {
+
+    let create_device_link_token = app.access_token.clone();
+
+    let endpoint = app.endpoints.create_device_link;
+
+    let (text, color) = match DeviceLinkBundle::collect() {
+
+        None => ("Only a device holding the account keys can link others".to_string(), ratatui::style::Color::LightYellow),
+
+        Some(bundle) => {
+
+            //The secret half of the code stays on screen, the server gets the link id and the sealed bundle only
+            let link_code = generate_link_code();
+            let (link_id, secret) = parse_link_code(&link_code).unwrap();
+            let sealed_bundle = seal_link_bundle(&app.username, &link_id, &secret, &bundle);
+
+            match create_device_link(create_device_link_token, link_id, sealed_bundle, endpoint).await {
+
+                CreateDeviceLinkResponseResult::REQUEST_ERROR => ("Network error or bad request".to_string(), ratatui::style::Color::LightRed),
+
+                CreateDeviceLinkResponseResult::DATABASE_ERROR => ("Database Error".to_string(), ratatui::style::Color::LightRed),
+
+                CreateDeviceLinkResponseResult::UNKNOWN_ERROR => ("Unknown Server Error".to_string(), ratatui::style::Color::LightRed),
+
+                CreateDeviceLinkResponseResult::INVALID_DEVICE_LINK => ("Too much to hand over in one link".to_string(), ratatui::style::Color::LightRed),
+
+                CreateDeviceLinkResponseResult::DEVICE_LINK_CREATED(expires_at) => {
+                    let minutes_left = (expires_at - Utc::now().timestamp() + 59)/60;
+                    (format!("Code {} ({} min)", link_code, minutes_left), ratatui::style::Color::LightGreen)
+                }
+            }
+        }
+    };
+
+    let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(color));
+
+    app.devices_comps.action_status_block = Paragraph::new(text.fg(color))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+}
@@ -0,0 +1,51 @@
+use ratatui::{style::{Style, Stylize}, widgets::{Block, Borders, Paragraph}};
+
+use crate::tui_main::MaclincommsApp;
+
+use super::get_devices::{get_devices, DevicesResponseResult};
+
+
+
+
+pub async fn start_devices_thread(app: &mut MaclincommsApp) {
+
+    let devices_token = app.access_token.clone();
+
+    let endpoint = app.endpoints.devices;
+
+    let devices_result = get_devices(devices_token, endpoint).await;
+
+
+    let (text, color) = match devices_result {
+
+        DevicesResponseResult::REQUEST_ERROR => ("Network error or bad request".to_string(), ratatui::style::Color::LightRed),
+
+        DevicesResponseResult::DATABASE_ERROR => ("Database Error".to_string(), ratatui::style::Color::LightRed),
+
+        DevicesResponseResult::UNKNOWN_ERROR => ("Unknown Server Error".to_string(), ratatui::style::Color::LightRed),
+
+        DevicesResponseResult::DEVICES_FETCHED(devices, current_device_id) => {
+
+            let text = match devices.len() {
+                0 | 1 => "No other devices are linked to your account".to_string(),
+                count => format!("{} devices linked to your account", count)
+            };
+
+            app.devices_comps.devices = devices;
+            app.devices_comps.current_device_id = current_device_id;
+            app.devices_comps.current_index = 0;
+            app.devices_comps.scroll_state.scroll_to_top();
+
+            (text, ratatui::style::Color::LightCyan)
+        }
+    };
+
+    let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(color));
+
+    app.devices_comps.action_status_block = Paragraph::new(text.fg(color))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+}
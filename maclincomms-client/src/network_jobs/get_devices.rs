@@ -0,0 +1,63 @@
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::user_model::{LinkedDevice, StatusTypes};
+
+#[derive(Debug)]
+pub enum DevicesResponseResult {
+    DEVICES_FETCHED(Vec<LinkedDevice>, String), //With the account's devices and the id of the one asking
+    DATABASE_ERROR,
+    REQUEST_ERROR,
+    UNKNOWN_ERROR
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DevicesResponseData {
+    pub status_type: StatusTypes,
+    pub devices: Vec<LinkedDevice>,
+    pub current_device_id: String,
+    pub message: String
+}
+
+
+
+
+pub async fn get_devices(
+    token: String,
+    devices_endpoint: &'static str
+) -> DevicesResponseResult {
+
+    let url = devices_endpoint.to_string();
+    let client = Client::new();
+
+
+    let response = client
+        .get(url)
+        .header("Authorization", token)
+        .send()
+        .await;
+
+
+    match response {
+        Ok(data) => {
+
+            let res_data = data.json::<DevicesResponseData>().await.unwrap();
+
+            match res_data.status_type {
+                StatusTypes::DEVICES_FETCHED => {
+                    return DevicesResponseResult::DEVICES_FETCHED(res_data.devices, res_data.current_device_id);
+                }
+                StatusTypes::DATABASE_ERROR => {
+                    return DevicesResponseResult::DATABASE_ERROR;
+                }
+                _ => {
+                    return DevicesResponseResult::UNKNOWN_ERROR;
+                }
+            }
+
+        },
+        Err(_) => {
+            return DevicesResponseResult::REQUEST_ERROR;
+        }
+    }
+}
@@ -62,4 +62,31 @@ pub async fn get_dm_chats(
             return GetDmChatsResponseResult::REQUEST_ERROR;
         }
     }
-}
\ No newline at end of file
+}
+
+//Username -> chats, still encrypted with each DM's session key
+pub fn dm_chats_map(data: ChatData) -> HashMap<String, Vec<(String, String, String, String, bool, String)>> {
+    let mut dms_data_map: HashMap<String, Vec<(String, String, String, String, bool, String)>> = HashMap::new();
+
+    for user_dm in data.0{
+        if let Some((username, chats)) = user_dm.0.into_iter().next() {
+            let dm_messages: Vec<(String, String, String, String, bool, String)> = chats
+                .into_iter()
+                .map(|msg_tuple| 
+                    (
+                    msg_tuple.0, 
+                    msg_tuple.1, 
+                    msg_tuple.2, 
+                    msg_tuple.3, 
+                    msg_tuple.4, 
+                    msg_tuple.5
+                    )
+                )
+                .collect();
+
+            dms_data_map.insert(username, dm_messages);
+        }
+    }
+
+    return dms_data_map;
+}
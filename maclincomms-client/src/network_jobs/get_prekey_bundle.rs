@@ -0,0 +1,68 @@
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::user_model::{PrekeyBundle, StatusTypes};
+
+#[derive(Debug)]
+pub enum GetPrekeyBundleResponseResult {
+    PREKEY_BUNDLE_FETCHED(PrekeyBundle),
+    PREKEY_BUNDLE_NOT_FOUND,
+    DATABASE_ERROR,
+    REQUEST_ERROR,
+    UNKNOWN_ERROR
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GetPrekeyBundleResponseData {
+    pub status_type: StatusTypes,
+    pub bundle: Option<PrekeyBundle>,
+    pub message: String
+}
+
+
+
+
+pub async fn get_prekey_bundle(
+    token: String,
+    username: String,
+    prekey_bundle_endpoint: &'static str
+) -> GetPrekeyBundleResponseResult {
+
+    let url = prekey_bundle_endpoint.to_string();
+    let client = Client::new();
+
+
+    let response = client
+        .get(url)
+        .query(&[("username", username)])
+        .header("Authorization", token)
+        .send()
+        .await;
+
+
+    match response {
+        Ok(data) => {
+
+            let res_data = data.json::<GetPrekeyBundleResponseData>().await.unwrap();
+
+            match (res_data.status_type, res_data.bundle) {
+                (StatusTypes::PREKEY_BUNDLE_FETCHED, Some(bundle)) => {
+                    return GetPrekeyBundleResponseResult::PREKEY_BUNDLE_FETCHED(bundle);
+                }
+                (StatusTypes::PREKEY_BUNDLE_NOT_FOUND, _) => {
+                    return GetPrekeyBundleResponseResult::PREKEY_BUNDLE_NOT_FOUND;
+                }
+                (StatusTypes::DATABASE_ERROR, _) => {
+                    return GetPrekeyBundleResponseResult::DATABASE_ERROR;
+                }
+                _ => {
+                    return GetPrekeyBundleResponseResult::UNKNOWN_ERROR;
+                }
+            }
+
+        },
+        Err(_) => {
+            return GetPrekeyBundleResponseResult::REQUEST_ERROR;
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::user_model::{ListedRoom, StatusTypes};
+
+#[derive(Debug)]
+pub enum RoomDirectoryResponseResult {
+    ROOM_DIRECTORY_FETCHED(Vec<ListedRoom>, bool), //With the page's rooms and whether another page follows
+    DATABASE_ERROR,
+    REQUEST_ERROR,
+    UNKNOWN_ERROR
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoomDirectoryResponseData {
+    pub status_type: StatusTypes,
+    pub rooms: Vec<ListedRoom>,
+    pub page: usize,
+    pub has_more: bool,
+    pub message: String
+}
+
+
+
+
+pub async fn get_room_directory(
+    token: String,
+    page: usize,
+    room_directory_endpoint: &'static str
+) -> RoomDirectoryResponseResult {
+
+    let url = room_directory_endpoint.to_string();
+    let client = Client::new();
+
+
+    let response = client
+        .get(url)
+        .query(&[("page", page.to_string())])
+        .header("Authorization", token)
+        .send()
+        .await;
+
+
+    match response {
+        Ok(data) => {
+
+            let res_data = data.json::<RoomDirectoryResponseData>().await.unwrap();
+
+            match res_data.status_type {
+                StatusTypes::ROOM_DIRECTORY_FETCHED => {
+                    return RoomDirectoryResponseResult::ROOM_DIRECTORY_FETCHED(res_data.rooms, res_data.has_more);
+                }
+                StatusTypes::DATABASE_ERROR => {
+                    return RoomDirectoryResponseResult::DATABASE_ERROR;
+                }
+                _ => {
+                    return RoomDirectoryResponseResult::UNKNOWN_ERROR;
+                }
+            }
+
+        },
+        Err(_) => {
+            return RoomDirectoryResponseResult::REQUEST_ERROR;
+        }
+    }
+}
@@ -1,11 +1,11 @@
 use std::{collections::HashMap, thread};
 
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use ratatui::{style::{ Style, Stylize}, text::{Line, Text}, widgets::{Block, Borders, Paragraph}};
 
 use crate::{crypto::decrypt_msg::decrypt_dm_chats_session, tui_main::MaclincommsApp, user_model::{DmChats_Warehouse, DmSessionEncryption_Key}};
 
-use super::get_dm_chats::{get_dm_chats, GetDmChatsResponseResult};
+use super::get_dm_chats::{dm_chats_map, get_dm_chats, GetDmChatsResponseResult};
 
 
 
@@ -71,29 +71,9 @@ pub async fn start_getdmchats_thread(app: &mut MaclincommsApp) {
         GetDmChatsResponseResult::DMS_DATA_FETCHED(data) => {
 
             //Storing in Data Level Warehouse
-            let mut dms_data_map: HashMap<String, Vec<(String, String, String, String, bool, String)>> = HashMap::new();
-
-            for user_dm in data.0{
-                if let Some((username, chats)) = user_dm.0.into_iter().next() {
-                    let dm_messages: Vec<(String, String, String, String, bool, String)> = chats
-                        .into_iter()
-                        .map(|msg_tuple| 
-                            (
-                            msg_tuple.0, 
-                            msg_tuple.1, 
-                            msg_tuple.2, 
-                            msg_tuple.3, 
-                            msg_tuple.4, 
-                            msg_tuple.5
-                            )
-                        )
-                        .collect();
-
-                    dms_data_map.insert(username, dm_messages);
-                }
-            }
+            let dms_data_map = dm_chats_map(data);
 
-            let dm_session_keys: DiskPersist<HashMap<String, DmSessionEncryption_Key>> = DiskPersist::init("persistent-dms-session-keys").unwrap();
+            let dm_session_keys: SealedPersist<HashMap<String, DmSessionEncryption_Key>> = SealedPersist::init("persistent-dms-session-keys").unwrap();
             if let Err(e) = dm_session_keys.read() {
                 //error handling
             }
@@ -108,12 +88,15 @@ pub async fn start_getdmchats_thread(app: &mut MaclincommsApp) {
                         });
 
                         match decrypt_msgs_task_handle.join(){
-                            Ok(dms_data_decrypted) => {
+                            Ok(mut dms_data_decrypted) => {
+                                merge_restored_dm_chats(&mut dms_data_decrypted);
                                 //Storing into App State
                                 app.dmchats_warehouse = DmChats_Warehouse{
                                     dms_session_key: session_key_data,
                                     dms_data: dms_data_decrypted
-                                }
+                                };
+                                //Local copy for `maclincomms export`
+                                app.dmchats_warehouse.save_local();
                             }
                             Err(err) => {
                                 //thread joinhandle error
@@ -127,4 +110,22 @@ pub async fn start_getdmchats_thread(app: &mut MaclincommsApp) {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+
+//Chats brought back by `maclincomms import` fill in whatever the cloud backup doesn't have,
+//they are uploaded with the rest on exit so the file is only needed once
+fn merge_restored_dm_chats(dms_data: &mut HashMap<String, Vec<(String, String, String, String, bool, String)>>) {
+    let restored_chats_disk: SealedPersist<HashMap<String, Vec<(String, String, String, String, bool, String)>>> = SealedPersist::init("persistent-restored-dm-chats").unwrap();
+    let Ok(Some(restored_chats)) = restored_chats_disk.read() else {
+        return;
+    };
+
+    for (username, chats) in restored_chats {
+        let cloud_chats = dms_data.entry(username).or_default();
+        if cloud_chats.is_empty() {
+            *cloud_chats = chats;
+        }
+    }
+    restored_chats_disk.remove();
+}
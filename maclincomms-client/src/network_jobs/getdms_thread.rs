@@ -1,7 +1,7 @@
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use ratatui::{style::{ Style, Stylize}, text::{Line, Text}, widgets::{Block, Borders, Paragraph}};
 
-use crate::{tui_main::MaclincommsApp, user_model::DmUser_Data};
+use crate::{tui_main::MaclincommsApp, tui_widgets::dmuser_panel::DmUserComponents, user_model::{DmUser_Data, VerifiedContacts}};
 
 use super::get_dms::{get_dms, GetDmsResponseResult};
 
@@ -69,8 +69,36 @@ pub async fn start_getdms_thread(app: &mut MaclincommsApp) {
         GetDmsResponseResult::DMS_DATA_FETCHED(data) => {
 
             //logic to store dms data and keys
-            let persistent_storage: DiskPersist<Vec<DmUser_Data>> = DiskPersist::init("persistent-user-dms-list").unwrap();
+            let persistent_storage: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
             persistent_storage.write(&data).unwrap();
+
+            //Checking identity keys against the ones the user verified, a changed key could mean the server swapped it
+            let verified_contacts = VerifiedContacts::load();
+            app.dmuser_comps.verification_status = data.iter()
+                .map(|dm| (dm.username.clone(), verified_contacts.status(&dm.username, &dm.public_identity_key)))
+                .collect();
+
+            let key_changed_users = app.dmuser_comps.key_changed_users();
+            if !key_changed_users.is_empty() {
+                app.dmuser_comps.action_status_block = DmUserComponents::get_key_changed_block(&key_changed_users);
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+
+//The contact from the dms list, fetched again first when their message came from a device the list doesn't have yet
+pub async fn load_dm_contact(app: &mut MaclincommsApp, username: &str, device_id: &str) -> Option<DmUser_Data> {
+
+    let contact = DmUser_Data::load(username);
+
+    let knows_device = contact.as_ref()
+        .is_some_and(|dm| device_id.is_empty() || dm.devices.iter().any(|device| device.device_id==device_id));
+    if knows_device {
+        return contact;
+    }
+
+    start_getdms_thread(app).await;
+
+    return DmUser_Data::load(username);
+}
@@ -2,13 +2,17 @@ use reqwest::{Client, Response, Error};
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::user_model::StatusTypes;
+use crate::user_model::{StatusTypes, INVITE_TOKEN_PREFIX};
 
 #[derive(Debug)]
 pub enum JoinRoomResponseResult {
     ROOM(JoinRoomResponseData),
     ROOM_NOT_FOUND,
     INVALID_CREDENTIALS,
+    INVALID_INVITE,
+    ROOM_FULL,
+    JOIN_REQUEST_SENT,
+    BANNED_FROM_ROOM,
     DATABASE_ERROR,
     REQUEST_ERROR,
     UNKNOWN_ERROR
@@ -33,10 +37,17 @@ pub async fn join_room(
     let url = join_room_endpoint.to_string();
     let client = Client::new();
 
-    let room = json!({
-        "room_name": room_name,
-        "room_key": room_key
-    });
+    //Invites are pasted in the room key field, the server picks the room from the invite
+    let room = match room_key.starts_with(INVITE_TOKEN_PREFIX) {
+        true => json!({
+            "room_name": room_name,
+            "invite_token": room_key
+        }),
+        false => json!({
+            "room_name": room_name,
+            "room_key": room_key
+        })
+    };
 
     let response = client
         .post(url)
@@ -59,6 +70,18 @@ pub async fn join_room(
                 StatusTypes::INVALID_CREDENTIALS => {
                     return JoinRoomResponseResult::INVALID_CREDENTIALS;
                 }
+                StatusTypes::INVALID_INVITE => {
+                    return JoinRoomResponseResult::INVALID_INVITE;
+                }
+                StatusTypes::ROOM_FULL => {
+                    return JoinRoomResponseResult::ROOM_FULL;
+                }
+                StatusTypes::JOIN_REQUEST_SENT => {
+                    return JoinRoomResponseResult::JOIN_REQUEST_SENT;
+                }
+                StatusTypes::BANNED_FROM_ROOM => {
+                    return JoinRoomResponseResult::BANNED_FROM_ROOM;
+                }
                 StatusTypes::DATABASE_ERROR => {
                     return JoinRoomResponseResult::DATABASE_ERROR;
                 }
@@ -1,4 +1,4 @@
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use ratatui::{style::{Style, Stylize}, text::Line, widgets::{Block, Borders, Paragraph}};
 
 use crate::{event_model::Event, get_current_time, screens_model::Screens, tui_main::MaclincommsApp, tui_widgets::joinroom_textarea::JoinRoomTaskStatus, user_model::Room_Keys};
@@ -85,6 +85,26 @@ pub async fn start_joinroom_task(app: &mut MaclincommsApp) {
 
         }
 
+        JoinRoomResponseResult::BANNED_FROM_ROOM => {
+
+            app.joinroom_textarea.task_status = JoinRoomTaskStatus::COMPLETED;
+
+            let text = "You are banned from this room".to_string();
+                
+            let status_block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::default())
+                .border_style(Style::default().fg(ratatui::style::Color::LightRed));
+                
+            app.joinroom_textarea.status_block = Paragraph::new(text.light_red())
+                .alignment(ratatui::layout::Alignment::Center)
+                .block(status_block);
+
+            /* Refresh status for retries */
+            app.joinroom_textarea.task_status = JoinRoomTaskStatus::NOT_INITIATED;
+
+        }
+
         JoinRoomResponseResult::ROOM_NOT_FOUND => {
 
             app.joinroom_textarea.task_status = JoinRoomTaskStatus::COMPLETED;
@@ -125,39 +145,116 @@ pub async fn start_joinroom_task(app: &mut MaclincommsApp) {
 
         }
 
-        JoinRoomResponseResult::ROOM(room) => {
+        JoinRoomResponseResult::INVALID_INVITE => {
 
             app.joinroom_textarea.task_status = JoinRoomTaskStatus::COMPLETED;
 
-            let text = "Room joined Successfully".to_string();
+            let text = "Invite is invalid, expired or used up".to_string();
                 
             let status_block = Block::default()
                 .borders(Borders::ALL)
                 .border_type(ratatui::widgets::BorderType::default())
-                .border_style(Style::default().fg(ratatui::style::Color::LightGreen));
+                .border_style(Style::default().fg(ratatui::style::Color::LightRed));
                 
-            app.joinroom_textarea.status_block = Paragraph::new(text.light_green())
+            app.joinroom_textarea.status_block = Paragraph::new(text.light_red())
                 .alignment(ratatui::layout::Alignment::Center)
                 .block(status_block);
 
+            /* Refresh status for retries */
+            app.joinroom_textarea.task_status = JoinRoomTaskStatus::NOT_INITIATED;
 
-//------------JOINING ROOM-------------------------------------------------------------------------------
+        }
 
-            app.roomchat_comps.chat_history.lock().unwrap().clear(); //Clear old room chats ui history if any
-            app.room_keys = Room_Keys::new(); //Clear old room keys and data
+        JoinRoomResponseResult::ROOM_FULL => {
 
-            let room_token = room.room_token;
-            let room_joined_name = room.room_name;
+            app.joinroom_textarea.task_status = JoinRoomTaskStatus::COMPLETED;
 
-            /* UPDATING CURRENT ROOM DATA IN APP */
-            app.is_current_room_owner = false;
-            app.roomchat_comps.room_name = room_joined_name;
-            app.room_token = room_token.clone();
+            let text = "Room is full".to_string();
+                
+            let status_block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::default())
+                .border_style(Style::default().fg(ratatui::style::Color::LightRed));
+                
+            app.joinroom_textarea.status_block = Paragraph::new(text.light_red())
+                .alignment(ratatui::layout::Alignment::Center)
+                .block(status_block);
 
-            let roomchat_event_tx = app.network_event_tx.clone();
+            /* Refresh status for retries */
+            app.joinroom_textarea.task_status = JoinRoomTaskStatus::NOT_INITIATED;
 
-            roomchat_event_tx.send(Event::RoomChatEvent(room_token));
+        }
+
+        JoinRoomResponseResult::JOIN_REQUEST_SENT => {
+
+            app.joinroom_textarea.task_status = JoinRoomTaskStatus::COMPLETED;
+
+            let text = "Knocked, you'll be notified once the owner approves".to_string();
+                
+            let status_block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::default())
+                .border_style(Style::default().fg(ratatui::style::Color::LightYellow));
+                
+            app.joinroom_textarea.status_block = Paragraph::new(text.light_yellow())
+                .alignment(ratatui::layout::Alignment::Center)
+                .block(status_block);
+
+            /* Refresh status for retries */
+            app.joinroom_textarea.task_status = JoinRoomTaskStatus::NOT_INITIATED;
+
+        }
+
+        JoinRoomResponseResult::ROOM(room) => {
+
+            app.joinroom_textarea.task_status = JoinRoomTaskStatus::COMPLETED;
+
+            let text = "Room joined Successfully".to_string();
+                
+            let status_block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::default())
+                .border_style(Style::default().fg(ratatui::style::Color::LightGreen));
+                
+            app.joinroom_textarea.status_block = Paragraph::new(text.light_green())
+                .alignment(ratatui::layout::Alignment::Center)
+                .block(status_block);
+
+
+            enter_joined_room(app, room.room_name, room.room_token).await;
                     
         }
     }
+}
+
+
+//------------JOINING ROOM-------------------------------------------------------------------------------
+//Also where approved knocks come in, with the room token from the owner's approval
+pub async fn enter_joined_room(app: &mut MaclincommsApp, room_joined_name: String, room_token: String) {
+
+    //Already in it, on screen or in the background
+    if app.is_room_active(&room_joined_name) {
+        app.switch_to_room(&room_joined_name);
+        return;
+    }
+    app.park_current_room(); //The room on screen stays open in the background
+
+    app.roomchat_comps.chat_history.lock().unwrap().clear(); //Clear old room chats ui history if any
+    app.room_keys = Room_Keys::new(); //Clear old room keys and data
+
+    /* UPDATING CURRENT ROOM DATA IN APP */
+    //Owners of persistent rooms come back through here too
+    let room_data = get_room_data(room_token.clone(), app.endpoints.get_room_data).await;
+    app.is_current_room_owner = room_data.as_ref().is_some_and(|room_data| room_data.room_owner==app.username);
+    app.current_room_owner = room_data.as_ref().map(|room_data| room_data.room_owner.clone()).unwrap_or_default();
+    app.current_room_settings = room_data.map(|room_data| room_data.settings).unwrap_or_default();
+    app.last_room_message_at = None;
+    app.room_history.clear();
+    app.room_history_shared = None;
+    app.roomchat_comps.room_name = room_joined_name;
+    app.room_token = room_token.clone();
+
+    let roomchat_event_tx = app.network_event_tx.clone();
+
+    roomchat_event_tx.send(Event::RoomChatEvent(room_token));
 }
\ No newline at end of file
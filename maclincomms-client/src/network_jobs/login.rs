@@ -0,0 +1,25 @@
+use reqwest::{Client, Error};
+
+use crate::user_model::{LoginResponse, UserLoginPayload};
+
+
+
+pub async fn login_account(
+    user: UserLoginPayload,
+    login_endpoint: &'static str
+) -> Result<LoginResponse, Error> {
+
+    let url = login_endpoint.to_string();
+    let client = Client::new();
+
+    let response = client
+        .post(url)
+        .json(&user)
+        .send()
+        .await?;
+
+    let data = response.json::<LoginResponse>().await?;
+
+    return Ok(data);
+
+}
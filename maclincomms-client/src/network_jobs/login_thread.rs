@@ -1,6 +1,7 @@
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use ratatui::{style::{Style, Stylize}, widgets::{Block, Borders, Paragraph}};
 
+use super::prekeys_thread::start_uploadprekeys_thread;
 use crate::{login_user::{login, LoginResponseResult}, screens_model::Screens, tui_main::MaclincommsApp, tui_widgets::login_textarea::LoginTaskStatus, user_model::UserData};
 
 
@@ -124,6 +125,26 @@ pub async fn start_login_task(app: &mut MaclincommsApp) {
 
         }
 
+        LoginResponseResult::DEVICE_REVOKED => {
+
+            app.login_textarea.task_status = LoginTaskStatus::COMPLETED;
+
+            let text = "This device was revoked, log in again to add it as a new device".to_string();
+                
+            let status_block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::default())
+                .border_style(Style::default().fg(ratatui::style::Color::LightYellow));
+                
+            app.login_textarea.status_block = Paragraph::new(text.light_yellow())
+                .alignment(ratatui::layout::Alignment::Center)
+                .block(status_block);
+
+            /* Refresh status for retries */
+            app.login_textarea.task_status = LoginTaskStatus::NOT_INITIATED;
+
+        }
+
         LoginResponseResult::TOKEN(token) => {
 
             app.login_textarea.task_status = LoginTaskStatus::COMPLETED;
@@ -158,9 +179,12 @@ pub async fn start_login_task(app: &mut MaclincommsApp) {
                 refresh_token: refreshtoken, 
                 token_expiry: exp 
             };
-            let persistent_storage: DiskPersist<UserData> = DiskPersist::init("persistent-user-data").unwrap();
+            let persistent_storage: SealedPersist<UserData> = SealedPersist::init("persistent-user-data").unwrap();
             persistent_storage.write(&data).unwrap();
 
+            /* PUBLISHING X3DH PREKEYS */
+            start_uploadprekeys_thread(app).await;
+
             /* SWITCHING SCREEN */
             app.current_screen = Screens::CHAT_OPTIONS_SCREEN;
             app.chatoptions_menu.activate();
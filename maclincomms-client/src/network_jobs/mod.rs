@@ -1,4 +1,6 @@
+pub mod login;
 pub mod login_thread;
+pub mod register;
 pub mod register_thread;
 pub mod roomcreation_thread;
 pub mod joinroom_thread;
@@ -6,6 +8,10 @@ pub mod authN_user;
 pub mod request_token;
 pub mod create_room;
 pub mod join_room;
+pub mod create_room_invite;
+pub mod roominvite_thread;
+pub mod respond_join_request;
+pub mod respondjoinrequest_thread;
 pub mod add_user;
 pub mod adduser_thread;
 pub mod block_user;
@@ -21,4 +27,19 @@ pub mod getdms_thread;
 pub mod get_dm_chats;
 pub mod getdmchats_thread;
 pub mod get_roomdata;
-pub mod upload_dm_chats;
\ No newline at end of file
+pub mod get_room_directory;
+pub mod roomdirectory_thread;
+pub mod get_devices;
+pub mod devices_thread;
+pub mod revoke_device;
+pub mod revokedevice_thread;
+pub mod create_device_link;
+pub mod createdevicelink_thread;
+pub mod claim_device_link;
+pub mod claimdevicelink_thread;
+pub mod rotate_identity;
+pub mod rotateidentity_thread;
+pub mod upload_dm_chats;
+pub mod upload_prekeys;
+pub mod get_prekey_bundle;
+pub mod prekeys_thread;
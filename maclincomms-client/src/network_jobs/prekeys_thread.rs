@@ -0,0 +1,47 @@
+use crate::{tui_main::MaclincommsApp, user_model::{UserDevice, UserIdentityKeys, UserPrekeys}};
+
+use super::upload_prekeys::{upload_prekeys, UploadPrekeysResponseResult};
+
+
+//Refill the server's one time prekeys once fewer than this are left
+const MIN_ONE_TIME_PREKEYS: usize = 20;
+const ONE_TIME_PREKEYS_BATCH: usize = 100;
+
+
+
+//Publishes our X3DH prekeys after login so others can start DMs with us while offline
+pub async fn start_uploadprekeys_thread(app: &mut MaclincommsApp) {
+
+    //Prekeys are published for the account's identity key, which other devices don't hold
+    if !UserDevice::load_or_create().account_device {
+        return;
+    }
+
+    let Some(identity_pub_key) = UserIdentityKeys::load_public_key() else {
+        return;
+    };
+
+    //New prekeys if there are none yet or the identity key changed (re-registered)
+    let mut prekeys = match UserPrekeys::load() {
+        Some(prekeys) if prekeys.identity_pub_key==identity_pub_key => prekeys,
+        _ => {
+            let prekeys = UserPrekeys::new(identity_pub_key);
+            prekeys.save();
+            prekeys
+        }
+    };
+
+    let endpoint = app.endpoints.upload_prekeys;
+
+    //Signed prekey first, the server tells how many one time prekeys it still has
+    let upload_result = upload_prekeys(app.access_token.clone(), endpoint, prekeys.upload_payload(Vec::new())).await;
+
+    if let UploadPrekeysResponseResult::PREKEYS_UPLOADED(count) = upload_result {
+        if count < MIN_ONE_TIME_PREKEYS {
+            let one_time_prekeys = prekeys.generate_one_time_prekeys(ONE_TIME_PREKEYS_BATCH);
+            prekeys.save();
+
+            upload_prekeys(app.access_token.clone(), endpoint, prekeys.upload_payload(one_time_prekeys)).await;
+        }
+    }
+}
@@ -1,13 +1,13 @@
 use std::collections::HashMap;
 
 use base64::{engine::general_purpose, Engine};
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use reqwest::{Client};
 use serde::{Deserialize, Serialize};
 
-use crate::{crypto::{decrypt_msg::decrypt_dm_message, dm_keys::{derive_message_key, generate_dh_keypair, generate_receiver_chainkey, generate_sender_chainkey, generate_shared_rootkey}}, get_current_date, get_current_time, tui_main::MaclincommsApp, tui_widgets::notifications_panel::NotificationStatus, user_model::{DmDoubleRatchet_Keys, DmMessage, DmUser_Data, NotificationData, NotificationType, StatusTypes, UserIdentityKeys}};
+use crate::{crypto::{double_ratchet::DoubleRatchet, x3dh::accept_x3dh_handshake}, get_current_date, get_current_time, tui_main::MaclincommsApp, tui_widgets::notifications_panel::NotificationStatus, user_model::{AcceptedNotificationContent, DmMessage, DmUser_Data, NotificationData, NotificationType, StatusTypes, UserDevice}};
 
-use super::getdms_thread::start_getdms_thread;
+use super::{getdms_thread::{load_dm_contact, start_getdms_thread}, rotateidentity_thread::accept_identity_rotation};
 
 
 #[derive(Deserialize, Debug, Clone)]
@@ -68,82 +68,27 @@ pub async fn get_queued_notifications(app: &mut MaclincommsApp) {
                                 };
                     
                                 let notification_cloned = notification.clone(); //for second check for accepted notification
+                                //Parsing Message Contents, and the sender's entry in the dms list for the device it came from
+                                let msg_data_res = match notification.n_type {
+                                    NotificationType::MESSAGE => serde_json::from_str::<DmMessage>(&notification.content).ok(),
+                                    _ => None
+                                };
+                                let msg_contact_res = match &msg_data_res {
+                                    Some(msg_data) => load_dm_contact(app, &notification.from, &msg_data.device_id).await,
+                                    None => None
+                                };
+                                //A contact's new identity key is checked against their old one before the session starts over
+                                let identity_rotated_res = match notification.n_type {
+                                    NotificationType::IDENTITY_ROTATED => Some(accept_identity_rotation(app, &notification.from, &notification.content)),
+                                    _ => None
+                                };
                                 if let Ok(mut n_history_lock) = app.notifications_comps.notifications_history.lock() {
                                     //For Message Notification, parsing its content
                                     if matches!(notification.n_type, NotificationType::MESSAGE){
-                                        if let Some(keys) = app.dme2ee_data.dms.get_mut(&notification.from){
-                                                let mut decrypted_message = "".to_string();
-                                                //Parsing Message Contents
-                                                let msg_data_res = serde_json::from_str::<DmMessage>(&notification.content);
-                                                if let Ok(msg_data) = msg_data_res{
-                                                    //Check if the public key sent with the message is the same as old or not
-                                                    // ------> Extracting dh_pub key from [encrpted_msg_content].[dhpub_key]
-                                                    let msg_parts: Vec<&str> = msg_data.content.split('.').collect();
-                                                    let ciphertext = msg_parts[0];
-                                                    let their_dh_pub = msg_parts[1];
-                                                    let their_dh_pub_bytes: [u8;32] = general_purpose::STANDARD.decode(their_dh_pub).unwrap().try_into().unwrap();
-                                                    //match new and old
-                                                    if keys.their_old_dh_pub_key==their_dh_pub_bytes{
-                                                        //Get Recv Chainkey
-                                                        let recv_chain_key = keys.receiving_chain_key;
-                                                        let recv_msg_key = derive_message_key(recv_chain_key);
-                                                        //Decrypt Message
-                                                        decrypted_message = decrypt_dm_message(recv_msg_key, ciphertext);
-                                                    }
-                                                    else{
-                                                        //Check if receiving first message
-                                                        if keys.their_old_dh_pub_key==[0u8;32]{
-                                                            keys.their_old_dh_pub_key = their_dh_pub_bytes;
-                                                            //Load private key from disk
-                                                            let id_keys: DiskPersist<UserIdentityKeys> = DiskPersist::init("persistent-user-identity-keypair").unwrap();
-                                                            if let Ok(data_res) = id_keys.read(){
-                                                                match data_res{
-                                                                    Some(data) => {
-                                                                        let priv_key = data.private_identity_key;
-                                                                        let priv_key_bytes: [u8;32] = general_purpose::STANDARD.decode(priv_key).unwrap().try_into().unwrap();
-                                                                        let rootkey = generate_shared_rootkey(their_dh_pub_bytes, priv_key_bytes);
-                                                                        let receiving_chainkey = generate_receiver_chainkey(rootkey);
-                                                                        keys.receiving_chain_key = receiving_chainkey;
-                                                                        let recv_mkey = derive_message_key(receiving_chainkey);
-                                                                        //Decrypt Message
-                                                                        decrypted_message = decrypt_dm_message(recv_mkey, ciphertext);
-                                                                        //Generate new dh pair
-                                                                        let (public_dh_key, private_dh_key) = generate_dh_keypair();
-                                                                        let new_rootkey = generate_shared_rootkey(their_dh_pub_bytes, private_dh_key);
-                                                                        let sending_chainkey = generate_sender_chainkey(new_rootkey);
-                                                                        //Store new ratcheted keys
-                                                                        keys.root_key = new_rootkey;
-                                                                        keys.dh_pub_key = public_dh_key;
-                                                                        keys.dh_priv_key = private_dh_key;
-                                                                        keys.sending_chain_key = sending_chainkey;
-                                                                        keys.receiving_chain_key = receiving_chainkey;
-                                                                    }
-                                                                    None => {}
-                                                                }
-                                                            }
-
-                                                        }
-                                                        else{
-                                                            keys.their_old_dh_pub_key = their_dh_pub_bytes;
-                                                            let my_dh_priv = keys.dh_priv_key;
-                                                            let rootkey = generate_shared_rootkey(their_dh_pub_bytes, my_dh_priv);
-                                                            let receiving_chainkey = generate_receiver_chainkey(rootkey);
-                                                            keys.receiving_chain_key = receiving_chainkey;
-                                                            let recv_mkey = derive_message_key(receiving_chainkey);
-                                                            //Decrypt Message
-                                                            decrypted_message = decrypt_dm_message(recv_mkey, ciphertext);
-                                                            //Generate new dh pair
-                                                            let (public_dh_key, private_dh_key) = generate_dh_keypair();
-                                                            let new_rootkey = generate_shared_rootkey(their_dh_pub_bytes, private_dh_key);
-                                                            let sending_chainkey = generate_sender_chainkey(new_rootkey);
-                                                            //Store new ratcheted keys
-                                                            keys.root_key = new_rootkey;
-                                                            keys.dh_pub_key = public_dh_key;
-                                                            keys.dh_priv_key = private_dh_key;
-                                                            keys.sending_chain_key = sending_chainkey;
-                                                            keys.receiving_chain_key = receiving_chainkey;
-                                                        }
-                                                    }
+                                        if let Some(contact) = msg_contact_res{
+                                                if let Some(msg_data) = msg_data_res{
+                                                    // ------> Decrypting the DM envelope with the ratchet of the device it came from, ratcheting and skipping ahead on their chain as needed
+                                                    let decrypted_message = app.dme2ee_data.decrypt_from_device(&contact, &msg_data.device_id, &msg_data.content).unwrap_or_default();
 
                                                     //Pushing to chats in data level
                                                     if let Some(dm_chats) = app.dmchats_warehouse.dms_data.get_mut(&notification.from){
@@ -175,7 +120,12 @@ pub async fn get_queued_notifications(app: &mut MaclincommsApp) {
                                                 n_type: notification.n_type,
                                                 from: notification.from,
                                                 to: notification.to,
-                                                content: notification.content,
+                                                //ACCEPTED carries the X3DH handshake along with the message
+                                                content: match notification_cloned.n_type {
+                                                    NotificationType::ACCEPTED => AcceptedNotificationContent::parse(&notification.content).message,
+                                                    NotificationType::IDENTITY_ROTATED => identity_rotated_res.unwrap_or_default(),
+                                                    _ => notification.content
+                                                },
                                                 time: notification.time
                                             }
                                         );
@@ -192,7 +142,7 @@ pub async fn get_queued_notifications(app: &mut MaclincommsApp) {
                                         //Retrieve latest dms list
                                         start_getdms_thread(app).await;
                                         //Read the latest written data on disk
-                                        let persistent_dms_list: DiskPersist<Vec<DmUser_Data>> = DiskPersist::init("persistent-user-dms-list").unwrap();
+                                        let persistent_dms_list: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
                                         if let Ok(data_res) = persistent_dms_list.read(){
                                             match data_res{
                                                 Some(dms_list) => {
@@ -205,21 +155,12 @@ pub async fn get_queued_notifications(app: &mut MaclincommsApp) {
                                                         their_pub_key = k;
                                                     }
                                                     let their_pub_key_bytes: [u8;32] = general_purpose::STANDARD.decode(their_pub_key).unwrap().try_into().unwrap();
-                                                    //Generate first DH pair
-                                                    let (public_dh_key, private_dh_key) = generate_dh_keypair();
-                                                    //Compute root key
-                                                    let rootkey = generate_shared_rootkey(their_pub_key_bytes, private_dh_key);
-                                                    //Derive sending chain key
-                                                    let sending_chainkey = generate_sender_chainkey(rootkey);
-                                                    //Initialise first keys with user and store in dme2ee data
-                                                    let keys_data = DmDoubleRatchet_Keys{
-                                                        root_key: rootkey,
-                                                        their_old_dh_pub_key: [0u8;32],
-                                                        dh_pub_key: public_dh_key,
-                                                        dh_priv_key: private_dh_key,
-                                                        sending_chain_key: sending_chainkey,
-                                                        receiving_chain_key: [0u8;32],
-                                                    };
+                                                    //Initialise the DM ratchet from the X3DH handshake they sent, or from their identity key, and store in dme2ee data
+                                                    //Only the account device holds the prekeys the handshake was made with
+                                                    let keys_data = AcceptedNotificationContent::parse(&notification_cloned.content).x3dh_handshake
+                                                        .filter(|_| UserDevice::load_or_create().account_device)
+                                                        .and_then(|handshake| accept_x3dh_handshake(their_pub_key_bytes, &handshake))
+                                                        .unwrap_or_else(|| DoubleRatchet::new(their_pub_key_bytes));
 
                                                     app.dme2ee_data.dms.insert(notification_cloned.from, keys_data);     
                                                 }
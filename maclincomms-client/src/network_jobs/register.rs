@@ -0,0 +1,25 @@
+use reqwest::{Client, Error};
+
+use crate::user_model::{RegisterResponse, UserRegisterPayload};
+
+
+
+pub async fn register_account(
+    user: UserRegisterPayload,
+    register_endpoint: &'static str
+) -> Result<RegisterResponse, Error> {
+
+    let url = register_endpoint.to_string();
+    let client = Client::new();
+
+    let response = client
+        .post(url)
+        .json(&user)
+        .send()
+        .await?;
+
+    let data = response.json::<RegisterResponse>().await?;
+
+    return Ok(data);
+
+}
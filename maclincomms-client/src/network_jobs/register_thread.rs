@@ -1,6 +1,7 @@
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use ratatui::{style::{Style, Stylize}, widgets::{Block, Borders, Paragraph}};
 
+use super::prekeys_thread::start_uploadprekeys_thread;
 use crate::{register_user::{register, RegisterResponseResult}, screens_model::Screens, tui_main::MaclincommsApp, tui_widgets::register_textarea::RegisterTaskStatus, user_model::UserData};
 
 
@@ -105,6 +106,26 @@ pub async fn start_register_task(app: &mut MaclincommsApp) {
 
         }
 
+        RegisterResponseResult::INVALID_USERNAME => {
+
+            app.register_textarea.task_status = RegisterTaskStatus::COMPLETED;
+
+            let text = "Usernames can't contain '#'".to_string();
+                
+            let status_block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::default())
+                .border_style(Style::default().fg(ratatui::style::Color::LightRed));
+                
+            app.register_textarea.status_block = Paragraph::new(text.light_red())
+                .alignment(ratatui::layout::Alignment::Center)
+                .block(status_block);
+
+            /* Refresh status for retries */
+            app.register_textarea.task_status = RegisterTaskStatus::NOT_INITIATED;
+
+        }
+
         RegisterResponseResult::TOKEN(token) => {
 
             app.register_textarea.task_status = RegisterTaskStatus::COMPLETED;
@@ -139,9 +160,12 @@ pub async fn start_register_task(app: &mut MaclincommsApp) {
                 refresh_token: refreshtoken, 
                 token_expiry: exp 
             };
-            let persistent_storage: DiskPersist<UserData> = DiskPersist::init("persistent-user-data").unwrap();
+            let persistent_storage: SealedPersist<UserData> = SealedPersist::init("persistent-user-data").unwrap();
             persistent_storage.write(&data).unwrap();
 
+            /* PUBLISHING X3DH PREKEYS */
+            start_uploadprekeys_thread(app).await;
+
             /* SWITCHING SCREEN */
             app.current_screen = Screens::CHAT_OPTIONS_SCREEN;
             app.chatoptions_menu.activate();
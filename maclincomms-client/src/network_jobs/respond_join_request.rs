@@ -0,0 +1,99 @@
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::user_model::{AcceptanceStatus, StatusTypes};
+
+#[derive(Debug)]
+pub enum RespondJoinRequestResponseResult {
+    JOIN_REQUEST_APPROVED,
+    JOIN_REQUEST_DENIED,
+    NOT_NOTIFIED,
+    ROOM_NOT_FOUND,
+    NOT_ROOM_OWNER,
+    REQUEST_NOT_FOUND,
+    ROOM_FULL,
+    USER_BANNED,
+    DATABASE_ERROR,
+    REQUEST_ERROR,
+    UNKNOWN_ERROR
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RespondJoinRequestResponseData {
+    pub status_type: StatusTypes,
+    pub message: String
+}
+
+
+
+
+pub async fn respond_join_request(
+    token: String,
+    room_name: String,
+    username: String,
+    status: AcceptanceStatus,
+    respond_join_request_endpoint: &'static str
+) -> RespondJoinRequestResponseResult {
+
+    let url = respond_join_request_endpoint.to_string();
+    let client = Client::new();
+
+    let data = json!({
+        "room_name": room_name,
+        "username": username,
+        "acceptance_status": status
+    });
+
+    let response = client
+        .post(url)
+        .json(&data)
+        .header("Authorization", token)
+        .send()
+        .await;
+
+
+    match response {
+        Ok(data) => {
+            
+            let res_data = data.json::<RespondJoinRequestResponseData>().await.unwrap();
+
+            match res_data.status_type {
+                StatusTypes::JOIN_REQUEST_APPROVED => {
+                    return RespondJoinRequestResponseResult::JOIN_REQUEST_APPROVED;
+                }
+                StatusTypes::JOIN_REQUEST_DENIED => {
+                    return RespondJoinRequestResponseResult::JOIN_REQUEST_DENIED;
+                }
+                StatusTypes::NOTIFICATIONS_ERROR => {
+                    return RespondJoinRequestResponseResult::NOT_NOTIFIED;
+                }
+                StatusTypes::ROOM_NOT_FOUND => {
+                    return RespondJoinRequestResponseResult::ROOM_NOT_FOUND;
+                }
+                StatusTypes::INVALID_CREDENTIALS => {
+                    return RespondJoinRequestResponseResult::NOT_ROOM_OWNER;
+                }
+                StatusTypes::USER_NOT_FOUND => {
+                    return RespondJoinRequestResponseResult::REQUEST_NOT_FOUND;
+                }
+                StatusTypes::ROOM_FULL => {
+                    return RespondJoinRequestResponseResult::ROOM_FULL;
+                }
+                StatusTypes::BANNED_FROM_ROOM => {
+                    return RespondJoinRequestResponseResult::USER_BANNED;
+                }
+                StatusTypes::DATABASE_ERROR => {
+                    return RespondJoinRequestResponseResult::DATABASE_ERROR;
+                }
+                _ => {
+                    return RespondJoinRequestResponseResult::UNKNOWN_ERROR;
+                }
+            }
+
+        },
+        Err(_) => {
+            return RespondJoinRequestResponseResult::REQUEST_ERROR;
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use ratatui::{style::{Style, Stylize}, widgets::{Block, Borders, Paragraph}};
+
+use crate::{tui_main::MaclincommsApp, user_model::AcceptanceStatus};
+
+use super::respond_join_request::{respond_join_request, RespondJoinRequestResponseResult};
+
+
+
+
+pub async fn start_respondjoinrequest_thread(app: &mut MaclincommsApp, room_name: String, username: String, status: AcceptanceStatus) {
+
+    let respond_token = app.access_token.clone();
+
+    let endpoint = app.endpoints.respond_join_request;
+
+    let respond_result = respond_join_request(respond_token, room_name.clone(), username.clone(), status, endpoint).await;
+
+    let (text, color) = match respond_result {
+        RespondJoinRequestResponseResult::JOIN_REQUEST_APPROVED => (format!("{} can now join {}", username, room_name), ratatui::style::Color::LightGreen),
+        RespondJoinRequestResponseResult::JOIN_REQUEST_DENIED => (format!("Denied {}'s request to join {}", username, room_name), ratatui::style::Color::LightGreen),
+        RespondJoinRequestResponseResult::NOT_NOTIFIED => (format!("Approved {} but couldnt notify them, they can join with the Room Key", username), ratatui::style::Color::LightYellow),
+        RespondJoinRequestResponseResult::ROOM_NOT_FOUND => (format!("{} no longer exists", room_name), ratatui::style::Color::LightRed),
+        RespondJoinRequestResponseResult::NOT_ROOM_OWNER => (format!("You no longer own {}", room_name), ratatui::style::Color::LightRed),
+        RespondJoinRequestResponseResult::REQUEST_NOT_FOUND => (format!("{} has no pending request for {}", username, room_name), ratatui::style::Color::LightRed),
+        RespondJoinRequestResponseResult::ROOM_FULL => (format!("{} is full, the request stays pending", room_name), ratatui::style::Color::LightRed),
+        RespondJoinRequestResponseResult::USER_BANNED => (format!("{} is banned from {}", username, room_name), ratatui::style::Color::LightRed),
+        RespondJoinRequestResponseResult::DATABASE_ERROR => ("Database Error".to_string(), ratatui::style::Color::LightRed),
+        RespondJoinRequestResponseResult::UNKNOWN_ERROR => ("Unknown Server Error".to_string(), ratatui::style::Color::LightRed),
+        RespondJoinRequestResponseResult::REQUEST_ERROR => ("Network error or bad request".to_string(), ratatui::style::Color::LightRed)
+    };
+
+    let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(color));
+
+    app.notifications_comps.action_status_block = Paragraph::new(text.fg(color))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+}
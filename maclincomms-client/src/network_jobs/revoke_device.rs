@@ -0,0 +1,75 @@
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::user_model::StatusTypes;
+
+#[derive(Debug)]
+pub enum RevokeDeviceResponseResult {
+    DEVICE_REVOKED,
+    DEVICE_NOT_FOUND,
+    CANNOT_REVOKE_CURRENT_DEVICE,
+    DATABASE_ERROR,
+    REQUEST_ERROR,
+    UNKNOWN_ERROR
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RevokeDeviceResponseData {
+    pub status_type: StatusTypes,
+    pub message: String
+}
+
+
+
+
+pub async fn revoke_device(
+    token: String,
+    device_id: String,
+    revoke_device_endpoint: &'static str
+) -> RevokeDeviceResponseResult {
+
+    let url = revoke_device_endpoint.to_string();
+    let client = Client::new();
+
+    let data = json!({
+        "device_id": device_id,
+    });
+
+    let response = client
+        .post(url)
+        .json(&data)
+        .header("Authorization", token)
+        .send()
+        .await;
+
+
+    match response {
+        Ok(data) => {
+
+            let res_data = data.json::<RevokeDeviceResponseData>().await.unwrap();
+
+            match res_data.status_type {
+                StatusTypes::DEVICE_REVOKED => {
+                    return RevokeDeviceResponseResult::DEVICE_REVOKED;
+                }
+                StatusTypes::DEVICE_NOT_FOUND => {
+                    return RevokeDeviceResponseResult::DEVICE_NOT_FOUND;
+                }
+                StatusTypes::CANNOT_REVOKE_CURRENT_DEVICE => {
+                    return RevokeDeviceResponseResult::CANNOT_REVOKE_CURRENT_DEVICE;
+                }
+                StatusTypes::DATABASE_ERROR => {
+                    return RevokeDeviceResponseResult::DATABASE_ERROR;
+                }
+                _ => {
+                    return RevokeDeviceResponseResult::UNKNOWN_ERROR;
+                }
+            }
+
+        },
+        Err(_) => {
+            return RevokeDeviceResponseResult::REQUEST_ERROR;
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use ratatui::{style::{Style, Stylize}, widgets::{Block, Borders, Paragraph}};
+
+use crate::tui_main::MaclincommsApp;
+
+use super::{devices_thread::start_devices_thread, revoke_device::{revoke_device, RevokeDeviceResponseResult}};
+
+
+
+
+pub async fn start_revokedevice_thread(app: &mut MaclincommsApp, device_id: String) {
+
+    let revoke_device_token = app.access_token.clone();
+
+    let endpoint = app.endpoints.revoke_device;
+
+    let device_name = app.devices_comps.devices.iter()
+        .find(|device| device.device_id==device_id)
+        .map(|device| device.device_name.clone())
+        .unwrap_or(device_id.clone());
+
+    let revoke_device_result = revoke_device(revoke_device_token, device_id, endpoint).await;
+
+
+    let (text, color) = match revoke_device_result {
+
+        RevokeDeviceResponseResult::REQUEST_ERROR => ("Network error or bad request".to_string(), ratatui::style::Color::LightRed),
+
+        RevokeDeviceResponseResult::DATABASE_ERROR => ("Database Error".to_string(), ratatui::style::Color::LightRed),
+
+        RevokeDeviceResponseResult::UNKNOWN_ERROR => ("Unknown Server Error".to_string(), ratatui::style::Color::LightRed),
+
+        RevokeDeviceResponseResult::DEVICE_NOT_FOUND => ("Device already removed".to_string(), ratatui::style::Color::LightYellow),
+
+        RevokeDeviceResponseResult::CANNOT_REVOKE_CURRENT_DEVICE => ("This device can't revoke itself".to_string(), ratatui::style::Color::LightYellow),
+
+        RevokeDeviceResponseResult::DEVICE_REVOKED => {
+
+            //Fetching the devices left
+            start_devices_thread(app).await;
+
+            (format!("{} revoked", device_name), ratatui::style::Color::LightGreen)
+        }
+    };
+
+    let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(color));
+
+    app.devices_comps.action_status_block = Paragraph::new(text.fg(color))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+}
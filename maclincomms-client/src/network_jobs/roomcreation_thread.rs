@@ -1,7 +1,7 @@
-use disk_persist::DiskPersist;
+use base64::{engine::general_purpose, Engine};
 use ratatui::{style::{Style, Stylize}, text::{Line, Text}, widgets::{Block, Borders, Paragraph}};
 
-use crate::{event_model::Event, tui_main::MaclincommsApp, tui_widgets::roomcreation_textarea::RoomCreationTaskStatus, user_model::{Room_Keys, UserIdentityKeys}};
+use crate::{event_model::Event, tui_main::MaclincommsApp, tui_widgets::roomcreation_textarea::RoomCreationTaskStatus, user_model::{Room_Keys, SharedRoomHistory, UserIdentityKeys}};
 
 use super::{create_room::{create_room, CreateRoomResponseResult}};
 
@@ -14,24 +14,26 @@ pub async fn start_roomcreation_task(app: &mut MaclincommsApp) {
 
     let create_room_token = app.access_token.clone();
 
-    let persistent_storage: DiskPersist<UserIdentityKeys> = DiskPersist::init("persistent-user-identity-keypair").unwrap();
-
-    let user_keydata = persistent_storage.read().unwrap();
-
-    let key:String = match user_keydata {
-        Some(data) => {
-            let p_key = data.public_identity_key;
-            p_key
-        }
+    //Rooms know members by the account key
+    let key:String = match UserIdentityKeys::load_account_public_key() {
+        Some(p_key) => general_purpose::STANDARD.encode(p_key),
         None => {
             "".to_owned()
         }
     };
 
+    let persistent = app.roomcreation_textarea.persistent;
+
+    let mut settings = app.roomcreation_textarea.settings.clone();
+    settings.topic = match settings.listed {
+        true => app.roomcreation_textarea.topic_ta.lines()[0].trim().to_string(),
+        false => "".to_string()
+    };
+
     let endpoint = app.endpoints.create_room;
 
 
-    let create_room_result: CreateRoomResponseResult = create_room(create_room_token, roomname, key, endpoint).await;
+    let create_room_result: CreateRoomResponseResult = create_room(create_room_token, roomname, key, persistent, settings.clone(), endpoint).await;
 
 
     match create_room_result {
@@ -150,8 +152,15 @@ pub async fn start_roomcreation_task(app: &mut MaclincommsApp) {
             let room_created_name = room.room_name.clone();
             let room_created_key = room.room_key.clone();
 
+            app.park_current_room(); //The room on screen stays open in the background
+
             /* UPDATING CURRENT ROOM DATA IN APP */
             app.is_current_room_owner = true;
+            app.current_room_owner = app.username.clone();
+            app.current_room_settings = settings.clone();
+            app.last_room_message_at = None;
+            app.room_history.clear();
+            app.room_history_shared = Some(SharedRoomHistory { shared_by: app.username.clone(), messages: Vec::new() }); //Nobody was here before the owner
             app.roomchat_comps.room_name = room_created_name;
             app.roomchat_comps.room_key = room_created_key;
             app.room_token = room_token.clone();
@@ -167,14 +176,37 @@ pub async fn start_roomcreation_task(app: &mut MaclincommsApp) {
             let mut chat_history = app.roomchat_comps.chat_history.lock().unwrap();
             
             //Pushing info messages to room chat history
+            let mut info_lines = vec![
+                Line::from(vec!["ROOM NAME: ".bold().light_magenta(), info_roomname.light_cyan()]),
+                Line::from(vec!["ROOM KEY: ".bold().light_magenta(), info_roomkey.light_cyan()]),
+                Line::from(""),
+                Line::from("Share it with your friends!".light_cyan())
+            ];
+            if persistent {
+                info_lines.push(Line::from("This room stays after you leave, rejoin it with its name and key".light_cyan()));
+                info_lines.push(Line::from("Hand it over with /transfer <username>".light_cyan()));
+            }
+            if settings.max_members > 0 {
+                info_lines.push(Line::from(format!("Up to {} members", settings.max_members).light_cyan()));
+            }
+            if !settings.whispers_allowed {
+                info_lines.push(Line::from("Whispers are turned off".light_cyan()));
+            }
+            if settings.slow_mode_seconds > 0 {
+                info_lines.push(Line::from(format!("Slow mode: one message every {}s", settings.slow_mode_seconds).light_cyan()));
+            }
+            if settings.share_history_count > 0 {
+                info_lines.push(Line::from(format!("Newcomers get the last {} messages from members", settings.share_history_count).light_cyan()));
+            }
+            if settings.listed {
+                info_lines.push(Line::from("Listed in the Room Directory, anyone can join without the Room Key".light_cyan()));
+            }
+            if settings.join_approval {
+                info_lines.push(Line::from("The Room Key only knocks, approve requests from Notifications or send /invite".light_cyan()));
+            }
             chat_history.push((
                 "maclincomms".to_owned(), 
-                Text::from(vec![
-                    Line::from(vec!["ROOM NAME: ".bold().light_magenta(), info_roomname.light_cyan()]),
-                    Line::from(vec!["ROOM KEY: ".bold().light_magenta(), info_roomkey.light_cyan()]),
-                    Line::from(""),
-                    Line::from("Share it with your friends!".light_cyan())
-                ]),
+                Text::from(info_lines),
                 "".to_owned(),
                 false,
                 "".to_string()
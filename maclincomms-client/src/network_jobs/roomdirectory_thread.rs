@@ -0,0 +1,53 @@
+use ratatui::{style::{Style, Stylize}, widgets::{Block, Borders, Paragraph}};
+
+use crate::tui_main::MaclincommsApp;
+
+use super::get_room_directory::{get_room_directory, RoomDirectoryResponseResult};
+
+
+
+
+pub async fn start_roomdirectory_thread(app: &mut MaclincommsApp, page: usize) {
+
+    let room_directory_token = app.access_token.clone();
+
+    let endpoint = app.endpoints.room_directory;
+
+    let room_directory_result = get_room_directory(room_directory_token, page, endpoint).await;
+
+
+    let (text, color) = match room_directory_result {
+
+        RoomDirectoryResponseResult::REQUEST_ERROR => ("Network error or bad request".to_string(), ratatui::style::Color::LightRed),
+
+        RoomDirectoryResponseResult::DATABASE_ERROR => ("Database Error".to_string(), ratatui::style::Color::LightRed),
+
+        RoomDirectoryResponseResult::UNKNOWN_ERROR => ("Unknown Server Error".to_string(), ratatui::style::Color::LightRed),
+
+        RoomDirectoryResponseResult::ROOM_DIRECTORY_FETCHED(rooms, has_more) => {
+
+            let text = match (rooms.is_empty(), page) {
+                (true, 0) => "No rooms are listed yet".to_string(),
+                (true, _) => "No more listed rooms".to_string(),
+                (false, _) => format!("Page {}, press [Enter] to join a room", page+1)
+            };
+
+            app.roomdirectory_comps.rooms = rooms;
+            app.roomdirectory_comps.page = page;
+            app.roomdirectory_comps.has_more = has_more;
+            app.roomdirectory_comps.current_index = 0;
+            app.roomdirectory_comps.scroll_state.scroll_to_top();
+
+            (text, ratatui::style::Color::LightCyan)
+        }
+    };
+
+    let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(color));
+
+    app.roomdirectory_comps.action_status_block = Paragraph::new(text.fg(color))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+}
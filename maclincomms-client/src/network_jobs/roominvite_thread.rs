@@ -0,0 +1,56 @@
+use ratatui::{style::Stylize, text::{Line, Text}};
+
+use crate::{format_local_timestamp, get_current_time, tui_main::MaclincommsApp, user_model::StatusTypes};
+
+use super::create_room_invite::{create_room_invite, CreateRoomInviteResponseResult};
+
+
+
+
+//Invites are created over REST with the user token, the room token may have expired by the time the owner asks
+pub async fn start_roominvite_task(app: &mut MaclincommsApp, max_uses: i64, expires_in_minutes: i64, send_to: Option<String>) {
+
+    let roomname = app.roomchat_comps.room_name.clone();
+
+    let invite_token = app.access_token.clone();
+
+    let endpoint = app.endpoints.create_room_invite;
+
+
+    let create_invite_result: CreateRoomInviteResponseResult = create_room_invite(invite_token, roomname, max_uses, expires_in_minutes, send_to.clone(), endpoint).await;
+
+
+    let info_lines = match create_invite_result {
+
+        CreateRoomInviteResponseResult::INVITE(invite) | CreateRoomInviteResponseResult::INVITE_NOT_SENT(invite) => {
+            let mut lines = vec![
+                Line::from(vec!["ROOM INVITE: ".bold().light_magenta(), invite.invite_token.light_cyan()]),
+                Line::from(format!("Lets in {} until {}", max_uses, format_local_timestamp(invite.expires_at)).light_cyan())
+            ];
+            match send_to {
+                Some(username) if matches!(invite.status_type, StatusTypes::ROOM_INVITE_CREATED) => lines.push(Line::from(format!("Sent to {}", username).light_cyan())),
+                Some(username) => lines.push(Line::from(format!("Couldnt send it to {}, share it yourself", username).light_red())),
+                None => lines.push(Line::from("Share it with your friends!".light_cyan()))
+            }
+            lines
+        }
+        CreateRoomInviteResponseResult::ROOM_NOT_FOUND => vec![Line::from("Room not found".light_red())],
+        CreateRoomInviteResponseResult::NOT_ROOM_OWNER => vec![Line::from("Only the Room Owner can create invites".light_red())],
+        CreateRoomInviteResponseResult::INVALID_INVITE => vec![Line::from("Invites need at least one use and must expire within 7 days".light_red())],
+        CreateRoomInviteResponseResult::USER_NOT_FOUND => vec![Line::from(format!("{} is not in your DMs list", send_to.unwrap_or_default()).light_red())],
+        CreateRoomInviteResponseResult::DATABASE_ERROR => vec![Line::from("Database Error".light_red())],
+        CreateRoomInviteResponseResult::REQUEST_ERROR => vec![Line::from("Network error or bad request".light_red())],
+        CreateRoomInviteResponseResult::UNKNOWN_ERROR => vec![Line::from("Unknown Server Error".light_red())]
+    };
+
+    if let Ok(mut chat_history_lock) = app.roomchat_comps.chat_history.lock() {
+        chat_history_lock.push((
+            "maclincomms".to_owned(),
+            Text::from(info_lines),
+            get_current_time(),
+            false,
+            "".to_string()
+        ));
+    }
+    app.roomchat_comps.scroll_state.scroll_to_bottom();
+}
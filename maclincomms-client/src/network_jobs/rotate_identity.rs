@@ -0,0 +1,82 @@
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::user_model::StatusTypes;
+
+#[derive(Debug)]
+pub enum RotateIdentityKeyResponseResult {
+    IDENTITY_KEY_ROTATED(usize), //DM contacts notified
+    INVALID_IDENTITY_KEY,
+    NOT_ACCOUNT_DEVICE,
+    DATABASE_ERROR,
+    REQUEST_ERROR,
+    UNKNOWN_ERROR
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RotateIdentityKeyResponseData {
+    pub status_type: StatusTypes,
+    pub notified_contacts: usize,
+    pub message: String
+}
+
+
+
+
+pub async fn rotate_identity_key(
+    token: String,
+    old_public_identity_key: String,
+    new_public_identity_key: String,
+    signature: String,
+    rotate_identity_key_endpoint: &'static str
+) -> RotateIdentityKeyResponseResult {
+
+    let url = rotate_identity_key_endpoint.to_string();
+    let client = Client::new();
+
+    let data = json!({
+        "old_public_identity_key": old_public_identity_key,
+        "new_public_identity_key": new_public_identity_key,
+        "signature": signature,
+    });
+
+    let response = client
+        .post(url)
+        .json(&data)
+        .header("Authorization", token)
+        .send()
+        .await;
+
+
+    match response {
+        Ok(data) => {
+
+            let Ok(res_data) = data.json::<RotateIdentityKeyResponseData>().await else {
+                return RotateIdentityKeyResponseResult::UNKNOWN_ERROR;
+            };
+
+            match res_data.status_type {
+                StatusTypes::IDENTITY_KEY_ROTATED => {
+                    return RotateIdentityKeyResponseResult::IDENTITY_KEY_ROTATED(res_data.notified_contacts);
+                }
+                StatusTypes::INVALID_IDENTITY_KEY => {
+                    return RotateIdentityKeyResponseResult::INVALID_IDENTITY_KEY;
+                }
+                StatusTypes::NOT_ACCOUNT_DEVICE => {
+                    return RotateIdentityKeyResponseResult::NOT_ACCOUNT_DEVICE;
+                }
+                StatusTypes::DATABASE_ERROR => {
+                    return RotateIdentityKeyResponseResult::DATABASE_ERROR;
+                }
+                _ => {
+                    return RotateIdentityKeyResponseResult::UNKNOWN_ERROR;
+                }
+            }
+
+        },
+        Err(_) => {
+            return RotateIdentityKeyResponseResult::REQUEST_ERROR;
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use base64::{engine::general_purpose, Engine};
+use ratatui::{style::{Style, Stylize}, widgets::{Block, Borders, Paragraph}};
+
+use crate::{crypto::{dm_keys::generate_dh_keypair, double_ratchet::DoubleRatchet, identity_pair::{sign_identity_rotation, verify_identity_rotation}}, local_store::SealedPersist, tui_main::MaclincommsApp, user_model::{DmE2EEncryption_Data, DmUser_Data, IdentityRotatedNotificationContent, UserDevice, UserIdentityKeys, UserPrekeys, VerificationStatus, VerifiedContacts}};
+
+use super::{prekeys_thread::start_uploadprekeys_thread, rotate_identity::{rotate_identity_key, RotateIdentityKeyResponseResult}};
+
+
+
+
+//Replaces the account's identity key with a new one the old key signs for, DM sessions start over with every contact
+pub async fn start_rotateidentity_thread(app: &mut MaclincommsApp) {
+
+    let (text, color) = match rotate_identity(app).await {
+        Ok(text) => (text, ratatui::style::Color::LightGreen),
+        Err(text) => (text, ratatui::style::Color::LightRed)
+    };
+
+    let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(color));
+
+    app.devices_comps.action_status_block = Paragraph::new(text.fg(color))
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+}
+
+async fn rotate_identity(app: &mut MaclincommsApp) -> Result<String, String> {
+
+    //Only the account device holds the key contacts know us by
+    if !UserDevice::load_or_create().account_device {
+        return Err("Only the account device can rotate the identity key".to_string());
+    }
+
+    let identity_keys_disk: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-user-identity-keypair").unwrap();
+    let (Some(old_pub_key), Some(old_priv_key)) = (UserIdentityKeys::load_public_key(), UserIdentityKeys::load_private_key()) else {
+        return Err("No identity key on this device".to_string());
+    };
+
+    let (new_pub_key, new_priv_key) = generate_dh_keypair();
+    let signature = sign_identity_rotation(old_priv_key, new_pub_key);
+
+    let rotate_identity_result = rotate_identity_key(
+        app.access_token.clone(),
+        general_purpose::STANDARD.encode(old_pub_key),
+        general_purpose::STANDARD.encode(new_pub_key),
+        general_purpose::STANDARD.encode(signature),
+        app.endpoints.rotate_identity_key
+    ).await;
+
+    let notified_contacts = match rotate_identity_result {
+        RotateIdentityKeyResponseResult::IDENTITY_KEY_ROTATED(notified_contacts) => notified_contacts,
+        RotateIdentityKeyResponseResult::INVALID_IDENTITY_KEY => return Err("Server has a different identity key for you".to_string()),
+        RotateIdentityKeyResponseResult::NOT_ACCOUNT_DEVICE => return Err("Only the account device can rotate the identity key".to_string()),
+        RotateIdentityKeyResponseResult::DATABASE_ERROR => return Err("Database Error".to_string()),
+        RotateIdentityKeyResponseResult::UNKNOWN_ERROR => return Err("Unknown Server Error".to_string()),
+        RotateIdentityKeyResponseResult::REQUEST_ERROR => return Err("Network error or bad request".to_string())
+    };
+
+    //The server has the new key now, the old one is dropped
+    let new_identity_keys = UserIdentityKeys {
+        public_identity_key: general_purpose::STANDARD.encode(new_pub_key),
+        private_identity_key: general_purpose::STANDARD.encode(new_priv_key)
+    };
+    identity_keys_disk.write(&new_identity_keys).unwrap();
+    let account_keys_disk: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-account-identity-keypair").unwrap();
+    if let Ok(Some(_)) = account_keys_disk.read() {
+        new_identity_keys.save_account();
+    }
+
+    //Contacts reset their side when the notification reaches them
+    let dms_list_disk: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
+    let dms_list = dms_list_disk.read().ok().flatten().unwrap_or_default();
+    app.dme2ee_data.restart_sessions(&dms_list);
+    let dme2ee_disk: SealedPersist<DmE2EEncryption_Data> = SealedPersist::init("persistent-dms-e2e-keys").unwrap();
+    dme2ee_disk.write(&app.dme2ee_data).unwrap();
+
+    //Signed prekey for the new key, one time prekeys already on the server stay usable
+    if let Some(mut prekeys) = UserPrekeys::load() {
+        prekeys.resign(new_pub_key);
+        prekeys.save();
+    }
+    start_uploadprekeys_thread(app).await;
+
+    return Ok(format!("Identity key rotated, {} contacts notified", notified_contacts));
+}
+
+
+//Takes up a contact's new identity key if their old one signed for it, the text to show in the notification
+pub fn accept_identity_rotation(app: &mut MaclincommsApp, from: &str, content: &str) -> String {
+
+    let rejected = format!("{} sent an identity key change that couldn't be verified, check the safety number", from);
+
+    let Some(rotation) = IdentityRotatedNotificationContent::parse(content) else {
+        return rejected;
+    };
+    let decode_key = |key: &str| general_purpose::STANDARD.decode(key).ok().and_then(|key| <[u8;32]>::try_from(key).ok());
+    let (Some(old_pub_key), Some(new_pub_key), Ok(signature)) = (
+        decode_key(&rotation.old_public_identity_key),
+        decode_key(&rotation.new_public_identity_key),
+        general_purpose::STANDARD.decode(&rotation.signature)
+    ) else {
+        return rejected;
+    };
+
+    let dms_list_disk: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
+    let mut dms_list = dms_list_disk.read().ok().flatten().unwrap_or_default();
+    let Some(contact) = dms_list.iter_mut().find(|dm| dm.username==from) else {
+        return rejected;
+    };
+
+    //Only the key we knew them by can vouch for a new one. The server hands out new keys with the dms list,
+    //so a list already showing the new key proves nothing, that is a plain key change
+    let known_key = &contact.public_identity_key;
+    if *known_key==rotation.new_public_identity_key {
+        return format!("{}'s identity key changed without a rotation signed by the key you knew, check the safety number", from);
+    }
+    if *known_key!=rotation.old_public_identity_key || !verify_identity_rotation(old_pub_key, new_pub_key, &signature) {
+        return rejected;
+    }
+
+    contact.public_identity_key = rotation.new_public_identity_key.clone();
+    for device in contact.devices.iter_mut().filter(|device| device.public_identity_key==rotation.old_public_identity_key) {
+        device.public_identity_key = rotation.new_public_identity_key.clone();
+    }
+    dms_list_disk.write(&dms_list).unwrap();
+
+    //They started over from the new key, so do we
+    app.dme2ee_data.dms.insert(from.to_string(), DoubleRatchet::new(new_pub_key));
+    let dme2ee_disk: SealedPersist<DmE2EEncryption_Data> = SealedPersist::init("persistent-dms-e2e-keys").unwrap();
+    dme2ee_disk.write(&app.dme2ee_data).unwrap();
+
+    //The old key vouched for the new one, so a checked safety number carries over
+    let mut verified_contacts = VerifiedContacts::load();
+    if verified_contacts.status(from, &rotation.old_public_identity_key)==VerificationStatus::VERIFIED {
+        verified_contacts.verify(from.to_string(), rotation.new_public_identity_key);
+        verified_contacts.save();
+    }
+
+    return format!("{} rotated their identity key, signed by their old one", from);
+}
@@ -0,0 +1,64 @@
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::user_model::{StatusTypes, UploadPrekeysPayload};
+
+#[derive(Debug)]
+pub enum UploadPrekeysResponseResult {
+    PREKEYS_UPLOADED(usize), //one time prekeys left on the server
+    DATABASE_ERROR,
+    REQUEST_ERROR,
+    UNKNOWN_ERROR
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UploadPrekeysResponseData {
+    pub status_type: StatusTypes,
+    pub one_time_prekeys_count: usize,
+    pub message: String
+}
+
+
+
+
+pub async fn upload_prekeys(
+    token: String,
+    upload_prekeys_endpoint: &'static str,
+    prekeys: UploadPrekeysPayload
+) -> UploadPrekeysResponseResult {
+
+    let url = upload_prekeys_endpoint.to_string();
+    let client = Client::new();
+
+
+    let response = client
+        .post(url)
+        .json(&prekeys)
+        .header("Authorization", token)
+        .send()
+        .await;
+
+
+    match response {
+        Ok(data) => {
+
+            let res_data = data.json::<UploadPrekeysResponseData>().await.unwrap();
+
+            match res_data.status_type {
+                StatusTypes::PREKEYS_UPLOADED => {
+                    return UploadPrekeysResponseResult::PREKEYS_UPLOADED(res_data.one_time_prekeys_count);
+                }
+                StatusTypes::DATABASE_ERROR => {
+                    return UploadPrekeysResponseResult::DATABASE_ERROR;
+                }
+                _ => {
+                    return UploadPrekeysResponseResult::UNKNOWN_ERROR;
+                }
+            }
+
+        },
+        Err(_) => {
+            return UploadPrekeysResponseResult::REQUEST_ERROR;
+        }
+    }
+}
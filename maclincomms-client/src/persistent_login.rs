@@ -1,16 +1,17 @@
 use std::collections::HashMap;
 
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
+use ratatui::{style::{Style, Stylize}, widgets::{Block, Borders, Paragraph}};
 use reqwest::{Error, StatusCode};
 
-use crate::{network_jobs::{authN_user::authenticate_user, getdmchats_thread::start_getdmchats_thread, getdms_thread::start_getdms_thread, queued_notifications::get_queued_notifications, request_token::request_new_token}, screens_model::Screens, tui_main::MaclincommsApp, user_model::{DmDoubleRatchet_Keys, DmE2EEncryption_Data, DmSessionEncryption_Key, RequestNewTokenResponse, StatusTypes, UserData}};
+use crate::{network_jobs::{authN_user::authenticate_user, getdmchats_thread::start_getdmchats_thread, getdms_thread::start_getdms_thread, prekeys_thread::start_uploadprekeys_thread, queued_notifications::get_queued_notifications, request_token::request_new_token}, screens_model::Screens, tui_main::MaclincommsApp, user_model::{DmE2EEncryption_Data, DmSessionEncryption_Key, RequestNewTokenResponse, StatusTypes, UserData}};
 
 
 //This function also retrieves queued notifications and dms data
 pub async fn persistent_authentication(app: &mut MaclincommsApp){
 
     //PERSISTENT USER DATA
-    let persistent_storage: DiskPersist<UserData> = DiskPersist::init("persistent-user-data").unwrap();
+    let persistent_storage: SealedPersist<UserData> = SealedPersist::init("persistent-user-data").unwrap();
 
     let authN_url = app.endpoints.authN;
     let newtoken_url = app.endpoints.new_token;
@@ -54,6 +55,9 @@ pub async fn persistent_authentication(app: &mut MaclincommsApp){
                                 //retrieving dms list and keys
                                 start_getdms_thread(app).await;
 
+                                //publishing x3dh prekeys
+                                start_uploadprekeys_thread(app).await;
+
                                 
                                 
                                 app.current_screen = Screens::CHAT_OPTIONS_SCREEN;
@@ -93,6 +97,9 @@ pub async fn persistent_authentication(app: &mut MaclincommsApp){
                                                 //retrieving dms list and public keys
                                                 start_getdms_thread(app).await;
 
+                                                //publishing x3dh prekeys
+                                                start_uploadprekeys_thread(app).await;
+
             
             
                                                 //ALLOWING USER IN THE APP
@@ -100,6 +107,20 @@ pub async fn persistent_authentication(app: &mut MaclincommsApp){
                                                 app.chatoptions_menu.activate();
             
                                             },
+                                            StatusTypes::DEVICE_REVOKED => {
+                                                //Signed out from another device, logging in again adds it back under a new device id
+                                                let status_block = Block::default()
+                                                    .borders(Borders::ALL)
+                                                    .border_type(ratatui::widgets::BorderType::default())
+                                                    .border_style(Style::default().fg(ratatui::style::Color::LightYellow));
+
+                                                app.login_textarea.status_block = Paragraph::new("This device was revoked, log in again".light_yellow())
+                                                    .alignment(ratatui::layout::Alignment::Center)
+                                                    .block(status_block);
+
+                                                app.login_textarea.username_ta.insert_str(app.username.clone());
+                                                app.current_screen = Screens::LOGIN_SCREEN;
+                                            },
                                             _ => {
                                                 app.login_textarea.username_ta.insert_str(app.username.clone());
                                                 app.current_screen = Screens::LOGIN_SCREEN;
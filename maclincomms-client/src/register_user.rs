@@ -1,21 +1,22 @@
 
 use std::collections::HashMap;
 
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use futures_util::{StreamExt, SinkExt, stream::{SplitSink, SplitStream}};
 use ratatui::{style::Style, text::Line, widgets::{Block, Borders, Paragraph}};
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
 use throbber_widgets_tui::CLOCK;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::io::{self, AsyncBufReadExt};
 use tokio_tungstenite::{connect_async, tungstenite::{client::{self, IntoClientRequest}, http::Request, ClientRequestBuilder, Message}, WebSocketStream};
 
-use crate::{crypto::identity_pair::generate_identity_keypair, endpoints::Endpoints, tui_main::MaclincommsApp, tui_widgets::register_textarea::{RegisterTaskStatus, RegisterTextArea}, user_model::{DmE2EEncryption_Data, DmSessionEncryption_Key, RegisterResponse, StatusTypes, UserRegisterPayload}};
+use crate::{crypto::identity_pair::generate_identity_keypair, endpoints::Endpoints, network_jobs::register::register_account, tui_main::MaclincommsApp, tui_widgets::register_textarea::{RegisterTaskStatus, RegisterTextArea}, user_model::{DmE2EEncryption_Data, DmSessionEncryption_Key, RegisterResponse, StatusTypes, UserDevice, UserRegisterPayload}};
 
 #[derive(Debug)]
 pub enum RegisterResponseResult{
     TOKEN(String),
     EXISTING_USER,
+    INVALID_USERNAME,
     DATABASE_ERROR,
     UNKNOWN_ERROR,
     REQUEST_ERROR
@@ -25,12 +26,12 @@ pub enum RegisterResponseResult{
 pub async fn register(username: String, password: String) -> (String, RegisterResponseResult, String, i64){
 
     // Initialising Persistent Dms E2E Keys File
-    let persistent_dm_e2e_keys: DiskPersist<DmE2EEncryption_Data> = DiskPersist::init("persistent-dms-e2e-keys").unwrap();
+    let persistent_dm_e2e_keys: SealedPersist<DmE2EEncryption_Data> = SealedPersist::init("persistent-dms-e2e-keys").unwrap();
     let init_data = DmE2EEncryption_Data { dms: HashMap::new()};
     persistent_dm_e2e_keys.write(&init_data).unwrap();
 
     // Initialising Persistent Dms Session Keys File
-    let persistent_dm_session_keys: DiskPersist<HashMap<String, DmSessionEncryption_Key>> = DiskPersist::init("persistent-dms-session-keys").unwrap();
+    let persistent_dm_session_keys: SealedPersist<HashMap<String, DmSessionEncryption_Key>> = SealedPersist::init("persistent-dms-session-keys").unwrap();
     let init_data = HashMap::new();
     persistent_dm_session_keys.write(&init_data).unwrap();
     
@@ -66,6 +67,10 @@ pub async fn get_user(username: String, password: String) -> (RegisterResponseRe
                             
                             return (RegisterResponseResult::EXISTING_USER, "".to_string(), 0);
                         },
+                        StatusTypes::INVALID_USERNAME => {
+                            
+                            return (RegisterResponseResult::INVALID_USERNAME, "".to_string(), 0);
+                        },
                         StatusTypes::DATABASE_ERROR => {
                             
                             return (RegisterResponseResult::DATABASE_ERROR, "".to_string(), 0);
@@ -86,25 +91,21 @@ pub async fn get_user(username: String, password: String) -> (RegisterResponseRe
 pub async fn register_into_db(user_name:String, pass:String) -> Result< RegisterResponse, reqwest::Error > {
 
     let endpoints = Endpoints::new();
-    
-    let url = endpoints.register.to_owned();
 
     let pub_id_key = generate_identity_keypair();
 
+    //The device registering holds the account's identity key
+    let mut device = UserDevice::load_or_create();
+    device.account_device = true;
+    device.save();
+
     let user = UserRegisterPayload{
         username: user_name,
         password: pass,
-        public_identity_key: pub_id_key
+        public_identity_key: pub_id_key,
+        device_id: device.device_id,
+        device_name: device.device_name
     };
 
-    let client = Client::new();
-    let res = client
-        .post(url)
-        .json(&user)
-        .send()
-        .await?;   
-
-    let data = res.json::<RegisterResponse>().await?;
-    
-    Ok(data)
+    register_account(user, endpoints.register).await
 }
\ No newline at end of file
@@ -1,6 +1,5 @@
-use std::{sync::Arc, thread::sleep, time::Duration};
+use std::{collections::{HashMap, VecDeque}, sync::Arc, thread::sleep, time::{Duration, Instant}};
 
-use base64::{engine::general_purpose, Engine};
 use crossterm::{event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers}};
 use futures_util::lock::Mutex;
 use ratatui::{layout::Alignment, style::{Color, Modifier, Style, Stylize}, text::{Line, Text}, widgets::{Block, BorderType, Borders, Paragraph}};
@@ -8,7 +7,10 @@ use regex::Regex;
 use serde::de::value;
 use throbber_widgets_tui::CLOCK;
 
-use crate::{crypto::encrypt_msg::{encrypt_dm_message, encrypt_room_message, sign_room_ciphertext}, event_model::Event, get_current_time, screens_model::Screens, tui_main::MaclincommsApp, tui_widgets::{adduser_textarea::AddUserTaskStatus, blockuser_textarea::BlockUnblockUserTaskStatus, joinroom_textarea::JoinRoomTaskStatus, login_textarea::LoginTaskStatus, notifications_panel::NotificationStatus, register_textarea::RegisterTaskStatus, roomcreation_textarea::RoomCreationTaskStatus}, user_model::{DmMessage, MessageType, NotificationData, NotificationType, RoomMessageType, RoomSenderMessage, SocketMessage, WhisperMode, WorldChatMessage}};
+use base64::{engine::general_purpose, Engine};
+use crate::local_store::SealedPersist;
+
+use crate::{crypto::{encrypt_msg::{encrypt_room_message, sign_room_ciphertext}, room_whisper::encrypt_room_whisper, safety_number::safety_number}, event_model::Event, get_current_time, screens_model::Screens, tui_main::MaclincommsApp, tui_widgets::{adduser_textarea::AddUserTaskStatus, devices_panel::DevicesComponents, dmuser_panel::{DmUserComponents, SafetyNumberView}, blockuser_textarea::BlockUnblockUserTaskStatus, joinroom_textarea::{JoinRoomTaskStatus, JoinRoomTextArea}, login_textarea::LoginTaskStatus, notifications_panel::NotificationStatus, register_textarea::RegisterTaskStatus, roomcreation_textarea::{RoomCreationTaskStatus, RoomCreationTextArea}}, user_model::{AcceptanceStatus, DmMessage, RoomHistoryMessage, ROOM_HISTORY_LIMIT, RoomInviteNotificationContent, RoomJoinApprovedNotificationContent, INVITE_TOKEN_PREFIX, DmUser_Data, MessageType, ModerationAction, NotificationData, NotificationType, RoomMessageType, RoomSenderMessage, SocketMessage, UserIdentityKeys, VerificationStatus, VerifiedContacts, WhisperMode, WorldChatMessage}};
 
 
 
@@ -339,6 +341,14 @@ pub fn clean_input(text: String) -> String {
     cleaned
 }
 
+//Plain room messages, kept to hand to newcomers in rooms sharing history
+pub fn keep_room_history(room_history: &mut VecDeque<RoomHistoryMessage>, username: String, content: String) {
+    room_history.push_back(RoomHistoryMessage { username, content, time: get_current_time() });
+    if room_history.len() > ROOM_HISTORY_LIMIT {
+        room_history.pop_front();
+    }
+}
+
 pub fn take_next_lines(text: String) -> Vec<Line<'static>> {
     // Split by newlines and collect each line into a vector of Lines
     let lines: Vec<Line> = text.split("\\n")
@@ -364,6 +374,25 @@ pub fn handle_room_creation_screen_inputs( app: &mut MaclincommsApp, key_event:
             app.current_screen = Screens::CHAT_OPTIONS_SCREEN;
             app.chatoptions_menu.activate();
         },
+        KeyCode::Up | KeyCode::Down => {
+            let fields = RoomCreationTextArea::FIELDS_COUNT;
+            let selected = app.roomcreation_textarea.selected_field;
+            app.roomcreation_textarea.selected_field = match key_event.code {
+                KeyCode::Up => (selected + fields - 1) % fields,
+                _ => (selected + 1) % fields
+            };
+
+            //The cursor only shows while the room name or topic is being typed
+            let cursor_style = |typing: bool| match typing {
+                true => Style::default().add_modifier(Modifier::REVERSED),
+                false => Style::default()
+            };
+            let selected = app.roomcreation_textarea.selected_field;
+            app.roomcreation_textarea.roomname_ta.set_cursor_style(cursor_style(selected==0));
+            app.roomcreation_textarea.topic_ta.set_cursor_style(cursor_style(selected==RoomCreationTextArea::TOPIC_FIELD));
+        },
+        KeyCode::Left => app.roomcreation_textarea.change_selected_setting(false),
+        KeyCode::Right => app.roomcreation_textarea.change_selected_setting(true),
         KeyCode::Enter => {
 
             if !(app.roomcreation_textarea.roomname_ta.lines()[0].to_string().is_empty())
@@ -425,8 +454,10 @@ pub fn handle_room_join_screen_inputs( app: &mut MaclincommsApp, key_event: KeyE
         },
         KeyCode::Enter => {
 
-            if !(app.joinroom_textarea.roomname_ta.lines()[0].to_string().is_empty()) &&
-            !(app.joinroom_textarea.roomkey_ta.lines()[0].to_string().is_empty()) &&
+            let roomkey = app.joinroom_textarea.roomkey_ta.lines()[0].to_string();
+            //Invites already name their room
+            if (!(app.joinroom_textarea.roomname_ta.lines()[0].to_string().is_empty()) || roomkey.starts_with(INVITE_TOKEN_PREFIX)) &&
+            !(roomkey.is_empty()) &&
             matches!(app.joinroom_textarea.task_status.clone(), JoinRoomTaskStatus::NOT_INITIATED) {
 
                 app.joinroom_textarea.task_status = JoinRoomTaskStatus::IN_PROGRESS;
@@ -496,9 +527,28 @@ pub fn handle_room_chat_screen_inputs( app: &mut MaclincommsApp, key_event: KeyE
 
                 let cleaned_input= clean_input(user_input);
 
+                //ROOM COMMANDS (/transfer, /invite, /leave, /kick, /ban, /mute, /promote and /rotate-key)
+                if cleaned_input.starts_with('/') {
+                    handle_room_command(app, &cleaned_input);
+                    let _ = app.roomchat_comps.input_ta.delete_line_by_head();
+                    return;
+                }
+
                 //PARSE WHISPER COMMANDS (--hf or --sw)
                 let (users_list, mode, final_message) = parse_whisper_command(&cleaned_input);
 
+                //The server drops what the room's settings don't allow, the input is kept to send later
+                if !matches!(mode, WhisperMode::NONE) && !app.current_room_settings.whispers_allowed {
+                    push_room_system_message(app, "Whispers are turned off in this room".to_string());
+                    return;
+                }
+                let slow_mode_seconds = app.current_room_settings.slow_mode_seconds;
+                if app.last_room_message_at.is_some_and(|at| (at.elapsed().as_secs() as i64) < slow_mode_seconds) {
+                    push_room_system_message(app, format!("Slow mode is on, one message every {}s", slow_mode_seconds));
+                    return;
+                }
+                app.last_room_message_at = Some(Instant::now());
+
                 //Take lines vector for ui
                 let ui_input = take_next_lines(final_message.clone());
 
@@ -542,8 +592,25 @@ pub fn handle_room_chat_screen_inputs( app: &mut MaclincommsApp, key_event: KeyE
 
                 let _ = app.roomchat_comps.input_ta.delete_line_by_head();
 
-                //Encrypting Message 
-                let ciphertext = encrypt_room_message(app.room_keys.chain_key, &final_message);
+                if matches!(mode, WhisperMode::NONE) {
+                    keep_room_history(&mut app.room_history, app.username.clone(), final_message.clone());
+                }
+
+                //Encrypting Message, whispers pairwise to their recipients only and the rest with my room chain key
+                let ciphertext = match mode {
+                    WhisperMode::NONE => encrypt_room_message(app.room_keys.chain_key, &final_message),
+                    _ => {
+                        let recipients: Vec<(String, [u8;32])> = app.room_keys.their_idpublic_keys.iter()
+                            .filter(|(user, _)| **user!=app.username)
+                            .filter(|(user, _)| match mode {
+                                WhisperMode::SHARE_WITH => users_list.contains(user),
+                                _ => !users_list.contains(user)
+                            })
+                            .map(|(user, key)| (user.clone(), *key))
+                            .collect();
+                        encrypt_room_whisper(app.room_keys.my_idpriv_key, &recipients, &final_message)
+                    }
+                };
                 //Signing ciphertext
                 let signature_priv_key = app.signature_keys.private_signature_key;
                 let signature = sign_room_ciphertext(signature_priv_key, &ciphertext);
@@ -581,6 +648,124 @@ pub fn handle_room_chat_screen_inputs( app: &mut MaclincommsApp, key_event: KeyE
     }
 
 }
+fn push_room_system_message(app: &mut MaclincommsApp, text: String) {
+    app.roomchat_comps.chat_history.lock().unwrap().push((
+        "maclincomms".to_string(),
+        Text::from(Line::from(text.light_red())),
+        get_current_time(),
+        false,
+        "".to_string()
+    ));
+    app.roomchat_comps.scroll_state.scroll_to_bottom();
+}
+
+//Moderation commands are enforced by the server, which tells the room once one is applied
+fn handle_room_command(app: &mut MaclincommsApp, input: &str) {
+
+    let mut parts = input.split_whitespace();
+    let command = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let action = match command {
+        "/transfer" => {
+            transfer_room_ownership(app, target);
+            return;
+        }
+        "/invite" => {
+            request_room_invite(app, input);
+            return;
+        }
+        "/leave" => {
+            leave_room(app);
+            return;
+        }
+        "/kick" => ModerationAction::KICK,
+        "/ban" => ModerationAction::BAN,
+        "/mute" => ModerationAction::MUTE,
+        "/promote" => ModerationAction::PROMOTE,
+        "/rotate-key" => ModerationAction::ROTATE_KEY,
+        _ => {
+            push_room_system_message(app, format!("Unknown command {}", command));
+            return;
+        }
+    };
+
+    if target.is_empty() && action!=ModerationAction::ROTATE_KEY {
+        push_room_system_message(app, format!("Usage: {} <username>", command));
+        return;
+    }
+
+    if let Some(roomchat_ui_sender) = &app.outgoing_roomchat_msg_tx {
+        if let Err(e) = roomchat_ui_sender.send(SocketMessage::RoomModeration(action, target)) {
+            eprintln!("Failed to send message to WebSocket: {}", e);
+        }
+    }
+}
+
+//Leaves the room on screen for good, the other rooms stay open
+fn leave_room(app: &mut MaclincommsApp) {
+
+    app.leave_current_room();
+
+    match app.active_rooms.is_empty() {
+        true => {
+            app.current_screen = Screens::CHAT_OPTIONS_SCREEN;
+            app.chatoptions_menu.activate();
+        }
+        false => {
+            app.roomlist_comps.current_index = 0;
+            app.current_screen = Screens::ROOM_LIST_SCREEN;
+        }
+    }
+}
+
+//Usage: /invite <max uses> <minutes> [username], the username being a DM contact to send it to
+fn request_room_invite(app: &mut MaclincommsApp, input: &str) {
+
+    let mut parts = input.split_whitespace().skip(1);
+    let max_uses = parts.next().and_then(|n| n.parse::<i64>().ok());
+    let expires_in_minutes = parts.next().and_then(|n| n.parse::<i64>().ok());
+    let send_to = parts.next().map(|username| username.to_string());
+
+    match (max_uses, expires_in_minutes) {
+        _ if !app.is_current_room_owner => {
+            push_room_system_message(app, "Only the Room Owner can create invites".to_string());
+        }
+        (Some(max_uses), Some(expires_in_minutes)) => {
+            let invite_tx = app.network_event_tx.clone();
+            invite_tx.send(Event::RoomInviteEvent(max_uses, expires_in_minutes, send_to)).unwrap();
+        }
+        _ => {
+            push_room_system_message(app, "Usage: /invite <max uses> <minutes> [username]".to_string());
+        }
+    }
+}
+
+//Asks the server to hand the room to another member, it confirms to everyone in the room with an 0x44 informer
+fn transfer_room_ownership(app: &mut MaclincommsApp, new_owner: String) {
+
+    let rejection = if !app.is_current_room_owner {
+        Some("Only the Room Owner can transfer ownership".to_string())
+    } else if new_owner==app.username || !app.room_keys.their_idpublic_keys.contains_key(&new_owner) {
+        Some(format!("{} is not a member of this room", new_owner))
+    } else {
+        None
+    };
+
+    match rejection {
+        Some(reason) => {
+            push_room_system_message(app, reason);
+        }
+        None => {
+            if let Some(roomchat_ui_sender) = &app.outgoing_roomchat_msg_tx {
+                if let Err(e) = roomchat_ui_sender.send(SocketMessage::RoomOwnershipTransfer(new_owner)) {
+                    eprintln!("Failed to send message to WebSocket: {}", e);
+                }
+            }
+        }
+    }
+}
+
 pub fn parse_whisper_command(input: &str) -> (Vec<String>, WhisperMode, String) {
 
     let hide_from_re = Regex::new(r#"^whisper\s+--hf\s+\[([^\[\]]+)\]"#).unwrap();
@@ -594,7 +779,7 @@ pub fn parse_whisper_command(input: &str) -> (Vec<String>, WhisperMode, String)
                 users_str
                     .as_str()
                     .split(',')
-                    .map(|s| s.to_string())
+                    .map(|s| s.trim().to_string())
                     .collect(),
                 WhisperMode::HIDE_FROM,
                 final_message
@@ -608,7 +793,7 @@ pub fn parse_whisper_command(input: &str) -> (Vec<String>, WhisperMode, String)
                 users_str
                     .as_str()
                     .split(',')
-                    .map(|s| s.to_string())
+                    .map(|s| s.trim().to_string())
                     .collect(),
                 WhisperMode::SHARE_WITH,
                 final_message
@@ -704,6 +889,12 @@ pub fn switch_adduser_textfield(app: &mut MaclincommsApp, which: i32) {
 
 pub fn handle_dm_user_screen_inputs( app: &mut MaclincommsApp, key_event: KeyEvent,){
 
+    //Safety number view takes the keys while open
+    if app.dmuser_comps.safety_number_view.is_some() {
+        handle_safety_number_view_inputs(app, key_event);
+        return;
+    }
+
     match key_event.code {
         KeyCode::Esc => { 
             app.current_screen = Screens::CHAT_OPTIONS_SCREEN;
@@ -746,6 +937,48 @@ pub fn handle_dm_user_screen_inputs( app: &mut MaclincommsApp, key_event: KeyEve
            let mut dms_content = app.dmuser_comps.dms_list.lock().unwrap();
 
         },
+        KeyCode::Char('v') => {
+
+            let dm_user = {
+                let dms_content = app.dmuser_comps.dms_list.lock().unwrap();
+                let index = app.dmuser_comps.current_index;
+                match dms_content.iter().rev().nth(index as usize) {
+                    Some(dm) => dm.with_user.clone(),
+                    None => return
+                }
+            };
+
+            //Their identity key as last fetched from /get_dms and our account's
+            let dms_list: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
+            let their_pub_key = dms_list.read().ok().flatten()
+                .and_then(|list| list.into_iter().find(|dm| dm.username==dm_user))
+                .map(|dm| dm.public_identity_key);
+            let their_pub_key_bytes: Option<[u8;32]> = their_pub_key.as_ref()
+                .and_then(|key| general_purpose::STANDARD.decode(key).ok()?.try_into().ok());
+
+            match (their_pub_key, their_pub_key_bytes, UserIdentityKeys::load_account_public_key()) {
+                (Some(their_pub_key), Some(their_pub_key_bytes), Some(my_pub_key)) => {
+                    let status = VerifiedContacts::load().status(&dm_user, &their_pub_key);
+                    app.dmuser_comps.safety_number_view = Some(SafetyNumberView {
+                        safety_number: safety_number(&app.username, my_pub_key, &dm_user, their_pub_key_bytes),
+                        with_user: dm_user,
+                        public_identity_key: their_pub_key,
+                        status
+                    });
+                }
+                _ => {
+                    let text = format!("Couldn't load identity keys for {}", dm_user);
+                    let status_block = Block::default()
+                            .borders(Borders::ALL)
+                            .border_type(ratatui::widgets::BorderType::default())
+                            .border_style(Style::default().fg(ratatui::style::Color::LightRed));
+
+                    app.dmuser_comps.action_status_block = Paragraph::new(text.light_red())
+                            .alignment(ratatui::layout::Alignment::Center)
+                            .block(status_block);
+                }
+            }
+        },
         KeyCode::Enter => {
 
             let mut dms_content = app.dmuser_comps.dms_list.lock().unwrap();
@@ -782,6 +1015,42 @@ pub fn handle_dm_user_screen_inputs( app: &mut MaclincommsApp, key_event: KeyEve
 }
 
 
+fn handle_safety_number_view_inputs( app: &mut MaclincommsApp, key_event: KeyEvent,){
+
+    let Some(view) = app.dmuser_comps.safety_number_view.as_mut() else {
+        return;
+    };
+
+    match key_event.code {
+        KeyCode::Esc => {
+            app.dmuser_comps.safety_number_view = None;
+        },
+        KeyCode::Char('v') => {
+            let mut verified_contacts = VerifiedContacts::load();
+            verified_contacts.verify(view.with_user.clone(), view.public_identity_key.clone());
+            verified_contacts.save();
+
+            view.status = VerificationStatus::VERIFIED;
+            app.dmuser_comps.verification_status.insert(view.with_user.clone(), VerificationStatus::VERIFIED);
+        },
+        KeyCode::Char('u') => {
+            let mut verified_contacts = VerifiedContacts::load();
+            verified_contacts.unverify(&view.with_user);
+            verified_contacts.save();
+
+            view.status = VerificationStatus::UNVERIFIED;
+            app.dmuser_comps.verification_status.insert(view.with_user.clone(), VerificationStatus::UNVERIFIED);
+        },
+        _ => {}
+    }
+
+    //Key change warning goes away once every changed key is re-verified or unverified
+    if app.dmuser_comps.key_changed_users().is_empty() {
+        app.dmuser_comps.action_status_block = DmUserComponents::get_action_status_block();
+    }
+}
+
+
 pub fn handle_dm_chat_screen_inputs( app: &mut MaclincommsApp, key_event: KeyEvent,){
 
     match key_event.code {
@@ -812,14 +1081,13 @@ pub fn handle_dm_chat_screen_inputs( app: &mut MaclincommsApp, key_event: KeyEve
                 let _ = app.dmchat_comps.input_ta.delete_line_by_head();
                 
 
-                //Encrypting message before sending and appending User's DH Pub Key
-                //[Ciphertext].[DH Pub Key]
-                let mut appended_dh_key_message = "".to_string();
-                if let Some(keys) = app.dme2ee_data.dms.get(&app.dmchat_comps.to_user){
-                    let sending_chain_key = keys.sending_chain_key;
-                    let ciphertext = encrypt_dm_message(sending_chain_key, &cleaned_input);
-                    let dh_pub_key = general_purpose::STANDARD.encode(keys.dh_pub_key);
-                    appended_dh_key_message = ciphertext + "." + &dh_pub_key;
+                //Encrypting message before sending into a DM envelope carrying User's DH Pub Key
+                //v1.[DH Pub Key].[Message Counter].[Nonce].[Ciphertext]
+                //Once for each of the receiver's devices, the account device's envelope also going as the content
+                let mut dm_envelope_message = "".to_string();
+                let mut device_envelopes = HashMap::new();
+                if let Some(contact) = DmUser_Data::load(&app.dmchat_comps.to_user){
+                    (dm_envelope_message, device_envelopes) = app.dme2ee_data.encrypt_for_devices(&contact, &cleaned_input);
                 }
 
 
@@ -832,8 +1100,10 @@ pub fn handle_dm_chat_screen_inputs( app: &mut MaclincommsApp, key_event: KeyEve
                             SocketMessage::Message(MessageType::DM(
                                 DmMessage {
                                     username: user_name,
-                                    content: appended_dh_key_message,
-                                    is_online_offline_msg: false
+                                    content: dm_envelope_message,
+                                    is_online_offline_msg: false,
+                                    device_contents: device_envelopes,
+                                    device_id: "".to_string()
                                 }
                             )
                         )) {
@@ -927,6 +1197,278 @@ pub fn handle_block_user_screen_inputs( app: &mut MaclincommsApp, key_event: Key
 }
 
 
+pub fn handle_room_list_screen_inputs( app: &mut MaclincommsApp, key_event: KeyEvent,){
+
+    match key_event.code {
+        KeyCode::Esc => { 
+            app.current_screen = Screens::CHAT_OPTIONS_SCREEN;
+            app.chatoptions_menu.activate();
+        },
+        KeyCode::Up => {
+
+            if app.roomlist_comps.current_index!=0 {
+                app.roomlist_comps.current_index -= 1;
+            }
+            app.roomlist_comps.scroll_state.scroll_up();
+            app.roomlist_comps.scroll_state.scroll_up();
+            app.roomlist_comps.scroll_state.scroll_up();
+            app.roomlist_comps.scroll_state.scroll_up();
+            app.roomlist_comps.scroll_state.scroll_up();
+
+        },
+        KeyCode::Down => {
+
+            let rooms_len = app.room_list_entries().len();
+
+            if app.roomlist_comps.current_index+1 < rooms_len {
+                app.roomlist_comps.current_index += 1;
+            }
+            app.roomlist_comps.scroll_state.scroll_down();
+            app.roomlist_comps.scroll_state.scroll_down();
+            app.roomlist_comps.scroll_state.scroll_down();
+            app.roomlist_comps.scroll_state.scroll_down();
+            app.roomlist_comps.scroll_state.scroll_down();
+
+        },
+        KeyCode::Enter => {
+
+            let index = app.roomlist_comps.current_index;
+
+            if let Some(room) = app.room_list_entries().into_iter().nth(index) {
+                app.switch_to_room(&room.room_name);
+            }
+        },
+        _ => {}
+    }
+}
+
+pub fn handle_room_directory_screen_inputs( app: &mut MaclincommsApp, key_event: KeyEvent,){
+
+    match key_event.code {
+        KeyCode::Esc => { 
+            app.current_screen = Screens::CHAT_OPTIONS_SCREEN;
+            app.chatoptions_menu.activate();
+        },
+        KeyCode::Up => {
+
+            if app.roomdirectory_comps.current_index!=0 {
+                app.roomdirectory_comps.current_index -= 1;
+            }
+            app.roomdirectory_comps.scroll_state.scroll_up();
+            app.roomdirectory_comps.scroll_state.scroll_up();
+            app.roomdirectory_comps.scroll_state.scroll_up();
+            app.roomdirectory_comps.scroll_state.scroll_up();
+            app.roomdirectory_comps.scroll_state.scroll_up();
+            app.roomdirectory_comps.scroll_state.scroll_up();
+
+        },
+        KeyCode::Down => {
+
+            if app.roomdirectory_comps.current_index+1 < app.roomdirectory_comps.rooms.len() {
+                app.roomdirectory_comps.current_index += 1;
+            }
+            app.roomdirectory_comps.scroll_state.scroll_down();
+            app.roomdirectory_comps.scroll_state.scroll_down();
+            app.roomdirectory_comps.scroll_state.scroll_down();
+            app.roomdirectory_comps.scroll_state.scroll_down();
+            app.roomdirectory_comps.scroll_state.scroll_down();
+            app.roomdirectory_comps.scroll_state.scroll_down();
+
+        },
+        KeyCode::Left | KeyCode::Right => {
+
+            let page = app.roomdirectory_comps.page;
+
+            let next_page = match key_event.code {
+                KeyCode::Right if app.roomdirectory_comps.has_more => Some(page+1),
+                KeyCode::Left if page > 0 => Some(page-1),
+                _ => None
+            };
+
+            if let Some(next_page) = next_page {
+                let loaddirectory_tx = app.network_event_tx.clone();
+                loaddirectory_tx.send(Event::LoadRoomDirectoryEvent(next_page)).unwrap();
+            }
+        },
+        KeyCode::Enter => {
+
+            let index = app.roomdirectory_comps.current_index;
+
+            if let Some(room) = app.roomdirectory_comps.rooms.get(index) {
+                let room_name = room.room_name.clone();
+                join_listed_room(app, room_name);
+            }
+        },
+        _ => {}
+    }
+}
+
+pub fn handle_linked_devices_screen_inputs( app: &mut MaclincommsApp, key_event: KeyEvent,){
+
+    //Entering a link code from another device
+    if let Some(link_code_ta) = &app.devices_comps.link_code_ta {
+        match key_event.code {
+            KeyCode::Esc => {
+                app.devices_comps.link_code_ta = None;
+            },
+            KeyCode::Enter => {
+                let link_code = link_code_ta.lines()[0].trim().to_string();
+                if !link_code.is_empty() {
+                    let claimdevicelink_tx = app.network_event_tx.clone();
+                    claimdevicelink_tx.send(Event::ClaimDeviceLinkEvent(link_code)).unwrap();
+
+                    //Changing Action Bar to Loading
+                    let status_block = Block::default()
+                        .borders(Borders::ALL)
+                        .border_type(ratatui::widgets::BorderType::default())
+                        .border_style(Style::default().fg(ratatui::style::Color::LightYellow));
+
+                    app.devices_comps.action_status_block = Paragraph::new("Linking this device".light_yellow())
+                        .alignment(ratatui::layout::Alignment::Center)
+                        .block(status_block);
+
+                    app.devices_comps.link_code_ta = None;
+                }
+            },
+            _ => {}
+        }
+        return;
+    }
+
+    //Any other key calls off a rotation waiting for its second press
+    let rotate_identity_armed = std::mem::take(&mut app.devices_comps.rotate_identity_armed);
+
+    match key_event.code {
+        KeyCode::Esc => { 
+            app.current_screen = Screens::CHAT_OPTIONS_SCREEN;
+            app.chatoptions_menu.activate();
+        },
+        KeyCode::Up => {
+
+            if app.devices_comps.current_index!=0 {
+                app.devices_comps.current_index -= 1;
+            }
+            app.devices_comps.scroll_state.scroll_up();
+            app.devices_comps.scroll_state.scroll_up();
+            app.devices_comps.scroll_state.scroll_up();
+            app.devices_comps.scroll_state.scroll_up();
+            app.devices_comps.scroll_state.scroll_up();
+            app.devices_comps.scroll_state.scroll_up();
+
+        },
+        KeyCode::Down => {
+
+            if app.devices_comps.current_index+1 < app.devices_comps.devices.len() {
+                app.devices_comps.current_index += 1;
+            }
+            app.devices_comps.scroll_state.scroll_down();
+            app.devices_comps.scroll_state.scroll_down();
+            app.devices_comps.scroll_state.scroll_down();
+            app.devices_comps.scroll_state.scroll_down();
+            app.devices_comps.scroll_state.scroll_down();
+            app.devices_comps.scroll_state.scroll_down();
+
+        },
+        KeyCode::Char('r') | KeyCode::Char('R') => {
+
+            let index = app.devices_comps.current_index;
+
+            //The device in use signs out by logging out instead
+            if let Some(device) = app.devices_comps.devices.get(index) {
+                if device.device_id!=app.devices_comps.current_device_id {
+                    let revokedevice_tx = app.network_event_tx.clone();
+                    revokedevice_tx.send(Event::RevokeDeviceEvent(device.device_id.clone())).unwrap();
+
+                    //Changing Action Bar to Loading
+                    let status_block = Block::default()
+                        .borders(Borders::ALL)
+                        .border_type(ratatui::widgets::BorderType::default())
+                        .border_style(Style::default().fg(ratatui::style::Color::LightYellow));
+
+                    app.devices_comps.action_status_block = Paragraph::new(format!("Revoking {}", device.device_name).light_yellow())
+                        .alignment(ratatui::layout::Alignment::Center)
+                        .block(status_block);
+                }
+            }
+        },
+        KeyCode::Char('l') | KeyCode::Char('L') => {
+
+            let createdevicelink_tx = app.network_event_tx.clone();
+            createdevicelink_tx.send(Event::CreateDeviceLinkEvent).unwrap();
+
+            //Changing Action Bar to Loading
+            let status_block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::default())
+                .border_style(Style::default().fg(ratatui::style::Color::LightYellow));
+
+            app.devices_comps.action_status_block = Paragraph::new("Making a link code".light_yellow())
+                .alignment(ratatui::layout::Alignment::Center)
+                .block(status_block);
+        },
+        KeyCode::Char('i') | KeyCode::Char('I') => {
+            app.devices_comps.link_code_ta = Some(DevicesComponents::get_link_code_textarea());
+        },
+        KeyCode::Char('k') | KeyCode::Char('K') => {
+
+            //Every DM session starts over, so it takes a second press
+            let (text, color) = match rotate_identity_armed {
+                false => ("Press [K] again to rotate your identity key, DM sessions will start over", ratatui::style::Color::LightYellow),
+                true => {
+                    let rotateidentity_tx = app.network_event_tx.clone();
+                    rotateidentity_tx.send(Event::RotateIdentityKeyEvent).unwrap();
+                    ("Rotating your identity key", ratatui::style::Color::LightYellow)
+                }
+            };
+            app.devices_comps.rotate_identity_armed = !rotate_identity_armed;
+
+            let status_block = Block::default()
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::default())
+                .border_style(Style::default().fg(color));
+
+            app.devices_comps.action_status_block = Paragraph::new(text.fg(color))
+                .alignment(ratatui::layout::Alignment::Center)
+                .block(status_block);
+        },
+        _ => {}
+    }
+}
+
+//Listed rooms need no room key, the Join Room Screen shows how joining (or knocking) went
+fn join_listed_room(app: &mut MaclincommsApp, room_name: String) {
+
+    if !matches!(app.joinroom_textarea.task_status, JoinRoomTaskStatus::NOT_INITIATED) {
+        return;
+    }
+
+    let mut roomname_ta = JoinRoomTextArea::get_roomname_textarea();
+    roomname_ta.insert_str(room_name);
+    app.joinroom_textarea.roomname_ta = roomname_ta;
+    app.joinroom_textarea.roomkey_ta = JoinRoomTextArea::get_roomkey_textarea();
+    app.joinroom_textarea.which_ta = 0;
+
+    app.joinroom_textarea.task_status = JoinRoomTaskStatus::IN_PROGRESS;
+    let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(ratatui::style::Color::Yellow));
+
+    let throb_widget = throbber_widgets_tui::Throbber::default()
+            .label("Joining room...")
+            .throbber_set(CLOCK)
+            .style(ratatui::style::Style::default().fg(ratatui::style::Color::Yellow));
+
+    app.joinroom_textarea.status_block = Paragraph::new(vec![Line::from(throb_widget)])
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+
+    app.current_screen = Screens::ROOM_JOIN_SCREEN;
+
+    let joinroom_event_tx  = app.network_event_tx.clone();
+    joinroom_event_tx.send(Event::RoomJoinEvent).unwrap();
+}
+
 pub fn handle_notifications_screen_inputs( app: &mut MaclincommsApp, key_event: KeyEvent,){
 
     match key_event.code {
@@ -978,6 +1520,25 @@ pub fn handle_notifications_screen_inputs( app: &mut MaclincommsApp, key_event:
              
             n_content.remove(last_index - (index as usize));
  
+         },
+        KeyCode::Char('d') => { //Deny a join request
+
+            let mut n_content = app.notifications_comps.notifications_history.lock().unwrap();
+
+            let last_index = n_content.len()-1;
+
+            let index = app.notifications_comps.current_index;
+
+            let notification = n_content[last_index - (index as usize)].clone();
+
+            if let NotificationType::ROOM_JOIN_REQUEST = notification.n_type {
+                n_content.remove(last_index - (index as usize));
+
+                //EMITTING DENY REQ EVENT
+                let deny_tx = app.network_event_tx.clone();
+                deny_tx.send(Event::RespondJoinRequestEvent(notification.content, notification.from, AcceptanceStatus::IGNORED)).unwrap();
+            }
+
          },
         KeyCode::Enter => {
 
@@ -1005,6 +1566,55 @@ pub fn handle_notifications_screen_inputs( app: &mut MaclincommsApp, key_event:
                     let accept_tx = app.network_event_tx.clone();
                     accept_tx.send(Event::AcceptUserEvent(n_user)).unwrap();
                 }
+                NotificationType::ROOM_JOIN_REQUEST => {
+                    let n_room = reversed.get(index as usize).unwrap().content.clone();
+                    let text = format!("Letting {} into {}", n_user.clone(), n_room.clone());
+                    let status_block = Block::default()
+                            .borders(Borders::ALL)
+                            .border_type(ratatui::widgets::BorderType::default())
+                            .border_style(Style::default().fg(ratatui::style::Color::LightYellow));
+                                                                
+                    app.notifications_comps.action_status_block = Paragraph::new(text.light_yellow())
+                            .alignment(ratatui::layout::Alignment::Center)
+                            .block(status_block);
+
+                    //EMITTING APPROVE REQ EVENT
+                    let approve_tx = app.network_event_tx.clone();
+                    approve_tx.send(Event::RespondJoinRequestEvent(n_room, n_user, AcceptanceStatus::ACCEPTED)).unwrap();
+                }
+                NotificationType::ROOM_JOIN_APPROVED => {
+                    let n_approved = RoomJoinApprovedNotificationContent::parse(&reversed.get(index as usize).unwrap().content);
+                    if let Some(approved) = n_approved {
+                        //The room token is short lived, the Room Key still works after it expires
+                        let join_tx = app.network_event_tx.clone();
+                        join_tx.send(Event::RoomJoinApprovedEvent(approved.room_name, approved.room_token)).unwrap();
+                    }
+                }
+                NotificationType::ROOM_INVITE => {
+                    let n_invite = RoomInviteNotificationContent::parse(&reversed.get(index as usize).unwrap().content);
+                    if let Some(invite) = n_invite {
+                        //Filling the Join Room Screen with the invite, joining is one Enter away
+                        let mut roomname_ta = JoinRoomTextArea::get_roomname_textarea();
+                        roomname_ta.insert_str(invite.room_name.clone());
+                        let mut roomkey_ta = JoinRoomTextArea::get_roomkey_textarea();
+                        roomkey_ta.insert_str(invite.invite_token);
+                        roomkey_ta.set_cursor_style(Style::default());
+                        app.joinroom_textarea.roomname_ta = roomname_ta;
+                        app.joinroom_textarea.roomkey_ta = roomkey_ta;
+                        app.joinroom_textarea.which_ta = 0;
+
+                        let text = format!("Press Enter to join {} with {}'s invite", invite.room_name, n_user);
+                        let status_block = Block::default()
+                                .borders(Borders::ALL)
+                                .border_type(ratatui::widgets::BorderType::default())
+                                .border_style(Style::default().fg(ratatui::style::Color::Gray));
+                        app.joinroom_textarea.status_block = Paragraph::new(text.light_blue())
+                                .alignment(ratatui::layout::Alignment::Center)
+                                .block(status_block);
+
+                        app.current_screen = Screens::ROOM_JOIN_SCREEN;
+                    }
+                }
                 _ => {
                     let text = format!("Loading your chats with {}", n_user.clone());
                     let status_block = Block::default()
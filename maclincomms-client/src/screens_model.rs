@@ -12,5 +12,8 @@ pub enum Screens {
     DM_USER_SCREEN,
     DM_CHAT_SCREEN,
     BLOCK_USER_SCREEN,
-    NOTIFICATIONS_SCREEN
+    NOTIFICATIONS_SCREEN,
+    ROOM_LIST_SCREEN,
+    ROOM_DIRECTORY_SCREEN,
+    LINKED_DEVICES_SCREEN
 }
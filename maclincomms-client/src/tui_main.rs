@@ -1,9 +1,9 @@
-use std::{collections::HashMap, io::{self}, process::exit, sync::mpsc::{self, Sender}, thread::{self}};
+use std::{collections::{HashMap, VecDeque}, io::{self}, process::exit, sync::mpsc::{self, Sender}, thread::{self}, time::Instant};
 use base64::{engine::general_purpose, Engine};
 use crossterm::{
     event::{KeyCode, KeyEventKind, KeyModifiers}
 };
-use disk_persist::DiskPersist;
+use crate::local_store::SealedPersist;
 use ratatui::{
      layout::{Alignment, Constraint, Direction, Layout}, style::{Color, Modifier, Style, Stylize}, text::{Line, Span, Text}, widgets::{Block, Borders, Paragraph}, DefaultTerminal, Frame
 };
@@ -12,10 +12,15 @@ use tui_menu::{Menu, MenuEvent, MenuItem, MenuState};
 
 
 use crate::{
+    active_rooms::{ActiveRoom, RoomEventRoute},
     crypto::{
-        decrypt_msg::{decrypt_dm_message, decrypt_room_message, decrypt_senderkey_message}, 
-        dm_keys::{derive_message_key, generate_dh_keypair, generate_receiver_chainkey, generate_sender_chainkey, generate_shared_rootkey, update_receiving_chainkey, update_sending_chainkey}, 
+        decrypt_msg::{decrypt_room_message, decrypt_senderkey_message}, 
+        double_ratchet::DoubleRatchet, 
+        x3dh::accept_x3dh_handshake, 
+        dm_keys::{derive_message_key, generate_dh_keypair, generate_receiver_chainkey, generate_sender_chainkey, generate_shared_rootkey}, 
         encrypt_msg::{encrypt_dm_chats_session, encrypt_senderkey_message, verify_room_ciphertext}, 
+        room_whisper::{decrypt_room_whisper, is_room_whisper},
+        room_history::{compose_room_history_frame, merge_room_history, open_room_history, parse_room_history_frame, seal_room_history},
         room_keys::{compose_sender_key, derive_roommessage_key, generate_roomchain_key, update_my_roomchainkey, update_their_roomchainkey}
     }, 
     endpoints::Endpoints, event_model::Event, 
@@ -25,8 +30,16 @@ use crate::{
         adduser_thread::start_adduser_task, 
         blockuser_thread::start_blockuser_task, 
         get_roomdata::{get_room_data}, 
-        getdms_thread::start_getdms_thread, 
-        joinroom_thread::start_joinroom_task, 
+        getdms_thread::{load_dm_contact, start_getdms_thread}, 
+        joinroom_thread::{enter_joined_room, start_joinroom_task},
+        roominvite_thread::start_roominvite_task, 
+        roomdirectory_thread::start_roomdirectory_thread, 
+        devices_thread::start_devices_thread, 
+        revokedevice_thread::start_revokedevice_thread, 
+        createdevicelink_thread::start_createdevicelink_thread, 
+        claimdevicelink_thread::start_claimdevicelink_thread, 
+        rotateidentity_thread::{accept_identity_rotation, start_rotateidentity_thread}, 
+        respondjoinrequest_thread::start_respondjoinrequest_thread, 
         login_thread::start_login_task, 
         realtime_notifications::subscribe_to_realtime_notifications, 
         register_thread::start_register_task, 
@@ -35,7 +48,7 @@ use crate::{
         upload_dm_chats::upload_dm_chats
     }, 
     persistent_login::persistent_authentication, 
-    screen_inputs::{handle_add_user_screen_inputs, handle_block_user_screen_inputs, handle_chat_options_screen_inputs, handle_dm_chat_screen_inputs, handle_dm_user_screen_inputs, handle_login_screen_inputs, handle_notifications_screen_inputs, handle_public_chat_screen_inputs, handle_register_screen_inputs, handle_room_chat_screen_inputs, handle_room_creation_screen_inputs, handle_room_join_screen_inputs, handle_welcome_screen_inputs, take_next_lines, text_to_string}, 
+    screen_inputs::{handle_add_user_screen_inputs, handle_block_user_screen_inputs, handle_chat_options_screen_inputs, handle_dm_chat_screen_inputs, handle_dm_user_screen_inputs, handle_linked_devices_screen_inputs, handle_login_screen_inputs, handle_notifications_screen_inputs, handle_public_chat_screen_inputs, handle_register_screen_inputs, handle_room_chat_screen_inputs, handle_room_creation_screen_inputs, handle_room_directory_screen_inputs, handle_room_join_screen_inputs, handle_room_list_screen_inputs, handle_welcome_screen_inputs, keep_room_history, take_next_lines, text_to_string}, 
     screens_model::Screens, 
     tui_widgets::{
         adduser_panel, 
@@ -51,6 +64,9 @@ use crate::{
         login_screen, 
         login_textarea::LoginTextArea, 
         notifications_panel::{self, NotificationStatus, NotificationsComponents}, 
+        roomlist_panel::{self, RoomListComponents}, 
+        roomdirectory_panel::{self, RoomDirectoryComponents}, 
+        devices_panel::{self, DevicesComponents}, 
         publicchat_panel::{self, PublicChatComponents}, 
         register_screen, 
         register_textarea::RegisterTextArea, 
@@ -59,7 +75,7 @@ use crate::{
         roomcreation_textarea::RoomCreationTextArea, 
         splash_screen::draw_splash_screen
     }, 
-    user_model::{AckType, ChatData, ChatEntry, DisconnectType, DmChats_Warehouse, DmDoubleRatchet_Keys, DmE2EEncryption_Data, DmMessage, DmUser_Data, DmsListData, Message, MessageType, NotificationData, NotificationType, RoomMessageType, RoomSenderMessage, Room_Keys, SenderKey, SocketMessage, UserIdentityKeys, UserSignatureKeys, WhisperMode, WorldChatMessage}, 
+    user_model::{AcceptedNotificationContent, AckType, ChatData, ChatEntry, DisconnectType, DmChats_Warehouse, DmE2EEncryption_Data, DmMessage, DmUser_Data, DmsListData, Message, MessageType, ModerationAction, NotificationData, NotificationType, RoomHistoryMessage, RoomMessageType, RoomSenderMessage, RoomSettings, Room_Keys, SenderKey, SharedRoomHistory, SocketMessage, UserDevice, UserIdentityKeys, UserSignatureKeys, WhisperMode, WorldChatMessage}, 
     websockets::websocket_thread::{start_dmchat_websocket_task, start_roomchat_websocket_task, start_worldchat_websocket_task}
 };
 
@@ -173,7 +189,16 @@ pub struct MaclincommsApp {
     pub signature_keys: UserSignatureKeys,
     pub room_keys: Room_Keys,
     pub room_token: String,
-    pub is_current_room_owner: bool
+    pub is_current_room_owner: bool,
+    pub current_room_owner: String,
+    pub current_room_settings: RoomSettings,
+    pub last_room_message_at: Option<Instant>, //For keeping to the room's slow mode
+    pub room_history: VecDeque<RoomHistoryMessage>, //Latest plain messages, for newcomers in rooms sharing history
+    pub room_history_shared: Option<SharedRoomHistory>, //Every member shares on our joining, the owner's or else the first history is kept
+    pub active_rooms: HashMap<String, ActiveRoom>, //Joined rooms in the background, by room name
+    pub roomlist_comps: RoomListComponents,
+    pub roomdirectory_comps: RoomDirectoryComponents,
+    pub devices_comps: DevicesComponents
 }
 
 
@@ -213,13 +238,16 @@ impl MaclincommsApp {
                 vec![
                 MenuItem::item("World Chat", ChatOptionsAction::PUBLIC_CHAT),
                 MenuItem::item("Current Room", ChatOptionsAction::CURRENT_ROOM),
+                MenuItem::item("My Rooms", ChatOptionsAction::ROOM_LIST),
                 MenuItem::item("Current DM", ChatOptionsAction::CURRENT_DM),
                 MenuItem::item("Create Room", ChatOptionsAction::CREATE_ROOM),
                 MenuItem::item("Join Room", ChatOptionsAction::JOIN_ROOM),
+                MenuItem::item("Room Directory", ChatOptionsAction::ROOM_DIRECTORY),
                 MenuItem::item("Add User", ChatOptionsAction::ADD_USER),
                 MenuItem::item("DM User", ChatOptionsAction::DM_USER),
                 MenuItem::item("Block/Unblock User", ChatOptionsAction::BLOCK_USER),
                 MenuItem::item("Notifications", ChatOptionsAction::NOTIFICATIONS),
+                MenuItem::item("Linked Devices", ChatOptionsAction::LINKED_DEVICES),
                 ]
                 )
             ]),
@@ -241,7 +269,16 @@ impl MaclincommsApp {
             signature_keys: UserSignatureKeys::new(),
             room_keys: Room_Keys::new(),
             room_token: "".to_string(),
-            is_current_room_owner: false
+            is_current_room_owner: false,
+            current_room_owner: "".to_string(),
+            current_room_settings: RoomSettings::default(),
+            last_room_message_at: None,
+            room_history: VecDeque::new(),
+            room_history_shared: None,
+            active_rooms: HashMap::new(),
+            roomlist_comps: RoomListComponents::new(),
+            roomdirectory_comps: RoomDirectoryComponents::new(),
+            devices_comps: DevicesComponents::new()
         } 
     }
 
@@ -249,7 +286,17 @@ impl MaclincommsApp {
         while !self.exit {
             terminal.draw(|frame | self.draw(frame))?;
 
-            match main_events_channel_rx.recv().unwrap() {
+            //Room socket events are handled against the room they came from, a background room is swapped in for one
+            let (event, background_room) = match main_events_channel_rx.recv().unwrap() {
+                Event::RoomEvent(room_name, room_event) => match self.route_room_event(&room_name) {
+                    RoomEventRoute::ON_SCREEN => (*room_event, None),
+                    RoomEventRoute::BACKGROUND(background) => (*room_event, Some(background)),
+                    RoomEventRoute::GONE => continue
+                },
+                event => (event, None)
+            };
+
+            match event {
                 Event::IncomingPublicMessageEvent(msg) => {
                     let formatted_msg = take_next_lines(msg.content.clone());
                     //Update ui only if the message is not empty
@@ -296,13 +343,23 @@ impl MaclincommsApp {
                                 let their_chain_key = their_sender_key.chain_key;
                                 //Verify Signature
                                 if let Ok(()) = verify_room_ciphertext(their_signature_pub_key, ciphertext, signature_b64){
-                                    //Decrypt message
-                                    let receiving_msg_key = derive_roommessage_key(their_chain_key);
-                                    let decrypted_msg = decrypt_room_message(receiving_msg_key, ciphertext);
+                                    //Decrypt message, whispers with the key wrapped for us
+                                    let decrypted_msg = if is_room_whisper(ciphertext){
+                                        self.room_keys.their_idpublic_keys.get(&msg.username)
+                                            .and_then(|their_idpub_key| decrypt_room_whisper(self.room_keys.my_idpriv_key, &self.username, *their_idpub_key, ciphertext))
+                                            .unwrap_or_default()
+                                    }
+                                    else{
+                                        let receiving_msg_key = derive_roommessage_key(their_chain_key);
+                                        decrypt_room_message(receiving_msg_key, ciphertext)
+                                    };
                                     //Update Chain Key
                                     update_their_roomchainkey(their_sender_key);
                                     //Only update ui if message is not empty and decrypted successfully
                                     if !decrypted_msg.is_empty(){
+                                        if !is_room_whisper(ciphertext){
+                                            keep_room_history(&mut self.room_history, msg.username.clone(), decrypted_msg.clone());
+                                        }
                                         let formatted_msg = take_next_lines(decrypted_msg);
                                         chat_history_lock.push((
                                             msg.username,
@@ -367,6 +424,18 @@ impl MaclincommsApp {
                             {
                                 println!("Couldnt send sender key message");
                             }
+
+                            //Rooms sharing history get our latest plain messages, sealed for the new joiner alone
+                            let share_history_count = room_data.settings.share_history_count.max(0) as usize;
+                            if share_history_count > 0 && !self.room_history.is_empty() && self.room_keys.their_idpublic_keys.contains_key(&new_user){
+                                let skip = self.room_history.len().saturating_sub(share_history_count);
+                                let history: Vec<RoomHistoryMessage> = self.room_history.iter().skip(skip).cloned().collect();
+                                let history_frame = compose_room_history_frame(&new_user, seal_room_history(pub_key_bytes, self.signature_keys.private_signature_key, &history));
+                                if let Err(e) = skey_tx.send(SocketMessage::RoomHistory(history_frame))
+                                {
+                                    println!("Couldnt send room history");
+                                }
+                            }
                         }
                         //Pushing join/leave message to chat history
                         if let Ok(mut chat_history_lock) = self.roomchat_comps.chat_history.lock() {
@@ -381,10 +450,17 @@ impl MaclincommsApp {
                         }
                     }
                     //Leave Message (User left)
-                    //Retrieve dms list again, resend your new sender keys and get their new sender keys
+                    //The departed member holds my chain key, so generate a fresh one and send it to everyone else remaining
+                    //Their new sender keys come in the same way
                     else if msg.is_join_leave_msg==true && msg.content.split_whitespace().nth(1) == Some("left"){
-                        //Clear old room data
+                        let departed_user = msg.username.clone();
+                        //Clear old room data, keeping my identity key and the sender keys of those who stay
+                        let my_idpriv_key = self.room_keys.my_idpriv_key;
+                        let mut their_sender_keys = std::mem::take(&mut self.room_keys.their_sender_keys);
+                        their_sender_keys.remove(&departed_user);
                         self.room_keys = Room_Keys::new();
+                        self.room_keys.my_idpriv_key = my_idpriv_key;
+                        self.room_keys.their_sender_keys = their_sender_keys;
                         //Retrieving Room Data and Memeber Keys for BI-directional encrypted transfer of Sender Key
                         let get_room_data_endpoint = self.endpoints.get_room_data;
                         let room_data_res = get_room_data(self.room_token.clone(), get_room_data_endpoint).await;
@@ -399,8 +475,11 @@ impl MaclincommsApp {
                         
                         //Sending sender key to each user by encrypting with their shared root key derived message key
                         if let Some(data) = room_data_res{
-                            //For all users in room
+                            //For all users in room, except me and the one who left (room data may not have caught up yet)
                             for user in data.room_members{
+                                if user==self.username || user==departed_user{
+                                    continue;
+                                }
                                 let mut pub_key_bytes = [0u8;32];
                                 let their_pub_key_res = data.members_keys
                                         .iter()
@@ -466,6 +545,55 @@ impl MaclincommsApp {
                     self.roomchat_comps.scroll_state.scroll_to_bottom();
                 },
 
+                Event::IncomingRoomHistoryEvent(frame) => {
+                    //The owner's history wins over whichever came first, anyone else's only fills an empty spot
+                    let room_owner = self.current_room_owner.clone();
+                    let wanted = |shared_by: &String| match &self.room_history_shared {
+                        None => true,
+                        Some(shared) => *shared_by==room_owner && shared.shared_by!=room_owner
+                    };
+                    //Signed by the sharer's room signature key, which came with their sender key just before
+                    let opened = parse_room_history_frame(&frame)
+                        .filter(|(shared_by, _)| wanted(shared_by))
+                        .and_then(|(shared_by, sealed)| {
+                            let their_sigpub_key = self.room_keys.their_sender_keys.get(&shared_by)?.pub_sig_key;
+                            Some((shared_by, open_room_history(self.room_keys.my_idpriv_key, their_sigpub_key, &sealed)?))
+                        });
+                    if let Some((shared_by, history)) = opened {
+                        //What the first sharer told us gives way to the owner's
+                        if let Some(replaced) = self.room_history_shared.take() {
+                            if let Ok(mut chat_history_lock) = self.roomchat_comps.chat_history.lock() {
+                                let shown = (replaced.messages.len() + 1).min(chat_history_lock.len());
+                                chat_history_lock.drain(0..shown);
+                            }
+                            self.room_history.retain(|message| !replaced.messages.contains(message));
+                        }
+                        self.room_history_shared = Some(SharedRoomHistory { shared_by: shared_by.clone(), messages: history.clone() });
+                        //Earlier messages go above everything seen since joining, as the sharer's word for them
+                        if let Ok(mut chat_history_lock) = self.roomchat_comps.chat_history.lock() {
+                            let mut earlier = vec![(
+                                "maclincomms".to_string(),
+                                Text::from(Line::from(format!("Earlier messages, as shared by {}", shared_by).light_cyan())),
+                                "".to_string(),
+                                false,
+                                "".to_string()
+                            )];
+                            for message in history.iter() {
+                                earlier.push((
+                                    format!("{} (via {})", message.username, shared_by),
+                                    Text::from(take_next_lines(message.content.clone())),
+                                    message.time.clone(),
+                                    false,
+                                    "".to_string()
+                                ));
+                            }
+                            chat_history_lock.splice(0..0, earlier);
+                        }
+                        //Passed on in turn to those joining after us
+                        merge_room_history(&mut self.room_history, history);
+                    }
+                },
+
                 Event::IncomingRoomSenderKeyMessageEvent(payload_bytes) => {
                     //Extract Sender Key Encrypted Payload, DH_Pub_Key to form shared root key and Sender_Username at end
                     let encrypted_sender_key_bytes = &payload_bytes[1..81];
@@ -498,6 +626,51 @@ impl MaclincommsApp {
                     }
                 },
 
+                Event::RoomModerationEvent(action, payload) => {
+                    let info = match action {
+                        ModerationAction::KICK => Line::from(vec![payload.bold().light_cyan(), " was kicked from the room".light_magenta()]),
+                        ModerationAction::BAN => Line::from(vec![payload.bold().light_cyan(), " was banned from the room".light_magenta()]),
+                        ModerationAction::MUTE => Line::from(vec![payload.bold().light_cyan(), " was muted".light_magenta()]),
+                        ModerationAction::UNMUTE => Line::from(vec![payload.bold().light_cyan(), " was unmuted".light_magenta()]),
+                        ModerationAction::PROMOTE => Line::from(vec![payload.bold().light_cyan(), " is now a Room Moderator".light_magenta()]),
+                        //Only the owner who rotated it gets the new key
+                        ModerationAction::ROTATE_KEY if !payload.is_empty() => {
+                            self.roomchat_comps.room_key = payload.clone();
+                            Line::from(vec!["NEW ROOM KEY: ".bold().light_magenta(), payload.light_cyan()])
+                        }
+                        ModerationAction::ROTATE_KEY => Line::from("The Room Key was changed".light_magenta()),
+                        ModerationAction::REJECTED => {
+                            let command = payload.bytes().next().and_then(ModerationAction::from_byte).map(|a| a.command()).unwrap_or_default();
+                            Line::from(format!("{} was refused by the server", command).light_red())
+                        }
+                    };
+                    if let Ok(mut chat_history_lock) = self.roomchat_comps.chat_history.lock() {
+                        chat_history_lock.push((
+                            "maclincomms".to_owned(),
+                            Text::from(info),
+                            get_current_time(),
+                            false,
+                            "".to_string()
+                        ));
+                    }
+                    self.roomchat_comps.scroll_state.scroll_to_bottom();
+                },
+
+                Event::RoomOwnershipTransferredEvent(new_owner) => {
+                    self.is_current_room_owner = new_owner==self.username;
+                    self.current_room_owner = new_owner.clone();
+                    if let Ok(mut chat_history_lock) = self.roomchat_comps.chat_history.lock() {
+                        chat_history_lock.push((
+                            "maclincomms".to_owned(),
+                            Text::from(Line::from(vec![new_owner.bold().light_cyan(), " is now the Room Owner".light_magenta()])),
+                            get_current_time(),
+                            false,
+                            "".to_string()
+                        ));
+                    }
+                    self.roomchat_comps.scroll_state.scroll_to_bottom();
+                },
+
                 Event::IncomingRoomMessageAckEvent(ack_type) => {
                     if matches!(ack_type, AckType::ServerAck){
                         let room_keys_data = &mut self.room_keys;
@@ -517,82 +690,27 @@ impl MaclincommsApp {
 
                 Event::IncomingRealtimeNotificationEvent(notification) => {
                     let notification_cloned = notification.clone(); //for second check for accepted notification
+                    //Parsing Message Contents, and the sender's entry in the dms list for the device it came from
+                    let msg_data_res = match notification.n_type {
+                        NotificationType::MESSAGE => serde_json::from_str::<DmMessage>(&notification.content).ok(),
+                        _ => None
+                    };
+                    let msg_contact_res = match &msg_data_res {
+                        Some(msg_data) => load_dm_contact(self, &notification.from, &msg_data.device_id).await,
+                        None => None
+                    };
+                    //A contact's new identity key is checked against their old one before the session starts over
+                    let identity_rotated_res = match notification.n_type {
+                        NotificationType::IDENTITY_ROTATED => Some(accept_identity_rotation(self, &notification.from, &notification.content)),
+                        _ => None
+                    };
                     if let Ok(mut n_history_lock) = self.notifications_comps.notifications_history.lock() {
                         //For Message Notification, parsing its content
                         if matches!(notification.n_type, NotificationType::MESSAGE){
-                            if let Some(keys) = self.dme2ee_data.dms.get_mut(&notification.from){
-                                    let mut decrypted_message = "".to_string();
-                                    //Parsing Message Contents
-                                    let msg_data_res = serde_json::from_str::<DmMessage>(&notification.content);
-                                    if let Ok(msg_data) = msg_data_res{
-                                        //Check if the public key sent with the message is the same as old or not
-                                        // ------> Extracting dh_pub key from [encrpted_msg_content].[dhpub_key]
-                                        let msg_parts: Vec<&str> = msg_data.content.split('.').collect();
-                                        let ciphertext = msg_parts[0];
-                                        let their_dh_pub = msg_parts[1];
-                                        let their_dh_pub_bytes: [u8;32] = general_purpose::STANDARD.decode(their_dh_pub).unwrap().try_into().unwrap();
-                                        //match new and old
-                                        if keys.their_old_dh_pub_key==their_dh_pub_bytes{
-                                            //Get Recv Chainkey
-                                            let recv_chain_key = keys.receiving_chain_key;
-                                            let recv_msg_key = derive_message_key(recv_chain_key);
-                                            //Decrypt Message
-                                            decrypted_message = decrypt_dm_message(recv_msg_key, ciphertext);
-                                        }
-                                        else{
-                                            //Check if receiving first message
-                                            if keys.their_old_dh_pub_key==[0u8;32]{
-                                                keys.their_old_dh_pub_key = their_dh_pub_bytes;
-                                                //Load private key from disk
-                                                let id_keys: DiskPersist<UserIdentityKeys> = DiskPersist::init("persistent-user-identity-keypair").unwrap();
-                                                if let Ok(data_res) = id_keys.read(){
-                                                    match data_res{
-                                                        Some(data) => {
-                                                            let priv_key = data.private_identity_key;
-                                                            let priv_key_bytes: [u8;32] = general_purpose::STANDARD.decode(priv_key).unwrap().try_into().unwrap();
-                                                            let rootkey = generate_shared_rootkey(their_dh_pub_bytes, priv_key_bytes);
-                                                            let receiving_chainkey = generate_receiver_chainkey(rootkey);
-                                                            keys.receiving_chain_key = receiving_chainkey;
-                                                            let recv_mkey = derive_message_key(receiving_chainkey);
-                                                            //Decrypt Message
-                                                            decrypted_message = decrypt_dm_message(recv_mkey, ciphertext);
-                                                            //Generate new dh pair
-                                                            let (public_dh_key, private_dh_key) = generate_dh_keypair();
-                                                            let new_rootkey = generate_shared_rootkey(their_dh_pub_bytes, private_dh_key);
-                                                            let sending_chainkey = generate_sender_chainkey(new_rootkey);
-                                                            //Store new ratcheted keys
-                                                            keys.root_key = new_rootkey;
-                                                            keys.dh_pub_key = public_dh_key;
-                                                            keys.dh_priv_key = private_dh_key;
-                                                            keys.sending_chain_key = sending_chainkey;
-                                                            keys.receiving_chain_key = receiving_chainkey;
-                                                        }
-                                                        None => {}
-                                                    }
-                                                }
-
-                                            }
-                                            else{
-                                                keys.their_old_dh_pub_key = their_dh_pub_bytes;
-                                                let my_dh_priv = keys.dh_priv_key;
-                                                let rootkey = generate_shared_rootkey(their_dh_pub_bytes, my_dh_priv);
-                                                let receiving_chainkey = generate_receiver_chainkey(rootkey);
-                                                keys.receiving_chain_key = receiving_chainkey;
-                                                let recv_mkey = derive_message_key(receiving_chainkey);
-                                                //Decrypt Message
-                                                decrypted_message = decrypt_dm_message(recv_mkey, ciphertext);
-                                                //Generate new dh pair
-                                                let (public_dh_key, private_dh_key) = generate_dh_keypair();
-                                                let new_rootkey = generate_shared_rootkey(their_dh_pub_bytes, private_dh_key);
-                                                let sending_chainkey = generate_sender_chainkey(new_rootkey);
-                                                //Store new ratcheted keys
-                                                keys.root_key = new_rootkey;
-                                                keys.dh_pub_key = public_dh_key;
-                                                keys.dh_priv_key = private_dh_key;
-                                                keys.sending_chain_key = sending_chainkey;
-                                                keys.receiving_chain_key = receiving_chainkey;
-                                            }
-                                        }
+                            if let Some(contact) = msg_contact_res{
+                                    if let Some(msg_data) = msg_data_res{
+                                        // ------> Decrypting the DM envelope with the ratchet of the device it came from, ratcheting and skipping ahead on their chain as needed
+                                        let decrypted_message = self.dme2ee_data.decrypt_from_device(&contact, &msg_data.device_id, &msg_data.content).unwrap_or_default();
                                         //If message was decrypted successfully and is not empty
                                         if !decrypted_message.is_empty(){
                                             //Pushing to chats in data level
@@ -626,7 +744,12 @@ impl MaclincommsApp {
                                     n_type: notification.n_type,
                                     from: notification.from,
                                     to: notification.to,
-                                    content: notification.content,
+                                    //ACCEPTED carries the X3DH handshake along with the message
+                                    content: match notification_cloned.n_type {
+                                        NotificationType::ACCEPTED => AcceptedNotificationContent::parse(&notification.content).message,
+                                        NotificationType::IDENTITY_ROTATED => identity_rotated_res.unwrap_or_default(),
+                                        _ => notification.content
+                                    },
                                     time: notification.time
                                 }
                             );
@@ -643,7 +766,7 @@ impl MaclincommsApp {
                             //Retrieve latest dms list
                             start_getdms_thread(self).await;
                             //Read the latest written data on disk
-                            let persistent_dms_list: DiskPersist<Vec<DmUser_Data>> = DiskPersist::init("persistent-user-dms-list").unwrap();
+                            let persistent_dms_list: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
                             if let Ok(data_res) = persistent_dms_list.read(){
                                 match data_res{
                                     Some(dms_list) => {
@@ -656,21 +779,12 @@ impl MaclincommsApp {
                                             their_pub_key = k;
                                         }
                                         let their_pub_key_bytes: [u8;32] = general_purpose::STANDARD.decode(their_pub_key).unwrap().try_into().unwrap();
-                                        //Generate first DH pair
-                                        let (public_dh_key, private_dh_key) = generate_dh_keypair();
-                                        //Compute root key
-                                        let rootkey = generate_shared_rootkey(their_pub_key_bytes, private_dh_key);
-                                        //Derive sending chain key
-                                        let sending_chainkey = generate_sender_chainkey(rootkey);
-                                        //Initialise first keys with user and store in dme2ee data
-                                        let keys_data = DmDoubleRatchet_Keys{
-                                            root_key: rootkey,
-                                            their_old_dh_pub_key: [0u8;32],
-                                            dh_pub_key: public_dh_key,
-                                            dh_priv_key: private_dh_key,
-                                            sending_chain_key: sending_chainkey,
-                                            receiving_chain_key: [0u8;32],
-                                        };
+                                        //Initialise the DM ratchet from the X3DH handshake they sent, or from their identity key, and store in dme2ee data
+                                        //Only the account device holds the prekeys the handshake was made with
+                                        let keys_data = AcceptedNotificationContent::parse(&notification_cloned.content).x3dh_handshake
+                                            .filter(|_| UserDevice::load_or_create().account_device)
+                                            .and_then(|handshake| accept_x3dh_handshake(their_pub_key_bytes, &handshake))
+                                            .unwrap_or_else(|| DoubleRatchet::new(their_pub_key_bytes));
 
                                         self.dme2ee_data.dms.insert(notification_cloned.from, keys_data);     
                                     }
@@ -687,80 +801,10 @@ impl MaclincommsApp {
                 Event::IncomingDMMessageEvent(msg) => {
                     //Normal Message to be decrypted
                     if msg.is_online_offline_msg==false{
-                        //Extracting Public Key sent
-                        let msg_parts: Vec<&str> = msg.content.split('.').collect();
-                        let ciphertext = msg_parts[0];
-                        let their_dh_pub_key = msg_parts[1];
-                        let their_dh_pub_key_bytes: [u8;32] = general_purpose::STANDARD.decode(their_dh_pub_key).unwrap().try_into().unwrap();
                         let mut decrypted_message = "".to_string();
-                        //Comparing if they DH-RATCHETED and sent a new pub key
-                        if let Some(keys) = self.dme2ee_data.dms.get_mut(&msg.username){
-                            //Same DH for now
-                            if keys.their_old_dh_pub_key==their_dh_pub_key_bytes{
-                                //Get Recv Chainkey
-                                let recv_chain_key = keys.receiving_chain_key;
-                                let recv_msg_key = derive_message_key(recv_chain_key);
-                                //Decrypt Message
-                                decrypted_message = decrypt_dm_message(recv_msg_key, ciphertext);
-                                //Rotate chain key
-                                update_receiving_chainkey(keys);
-                            }
-                            else{
-                                //Check if receiving first message
-                                if keys.their_old_dh_pub_key==[0u8;32]{
-                                    keys.their_old_dh_pub_key = their_dh_pub_key_bytes;
-                                    //Load private key from disk
-                                    let id_keys: DiskPersist<UserIdentityKeys> = DiskPersist::init("persistent-user-identity-keypair").unwrap();
-                                    if let Ok(data_res) = id_keys.read(){
-                                        match data_res{
-                                            Some(data) => {
-                                                let priv_key = data.private_identity_key;
-                                                let priv_key_bytes: [u8;32] = general_purpose::STANDARD.decode(priv_key).unwrap().try_into().unwrap();
-                                                let rootkey = generate_shared_rootkey(their_dh_pub_key_bytes, priv_key_bytes);
-                                                let receiving_chainkey = generate_receiver_chainkey(rootkey);
-                                                keys.receiving_chain_key = receiving_chainkey;
-                                                let recv_mkey = derive_message_key(receiving_chainkey);
-                                                //Decrypt Message
-                                                decrypted_message = decrypt_dm_message(recv_mkey, ciphertext);
-                                                //Rotate chain key
-                                                update_receiving_chainkey(keys);
-                                                //Generate new dh pair
-                                                let (public_dh_key, private_dh_key) = generate_dh_keypair();
-                                                let new_rootkey = generate_shared_rootkey(their_dh_pub_key_bytes, private_dh_key);
-                                                let sending_chainkey = generate_sender_chainkey(new_rootkey);
-                                                //Store new ratcheted keys
-                                                keys.root_key = new_rootkey;
-                                                keys.dh_pub_key = public_dh_key;
-                                                keys.dh_priv_key = private_dh_key;
-                                                keys.sending_chain_key = sending_chainkey;
-                                            }
-                                            None => {}
-                                        }
-                                    }
-
-                                }
-                                else{
-                                    keys.their_old_dh_pub_key = their_dh_pub_key_bytes;
-                                    let my_dh_priv = keys.dh_priv_key;
-                                    let rootkey = generate_shared_rootkey(their_dh_pub_key_bytes, my_dh_priv);
-                                    let receiving_chainkey = generate_receiver_chainkey(rootkey);
-                                    keys.receiving_chain_key = receiving_chainkey;
-                                    let recv_mkey = derive_message_key(receiving_chainkey);
-                                    //Decrypt Message
-                                    decrypted_message = decrypt_dm_message(recv_mkey, ciphertext);
-                                    //Rotate chain key
-                                    update_receiving_chainkey(keys);
-                                    //Generate new dh pair
-                                    let (public_dh_key, private_dh_key) = generate_dh_keypair();
-                                    let new_rootkey = generate_shared_rootkey(their_dh_pub_key_bytes, private_dh_key);
-                                    let sending_chainkey = generate_sender_chainkey(new_rootkey);
-                                    //Store new ratcheted keys
-                                    keys.root_key = new_rootkey;
-                                    keys.dh_pub_key = public_dh_key;
-                                    keys.dh_priv_key = private_dh_key;
-                                    keys.sending_chain_key = sending_chainkey;
-                                }
-                            }
+                        //Parsing the DM envelope (versioned or legacy) and decrypting with the ratchet of the device it came from, ratcheting and skipping ahead on their chain as needed
+                        if let Some(contact) = load_dm_contact(self, &msg.username, &msg.device_id).await{
+                            decrypted_message = self.dme2ee_data.decrypt_from_device(&contact, &msg.device_id, &msg.content).unwrap_or_default();
                         }
                         //If decrypted message is successfully decrypted and is not empty
                         if !decrypted_message.is_empty(){
@@ -814,16 +858,13 @@ impl MaclincommsApp {
                             }
                         }
                         AckType::ReceiverAck => {
-                            if let Some(keys) = self.dme2ee_data.dms.get_mut(&self.dmchat_comps.to_user){
-                                update_sending_chainkey(keys);
-                                if let Ok(mut chat_history_lock) = self.dmchat_comps.chat_history.lock() {
-                                    for message in chat_history_lock.iter_mut().rev(){
-                                        //my sent message
-                                        if message.0==self.username{
-                                            //Update server ack tick
-                                            message.4 = ">>".to_string();
-                                            break;
-                                        }
+                            if let Ok(mut chat_history_lock) = self.dmchat_comps.chat_history.lock() {
+                                for message in chat_history_lock.iter_mut().rev(){
+                                    //my sent message
+                                    if message.0==self.username{
+                                        //Update server ack tick
+                                        message.4 = ">>".to_string();
+                                        break;
                                     }
                                 }
                             }
@@ -887,11 +928,22 @@ impl MaclincommsApp {
                                 }
 
                                 Screens::ROOM_CREATION_SCREEN => {
+                                    //Typing goes to the room name or the topic, settings between them take Left/Right
+                                    let typing_field = match self.roomcreation_textarea.selected_field {
+                                        0 => Some(&mut self.roomcreation_textarea.roomname_ta),
+                                        RoomCreationTextArea::TOPIC_FIELD => Some(&mut self.roomcreation_textarea.topic_ta),
+                                        _ => None
+                                    };
                                     if key_event.code != KeyCode::Esc &&
+                                       key_event.code != KeyCode::Up &&
+                                       key_event.code != KeyCode::Down &&
                                        key_event.code != KeyCode::Enter &&
+                                       typing_field.is_some() &&
                                        (!key_event.modifiers.contains(KeyModifiers::CONTROL)) {
                                         
-                                        self.roomcreation_textarea.roomname_ta.input(key_event);
+                                        if let Some(ta) = typing_field {
+                                            ta.input(key_event);
+                                        }
                                         
                                     } else {
                                         self.handle_key_event(key_event)?;
@@ -978,6 +1030,22 @@ impl MaclincommsApp {
                                         self.handle_key_event(key_event)?;
                                     }
                                 }
+
+                                Screens::LINKED_DEVICES_SCREEN => {
+                                    //Typing goes to the link code field while it is open
+                                    match &mut self.devices_comps.link_code_ta {
+                                        Some(ta) if key_event.code != KeyCode::Esc &&
+                                            key_event.code != KeyCode::Enter &&
+                                            (!key_event.modifiers.contains(KeyModifiers::CONTROL)) => {
+
+                                            ta.input(key_event);
+
+                                        }
+                                        _ => {
+                                            self.handle_key_event(key_event)?;
+                                        }
+                                    }
+                                }
             
                                 _ => {
                                     self.handle_key_event(key_event)?;
@@ -1011,19 +1079,37 @@ impl MaclincommsApp {
                     start_joinroom_task(self).await;
                 },
 
+                Event::LoadRoomDirectoryEvent(page) => {
+                    start_roomdirectory_thread(self, page).await;
+                },
+
+                Event::LoadDevicesEvent => {
+                    start_devices_thread(self).await;
+                },
+
+                Event::RevokeDeviceEvent(device_id) => {
+                    start_revokedevice_thread(self, device_id).await;
+                },
+
+                Event::CreateDeviceLinkEvent => {
+                    start_createdevicelink_thread(self).await;
+                },
+
+                Event::ClaimDeviceLinkEvent(link_code) => {
+                    start_claimdevicelink_thread(self, link_code).await;
+                },
+
+                Event::RotateIdentityKeyEvent => {
+                    start_rotateidentity_thread(self).await;
+                },
+
+                Event::RoomInviteEvent(max_uses, expires_in_minutes, send_to) => {
+                    start_roominvite_task(self, max_uses, expires_in_minutes, send_to).await;
+                },
+
                 Event::RoomChatEvent(room_token) => {
-                    //Load private key from disk
-                    let mut my_priv_key = "".to_string();
-                    let id_keys: DiskPersist<UserIdentityKeys> = DiskPersist::init("persistent-user-identity-keypair").unwrap();
-                    if let Ok(data_res) = id_keys.read(){
-                        match data_res{
-                            Some(data) => {
-                                my_priv_key = data.private_identity_key;
-                            }
-                            None => {}
-                        }
-                    }
-                    let my_priv_key_bytes: [u8;32] = general_purpose::STANDARD.decode(my_priv_key).unwrap().try_into().unwrap();
+                    //Load private key from disk, rooms know members by the account key
+                    let my_priv_key_bytes: [u8;32] = UserIdentityKeys::load_account_private_key().unwrap();
                     self.room_keys.my_idpriv_key = my_priv_key_bytes;
 
                     //Retrieving Room Data and Memeber Keys for BI-directional encrypted transfer of Sender Key
@@ -1078,137 +1164,65 @@ impl MaclincommsApp {
                         }
                     }
 
-                    match self.is_roomchat_joined {
-                        true => {
-                            let outgoing_tx = &self.outgoing_roomchat_msg_tx;
-                            match outgoing_tx {
-                                Some(roomchat_sender) => {
-                                    let leave_tx = self.outgoing_roomchat_msg_tx.clone().unwrap();
-                                    //Sending Leave Message only if user is NOT room owner (cuz when owner leaves, room anyways is deleted)
-                                    if self.is_current_room_owner==false{
-                                        if let Err(e) = leave_tx.send(SocketMessage::Leave(MessageType::ROOM(RoomMessageType::SENDER(RoomSenderMessage{
-                                            username: self.username.clone(),
-                                            content: format!("{} left", self.username.clone()),
-                                            users: Vec::new(),
-                                            whisper_mode: WhisperMode::NONE,
-                                            is_join_leave_msg: true
-                                        }))))
-                                        {
-                                            println!("Couldnt send leave message");
-                                        }
-                                    }
-
-                                    if let Err(e) = roomchat_sender.send(
-                                        SocketMessage::Disconnect(DisconnectType::ROOM)
-                                    ) {
-                                        eprintln!("Failed to send message to WebSocket: {}", e);
-                                    }
-                                    //OTHER ROOM ACTIVITY STARTING
-                                    self.current_screen = Screens::ROOM_CHAT_SCREEN;
-                                    //Initialising for new channel
-                                    self.outgoing_roomchat_msg_tx = None;
-                                    //Another cloned producer that sends user message events in main channel
-                                    let inc_tx = main_events_channel_tx.clone();
-                                    start_roomchat_websocket_task(
-                                        self,
-                                        self.username.clone(), 
-                                        room_token, 
-                                        self.endpoints.room_chat, 
-                                        //chat_history,
-                                        inc_tx
-                                    ).await;
-
-                                    let join_tx = self.outgoing_roomchat_msg_tx.clone().unwrap();
-                                    if let Err(e) = join_tx.send(SocketMessage::Join(MessageType::ROOM(RoomMessageType::SENDER(RoomSenderMessage{
-                                        username: self.username.clone(),
-                                        content: format!("{} joined", self.username.clone()),
-                                        users: Vec::new(),
-                                        whisper_mode: WhisperMode::NONE,
-                                        is_join_leave_msg: true
-                                    }))))
-                                    {
-                                        println!("Couldnt send join message");
-                                    }
+                    //Any room on screen was parked before getting here, its socket stays open
+                    /* NAVIGATING TO ROOM CHAT SCREEN */
+                    self.current_screen = Screens::ROOM_CHAT_SCREEN;
+                    self.is_roomchat_joined = true;
+                    self.roomchat_comps.username = self.username.clone();
+                    //let chat_history = Arc::clone(&self.roomchat_comps.chat_history);
+                    //Another cloned producer that sends user message events in main channel
+                    let inc_tx = main_events_channel_tx.clone();
+                    start_roomchat_websocket_task(
+                        self,
+                        self.username.clone(), 
+                        self.roomchat_comps.room_name.clone(),
+                        room_token, 
+                        self.endpoints.room_chat, 
+                        //chat_history,
+                        inc_tx
+                    ).await; 
+
+                    let join_tx = self.outgoing_roomchat_msg_tx.clone().unwrap();
+                    if let Err(e) = join_tx.send(SocketMessage::Join(MessageType::ROOM(RoomMessageType::SENDER(RoomSenderMessage{
+                        username: self.username.clone(),
+                        content: format!("{} joined", self.username.clone()),
+                        users: Vec::new(),
+                        whisper_mode: WhisperMode::NONE,
+                        is_join_leave_msg: true
+                    }))))
+                    {
+                        println!("Couldnt send join message");
+                    }
 
-                                    //Sending my sender key to every user in bytes
-                                    for (username, encrypted_key) in self.room_keys.my_sender_key_encryptions.clone(){
-                                        if let Some(pub_dh_key) = self.room_keys.my_dh_pub_keys.get(&username){
-                                            if username==self.username{
-                                                continue;
-                                            }
-                                            let skey_descriptor_byte = [0x11].to_vec();
-                                            let username_bytes = username.as_bytes().to_vec();
-                                            let skey_binary_mesg = [skey_descriptor_byte, encrypted_key, pub_dh_key.to_vec(), username_bytes].concat();
-                                            //Sending ENCRYPTED SENDER KEY in bytes
-                                            if let Err(e) = join_tx.send(SocketMessage::RoomSenderKey(skey_binary_mesg))
-                                            {
-                                                println!("Couldnt send sender key message");
-                                            }
-                                        }
-                                    }
-                                }
-                                None => {
-                                    //Later
-                                }
+                    //Sending my sender key to every user in bytes
+                    for (username, encrypted_key) in self.room_keys.my_sender_key_encryptions.clone(){
+                        if let Some(pub_dh_key) = self.room_keys.my_dh_pub_keys.get(&username){
+                            if username==self.username{
+                                continue;
                             }
-                        }
-                        false => {
-                            /* NAVIGATING TO ROOM CHAT SCREEN */
-                            self.current_screen = Screens::ROOM_CHAT_SCREEN;
-                            self.is_roomchat_joined = true;
-                            self.roomchat_comps.username = self.username.clone();
-                            //let chat_history = Arc::clone(&self.roomchat_comps.chat_history);
-                            //Another cloned producer that sends user message events in main channel
-                            let inc_tx = main_events_channel_tx.clone();
-                            start_roomchat_websocket_task(
-                                self,
-                                self.username.clone(), 
-                                room_token, 
-                                self.endpoints.room_chat, 
-                                //chat_history,
-                                inc_tx
-                            ).await; 
-
-                            let join_tx = self.outgoing_roomchat_msg_tx.clone().unwrap();
-                            if let Err(e) = join_tx.send(SocketMessage::Join(MessageType::ROOM(RoomMessageType::SENDER(RoomSenderMessage{
-                                username: self.username.clone(),
-                                content: format!("{} joined", self.username.clone()),
-                                users: Vec::new(),
-                                whisper_mode: WhisperMode::NONE,
-                                is_join_leave_msg: true
-                            }))))
+                            let skey_descriptor_byte = [0x11].to_vec();
+                            let username_bytes = username.as_bytes().to_vec();
+                            let skey_binary_mesg = [skey_descriptor_byte, encrypted_key, pub_dh_key.to_vec(), username_bytes].concat();
+                            //Sending ENCRYPTED SENDER KEY in bytes
+                            if let Err(e) = join_tx.send(SocketMessage::RoomSenderKey(skey_binary_mesg))
                             {
-                                println!("Couldnt send join message");
-                            }
-
-                            //Sending my sender key to every user in bytes
-                            for (username, encrypted_key) in self.room_keys.my_sender_key_encryptions.clone(){
-                                if let Some(pub_dh_key) = self.room_keys.my_dh_pub_keys.get(&username){
-                                    if username==self.username{
-                                        continue;
-                                    }
-                                    let skey_descriptor_byte = [0x11].to_vec();
-                                    let username_bytes = username.as_bytes().to_vec();
-                                    let skey_binary_mesg = [skey_descriptor_byte, encrypted_key, pub_dh_key.to_vec(), username_bytes].concat();
-                                    //Sending ENCRYPTED SENDER KEY in bytes
-                                    if let Err(e) = join_tx.send(SocketMessage::RoomSenderKey(skey_binary_mesg))
-                                    {
-                                        println!("Couldnt send sender key message");
-                                    }
-                                }
+                                println!("Couldnt send sender key message");
                             }
                         }
                     }
                 },
 
                 Event::ExitRoomChatEvent => {
+                    //Done with the room, dropped from the background rooms if it was there
+                    self.is_roomchat_joined = false;
+                    self.outgoing_roomchat_msg_tx = None;
                     self.current_screen = Screens::ROOM_JOIN_SCREEN;
                     let status_block = Block::default()
                         .borders(Borders::ALL)
                         .border_type(ratatui::widgets::BorderType::default())
                         .border_style(Style::default().fg(ratatui::style::Color::LightRed));
                 
-                    self.joinroom_textarea.status_block = Paragraph::new("Room closed".light_red())
+                    self.joinroom_textarea.status_block = Paragraph::new(format!("Room {} closed", self.roomchat_comps.room_name).light_red())
                         .alignment(ratatui::layout::Alignment::Center)
                         .block(status_block);
 
@@ -1224,6 +1238,14 @@ impl MaclincommsApp {
                     start_acceptuser_thread(self, user).await;
                 },
 
+                Event::RespondJoinRequestEvent(room_name, user, status) => {
+                    start_respondjoinrequest_thread(self, room_name, user, status).await;
+                },
+
+                Event::RoomJoinApprovedEvent(room_name, room_token) => {
+                    enter_joined_room(self, room_name, room_token).await;
+                },
+
                 Event::DmChatEvent(target_user) => {
                     match self.is_dmchat_joined {
                         true => {
@@ -1234,7 +1256,9 @@ impl MaclincommsApp {
                                     if let Err(e) = leave_tx.send(SocketMessage::Leave(MessageType::DM(DmMessage{
                                         username: self.username.clone(),
                                         content: format!("{} went offline", self.username.clone()),
-                                        is_online_offline_msg: true
+                                        is_online_offline_msg: true,
+                                        device_contents: HashMap::new(),
+                                        device_id: "".to_string()
                                     })))
                                     {
                                         println!("Couldnt send offline message");
@@ -1262,7 +1286,7 @@ impl MaclincommsApp {
                                     }
                                     //Getting last state of keys and saving it to persistent disk
                                     let data = &self.dme2ee_data;
-                                    let disk: DiskPersist<DmE2EEncryption_Data> = DiskPersist::init("persistent-dms-e2e-keys").unwrap();
+                                    let disk: SealedPersist<DmE2EEncryption_Data> = SealedPersist::init("persistent-dms-e2e-keys").unwrap();
                                     disk.write(data).unwrap();
 
                                     //Disconnecting from web socket
@@ -1317,7 +1341,9 @@ impl MaclincommsApp {
                                             if let Err(e) = tx.send(SocketMessage::Join(MessageType::DM(DmMessage{
                                                 username: self.username.clone(),
                                                 content: format!("{} is online", self.username.clone()),
-                                                is_online_offline_msg: true
+                                                is_online_offline_msg: true,
+                                                device_contents: HashMap::new(),
+                                                device_id: "".to_string()
                                             })))
                                             {
                                                 println!("Couldnt send online message");
@@ -1403,7 +1429,9 @@ impl MaclincommsApp {
                                     if let Err(e) = tx.send(SocketMessage::Join(MessageType::DM(DmMessage{
                                         username: self.username.clone(),
                                         content: format!("{} is online", self.username.clone()),
-                                        is_online_offline_msg: true
+                                        is_online_offline_msg: true,
+                                        device_contents: HashMap::new(),
+                                        device_id: "".to_string()
                                     })))
                                     {
                                         println!("Couldnt send online message");
@@ -1466,7 +1494,7 @@ impl MaclincommsApp {
                     }
                     //Getting last state of keys and saving it to persistent disk
                     let data = &self.dme2ee_data;
-                    let disk: DiskPersist<DmE2EEncryption_Data> = DiskPersist::init("persistent-dms-e2e-keys").unwrap();
+                    let disk: SealedPersist<DmE2EEncryption_Data> = SealedPersist::init("persistent-dms-e2e-keys").unwrap();
                     disk.write(data).unwrap();
 
                     //Back to DMS list screen and a alert 
@@ -1494,7 +1522,7 @@ impl MaclincommsApp {
                     start_getdms_thread(self).await;
                     //Get latest user message from Data Warehouse and append to list
                     let warehouse_data = &mut self.dmchats_warehouse.dms_data;
-                    let new_dms_list: DiskPersist<Vec<DmUser_Data>> = DiskPersist::init("persistent-user-dms-list").unwrap();
+                    let new_dms_list: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
                     let new_dms = match new_dms_list.read(){
                         Ok(list) => {
                             if let Some(data) = list{
@@ -1541,16 +1569,22 @@ impl MaclincommsApp {
                             warehouse_data.insert(user.to_string(), Vec::new());
                         }
                     }
-                    //Changing to normal
-                    let text = "Press [Enter] to DM Users".to_string();
-                    let status_block = Block::default()
-                            .borders(Borders::ALL)
-                            .border_type(ratatui::widgets::BorderType::default())
-                            .border_style(Style::default().fg(ratatui::style::Color::LightCyan));
-                                                                
-                    self.dmuser_comps.action_status_block = Paragraph::new(text.light_cyan())
-                            .alignment(ratatui::layout::Alignment::Center)
-                            .block(status_block);           
+                    //Changing to normal, unless a verified contact's identity key changed
+                    let key_changed_users = self.dmuser_comps.key_changed_users();
+                    if !key_changed_users.is_empty(){
+                        self.dmuser_comps.action_status_block = DmUserComponents::get_key_changed_block(&key_changed_users);
+                    }
+                    else{
+                        let text = "Press [Enter] to DM Users".to_string();
+                        let status_block = Block::default()
+                                .borders(Borders::ALL)
+                                .border_type(ratatui::widgets::BorderType::default())
+                                .border_style(Style::default().fg(ratatui::style::Color::LightCyan));
+                                                                    
+                        self.dmuser_comps.action_status_block = Paragraph::new(text.light_cyan())
+                                .alignment(ratatui::layout::Alignment::Center)
+                                .block(status_block);           
+                    }
                 }
 
                 Event::BlockEvent => {
@@ -1563,6 +1597,10 @@ impl MaclincommsApp {
 
                 _ => {}
             }
+
+            if let Some(background) = background_room {
+                self.finish_background_room_event(background);
+            }
             
 
             for e in self.login_menu.drain_events() {
@@ -1626,11 +1664,36 @@ impl MaclincommsApp {
                                 true => {
                                     self.current_screen = Screens::ROOM_CHAT_SCREEN;
                                 }
+                                false if !self.active_rooms.is_empty() => {
+                                    self.current_screen = Screens::ROOM_LIST_SCREEN;
+                                }
                                 false => {
                                     self.current_screen = Screens::ROOM_JOIN_SCREEN;
                                 }
                             }
                         }
+                        ChatOptionsAction::ROOM_LIST => {
+                            self.roomlist_comps.current_index = 0;
+                            self.current_screen = Screens::ROOM_LIST_SCREEN;
+                        }
+                        ChatOptionsAction::ROOM_DIRECTORY => {
+                            let loaddirectory_tx = self.network_event_tx.clone();
+                            if let Err(_) = loaddirectory_tx.send(Event::LoadRoomDirectoryEvent(0)){
+                                eprintln!("Coudlnt send load room directory event");
+                            }
+
+                            self.current_screen = Screens::ROOM_DIRECTORY_SCREEN;
+                            //Changing Action Bar to Loading
+                            let text = format!("Loading listed rooms");
+                            let status_block = Block::default()
+                                    .borders(Borders::ALL)
+                                    .border_type(ratatui::widgets::BorderType::default())
+                                    .border_style(Style::default().fg(ratatui::style::Color::LightYellow));
+
+                            self.roomdirectory_comps.action_status_block = Paragraph::new(text.light_yellow())
+                                    .alignment(ratatui::layout::Alignment::Center)
+                                    .block(status_block);
+                        }
                         ChatOptionsAction::ADD_USER => {
                             self.current_screen = Screens::ADD_USER_SCREEN;
                         }
@@ -1669,6 +1732,24 @@ impl MaclincommsApp {
                             self.current_screen = Screens::NOTIFICATIONS_SCREEN;
                             
                         }
+                        ChatOptionsAction::LINKED_DEVICES => {
+                            let loaddevices_tx = self.network_event_tx.clone();
+                            if let Err(_) = loaddevices_tx.send(Event::LoadDevicesEvent){
+                                eprintln!("Coudlnt send load devices event");
+                            }
+
+                            self.current_screen = Screens::LINKED_DEVICES_SCREEN;
+                            //Changing Action Bar to Loading
+                            let text = format!("Loading your devices");
+                            let status_block = Block::default()
+                                    .borders(Borders::ALL)
+                                    .border_type(ratatui::widgets::BorderType::default())
+                                    .border_style(Style::default().fg(ratatui::style::Color::LightYellow));
+
+                            self.devices_comps.action_status_block = Paragraph::new(text.light_yellow())
+                                    .alignment(ratatui::layout::Alignment::Center)
+                                    .block(status_block);
+                        }
                     },
                 }
                 self.chatoptions_menu.reset();
@@ -1808,6 +1889,12 @@ impl MaclincommsApp {
             Screens::DM_CHAT_SCREEN => dmchat_panel::draw_dmchat_panel(frame, panel_chunks[1], &mut self.dmchat_comps),
             Screens::BLOCK_USER_SCREEN => blockuser_panel::draw_blockunblockuser_panel(frame, panel_chunks[1], &mut self.blockunblock_textarea),
             Screens::NOTIFICATIONS_SCREEN => notifications_panel::draw_notifications_panel(frame, panel_chunks[1], &mut self.notifications_comps),
+            Screens::ROOM_LIST_SCREEN => {
+                let rooms = self.room_list_entries();
+                roomlist_panel::draw_roomlist_panel(frame, panel_chunks[1], &mut self.roomlist_comps, rooms)
+            }
+            Screens::ROOM_DIRECTORY_SCREEN => roomdirectory_panel::draw_roomdirectory_panel(frame, panel_chunks[1], &mut self.roomdirectory_comps),
+            Screens::LINKED_DEVICES_SCREEN => devices_panel::draw_devices_panel(frame, panel_chunks[1], &mut self.devices_comps),
         }
         
 
@@ -1842,33 +1929,8 @@ impl MaclincommsApp {
                 }
                 None => {}
             }
-            //DISCONNECTING FROM ROOM CHAT WEB SOCKET
-            let room_outgoing_tx = &self.outgoing_roomchat_msg_tx;
-            match room_outgoing_tx {
-                Some(roomchat_sender) => {
-                    let leave_tx = self.outgoing_roomchat_msg_tx.clone().unwrap();
-                    //Sending Leave Message only if user is NOT room owner (cuz when owner leaves, room anyways is deleted)
-                    if self.is_current_room_owner==false{
-                        if let Err(e) = leave_tx.send(SocketMessage::Leave(MessageType::ROOM(RoomMessageType::SENDER(RoomSenderMessage{
-                            username: self.username.clone(),
-                            content: format!("{} left", self.username.clone()),
-                            users: Vec::new(),
-                            whisper_mode: WhisperMode::NONE,
-                            is_join_leave_msg: true
-                        }))))
-                        {
-                            println!("Couldnt send leave message");
-                        }
-                    }
-
-                    if let Err(e) = roomchat_sender.send(
-                        SocketMessage::Disconnect(DisconnectType::ROOM)
-                    ) {
-                        eprintln!("Failed to send message to WebSocket: {}", e);
-                    }
-                }
-                None => {}
-            }
+            //DISCONNECTING FROM EVERY ROOM CHAT WEB SOCKET
+            self.leave_all_rooms();
             //UPDATING DMS UI CHATS HISTORY TO DATA WAREHOUSE
             if let Ok(chat_history_lock) = self.dmchat_comps.chat_history.lock() {
                 //Getting mutable reference of data level dm chats of previous user to be updated
@@ -1891,7 +1953,7 @@ impl MaclincommsApp {
             }
             //Getting last state of keys and saving it to persistent disk
             let data = &self.dme2ee_data;
-            let disk: DiskPersist<DmE2EEncryption_Data> = DiskPersist::init("persistent-dms-e2e-keys").unwrap();
+            let disk: SealedPersist<DmE2EEncryption_Data> = SealedPersist::init("persistent-dms-e2e-keys").unwrap();
             disk.write(data).unwrap();
             //DISCONNECTING FROM DM CHAT WEB SOCKET
             let dm_outgoing_tx = &self.outgoing_dmchat_msg_tx;
@@ -1901,7 +1963,9 @@ impl MaclincommsApp {
                     if let Err(e) = leave_tx.send(SocketMessage::Leave(MessageType::DM(DmMessage{
                         username: self.username.clone(),
                         content: format!("{} went offline", self.username.clone()),
-                        is_online_offline_msg: true
+                        is_online_offline_msg: true,
+                        device_contents: HashMap::new(),
+                        device_id: "".to_string()
                     })))
                     {
                         println!("Couldnt send offline message");
@@ -1915,6 +1979,10 @@ impl MaclincommsApp {
                 }
                 None => {}
             }
+            //SAVING DECRYPTED CHATS LOCALLY FOR EXPORT
+            if !self.access_token.is_empty() {
+                self.dmchats_warehouse.save_local();
+            }
             //ENCRYPTING DM SESSION CHATS
             encrypt_dm_chats_session(&mut self.dmchats_warehouse.dms_session_key, &mut self.dmchats_warehouse.dms_data);
             //UPLOADING ENCRYPTED SESSION CHATS TO DB
@@ -1956,7 +2024,10 @@ impl MaclincommsApp {
             Screens::DM_USER_SCREEN => handle_dm_user_screen_inputs(self, key_event),
             Screens::DM_CHAT_SCREEN => handle_dm_chat_screen_inputs(self, key_event),
             Screens::BLOCK_USER_SCREEN => handle_block_user_screen_inputs(self, key_event),
-            Screens::NOTIFICATIONS_SCREEN => handle_notifications_screen_inputs(self, key_event)
+            Screens::NOTIFICATIONS_SCREEN => handle_notifications_screen_inputs(self, key_event),
+            Screens::ROOM_LIST_SCREEN => handle_room_list_screen_inputs(self, key_event),
+            Screens::ROOM_DIRECTORY_SCREEN => handle_room_directory_screen_inputs(self, key_event),
+            Screens::LINKED_DEVICES_SCREEN => handle_linked_devices_screen_inputs(self, key_event)
         }
 
         Ok(())
@@ -10,11 +10,14 @@ pub enum ChatOptionsAction {
     CREATE_ROOM,
     JOIN_ROOM,
     CURRENT_ROOM,
+    ROOM_LIST,
+    ROOM_DIRECTORY,
     ADD_USER,
     DM_USER,
     CURRENT_DM,
     BLOCK_USER,
-    NOTIFICATIONS
+    NOTIFICATIONS,
+    LINKED_DEVICES
 }
 
 pub fn draw_chatoptions_panel(
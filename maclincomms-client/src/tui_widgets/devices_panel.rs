@@ -0,0 +1,211 @@
+use ratatui::{layout::{Alignment, Constraint, Direction, Layout, Margin, Rect, Size}, style::{Color, Modifier, Style, Stylize}, text::Line, widgets::{Block, Borders, Paragraph, Wrap}, Frame};
+use tui_scrollview::{ScrollView, ScrollViewState, ScrollbarVisibility};
+use tui_textarea::TextArea;
+
+use crate::{format_local_timestamp, get_current_date, user_model::LinkedDevice};
+
+
+
+pub fn draw_devices_panel(
+    frame: &mut Frame,
+    area: Rect,
+    devicescomps: &mut DevicesComponents
+) {
+
+    let devicespanel_block = Block::default()
+            .title("Linked Devices")
+            .title_alignment(Alignment::Center)
+            .title_top(Line::from(get_current_date()).right_aligned())
+            .title_top(Line::from("[Esc]Go to Options Menu").left_aligned().on_black().white())
+            .title_bottom(Line::from("[Up/Down]Navigate between devices  [R]Revoke device  [L]Link a new device  [I]Enter a link code  [K]Rotate identity key").centered().on_black().white())
+            .title_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Cyan));
+
+    //Room for the link code field while one is being entered
+    let link_code_height = match devicescomps.link_code_ta {
+        Some(_) => 3,
+        None => 0
+    };
+
+    let [action_status_area, link_code_area, deviceslist_area] = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(link_code_height),
+                Constraint::Fill(1)
+            ]
+        ).areas(area);
+
+    let [_, devices_chunk, __] = Layout::default()
+        .direction(Direction::Horizontal)
+        .margin(2)
+        .constraints(
+            [
+                Constraint::Percentage(10),
+                Constraint::Percentage(80),
+                Constraint::Percentage(10),
+            ]
+        ).areas(deviceslist_area);
+
+    let devices = &devicescomps.devices;
+
+    let scroll_height: u16 = (6*devices.len()) as u16;
+
+    let mut devices_scroll_view = ScrollView::new(Size::new(
+        devices_chunk.width,
+        scroll_height
+    ))
+    .horizontal_scrollbar_visibility(ScrollbarVisibility::Never);
+
+    let devices_area = devices_scroll_view.area().inner(Margin{
+        horizontal: 2,
+        vertical: 0
+    });
+
+    //Declaring device block size and position
+    let device_block_width = devices_area.width;
+    let device_block_height = 4;
+    let device_block_x = devices_area.x;
+    let mut device_block_y = devices_area.y;
+
+    for (index, device) in devices.iter().enumerate() {
+
+        let is_current_device = device.device_id==devicescomps.current_device_id;
+
+        let device_title = match is_current_device {
+            true => Line::from(vec![device.device_name.clone().bold().light_magenta(), " (This device)".light_green()]),
+            false => Line::from(device.device_name.clone().bold().light_magenta())
+        };
+
+        //Short id, enough to tell devices with the same name apart
+        let short_id: String = device.device_id.chars().take(8).collect();
+        let device_info = Line::from(format!("Id {}", short_id)).right_aligned().light_magenta();
+
+        let added_at = Line::from(format!("Linked at {}", format_local_timestamp(device.added_at)).gray());
+
+        //Formatting as per selection
+        let device_block = if devicescomps.current_index == index {
+            let action = match is_current_device {
+                true => "".light_green(),
+                false => "[R]Revoke ".light_red().bold()
+            };
+            Block::default()
+                .title(device_title)
+                .title_alignment(Alignment::Left)
+                .title_top(Line::from(action).alignment(Alignment::Right))
+                .title_bottom(device_info)
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::Thick)
+                .border_style(Style::default().fg(ratatui::style::Color::LightGreen))
+        } else {
+            Block::default()
+                .title(device_title)
+                .title_alignment(Alignment::Left)
+                .title_bottom(device_info)
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::Rounded)
+                .border_style(Style::default().fg(ratatui::style::Color::LightBlue))
+        };
+
+        let device_entry = Paragraph::new(added_at)
+                .alignment(ratatui::layout::Alignment::Left)
+                .wrap(Wrap { trim: true })
+                .block(device_block);
+
+        let device_block_area = Rect::new(
+            device_block_x,
+            device_block_y,
+            device_block_width,
+            device_block_height
+        );
+
+        device_block_y += device_block_height+2;
+
+        devices_scroll_view.render_widget(device_entry, device_block_area);
+    }
+
+    frame.render_widget(devicespanel_block, area);
+
+    frame.render_widget(&devicescomps.action_status_block,
+        Rect::new(
+             action_status_area.x + (action_status_area.width/4),
+             action_status_area.y,
+             action_status_area.width - (action_status_area.width/2),
+             action_status_area.height
+    ));
+
+    if let Some(link_code_ta) = &devicescomps.link_code_ta {
+        frame.render_widget(link_code_ta,
+            Rect::new(
+                 link_code_area.x + (link_code_area.width/4),
+                 link_code_area.y,
+                 link_code_area.width - (link_code_area.width/2),
+                 link_code_area.height
+        ));
+    }
+
+    frame.render_stateful_widget(devices_scroll_view, devices_chunk, &mut devicescomps.scroll_state);
+
+}
+
+
+
+pub struct DevicesComponents {
+    pub scroll_state: ScrollViewState,
+    pub devices: Vec<LinkedDevice>,
+    pub current_device_id: String,
+    pub current_index: usize,
+    pub action_status_block: Paragraph<'static>,
+    pub link_code_ta: Option<TextArea<'static>>, //Some while a link code from another device is being entered
+    pub rotate_identity_armed: bool //[K] pressed once, the second press rotates
+}
+
+impl DevicesComponents {
+
+    pub fn new() -> Self {
+        Self {
+            scroll_state: ScrollViewState::default(),
+            devices: Vec::new(),
+            current_device_id: "".to_string(),
+            current_index: 0,
+            action_status_block: DevicesComponents::get_action_status_block(),
+            link_code_ta: None,
+            rotate_identity_armed: false
+        }
+    }
+
+    pub fn get_action_status_block() -> Paragraph<'static> {
+
+        let text = "Devices signed in to your account".to_string();
+
+        let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(ratatui::style::Color::LightCyan));
+
+        let status = Paragraph::new(text.light_cyan())
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+
+        status
+    }
+
+    pub fn get_link_code_textarea() -> TextArea<'static> {
+        let mut ta = TextArea::default();
+        ta.set_cursor_line_style(Style::default());
+        ta.set_placeholder_text("Code shown on your other device, [Enter]Link [Esc]Cancel");
+        ta.set_style(Style::default().fg(Color::White));
+        ta.set_block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Color::Magenta)
+                .title("Link code"),
+        );
+
+        ta
+    }
+}
@@ -1,8 +1,8 @@
-use ratatui::{layout::{Alignment, Constraint, Direction, Layout, Margin, Rect, Size}, style::{Color, Modifier, Style, Stylize}, text::Line, widgets::{Block, Borders, Paragraph}, Frame};
+use ratatui::{layout::{Alignment, Constraint, Direction, Layout, Margin, Rect, Size}, style::{Color, Modifier, Style, Stylize}, text::{Line, Text}, widgets::{Block, Borders, Clear, Paragraph, Wrap}, Frame};
 use tui_scrollview::{ScrollView, ScrollViewState, ScrollbarVisibility};
-use std::sync::{Arc, Mutex};
+use std::{collections::HashMap, sync::{Arc, Mutex}};
 
-use crate::{get_current_date, user_model::{DmsListData, NotificationData, NotificationType}};
+use crate::{crypto::safety_number::format_safety_number, get_current_date, user_model::{DmsListData, NotificationData, NotificationType, VerificationStatus}};
 
 
 
@@ -17,7 +17,7 @@ pub fn draw_dmuser_panel(
             .title_alignment(Alignment::Center)
             .title_top(Line::from(get_current_date()).right_aligned())
             .title_top(Line::from("[Esc]Go to Options Menu").left_aligned().on_black().white())
-            .title_bottom(Line::from("[Up/Down]Navigate between dms").centered().on_black().white())
+            .title_bottom(Line::from("[Up/Down]Navigate between dms  [v]Verify safety number").centered().on_black().white())
             .title_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
             .borders(Borders::ALL)
             .border_type(ratatui::widgets::BorderType::Rounded)
@@ -85,10 +85,18 @@ pub fn draw_dmuser_panel(
         
         let dm_time = dm.time.clone();
 
+        //Verification badge next to the username
+        let badge = match dmusercomps.verification_status.get(&dm.with_user) {
+            Some(VerificationStatus::VERIFIED) => " ✔ verified ".light_green(),
+            Some(VerificationStatus::KEY_CHANGED) => " ⚠ IDENTITY KEY CHANGED ".white().on_red().bold(),
+            _ => "".into()
+        };
+        let dm_title = Line::from(vec![with_user.bold().light_magenta(), " ".into(), badge]);
+
         //Formatting as per different 
         let dm_block = if dmusercomps.current_index == index {
             Block::default()
-                .title(with_user.bold().light_magenta())
+                .title(dm_title)
                 .title_alignment(Alignment::Left)
                 .title_top(Line::from("[Enter]DM User ".light_green().bold()).alignment(Alignment::Right))
                 .title_bottom(Line::from(dm_time).right_aligned().light_magenta())
@@ -97,7 +105,7 @@ pub fn draw_dmuser_panel(
                 .border_style(Style::default().fg(ratatui::style::Color::LightGreen))
         } else {
             Block::default()
-                .title(with_user.bold().light_magenta())
+                .title(dm_title)
                 .title_alignment(Alignment::Left)
                 .title_bottom(Line::from(dm_time).right_aligned().light_magenta())
                 .borders(Borders::ALL)
@@ -138,6 +146,82 @@ pub fn draw_dmuser_panel(
 
     frame.render_stateful_widget(dms_scroll_view, dms_chunk, &mut dmusercomps.scroll_state);
 
+    if let Some(view) = &dmusercomps.safety_number_view {
+        draw_safety_number_view(frame, dmslist_area, view);
+    }
+
+}
+
+
+fn draw_safety_number_view(
+    frame: &mut Frame,
+    area: Rect,
+    view: &SafetyNumberView
+) {
+
+    let [_, popup_area, _] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Fill(1),
+                Constraint::Length(16),
+                Constraint::Fill(1)
+            ]
+        ).areas(area);
+
+    let [_, popup_area, _] = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(15),
+                Constraint::Percentage(70),
+                Constraint::Percentage(15)
+            ]
+        ).areas(popup_area);
+
+    let border_color = match view.status {
+        VerificationStatus::VERIFIED => Color::LightGreen,
+        VerificationStatus::KEY_CHANGED => Color::LightRed,
+        VerificationStatus::UNVERIFIED => Color::LightYellow
+    };
+
+    let popup_block = Block::default()
+        .title(format!(" Safety number with {} ", view.with_user))
+        .title_alignment(Alignment::Center)
+        .title_bottom(Line::from("[v]Mark as verified  [u]Unverify  [Esc]Close").centered().on_black().white())
+        .title_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Thick)
+        .border_style(Style::default().fg(border_color));
+
+    let mut lines = vec![Line::from("")];
+    for digits_line in format_safety_number(&view.safety_number) {
+        lines.push(Line::from(digits_line.bold().light_cyan()).centered());
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(format!("Compare this number with {} in person or over a call.", view.with_user).gray()).centered());
+    lines.push(Line::from("If it matches on both screens, no one is intercepting your DMs.".gray()).centered());
+    lines.push(Line::from(""));
+
+    match view.status {
+        VerificationStatus::VERIFIED => {
+            lines.push(Line::from("✔ Verified".light_green().bold()).centered());
+        }
+        VerificationStatus::UNVERIFIED => {
+            lines.push(Line::from("Not verified".light_yellow().bold()).centered());
+        }
+        VerificationStatus::KEY_CHANGED => {
+            lines.push(Line::from(format!("⚠ {}'s identity key CHANGED since you verified it ⚠", view.with_user).white().on_red().bold()).centered());
+            lines.push(Line::from("Don't trust this DM until you compare the new number".light_red().bold()).centered());
+        }
+    }
+
+    let popup = Paragraph::new(Text::from(lines))
+        .wrap(Wrap { trim: true })
+        .block(popup_block);
+
+    frame.render_widget(Clear, popup_area);
+    frame.render_widget(popup, popup_area);
 }
 
 
@@ -149,7 +233,17 @@ pub struct DmUserComponents {
     pub scroll_state: ScrollViewState,
     pub dms_list: Arc<Mutex<Vec<DmsListData>>>,
     pub current_index: i32,
-    pub action_status_block: Paragraph<'static> 
+    pub action_status_block: Paragraph<'static>,
+    pub verification_status: HashMap<String, VerificationStatus>,
+    pub safety_number_view: Option<SafetyNumberView>
+}
+
+#[derive(Debug, Clone)]
+pub struct SafetyNumberView {
+    pub with_user: String,
+    pub public_identity_key: String, //Their key the safety number was computed with
+    pub safety_number: String,
+    pub status: VerificationStatus
 }
 
 impl DmUserComponents {
@@ -159,10 +253,37 @@ impl DmUserComponents {
             scroll_state: ScrollViewState::default(),
             dms_list: Arc::new(Mutex::new(Vec::new())),
             current_index: 0,
-            action_status_block: Self::get_action_status_block()
+            action_status_block: Self::get_action_status_block(),
+            verification_status: HashMap::new(),
+            safety_number_view: None
         }
     }
 
+    pub fn key_changed_users(&self) -> Vec<String> {
+        let mut users: Vec<String> = self.verification_status.iter()
+            .filter(|(_, status)| **status==VerificationStatus::KEY_CHANGED)
+            .map(|(username, _)| username.clone())
+            .collect();
+        users.sort();
+        users
+    }
+
+    pub fn get_key_changed_block(users: &[String]) -> Paragraph<'static> {
+
+        let text = format!("⚠ Identity key of {} CHANGED! Press [v] to check the safety number ⚠", users.join(", "));
+
+        let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Thick)
+        .border_style(Style::default().fg(ratatui::style::Color::LightRed));
+
+        let status = Paragraph::new(text.white().on_red().bold())
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+
+        status
+    }
+
     pub fn get_action_status_block() -> Paragraph<'static> {
 
         let text = "Press [Enter] to DM Users".to_string();
@@ -49,7 +49,7 @@ impl JoinRoomTextArea {
     pub fn get_roomkey_textarea() -> TextArea<'static> {
         let mut ta = TextArea::default();
         ta.set_cursor_line_style(Style::default());
-        ta.set_placeholder_text("Enter the room key");
+        ta.set_placeholder_text("Enter the room key or an invite");
         ta.set_style(Style::default().fg(Color::White));
         ta.set_block(
             Block::default()
@@ -18,4 +18,7 @@ pub mod adduser_textarea;
 pub mod splash_screen;
 pub mod notifications_panel;
 pub mod dmchat_panel;
-pub mod dmuser_panel;
\ No newline at end of file
+pub mod dmuser_panel;
+pub mod roomlist_panel;
+pub mod roomdirectory_panel;
+pub mod devices_panel;
\ No newline at end of file
@@ -2,7 +2,7 @@ use ratatui::{layout::{Alignment, Constraint, Direction, Layout, Margin, Rect, S
 use tui_scrollview::{ScrollView, ScrollViewState, ScrollbarVisibility};
 use std::sync::{Arc, Mutex};
 
-use crate::{get_current_date, user_model::{NotificationData, NotificationType}};
+use crate::{format_local_timestamp, get_current_date, user_model::{NotificationData, NotificationType, RoomInviteNotificationContent, RoomJoinApprovedNotificationContent}};
 
 
 
@@ -208,6 +208,180 @@ pub fn draw_notifications_panel(
                             .block(n_block);
 
 
+                        let n_block_area = Rect::new(
+                            n_block_x, 
+                            n_block_y, 
+                            n_block_width, 
+                            n_block_height
+                        );
+
+                        n_block_y += n_block_height+2;
+
+                        n_scroll_view.render_widget(notif, n_block_area);
+                    }
+
+                    NotificationType::ROOM_INVITE => {
+
+                        let n_block = if notificationscomps.current_index == index {
+                            Block::default()
+                                .title(from_user.bold().light_magenta())
+                                .title_alignment(Alignment::Left)
+                                .title_top(Line::from(vec!["[Enter]Join Room ".light_green().bold(), " [I]Ignore".light_yellow().bold()]).alignment(Alignment::Right))
+                                .title_bottom(Line::from(n_time).right_aligned().light_magenta())
+                                .borders(Borders::ALL)
+                                .border_type(ratatui::widgets::BorderType::Thick)
+                                .border_style(Style::default().fg(ratatui::style::Color::LightGreen))
+                        } else {
+                            Block::default()
+                                .title(from_user.bold().light_magenta())
+                                .title_alignment(Alignment::Left)
+                                .title_bottom(Line::from(n_time).right_aligned().light_magenta())
+                                .borders(Borders::ALL)
+                                .border_type(ratatui::widgets::BorderType::Rounded)
+                                .border_style(Style::default().fg(ratatui::style::Color::LightBlue))
+                        };
+
+                        let n_invite = match RoomInviteNotificationContent::parse(&notification.content) {
+                            Some(invite) => format!("Invited you to {}, valid until {}", invite.room_name, format_local_timestamp(invite.expires_at)),
+                            None => n_content
+                        };
+
+                        let notif = Paragraph::new(vec![
+                                Line::from("ROOM INVITE").bold().light_cyan(),
+                                Line::from(n_invite).white()
+                            ])
+                            .alignment(ratatui::layout::Alignment::Left)
+                            .block(n_block);
+
+
+                        let n_block_area = Rect::new(
+                            n_block_x, 
+                            n_block_y, 
+                            n_block_width, 
+                            n_block_height
+                        );
+
+                        n_block_y += n_block_height+2;
+
+                        n_scroll_view.render_widget(notif, n_block_area);
+                    }
+
+                    NotificationType::ROOM_JOIN_REQUEST => {
+
+                        let n_block = if notificationscomps.current_index == index {
+                            Block::default()
+                                .title(from_user.bold().light_magenta())
+                                .title_alignment(Alignment::Left)
+                                .title_top(Line::from(vec!["[Enter]Approve ".light_green().bold(), " [D]Deny ".light_red().bold(), " [I]Ignore".light_yellow().bold()]).alignment(Alignment::Right))
+                                .title_bottom(Line::from(n_time).right_aligned().light_magenta())
+                                .borders(Borders::ALL)
+                                .border_type(ratatui::widgets::BorderType::Thick)
+                                .border_style(Style::default().fg(ratatui::style::Color::LightGreen))
+                        } else {
+                            Block::default()
+                                .title(from_user.bold().light_magenta())
+                                .title_alignment(Alignment::Left)
+                                .title_bottom(Line::from(n_time).right_aligned().light_magenta())
+                                .borders(Borders::ALL)
+                                .border_type(ratatui::widgets::BorderType::Rounded)
+                                .border_style(Style::default().fg(ratatui::style::Color::LightBlue))
+                        };
+
+                        let notif = Paragraph::new(vec![
+                                Line::from("WANTS TO JOIN").bold().light_cyan(),
+                                Line::from(format!("Knocked on {}", n_content)).white()
+                            ])
+                            .alignment(ratatui::layout::Alignment::Left)
+                            .block(n_block);
+
+
+                        let n_block_area = Rect::new(
+                            n_block_x, 
+                            n_block_y, 
+                            n_block_width, 
+                            n_block_height
+                        );
+
+                        n_block_y += n_block_height+2;
+
+                        n_scroll_view.render_widget(notif, n_block_area);
+                    }
+
+                    NotificationType::ROOM_JOIN_APPROVED => {
+
+                        let n_block = if notificationscomps.current_index == index {
+                            Block::default()
+                                .title(from_user.bold().light_magenta())
+                                .title_alignment(Alignment::Left)
+                                .title_top(Line::from(vec!["[Enter]Join Room ".light_green().bold(), " [I]Ignore".light_yellow().bold()]).alignment(Alignment::Right))
+                                .title_bottom(Line::from(n_time).right_aligned().light_magenta())
+                                .borders(Borders::ALL)
+                                .border_type(ratatui::widgets::BorderType::Thick)
+                                .border_style(Style::default().fg(ratatui::style::Color::LightGreen))
+                        } else {
+                            Block::default()
+                                .title(from_user.bold().light_magenta())
+                                .title_alignment(Alignment::Left)
+                                .title_bottom(Line::from(n_time).right_aligned().light_magenta())
+                                .borders(Borders::ALL)
+                                .border_type(ratatui::widgets::BorderType::Rounded)
+                                .border_style(Style::default().fg(ratatui::style::Color::LightBlue))
+                        };
+
+                        let n_approved = match RoomJoinApprovedNotificationContent::parse(&notification.content) {
+                            Some(approved) => format!("Let you into {}", approved.room_name),
+                            None => n_content
+                        };
+
+                        let notif = Paragraph::new(vec![
+                                Line::from("JOIN APPROVED").bold().light_cyan(),
+                                Line::from(n_approved).white()
+                            ])
+                            .alignment(ratatui::layout::Alignment::Left)
+                            .block(n_block);
+
+
+                        let n_block_area = Rect::new(
+                            n_block_x, 
+                            n_block_y, 
+                            n_block_width, 
+                            n_block_height
+                        );
+
+                        n_block_y += n_block_height+2;
+
+                        n_scroll_view.render_widget(notif, n_block_area);
+                    }
+
+                    NotificationType::IDENTITY_ROTATED => {
+
+                        let n_block = if notificationscomps.current_index == index {
+                            Block::default()
+                                .title(from_user.bold().light_magenta())
+                                .title_alignment(Alignment::Left)
+                                .title_top(Line::from(vec!["[Enter]DM User ".light_green().bold(), " [I]Ignore".light_yellow().bold()]).alignment(Alignment::Right))
+                                .title_bottom(Line::from(n_time).right_aligned().light_magenta())
+                                .borders(Borders::ALL)
+                                .border_type(ratatui::widgets::BorderType::Thick)
+                                .border_style(Style::default().fg(ratatui::style::Color::LightGreen))
+                        } else {
+                            Block::default()
+                                .title(from_user.bold().light_magenta())
+                                .title_alignment(Alignment::Left)
+                                .title_bottom(Line::from(n_time).right_aligned().light_magenta())
+                                .borders(Borders::ALL)
+                                .border_type(ratatui::widgets::BorderType::Rounded)
+                                .border_style(Style::default().fg(ratatui::style::Color::LightBlue))
+                        };
+
+                        let notif = Paragraph::new(vec![
+                                Line::from("NEW IDENTITY KEY").bold().light_cyan(),
+                                Line::from(n_content).white()
+                            ])
+                            .alignment(ratatui::layout::Alignment::Left)
+                            .block(n_block);
+
+
                         let n_block_area = Rect::new(
                             n_block_x, 
                             n_block_y, 
@@ -13,6 +13,7 @@ pub fn draw_roomcreate_panel(
             .title("Create a room")
             .title_alignment(Alignment::Center)
             .title_top(Line::from("[Esc]Go to Options Menu").left_aligned().on_black().white())
+            .title_bottom(Line::from("[Up/Down]Select setting  [Left/Right]Change it").centered().on_black().white())
             .title_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
             .borders(Borders::ALL)
             .border_type(ratatui::widgets::BorderType::Rounded)
@@ -27,7 +28,7 @@ pub fn draw_roomcreate_panel(
         .constraints(
             [
                 Constraint::Percentage(20), // Top margin
-                Constraint::Length(12),     // height 
+                Constraint::Length(24),     // height 
                 Constraint::Length(3), // Bottom margin
             ]
             .as_ref(),
@@ -74,7 +75,7 @@ pub fn draw_roomcreate_panel(
     let block_chunks = Layout::default()
     .direction(Direction::Vertical)
     .margin(0)
-    .constraints([Constraint::Length(10), Constraint::Min(0)].as_ref())
+    .constraints([Constraint::Length(22), Constraint::Min(0)].as_ref())
     .split(inner_area);
 
     let top_area = block_chunks[0];
@@ -96,7 +97,8 @@ pub fn draw_roomcreate_panel(
     .constraints(
         [
             Constraint::Length(3), // roomname field
-            Constraint::Length(2), //Gap
+            Constraint::Length(9), // room settings
+            Constraint::Length(3), // topic field
             Constraint::Length(3), // Submit Button
             Constraint::Min(0),    // any leftover space
         ]
@@ -108,6 +110,33 @@ pub fn draw_roomcreate_panel(
 
     frame.render_widget(&roomcreation_ta.roomname_ta, ta_chunks[0]);
 
+    let on_off = |on: bool| if on { "ON".to_string() } else { "OFF".to_string() };
+    let settings = &roomcreation_ta.settings;
+
+    let setting_values = [
+        ("Persistent room", on_off(roomcreation_ta.persistent)),
+        ("Max members", if settings.max_members==0 { "Unlimited".to_string() } else { settings.max_members.to_string() }),
+        ("Whispers", if settings.whispers_allowed { "ALLOWED".to_string() } else { "OFF".to_string() }),
+        ("Join approval", on_off(settings.join_approval)),
+        ("Slow mode", if settings.slow_mode_seconds==0 { "OFF".to_string() } else { format!("{}s", settings.slow_mode_seconds) }),
+        ("Share history", if settings.share_history_count==0 { "OFF".to_string() } else { format!("Last {}", settings.share_history_count) }),
+        ("Listed in directory", on_off(settings.listed))
+    ];
+
+    let mut setting_lines = vec![Line::from("")];
+    for (index, (label, value)) in setting_values.into_iter().enumerate() {
+        //Field 0 is the room name
+        let line = match roomcreation_ta.selected_field==index+1 {
+            true => Line::from(vec![format!("> {}: ", label).light_cyan().bold(), value.light_green().bold()]),
+            false => Line::from(vec![format!("{}: ", label).white(), value.gray()])
+        };
+        setting_lines.push(line);
+    }
+
+    frame.render_widget(Paragraph::new(setting_lines).alignment(Alignment::Center), ta_chunks[1]);
+
+    frame.render_widget(&roomcreation_ta.topic_ta, ta_chunks[2]);
+
 
 
     let submit_block = Block::default()
@@ -120,7 +149,7 @@ pub fn draw_roomcreate_panel(
         .block(submit_block);
 
 
-    let original = ta_chunks[2];
+    let original = ta_chunks[3];
 
     // Calculate a new width (e.g., half of the original) and center it.
     let new_width = original.width / 1;
@@ -2,12 +2,18 @@
 use ratatui::{style::{Color, Style, Stylize}, widgets::{Block, Borders, Paragraph}};
 use tui_textarea::TextArea;
 
+use crate::user_model::RoomSettings;
+
 
 #[derive(Debug, Clone)]
 pub struct RoomCreationTextArea {
     pub roomname_ta: TextArea<'static>,
+    pub topic_ta: TextArea<'static>, //Only sent for listed rooms
     pub status_block: Paragraph<'static>,
-    pub task_status: RoomCreationTaskStatus
+    pub task_status: RoomCreationTaskStatus,
+    pub persistent: bool, //Persistent rooms survive the owner leaving
+    pub settings: RoomSettings,
+    pub selected_field: usize //0 is the room name, the settings are below it and the topic is last
 }
 
 #[derive(Debug, Clone)]
@@ -22,8 +28,61 @@ impl RoomCreationTextArea {
     pub fn new() -> Self {
         Self {
             roomname_ta: Self::get_roomname_textarea(),
+            topic_ta: Self::get_topic_textarea(),
             status_block: Self::get_status_block(),
-            task_status: RoomCreationTaskStatus::NOT_INITIATED
+            task_status: RoomCreationTaskStatus::NOT_INITIATED,
+            persistent: false,
+            settings: RoomSettings::default(),
+            selected_field: 0
+        }
+    }
+
+    pub const FIELDS_COUNT: usize = 9;
+
+    pub const TOPIC_FIELD: usize = 8;
+
+    const MAX_MEMBERS_LIMIT: i64 = 100;
+
+    const SLOW_MODE_STEPS: [i64; 6] = [0, 5, 10, 30, 60, 300];
+
+    const SHARE_HISTORY_STEPS: [i64; 4] = [0, 10, 25, 50];
+
+    //Left/Right on a selected setting, toggles flip and numbers step up or down
+    pub fn change_selected_setting(&mut self, forward: bool) {
+        match self.selected_field {
+            1 => self.persistent = !self.persistent,
+            2 => {
+                let max_members = self.settings.max_members;
+                //Unlimited sits below 2, a room of one is just the owner
+                self.settings.max_members = match (forward, max_members) {
+                    (true, 0) => 2,
+                    (true, n) => (n+1).min(Self::MAX_MEMBERS_LIMIT),
+                    (false, n) if n <= 2 => 0,
+                    (false, n) => n-1
+                };
+            }
+            3 => self.settings.whispers_allowed = !self.settings.whispers_allowed,
+            4 => self.settings.join_approval = !self.settings.join_approval,
+            5 => {
+                let steps = Self::SLOW_MODE_STEPS;
+                let current = steps.iter().position(|s| *s==self.settings.slow_mode_seconds).unwrap_or(0);
+                let next = match forward {
+                    true => (current+1).min(steps.len()-1),
+                    false => current.saturating_sub(1)
+                };
+                self.settings.slow_mode_seconds = steps[next];
+            }
+            6 => {
+                let steps = Self::SHARE_HISTORY_STEPS;
+                let current = steps.iter().position(|s| *s==self.settings.share_history_count).unwrap_or(0);
+                let next = match forward {
+                    true => (current+1).min(steps.len()-1),
+                    false => current.saturating_sub(1)
+                };
+                self.settings.share_history_count = steps[next];
+            }
+            7 => self.settings.listed = !self.settings.listed,
+            _ => {}
         }
     }
 
@@ -42,6 +101,22 @@ impl RoomCreationTextArea {
         ta
     }
 
+    pub fn get_topic_textarea() -> TextArea<'static> {
+        let mut ta = TextArea::default();
+        ta.set_cursor_line_style(Style::default());
+        ta.set_cursor_style(Style::default());
+        ta.set_placeholder_text("What the room is about, for the directory");
+        ta.set_style(Style::default().fg(Color::White));
+        ta.set_block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Color::Magenta)
+                .title("Topic"),
+        );
+
+        ta
+    }
+
     pub fn get_status_block() -> Paragraph<'static> {
 
         let text = "Chat with your friends in a private room!".to_string();
@@ -0,0 +1,180 @@
+use ratatui::{layout::{Alignment, Constraint, Direction, Layout, Margin, Rect, Size}, style::{Color, Modifier, Style, Stylize}, text::Line, widgets::{Block, Borders, Paragraph, Wrap}, Frame};
+use tui_scrollview::{ScrollView, ScrollViewState, ScrollbarVisibility};
+
+use crate::{get_current_date, user_model::ListedRoom};
+
+
+
+pub fn draw_roomdirectory_panel(
+    frame: &mut Frame,
+    area: Rect,
+    roomdirectorycomps: &mut RoomDirectoryComponents
+) {
+
+    let roomdirectorypanel_block = Block::default()
+            .title("Room Directory")
+            .title_alignment(Alignment::Center)
+            .title_top(Line::from(get_current_date()).right_aligned())
+            .title_top(Line::from("[Esc]Go to Options Menu").left_aligned().on_black().white())
+            .title_bottom(Line::from("[Up/Down]Navigate between rooms  [Left/Right]Previous/Next page  [Enter]Join room").centered().on_black().white())
+            .title_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Cyan));
+
+    let [action_status_area, roomslist_area] = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Fill(1)
+            ]
+        ).areas(area);
+
+    let [_, rooms_chunk, __] = Layout::default()
+        .direction(Direction::Horizontal)
+        .margin(2)
+        .constraints(
+            [
+                Constraint::Percentage(10),
+                Constraint::Percentage(80),
+                Constraint::Percentage(10),
+            ]
+        ).areas(roomslist_area);
+
+    let rooms = &roomdirectorycomps.rooms;
+
+    let scroll_height: u16 = (6*rooms.len()) as u16;
+
+    let mut rooms_scroll_view = ScrollView::new(Size::new(
+        rooms_chunk.width,
+        scroll_height
+    ))
+    .horizontal_scrollbar_visibility(ScrollbarVisibility::Never);
+
+    let rooms_area = rooms_scroll_view.area().inner(Margin{
+        horizontal: 2,
+        vertical: 0
+    });
+
+    //Declaring room block size and position
+    let room_block_width = rooms_area.width;
+    let room_block_height = 4;
+    let room_block_x = rooms_area.x;
+    let mut room_block_y = rooms_area.y;
+
+    for (index, room) in rooms.iter().enumerate() {
+
+        let room_title = Line::from(room.room_name.clone().bold().light_magenta());
+
+        let members = match room.max_members {
+            0 => format!("{} members", room.member_count),
+            max_members => format!("{}/{} members", room.member_count, max_members)
+        };
+        let mut room_info = vec![members];
+        if room.join_approval {
+            room_info.push("Knock to join".to_string());
+        }
+        if room.persistent {
+            room_info.push("Persistent".to_string());
+        }
+        let room_info = Line::from(room_info.join(" · ")).right_aligned().light_magenta();
+
+        let topic = match room.topic.is_empty() {
+            true => Line::from("No topic".dark_gray()),
+            false => Line::from(room.topic.clone().gray())
+        };
+
+        //Formatting as per selection
+        let room_block = if roomdirectorycomps.current_index == index {
+            let action = if room.join_approval { "[Enter]Knock " } else { "[Enter]Join Room " };
+            Block::default()
+                .title(room_title)
+                .title_alignment(Alignment::Left)
+                .title_top(Line::from(action.light_green().bold()).alignment(Alignment::Right))
+                .title_bottom(room_info)
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::Thick)
+                .border_style(Style::default().fg(ratatui::style::Color::LightGreen))
+        } else {
+            Block::default()
+                .title(room_title)
+                .title_alignment(Alignment::Left)
+                .title_bottom(room_info)
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::Rounded)
+                .border_style(Style::default().fg(ratatui::style::Color::LightBlue))
+        };
+
+        let room_entry = Paragraph::new(topic)
+                .alignment(ratatui::layout::Alignment::Left)
+                .wrap(Wrap { trim: true })
+                .block(room_block);
+
+        let room_block_area = Rect::new(
+            room_block_x,
+            room_block_y,
+            room_block_width,
+            room_block_height
+        );
+
+        room_block_y += room_block_height+2;
+
+        rooms_scroll_view.render_widget(room_entry, room_block_area);
+    }
+
+    frame.render_widget(roomdirectorypanel_block, area);
+
+    frame.render_widget(&roomdirectorycomps.action_status_block,
+        Rect::new(
+             action_status_area.x + (action_status_area.width/4),
+             action_status_area.y,
+             action_status_area.width - (action_status_area.width/2),
+             action_status_area.height
+    ));
+
+    frame.render_stateful_widget(rooms_scroll_view, rooms_chunk, &mut roomdirectorycomps.scroll_state);
+
+}
+
+
+
+pub struct RoomDirectoryComponents {
+    pub scroll_state: ScrollViewState,
+    pub rooms: Vec<ListedRoom>,
+    pub page: usize,
+    pub has_more: bool, //Another page follows this one
+    pub current_index: usize,
+    pub action_status_block: Paragraph<'static>
+}
+
+impl RoomDirectoryComponents {
+
+    pub fn new() -> Self {
+        Self {
+            scroll_state: ScrollViewState::default(),
+            rooms: Vec::new(),
+            page: 0,
+            has_more: false,
+            current_index: 0,
+            action_status_block: RoomDirectoryComponents::get_action_status_block()
+        }
+    }
+
+    pub fn get_action_status_block() -> Paragraph<'static> {
+
+        let text = "Rooms their owners listed, open to anyone".to_string();
+
+        let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(ratatui::style::Color::LightCyan));
+
+        let status = Paragraph::new(text.light_cyan())
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+
+        status
+    }
+}
@@ -0,0 +1,162 @@
+use ratatui::{layout::{Alignment, Constraint, Direction, Layout, Margin, Rect, Size}, style::{Color, Modifier, Style, Stylize}, text::Line, widgets::{Block, Borders, Paragraph}, Frame};
+use tui_scrollview::{ScrollView, ScrollViewState, ScrollbarVisibility};
+
+use crate::{active_rooms::RoomListEntry, get_current_date};
+
+
+
+pub fn draw_roomlist_panel(
+    frame: &mut Frame,
+    area: Rect,
+    roomlistcomps: &mut RoomListComponents,
+    rooms: Vec<RoomListEntry>
+) {
+
+    let roomlistpanel_block = Block::default()
+            .title("My Rooms")
+            .title_alignment(Alignment::Center)
+            .title_top(Line::from(get_current_date()).right_aligned())
+            .title_top(Line::from("[Esc]Go to Options Menu").left_aligned().on_black().white())
+            .title_bottom(Line::from("[Up/Down]Navigate between rooms  [Enter]Open room").centered().on_black().white())
+            .title_style(Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD))
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(Color::Cyan));
+
+    let [action_status_area, roomslist_area] = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Fill(1)
+            ]
+        ).areas(area);
+
+    let [_, rooms_chunk, __] = Layout::default()
+        .direction(Direction::Horizontal)
+        .margin(2)
+        .constraints(
+            [
+                Constraint::Percentage(10),
+                Constraint::Percentage(80),
+                Constraint::Percentage(10),
+            ]
+        ).areas(roomslist_area);
+
+    //Rooms may have been left since the selection was made
+    roomlistcomps.current_index = roomlistcomps.current_index.min(rooms.len().saturating_sub(1));
+
+    let scroll_height: u16 = (5*rooms.len()) as u16;
+
+    let mut rooms_scroll_view = ScrollView::new(Size::new(
+        rooms_chunk.width,
+        scroll_height
+    ))
+    .horizontal_scrollbar_visibility(ScrollbarVisibility::Never);
+
+    let rooms_area = rooms_scroll_view.area().inner(Margin{
+        horizontal: 2,
+        vertical: 0
+    });
+
+    //Declaring room block size and position
+    let room_block_width = rooms_area.width;
+    let room_block_height = 3;
+    let room_block_x = rooms_area.x;
+    let mut room_block_y = rooms_area.y;
+
+    for (index, room) in rooms.iter().enumerate() {
+
+        let room_title = Line::from(room.room_name.clone().bold().light_magenta());
+
+        let room_status = if room.is_on_screen {
+            Line::from("Open".light_green())
+        } else if room.unread_count > 0 {
+            Line::from(format!("{} new messages", room.unread_count).light_yellow())
+        } else {
+            Line::from("No new messages".gray())
+        };
+
+        //Formatting as per selection
+        let room_block = if roomlistcomps.current_index == index {
+            Block::default()
+                .title(room_title)
+                .title_alignment(Alignment::Left)
+                .title_top(Line::from("[Enter]Open Room ".light_green().bold()).alignment(Alignment::Right))
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::Thick)
+                .border_style(Style::default().fg(ratatui::style::Color::LightGreen))
+        } else {
+            Block::default()
+                .title(room_title)
+                .title_alignment(Alignment::Left)
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::Rounded)
+                .border_style(Style::default().fg(ratatui::style::Color::LightBlue))
+        };
+
+        let room_entry = Paragraph::new(room_status)
+                .alignment(ratatui::layout::Alignment::Left)
+                .block(room_block);
+
+        let room_block_area = Rect::new(
+            room_block_x,
+            room_block_y,
+            room_block_width,
+            room_block_height
+        );
+
+        room_block_y += room_block_height+2;
+
+        rooms_scroll_view.render_widget(room_entry, room_block_area);
+    }
+
+    frame.render_widget(roomlistpanel_block, area);
+
+    frame.render_widget(&roomlistcomps.action_status_block,
+        Rect::new(
+             action_status_area.x + (action_status_area.width/4),
+             action_status_area.y,
+             action_status_area.width - (action_status_area.width/2),
+             action_status_area.height
+    ));
+
+    frame.render_stateful_widget(rooms_scroll_view, rooms_chunk, &mut roomlistcomps.scroll_state);
+
+}
+
+
+
+pub struct RoomListComponents {
+    pub scroll_state: ScrollViewState,
+    pub current_index: usize,
+    pub action_status_block: Paragraph<'static>
+}
+
+impl RoomListComponents {
+
+    pub fn new() -> Self {
+        Self {
+            scroll_state: ScrollViewState::default(),
+            current_index: 0,
+            action_status_block: RoomListComponents::get_action_status_block()
+        }
+    }
+
+    pub fn get_action_status_block() -> Paragraph<'static> {
+
+        let text = "Rooms you're in stay open in the background".to_string();
+
+        let status_block = Block::default()
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::default())
+        .border_style(Style::default().fg(ratatui::style::Color::LightCyan));
+
+        let status = Paragraph::new(text.light_cyan())
+        .alignment(ratatui::layout::Alignment::Center)
+        .block(status_block);
+
+        status
+    }
+}
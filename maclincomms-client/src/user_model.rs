@@ -1,10 +1,12 @@
 use std::{collections::HashMap};
 
-use disk_persist::DiskPersist;
+use base64::{engine::general_purpose, Engine};
+use crate::local_store::SealedPersist;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use rand_core::{OsRng, RngCore};
 
-use crate::crypto::signature_keypair::generate_signature_keypair;
+use crate::crypto::{dm_envelope::DmEnvelope, dm_keys::generate_dh_keypair, double_ratchet::DoubleRatchet, signature_keypair::generate_signature_keypair, x3dh::sign_prekey};
 
 //USER DATA IN APP FOR PERSISTENCE 
 #[derive(Deserialize, Serialize, Debug)]
@@ -20,6 +22,91 @@ pub struct UserIdentityKeys{ //Identity Key Pair
     pub private_identity_key: String,
 }
 
+impl UserIdentityKeys{
+    pub fn load_private_key() -> Option<[u8;32]>{
+        let disk: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-user-identity-keypair").unwrap();
+        let data = disk.read().ok()??;
+        general_purpose::STANDARD.decode(data.private_identity_key).ok()?.try_into().ok()
+    }
+
+    pub fn load_public_key() -> Option<[u8;32]>{
+        let disk: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-user-identity-keypair").unwrap();
+        let data = disk.read().ok()??;
+        general_purpose::STANDARD.decode(data.public_identity_key).ok()?.try_into().ok()
+    }
+
+    //The key pair the account registered with. The account device holds it as its own, linked devices get a copy when linking
+    pub fn load_account() -> Option<Self>{
+        let disk: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-account-identity-keypair").unwrap();
+        if let Ok(Some(keys)) = disk.read() {
+            return Some(keys);
+        }
+        if !UserDevice::load_or_create().account_device {
+            return None;
+        }
+        let device_disk: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-user-identity-keypair").unwrap();
+        device_disk.read().ok()?
+    }
+
+    pub fn save_account(&self){
+        let disk: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-account-identity-keypair").unwrap();
+        disk.write(self).unwrap();
+    }
+
+    //Rooms know members by the account key, devices that were never linked fall back to their own
+    pub fn load_account_private_key() -> Option<[u8;32]>{
+        match Self::load_account() {
+            Some(keys) => general_purpose::STANDARD.decode(keys.private_identity_key).ok()?.try_into().ok(),
+            None => Self::load_private_key()
+        }
+    }
+
+    pub fn load_account_public_key() -> Option<[u8;32]>{
+        match Self::load_account() {
+            Some(keys) => general_purpose::STANDARD.decode(keys.public_identity_key).ok()?.try_into().ok(),
+            None => Self::load_public_key()
+        }
+    }
+}
+
+//This install, registered with the server as one of the account's devices
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UserDevice{
+    pub device_id: String,
+    pub device_name: String,
+    pub account_device: bool //Signed in with the account's identity key, the only device publishing X3DH prekeys
+}
+
+impl UserDevice{
+    //Reads the device of this install, creating it on first run
+    pub fn load_or_create() -> Self{
+        let disk: SealedPersist<UserDevice> = SealedPersist::init("persistent-user-device").unwrap();
+        if let Ok(Some(device)) = disk.read() {
+            return device;
+        }
+        let mut id_bytes = [0u8;8];
+        OsRng.fill_bytes(&mut id_bytes);
+        let device = UserDevice {
+            device_id: id_bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            device_name: format!("{} device", std::env::consts::OS),
+            account_device: true
+        };
+        device.save();
+        return device;
+    }
+
+    pub fn save(&self){
+        let disk: SealedPersist<UserDevice> = SealedPersist::init("persistent-user-device").unwrap();
+        disk.write(self).unwrap();
+    }
+
+    //Forgets the device id, the next load_or_create makes a fresh one
+    pub fn reset(){
+        let disk: SealedPersist<UserDevice> = SealedPersist::init("persistent-user-device").unwrap();
+        disk.remove();
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct UserSignatureKeys{ //Signature Key Pair
     pub public_signature_key: [u8;32],
@@ -37,18 +124,157 @@ impl UserSignatureKeys{
 }
 
 
+//----------X3DH Prekeys-------------------
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UserPrekeys{ //Private halves of the prekeys uploaded to the server
+    pub identity_pub_key: [u8;32], //Identity key the signed prekey was signed for
+    pub signing_pub_key: [u8;32],
+    pub signing_priv_key: [u8;32],
+    pub signed_prekey_id: u32,
+    pub signed_prekey_pub: [u8;32],
+    pub signed_prekey_priv: [u8;32],
+    pub signed_prekey_signature: Vec<u8>,
+    pub one_time_prekeys: Vec<(u32, [u8;32], [u8;32])>, // (Key Id, Pub Key, Priv Key) not used up yet
+    pub next_one_time_prekey_id: u32
+}
+
+impl UserPrekeys{
+    pub fn new(identity_pub_key: [u8;32]) -> Self{
+        let (signing_pub_key, signing_priv_key) = generate_signature_keypair();
+        let (signed_prekey_pub, signed_prekey_priv) = generate_dh_keypair();
+        Self {
+            identity_pub_key,
+            signing_pub_key,
+            signing_priv_key,
+            signed_prekey_id: 1,
+            signed_prekey_pub,
+            signed_prekey_priv,
+            signed_prekey_signature: sign_prekey(signing_priv_key, identity_pub_key, signed_prekey_pub).to_vec(),
+            one_time_prekeys: Vec::new(),
+            next_one_time_prekey_id: 1
+        }
+    }
+
+    pub fn load() -> Option<Self>{
+        let disk: SealedPersist<UserPrekeys> = SealedPersist::init("persistent-user-prekeys").unwrap();
+        disk.read().ok()?
+    }
+
+    pub fn save(&self){
+        let disk: SealedPersist<UserPrekeys> = SealedPersist::init("persistent-user-prekeys").unwrap();
+        disk.write(self).unwrap();
+    }
+
+    //New one time prekeys, returned ready for upload
+    pub fn generate_one_time_prekeys(&mut self, count: usize) -> Vec<OneTimePrekey>{
+        let mut new_prekeys = Vec::new();
+        for _ in 0..count {
+            let (prekey_pub, prekey_priv) = generate_dh_keypair();
+            self.one_time_prekeys.push((self.next_one_time_prekey_id, prekey_pub, prekey_priv));
+            new_prekeys.push(OneTimePrekey {
+                key_id: self.next_one_time_prekey_id,
+                prekey: general_purpose::STANDARD.encode(prekey_pub)
+            });
+            self.next_one_time_prekey_id += 1;
+        }
+        return new_prekeys;
+    }
+
+    //A new signed prekey for a rotated identity key, the signing key and one time prekeys carry on
+    pub fn resign(&mut self, identity_pub_key: [u8;32]){
+        let (signed_prekey_pub, signed_prekey_priv) = generate_dh_keypair();
+        self.identity_pub_key = identity_pub_key;
+        self.signed_prekey_id += 1;
+        self.signed_prekey_pub = signed_prekey_pub;
+        self.signed_prekey_priv = signed_prekey_priv;
+        self.signed_prekey_signature = sign_prekey(self.signing_priv_key, identity_pub_key, signed_prekey_pub).to_vec();
+    }
+
+    //Removes the one time prekey so it's never used twice
+    pub fn take_one_time_prekey(&mut self, key_id: u32) -> Option<[u8;32]>{
+        let pos = self.one_time_prekeys.iter().position(|(id, _, _)| *id==key_id)?;
+        return Some(self.one_time_prekeys.remove(pos).2);
+    }
+
+    pub fn upload_payload(&self, one_time_prekeys: Vec<OneTimePrekey>) -> UploadPrekeysPayload{
+        UploadPrekeysPayload {
+            identity_signing_key: general_purpose::STANDARD.encode(self.signing_pub_key),
+            signed_prekey_id: self.signed_prekey_id,
+            signed_prekey: general_purpose::STANDARD.encode(self.signed_prekey_pub),
+            signed_prekey_signature: general_purpose::STANDARD.encode(&self.signed_prekey_signature),
+            one_time_prekeys
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UploadPrekeysPayload{
+    pub identity_signing_key: String,
+    pub signed_prekey_id: u32,
+    pub signed_prekey: String,
+    pub signed_prekey_signature: String,
+    pub one_time_prekeys: Vec<OneTimePrekey>
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct OneTimePrekey{
+    pub key_id: u32,
+    pub prekey: String
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PrekeyBundle{
+    pub username: String,
+    pub public_identity_key: String,
+    pub identity_signing_key: String,
+    pub signed_prekey_id: u32,
+    pub signed_prekey: String,
+    pub signed_prekey_signature: String,
+    pub one_time_prekey: Option<OneTimePrekey>
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct X3dhHandshake{
+    pub ephemeral_key: String,
+    pub signed_prekey_id: u32,
+    pub one_time_prekey_id: Option<u32>
+}
+
+//Content of ACCEPTED notifications
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AcceptedNotificationContent{
+    pub message: String,
+    pub x3dh_handshake: Option<X3dhHandshake>
+}
+
+impl AcceptedNotificationContent{
+    //Older servers send just the message
+    pub fn parse(content: &str) -> Self{
+        serde_json::from_str(content).unwrap_or(Self {
+            message: content.to_string(),
+            x3dh_handshake: None
+        })
+    }
+}
+
+
 //----------------------------------------------
 #[derive(Deserialize, Serialize)]
 pub struct UserLoginPayload{
     pub username: String,
     pub password: String,
+    pub device_id: String,
+    pub device_name: String,
+    pub public_identity_key: String //This device's own identity key
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct UserRegisterPayload{
     pub username: String,
     pub password: String,
-    pub public_identity_key: String
+    pub public_identity_key: String,
+    pub device_id: String,
+    pub device_name: String
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -65,13 +291,18 @@ pub struct LoginResponse{
     pub access_token: String,
     pub refresh_token: String,
     pub exp: i64,
+    #[serde(default)]
+    pub account_device: bool
 }
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct RequestNewTokenResponse{
     pub status_type: StatusTypes,
-    pub access_token: String,
+    #[serde(default)]
+    pub access_token: String, //Left out when a revoked device is turned away
+    #[serde(default)]
     pub refresh_token: String,
+    #[serde(default)]
     pub exp: i64,
 }
 
@@ -87,7 +318,58 @@ pub struct Rooms_Table{
     pub room_key: String,
     pub room_owner: String,
     pub room_members: Vec<String>,
-    pub members_keys: Vec<Value>
+    pub members_keys: Vec<Value>,
+    #[serde(default)]
+    pub persistent: bool,
+    #[serde(default)]
+    pub moderators: Vec<String>,
+    #[serde(default)]
+    pub banned: Vec<String>,
+    #[serde(default)]
+    pub muted: Vec<String>,
+    #[serde(default)]
+    pub settings: RoomSettings
+}
+
+//Picked on the Create Room Screen, the server enforces them
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RoomSettings{
+    pub max_members: i64, // 0 leaves the room uncapped
+    pub whispers_allowed: bool,
+    pub join_approval: bool,
+    pub slow_mode_seconds: i64, // 0 turns slow mode off
+    pub share_history_count: i64, // Earlier messages handed to newcomers, 0 turns history sharing off
+    pub listed: bool, // Shown in the room directory, anyone can join without the room key
+    pub topic: String
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            max_members: 0,
+            whispers_allowed: true,
+            join_approval: false,
+            slow_mode_seconds: 0,
+            share_history_count: 0,
+            listed: false,
+            topic: "".to_string()
+        }
+    }
+}
+
+//Invite tokens are redeemed in place of the room key
+pub const INVITE_TOKEN_PREFIX: &str = "invite-";
+
+//A room in the room directory
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ListedRoom{
+    pub room_name: String,
+    pub topic: String,
+    pub member_count: i64, // Connected right now
+    pub max_members: i64, // 0 when uncapped
+    pub join_approval: bool, // Joining knocks instead
+    pub persistent: bool
 }
 
 
@@ -116,7 +398,29 @@ pub enum StatusTypes{
     ROOM_FOUND,
     ROOM_ALREADY_EXISTS,
     ROOM_CREATION_SUCCESSFUL,
-    ROOM_AUTHORIZATION_SUCCESSFUL
+    ROOM_AUTHORIZATION_SUCCESSFUL,
+    PREKEYS_UPLOADED,
+    PREKEY_BUNDLE_FETCHED,
+    PREKEY_BUNDLE_NOT_FOUND,
+    BANNED_FROM_ROOM,
+    ROOM_INVITE_CREATED,
+    INVALID_INVITE,
+    ROOM_FULL,
+    JOIN_REQUEST_SENT,
+    JOIN_REQUEST_APPROVED,
+    JOIN_REQUEST_DENIED,
+    ROOM_DIRECTORY_FETCHED,
+    INVALID_USERNAME,
+    DEVICES_FETCHED,
+    DEVICE_REVOKED,
+    DEVICE_NOT_FOUND,
+    CANNOT_REVOKE_CURRENT_DEVICE,
+    DEVICE_LINK_CREATED,
+    DEVICE_LINK_CLAIMED,
+    INVALID_DEVICE_LINK,
+    IDENTITY_KEY_ROTATED,
+    INVALID_IDENTITY_KEY,
+    NOT_ACCOUNT_DEVICE
 }
 
 //-----------Message Types------------------------
@@ -169,8 +473,12 @@ pub struct RoomReceiverMessage {
 #[derive(Clone, Serialize, Deserialize)]
 pub struct DmMessage {
     pub username: String,
-    pub content: String,
-    pub is_online_offline_msg: bool
+    pub content: String, //For the receiver's account device
+    pub is_online_offline_msg: bool,
+    #[serde(default)]
+    pub device_contents: HashMap<String, String>, //Device Id -> Ciphertext for each of the receiver's devices
+    #[serde(default)]
+    pub device_id: String //Sending device, set by the server
 }
 
 
@@ -182,6 +490,9 @@ pub enum SocketMessage {
     Join(MessageType),
     Leave(MessageType),
     RoomSenderKey(Vec<u8>),
+    RoomHistory(Vec<u8>), //Frame with the history sealed for a newcomer
+    RoomOwnershipTransfer(String), //New owner's username
+    RoomModeration(ModerationAction, String), //Target username, empty for ROTATE_KEY
     Acknowledgement(Vec<u8>),
     Disconnect(DisconnectType),
     // File(FileInfo)
@@ -219,6 +530,87 @@ impl AckType {
 }
 
 
+//--------------ROOM HISTORY
+
+// In rooms sharing history, members seal their latest messages for a newcomer as [0x66][Username Length][Username][Sealed History].
+// The sender names the newcomer, the newcomer gets the sender's name in its place
+pub const ROOM_HISTORY_BYTE: u8 = 0x66;
+
+//Most messages kept for sharing, the server holds the setting to the same
+pub const ROOM_HISTORY_LIMIT: usize = 50;
+
+//Only plain room messages, whispers never leave the people they were for
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RoomHistoryMessage {
+    pub username: String,
+    pub content: String,
+    pub time: String
+}
+
+//The history taken up on joining. Nothing proves who wrote the lines, only who shared them
+#[derive(Debug, Clone)]
+pub struct SharedRoomHistory {
+    pub shared_by: String,
+    pub messages: Vec<RoomHistoryMessage>
+}
+
+
+//--------------ROOM MODERATION
+
+// Moderation commands go over the room socket as [0x55][Action][Target Username].
+// The server informs the room with the same frame once applied, the issuer gets [0x55][REJECTED][Action] otherwise
+pub const ROOM_MODERATION_BYTE: u8 = 0x55;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModerationAction {
+    KICK,
+    BAN,
+    MUTE,
+    UNMUTE, //Sent by the server only, muting a muted member unmutes them
+    PROMOTE,
+    ROTATE_KEY,
+    REJECTED
+}
+
+impl ModerationAction {
+    pub fn byte(&self) -> u8 {
+        match self {
+            Self::KICK => 0x01,
+            Self::BAN => 0x02,
+            Self::MUTE => 0x03,
+            Self::UNMUTE => 0x04,
+            Self::PROMOTE => 0x05,
+            Self::ROTATE_KEY => 0x06,
+            Self::REJECTED => 0x00
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x01 => Some(Self::KICK),
+            0x02 => Some(Self::BAN),
+            0x03 => Some(Self::MUTE),
+            0x04 => Some(Self::UNMUTE),
+            0x05 => Some(Self::PROMOTE),
+            0x06 => Some(Self::ROTATE_KEY),
+            0x00 => Some(Self::REJECTED),
+            _ => None
+        }
+    }
+
+    pub fn command(&self) -> &'static str {
+        match self {
+            Self::KICK => "/kick",
+            Self::BAN => "/ban",
+            Self::MUTE | Self::UNMUTE => "/mute",
+            Self::PROMOTE => "/promote",
+            Self::ROTATE_KEY => "/rotate-key",
+            Self::REJECTED => ""
+        }
+    }
+}
+
+
 //-----------NOTIFICATIONS---------------------
 
 #[derive(Debug, Serialize, Clone, Deserialize)]
@@ -234,7 +626,52 @@ pub struct NotificationData {
 pub enum NotificationType {
     MESSAGE,
     ADD_REQUEST,
-    ACCEPTED
+    ACCEPTED,
+    ROOM_INVITE,
+    ROOM_JOIN_REQUEST, //Content is the room name
+    ROOM_JOIN_APPROVED,
+    IDENTITY_ROTATED //A DM contact moved to a new identity key
+}
+
+//Content of ROOM_INVITE notifications
+#[derive(Debug, Serialize, Clone, Deserialize)]
+pub struct RoomInviteNotificationContent {
+    pub room_name: String,
+    pub invite_token: String,
+    pub expires_at: i64
+}
+
+impl RoomInviteNotificationContent{
+    pub fn parse(content: &str) -> Option<Self>{
+        serde_json::from_str(content).ok()
+    }
+}
+
+//Content of ROOM_JOIN_APPROVED notifications
+#[derive(Debug, Serialize, Clone, Deserialize)]
+pub struct RoomJoinApprovedNotificationContent {
+    pub room_name: String,
+    pub room_token: String
+}
+
+impl RoomJoinApprovedNotificationContent{
+    pub fn parse(content: &str) -> Option<Self>{
+        serde_json::from_str(content).ok()
+    }
+}
+
+//Content of IDENTITY_ROTATED notifications, keys and signature Base64 encoded
+#[derive(Debug, Serialize, Clone, Deserialize)]
+pub struct IdentityRotatedNotificationContent {
+    pub old_public_identity_key: String,
+    pub new_public_identity_key: String,
+    pub signature: String //XEdDSA signature of the old key over both keys
+}
+
+impl IdentityRotatedNotificationContent{
+    pub fn parse(content: &str) -> Option<Self>{
+        serde_json::from_str(content).ok()
+    }
 }
 
 
@@ -251,6 +688,188 @@ pub struct DmsListData {
 pub struct DmUser_Data{
     pub username: String,
     pub public_identity_key: String,
+    #[serde(default)]
+    pub devices: Vec<DeviceKey>
+}
+
+//One of a contact's devices, DMs are encrypted to each of them separately
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeviceKey{
+    pub device_id: String,
+    pub public_identity_key: String
+}
+
+impl DmUser_Data{
+    //The contact as last fetched with the dms list
+    pub fn load(username: &str) -> Option<Self>{
+        let disk: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
+        disk.read().ok()??.into_iter().find(|dm| dm.username==username)
+    }
+
+    //Ratchet slot of one of the contact's devices in the DM keys
+    //The device holding the account key keeps the plain username slot, so sessions from before linked devices carry on
+    pub fn ratchet_slot(&self, device: &DeviceKey) -> String{
+        if device.public_identity_key==self.public_identity_key {
+            return self.username.clone();
+        }
+        format!("{}#{}", self.username, device.device_id)
+    }
+}
+
+//A device of the user's own account, as listed on the linked devices screen
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkedDevice{
+    pub device_id: String,
+    pub device_name: String,
+    pub public_identity_key: String,
+    pub added_at: i64
+}
+
+//What a signed in device hands a new one of the same account, sealed with the link code
+//DM ratchets stay behind, every device runs its own with each contact
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeviceLinkBundle{
+    pub account_identity_keys: UserIdentityKeys,
+    pub dms_session_keys: HashMap<String, DmSessionEncryption_Key>, // Username -> Session key the backed up chats are encrypted with
+    pub dms_list: Vec<DmUser_Data>,
+    pub verified_contacts: VerifiedContacts
+}
+
+impl DeviceLinkBundle{
+    //None on a device without the account keys, it has nothing to hand over
+    pub fn collect() -> Option<Self>{
+        let account_identity_keys = UserIdentityKeys::load_account()?;
+        let session_keys_disk: SealedPersist<HashMap<String, DmSessionEncryption_Key>> = SealedPersist::init("persistent-dms-session-keys").unwrap();
+        let dms_list_disk: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
+        Some(Self {
+            account_identity_keys,
+            dms_session_keys: session_keys_disk.read().ok().flatten().unwrap_or_default(),
+            dms_list: dms_list_disk.read().ok().flatten().unwrap_or_default(),
+            verified_contacts: VerifiedContacts::load()
+        })
+    }
+
+    //The linking device's session keys win, they are the ones the chat backups were encrypted with
+    pub fn import(self) -> HashMap<String, DmSessionEncryption_Key>{
+        self.account_identity_keys.save_account();
+
+        let session_keys_disk: SealedPersist<HashMap<String, DmSessionEncryption_Key>> = SealedPersist::init("persistent-dms-session-keys").unwrap();
+        let mut dms_session_keys = session_keys_disk.read().ok().flatten().unwrap_or_default();
+        dms_session_keys.extend(self.dms_session_keys);
+        session_keys_disk.write(&dms_session_keys).unwrap();
+
+        let dms_list_disk: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
+        dms_list_disk.write(&self.dms_list).unwrap();
+
+        let mut verified_contacts = VerifiedContacts::load();
+        verified_contacts.contacts.extend(self.verified_contacts.contacts);
+        verified_contacts.save();
+
+        return dms_session_keys;
+    }
+}
+
+//Everything on this machine that makes up the account, for `maclincomms export` and `import`.
+//Tokens, the device id and prekeys belong to the exporting device and stay out
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountBackup{
+    pub username: String,
+    pub identity_keys: Option<UserIdentityKeys>,
+    pub account_identity_keys: Option<UserIdentityKeys>, //Only on devices that were linked with a code
+    pub dm_ratchets: DmE2EEncryption_Data,
+    pub dms_list: Vec<DmUser_Data>,
+    pub verified_contacts: VerifiedContacts,
+    pub dmchats_warehouse: DmChats_Warehouse //Session keys and the chats as last backed up, decrypted
+}
+
+impl AccountBackup{
+    //None before the first login, there is no account to back up
+    pub fn collect(dmchats_warehouse: DmChats_Warehouse) -> Option<Self>{
+        let user_data_disk: SealedPersist<UserData> = SealedPersist::init("persistent-user-data").unwrap();
+        let identity_keys_disk: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-user-identity-keypair").unwrap();
+        let account_keys_disk: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-account-identity-keypair").unwrap();
+        let dms_list_disk: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
+        Some(Self {
+            username: user_data_disk.read().ok()??.username,
+            identity_keys: identity_keys_disk.read().ok().flatten(),
+            account_identity_keys: account_keys_disk.read().ok().flatten(),
+            dm_ratchets: DmE2EEncryption_Data::load(),
+            dms_list: dms_list_disk.read().ok().flatten().unwrap_or_default(),
+            verified_contacts: VerifiedContacts::load(),
+            dmchats_warehouse
+        })
+    }
+
+    //Overwrites the account on this machine. Whatever device was signed in here is forgotten,
+    //logging in again registers this machine as a new device. The chats wait in their own file until then
+    pub fn restore(self){
+        let user_data_disk: SealedPersist<UserData> = SealedPersist::init("persistent-user-data").unwrap();
+        user_data_disk.remove();
+        UserDevice::reset();
+        let prekeys_disk: SealedPersist<UserPrekeys> = SealedPersist::init("persistent-user-prekeys").unwrap();
+        prekeys_disk.remove();
+
+        if let Some(identity_keys) = &self.identity_keys {
+            let disk: SealedPersist<UserIdentityKeys> = SealedPersist::init("persistent-user-identity-keypair").unwrap();
+            disk.write(identity_keys).unwrap();
+        }
+        if let Some(account_identity_keys) = &self.account_identity_keys {
+            account_identity_keys.save_account();
+        }
+        let dm_ratchets_disk: SealedPersist<DmE2EEncryption_Data> = SealedPersist::init("persistent-dms-e2e-keys").unwrap();
+        dm_ratchets_disk.write(&self.dm_ratchets).unwrap();
+        let dms_list_disk: SealedPersist<Vec<DmUser_Data>> = SealedPersist::init("persistent-user-dms-list").unwrap();
+        dms_list_disk.write(&self.dms_list).unwrap();
+        self.verified_contacts.save();
+
+        let session_keys_disk: SealedPersist<HashMap<String, DmSessionEncryption_Key>> = SealedPersist::init("persistent-dms-session-keys").unwrap();
+        session_keys_disk.write(&self.dmchats_warehouse.dms_session_key).unwrap();
+        let restored_chats_disk: SealedPersist<HashMap<String, Vec<(String, String, String, String, bool, String)>>> = SealedPersist::init("persistent-restored-dm-chats").unwrap();
+        restored_chats_disk.write(&self.dmchats_warehouse.dms_data).unwrap();
+        let local_chats_disk: SealedPersist<HashMap<String, Vec<(String, String, String, String, bool, String)>>> = SealedPersist::init("persistent-user-dm-chats").unwrap();
+        local_chats_disk.remove();
+    }
+}
+
+//Identity keys the user has checked against the safety number, by username
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct VerifiedContacts{
+    pub contacts: HashMap<String, String> // Username -> Base64 identity key at the time of verifying
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationStatus {
+    UNVERIFIED,
+    VERIFIED,
+    KEY_CHANGED //Verified before, but the server now gives a different identity key
+}
+
+impl VerifiedContacts{
+    pub fn load() -> Self{
+        let disk: SealedPersist<VerifiedContacts> = SealedPersist::init("persistent-user-verified-contacts").unwrap();
+        disk.read().ok().flatten().unwrap_or_default()
+    }
+
+    pub fn save(&self){
+        let disk: SealedPersist<VerifiedContacts> = SealedPersist::init("persistent-user-verified-contacts").unwrap();
+        disk.write(self).unwrap();
+    }
+
+    pub fn status(&self, username: &str, public_identity_key: &str) -> VerificationStatus{
+        match self.contacts.get(username) {
+            Some(verified_key) if verified_key==public_identity_key => VerificationStatus::VERIFIED,
+            Some(_) => VerificationStatus::KEY_CHANGED,
+            None => VerificationStatus::UNVERIFIED
+        }
+    }
+
+    pub fn verify(&mut self, username: String, public_identity_key: String){
+        self.contacts.insert(username, public_identity_key);
+    }
+
+    pub fn unverify(&mut self, username: &str){
+        self.contacts.remove(username);
+    }
 }
 
 //DATA LEVEL REPO FOR ALL DMS DATA SUPPLYING UI LEVEL DATA STRUCTS
@@ -274,28 +893,50 @@ impl DmChats_Warehouse{
             dms_data: HashMap::new() 
         }
     }
+
+    //Decrypted chats as the TUI last had them, what `maclincomms export` backs up
+    pub fn save_local(&self){
+        let disk: SealedPersist<HashMap<String, Vec<(String, String, String, String, bool, String)>>> = SealedPersist::init("persistent-user-dm-chats").unwrap();
+        disk.write(&self.dms_data).unwrap();
+    }
+
+    //None until the TUI has saved the chats once. The session keys are the current ones, same as the last upload
+    pub fn load_local() -> Option<Self>{
+        let disk: SealedPersist<HashMap<String, Vec<(String, String, String, String, bool, String)>>> = SealedPersist::init("persistent-user-dm-chats").unwrap();
+        let session_keys_disk: SealedPersist<HashMap<String, DmSessionEncryption_Key>> = SealedPersist::init("persistent-dms-session-keys").unwrap();
+        Some(Self {
+            dms_session_key: session_keys_disk.read().ok().flatten().unwrap_or_default(),
+            dms_data: disk.read().ok()??
+        })
+    }
 }
 
 
 //----------DM Double Ratchet Keys-------------------
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DmE2EEncryption_Data{
-    pub dms: HashMap<String, DmDoubleRatchet_Keys>
+    pub dms: HashMap<String, DoubleRatchet>
+}
+
+//Layout persisted by clients before the DM ratchet moved to crypto::double_ratchet
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct LegacyDmDoubleRatchetKeys{
+    root_key: [u8;32],
+    their_old_dh_pub_key: [u8;32],
+    dh_pub_key: [u8;32],
+    dh_priv_key: [u8;32],
+    sending_chain_key: [u8;32],
+    receiving_chain_key: [u8;32],
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct DmDoubleRatchet_Keys{
-    pub root_key: [u8;32],
-    pub their_old_dh_pub_key: [u8;32],
-    pub dh_pub_key: [u8;32],
-    pub dh_priv_key: [u8;32],
-    pub sending_chain_key: [u8;32],
-    pub receiving_chain_key: [u8;32],
+struct LegacyDmE2EEncryptionData{
+    dms: HashMap<String, LegacyDmDoubleRatchetKeys>
 }
 
 impl DmE2EEncryption_Data{
     pub fn load() -> Self{
-        let disk: DiskPersist<DmE2EEncryption_Data> = DiskPersist::init("persistent-dms-e2e-keys").unwrap(); 
+        let disk: SealedPersist<DmE2EEncryption_Data> = SealedPersist::init("persistent-dms-e2e-keys").unwrap(); 
         if let Ok(data_res) = disk.read(){
             match data_res {
                 Some(data) => data,
@@ -303,7 +944,96 @@ impl DmE2EEncryption_Data{
             }
         }
         else {
-            DmE2EEncryption_Data { dms: HashMap::new() }
+            //Keys written by an older client, carrying them over with fresh counters
+            let legacy_disk: SealedPersist<LegacyDmE2EEncryptionData> = SealedPersist::init("persistent-dms-e2e-keys").unwrap();
+            match legacy_disk.read() {
+                Ok(Some(legacy_data)) => DmE2EEncryption_Data {
+                    dms: legacy_data.dms.into_iter()
+                        .map(|(user, keys)| (user, DoubleRatchet{
+                            root_key: keys.root_key,
+                            dh_pub_key: keys.dh_pub_key,
+                            dh_priv_key: keys.dh_priv_key,
+                            their_dh_pub_key: keys.their_old_dh_pub_key,
+                            sending_chain_key: keys.sending_chain_key,
+                            receiving_chain_key: keys.receiving_chain_key,
+                            sending_counter: 0,
+                            previous_sending_counter: 0,
+                            receiving_counter: 0,
+                            skipped_message_keys: Vec::new(),
+                            first_contact_priv_key: None,
+                            first_contact_root_key: [0u8;32]
+                        }))
+                        .collect()
+                },
+                _ => DmE2EEncryption_Data { dms: HashMap::new() }
+            }
+        }
+    }
+
+    //Ratchet for one of the contact's devices, started from its identity key the first time round
+    //(the account slot is usually set up already from the X3DH handshake made when the DM request was accepted)
+    fn device_ratchet(&mut self, contact: &DmUser_Data, device: &DeviceKey) -> Option<&mut DoubleRatchet>{
+        let slot = contact.ratchet_slot(device);
+        if !self.dms.contains_key(&slot) {
+            let device_key: [u8;32] = general_purpose::STANDARD.decode(&device.public_identity_key).ok()?.try_into().ok()?;
+            self.dms.insert(slot.clone(), DoubleRatchet::new(device_key));
+        }
+        self.dms.get_mut(&slot)
+    }
+
+    //Encrypts a DM once for every device of the contact
+    //Returns the envelope for their account device and the envelopes by device id
+    pub fn encrypt_for_devices(&mut self, contact: &DmUser_Data, plaintext: &str) -> (String, HashMap<String, String>){
+        let mut content = "".to_string();
+        let mut device_contents = HashMap::new();
+
+        for device in &contact.devices {
+            let is_account_slot = contact.ratchet_slot(device)==contact.username;
+            if let Some(keys) = self.device_ratchet(contact, device) {
+                let envelope = keys.encrypt(plaintext);
+                if is_account_slot {
+                    content = envelope.clone();
+                }
+                device_contents.insert(device.device_id.clone(), envelope);
+            }
+        }
+
+        //Contacts on clients without linked devices only have the account slot
+        if contact.devices.is_empty() {
+            if let Some(keys) = self.dms.get_mut(&contact.username) {
+                content = keys.encrypt(plaintext);
+            }
+        }
+        return (content, device_contents);
+    }
+
+    //Decrypts a DM sent from one of the contact's devices, None when it doesn't decrypt or the device isn't known yet
+    pub fn decrypt_from_device(&mut self, contact: &DmUser_Data, device_id: &str, content: &str) -> Option<String>{
+        let envelope = DmEnvelope::parse(content)?;
+        let identity_priv_key = UserIdentityKeys::load_private_key()?;
+
+        //Senders without linked devices use the account slot
+        if device_id.is_empty() {
+            return self.dms.get_mut(&contact.username)?.decrypt(&envelope, identity_priv_key);
+        }
+        let device = contact.devices.iter().find(|device| device.device_id==device_id)?.clone();
+        self.device_ratchet(contact, &device)?.decrypt(&envelope, identity_priv_key)
+    }
+
+    //Drops the ratchets of every device of the contact
+    pub fn remove_contact(&mut self, username: &str){
+        let device_slot_prefix = format!("{}#", username);
+        self.dms.retain(|slot, _| slot!=username && !slot.starts_with(&device_slot_prefix));
+    }
+
+    //Fresh ratchets from the contacts' identity keys after ours was rotated, root keys start over on both sides.
+    //Device slots are set up again on the next message
+    pub fn restart_sessions(&mut self, dms_list: &[DmUser_Data]){
+        for contact in dms_list {
+            self.remove_contact(&contact.username);
+            if let Some(their_pub_key) = general_purpose::STANDARD.decode(&contact.public_identity_key).ok().and_then(|key| <[u8;32]>::try_from(key).ok()) {
+                self.dms.insert(contact.username.clone(), DoubleRatchet::new(their_pub_key));
+            }
         }
     }
 }
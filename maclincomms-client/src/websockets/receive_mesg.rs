@@ -4,7 +4,7 @@ use futures_util::{StreamExt, stream::SplitStream};
 use tokio::{io::{AsyncRead, AsyncWrite}};
 use tokio_tungstenite::{tungstenite::{Message}, WebSocketStream};
 
-use crate::{event_model::Event, user_model::{AckType, DisconnectType, DmMessage, RoomReceiverMessage, SocketMessage, WorldChatMessage}};
+use crate::{event_model::Event, user_model::{AckType, DisconnectType, DmMessage, ModerationAction, RoomReceiverMessage, SocketMessage, WorldChatMessage, ROOM_HISTORY_BYTE, ROOM_MODERATION_BYTE}};
 
 pub async fn handle_incoming_public_messages(
     mut read: SplitStream<WebSocketStream<impl AsyncRead + AsyncWrite + Unpin>>,
@@ -65,8 +65,14 @@ pub async fn handle_incoming_public_messages(
 }
 
 
+//Room events carry the room they came from, more than one room can be open at a time
+fn room_event(event: Event, room_name: &str) -> Event {
+    Event::RoomEvent(room_name.to_string(), Box::new(event))
+}
+
 pub async fn handle_incoming_room_messages(
     mut read: SplitStream<WebSocketStream<impl AsyncRead + AsyncWrite + Unpin>>,
+    room_name: String,
     incoming_tx: mpsc::Sender<Event>,
     socket_closer_tx: mpsc::Sender<SocketMessage>
 ) {
@@ -77,14 +83,14 @@ pub async fn handle_incoming_room_messages(
 
                 if let Ok(parsed) = serde_json::from_str::<RoomReceiverMessage>(&text) { // ✅ Deserialize JSON
                     
-                    if let Err(e) = incoming_tx.send(
+                    if let Err(e) = incoming_tx.send(room_event(
                         Event::IncomingRoomMessageEvent(
                             RoomReceiverMessage {
                                     username: parsed.username,
                                     content: parsed.content,
                                     is_join_leave_msg: parsed.is_join_leave_msg
                             }
-                        )
+                        ), &room_name)
                     ) {
                         eprintln!("Failed to send event: {}", e);
                     }
@@ -95,8 +101,8 @@ pub async fn handle_incoming_room_messages(
             }
             Ok(Message::Binary(bytes)) => {
                 if bytes==AckType::ServerAck.byte(){
-                    if let Err(e) = incoming_tx.send(
-                        Event::IncomingRoomMessageAckEvent(AckType::ServerAck)
+                    if let Err(e) = incoming_tx.send(room_event(
+                        Event::IncomingRoomMessageAckEvent(AckType::ServerAck), &room_name)
                     ) {
                         eprintln!("Failed to send event: {}", e);
                     }
@@ -104,8 +110,8 @@ pub async fn handle_incoming_room_messages(
                 //For ROOMS, for now no list of acknowledgements by people, and only by server
                 //Sender Key Descriptor
                 else if bytes[0]==0x11{
-                    if let Err(e) = incoming_tx.send(
-                        Event::IncomingRoomSenderKeyMessageEvent(bytes)
+                    if let Err(e) = incoming_tx.send(room_event(
+                        Event::IncomingRoomSenderKeyMessageEvent(bytes), &room_name)
                     ) {
                         eprintln!("Failed to send event: {}", e);
                     }
@@ -117,12 +123,40 @@ pub async fn handle_incoming_room_messages(
                         Ok(name)=> name,
                         Err(err) => "".to_string()
                     };
-                    if let Err(e) = incoming_tx.send(
-                        Event::UnknownRotateRoomChainKeyEvent(u)
+                    if let Err(e) = incoming_tx.send(room_event(
+                        Event::UnknownRotateRoomChainKeyEvent(u), &room_name)
+                    ) {
+                        eprintln!("Failed to send event: {}", e);
+                    }
+                }
+                //Moderation informer [0x55][Action][Target Username or new Room Key]
+                else if bytes[0]==ROOM_MODERATION_BYTE && bytes.len()>=2{
+                    if let (Some(action), Ok(payload)) = (ModerationAction::from_byte(bytes[1]), String::from_utf8(bytes[2..].to_vec())){
+                        if let Err(e) = incoming_tx.send(room_event(
+                            Event::RoomModerationEvent(action, payload), &room_name)
+                        ) {
+                            eprintln!("Failed to send event: {}", e);
+                        }
+                    }
+                }
+                //Earlier messages a member sealed for us on joining
+                else if bytes[0]==ROOM_HISTORY_BYTE{
+                    if let Err(e) = incoming_tx.send(room_event(
+                        Event::IncomingRoomHistoryEvent(bytes), &room_name)
                     ) {
                         eprintln!("Failed to send event: {}", e);
                     }
                 }
+                //Room ownership transferred, with the new owner's username
+                else if bytes[0]==0x44{
+                    if let Ok(new_owner) = String::from_utf8(bytes[1..].to_vec()){
+                        if let Err(e) = incoming_tx.send(room_event(
+                            Event::RoomOwnershipTransferredEvent(new_owner), &room_name)
+                        ) {
+                            eprintln!("Failed to send event: {}", e);
+                        }
+                    }
+                }
                 
             }
             Ok(Message::Pong(bytes)) => {
@@ -133,8 +167,8 @@ pub async fn handle_incoming_room_messages(
                 if let Err(err) = socket_closer_tx.send(SocketMessage::Disconnect(DisconnectType::ROOM)){
                     println!("Couldnt send disconnect event");
                 }
-                if let Err(e) = incoming_tx.send(
-                    Event::ExitRoomChatEvent
+                if let Err(e) = incoming_tx.send(room_event(
+                    Event::ExitRoomChatEvent, &room_name)
                 ){
                     eprintln!("Couldnt exit room chat screen");
                 }
@@ -158,13 +192,7 @@ pub async fn handle_incoming_dm_messages(
                 if let Ok(parsed) = serde_json::from_str::<DmMessage>(&text) { // ✅ Deserialize JSON
 
                     if let Err(e) = incoming_tx.send(
-                        Event::IncomingDMMessageEvent(
-                            DmMessage {
-                                    username: parsed.username,
-                                    content: parsed.content,
-                                    is_online_offline_msg: parsed.is_online_offline_msg
-                            }
-                        )
+                        Event::IncomingDMMessageEvent(parsed)
                     ) {
                         eprintln!("Failed to send event: {}", e);
                     }
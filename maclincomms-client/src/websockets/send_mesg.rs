@@ -4,7 +4,7 @@ use futures_util::{SinkExt, stream::{SplitSink}};
 use tokio::{io::{AsyncRead, AsyncWrite}, sync::{Mutex}};
 use tokio_tungstenite::{tungstenite::{self, Message}, WebSocketStream};
 
-use crate::user_model::{DisconnectType, MessageType, RoomMessageType, RoomSenderMessage, SocketMessage, WhisperMode};
+use crate::user_model::{DisconnectType, MessageType, RoomMessageType, RoomSenderMessage, SocketMessage, WhisperMode, ROOM_MODERATION_BYTE};
 
 
 
@@ -81,6 +81,23 @@ pub async fn send_messages(
                             break;
                         }
                     }
+                    SocketMessage::RoomHistory(history_frame) => {
+                        if let Err(e) = write.send(Message::Binary(history_frame)).await {
+                            break;
+                        }
+                    }
+                    SocketMessage::RoomModeration(action, target) => {
+                        let moderation_bytes = [vec![ROOM_MODERATION_BYTE, action.byte()], target.into_bytes()].concat();
+                        if let Err(e) = write.send(Message::Binary(moderation_bytes)).await {
+                            break;
+                        }
+                    }
+                    SocketMessage::RoomOwnershipTransfer(new_owner) => {
+                        let transfer_bytes = [[0x44].to_vec(), new_owner.into_bytes()].concat();
+                        if let Err(e) = write.send(Message::Binary(transfer_bytes)).await {
+                            break;
+                        }
+                    }
                     SocketMessage::Join(MessageType::ROOM(RoomMessageType::SENDER(msg))) => {
                         let json_msg = serde_json::to_string(&msg).unwrap(); // Serialize to JSON
                         if let Err(e) = write.send(Message::Text(json_msg)).await {
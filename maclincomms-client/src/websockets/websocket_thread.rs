@@ -80,6 +80,7 @@ pub async fn start_worldchat_websocket_task(
 pub async fn start_roomchat_websocket_task(
     app: &mut MaclincommsApp,
     username: String, 
+    room_name: String,
     token: String, 
     endpoint: &'static str, 
     //chat_history: Arc<Mutex<Vec<(String, Line<'static>, String)>>>,
@@ -128,7 +129,7 @@ pub async fn start_roomchat_websocket_task(
 
 
         // Handle incoming messages in a separate task
-        let read_task = tokio::spawn(receive_mesg::handle_incoming_room_messages(read, incoming_tx, socket_closer_tx));
+        let read_task = tokio::spawn(receive_mesg::handle_incoming_room_messages(read, room_name, incoming_tx, socket_closer_tx));
 
 
         // Receive from Ui channel and send messages
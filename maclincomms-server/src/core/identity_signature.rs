@@ -0,0 +1,75 @@
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use ed25519_dalek::{Signature, VerifyingKey};
+
+
+//Signed along with both keys so the signature can't stand for anything else
+const IDENTITY_ROTATION_CONTEXT: &[u8] = b"maclincomms-identity-rotation";
+
+
+pub fn identity_rotation_message(old_identity_key: [u8;32], new_identity_key: [u8;32]) -> Vec<u8> {
+    [IDENTITY_ROTATION_CONTEXT, &old_identity_key, &new_identity_key].concat()
+}
+
+//XEdDSA verification: the X25519 identity key is read as the Ed25519 key with a cleared sign bit
+pub fn verify_identity_rotation(old_identity_key: [u8;32], new_identity_key: [u8;32], signature: &[u8]) -> bool {
+    let Some(edwards_key) = MontgomeryPoint(old_identity_key).to_edwards(0) else {
+        return false;
+    };
+    let (Ok(verifying_key), Ok(signature)) = (VerifyingKey::from_bytes(&edwards_key.compress().to_bytes()), Signature::from_slice(signature)) else {
+        return false;
+    };
+    verifying_key.verify_strict(&identity_rotation_message(old_identity_key, new_identity_key), &signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Same vector as the client's identity_pair tests: identity keys from [0x11;32] and [0x22;32], zero XEdDSA nonce
+    const OLD_KEY: &str = "7b4e909bbe7ffe44c465a220037d608ee35897d31ef972f07f74892cb0f73f13";
+    const NEW_KEY: &str = "0faa684ed28867b97f4a6a2dee5df8ce974e76b7018e3f22a1c4cf2678570f20";
+    const SIGNATURE: &str = "2d4d37e7ca9bec4556391ca8769cd03c3bedd6715af2a92ce91f6b9774df4f1e2a73abf640bef101f37e55a18aa1c1821cc0806eb22ce1c659de16563e666300";
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    fn key(hex: &str) -> [u8;32] {
+        unhex(hex).try_into().unwrap()
+    }
+
+    #[test]
+    fn the_client_vector_verifies() {
+        assert!(verify_identity_rotation(key(OLD_KEY), key(NEW_KEY), &unhex(SIGNATURE)));
+    }
+
+    #[test]
+    fn a_wrong_key_doesnt_verify() {
+        let mut wrong_key = key(OLD_KEY);
+        wrong_key[0] ^= 1;
+        assert!(!verify_identity_rotation(wrong_key, key(NEW_KEY), &unhex(SIGNATURE)));
+
+        let mut wrong_new_key = key(NEW_KEY);
+        wrong_new_key[0] ^= 1;
+        assert!(!verify_identity_rotation(key(OLD_KEY), wrong_new_key, &unhex(SIGNATURE)));
+    }
+
+    #[test]
+    fn a_truncated_signature_doesnt_verify() {
+        let signature = unhex(SIGNATURE);
+        assert!(!verify_identity_rotation(key(OLD_KEY), key(NEW_KEY), &signature[..63]));
+        assert!(!verify_identity_rotation(key(OLD_KEY), key(NEW_KEY), &[]));
+    }
+
+    #[test]
+    fn swapped_keys_dont_verify() {
+        assert!(!verify_identity_rotation(key(NEW_KEY), key(OLD_KEY), &unhex(SIGNATURE)));
+    }
+
+    #[test]
+    fn a_tampered_signature_doesnt_verify() {
+        let mut signature = unhex(SIGNATURE);
+        signature[10] ^= 1;
+        assert!(!verify_identity_rotation(key(OLD_KEY), key(NEW_KEY), &signature));
+    }
+}
@@ -1,3 +1,4 @@
 pub mod hashing_data;
 pub mod encoding_token;
-pub mod decoding_header;
\ No newline at end of file
+pub mod decoding_header;
+pub mod identity_signature;
\ No newline at end of file
@@ -76,6 +76,43 @@ pub async fn insert_auth_data(user: Json<User_Auth_Table>) -> Result< (), reqwes
 }
 
 
+pub async fn update_identity_key(username: &str, public_identity_key: &str) -> Result< (), reqwest::Error > {
+
+    
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "USER_AUTH",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+    let body = json!({
+        "public_identity_key": public_identity_key
+    });
+
+   
+    let client = Client::new();
+    let res = client
+        .patch(url)
+        .query(&[("username", format!("eq.{username}"))])
+        .json(&body)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);     
+
+    Ok(())
+}
+
+
 //Keeping Alive Supabase DB during long inactivity periods
 pub async fn keep_alive_supabase() -> Result< (), reqwest::Error > {
 
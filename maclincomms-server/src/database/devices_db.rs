@@ -0,0 +1,298 @@
+use actix_web::web::Json;
+use reqwest::{header::{HeaderMap, AUTHORIZATION}, Client};
+use serde_json::json;
+
+use crate::secret_store::get_secret;
+
+use crate::models::device_data::{Device_Links_Table, Revoked_Devices_Table, User_Devices_Table};
+
+
+
+pub async fn get_user_devices(username: &str) -> Result< Vec<User_Devices_Table>, reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "USER_DEVICES",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+
+    let client = Client::new();
+    let res = client
+        .get(url)
+        .query(&[
+            ("username", format!("eq.{username}")),
+            ("order", "added_at.asc".to_string())
+        ])
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    let data = res.json::<Vec<User_Devices_Table>>().await?;
+    println!("{:#?}", data);
+    Ok(data)
+}
+
+
+
+pub async fn insert_user_device(device: Json<User_Devices_Table>) -> Result< (), reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "USER_DEVICES",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+
+    let client = Client::new();
+    let res = client
+        .post(url)
+        .json(&device)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    Ok(())
+}
+
+
+
+pub async fn remove_user_device(username: &str, device_id: &str) -> Result< (), reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "USER_DEVICES",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+
+    let client = Client::new();
+    let res = client
+        .delete(url)
+        .query(&[
+            ("username", format!("eq.{username}")),
+            ("device_id", format!("eq.{device_id}"))
+        ])
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    Ok(())
+}
+
+
+pub async fn update_device_identity_key(username: &str, device_id: &str, public_identity_key: &str) -> Result< (), reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "USER_DEVICES",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+    let body = json!({
+        "public_identity_key": public_identity_key
+    });
+
+
+    let client = Client::new();
+    let res = client
+        .patch(url)
+        .query(&[
+            ("username", format!("eq.{username}")),
+            ("device_id", format!("eq.{device_id}"))
+        ])
+        .json(&body)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    Ok(())
+}
+
+
+
+pub async fn insert_revoked_device(device: Json<Revoked_Devices_Table>) -> Result< (), reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "REVOKED_DEVICES",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+    headers.insert("Prefer", "resolution=ignore-duplicates".parse().unwrap());
+
+
+    let client = Client::new();
+    let res = client
+        .post(url)
+        .json(&device)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    Ok(())
+}
+
+
+
+pub async fn get_revoked_device(username: &str, device_id: &str) -> Result< Vec<Revoked_Devices_Table>, reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "REVOKED_DEVICES",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+
+    let client = Client::new();
+    let res = client
+        .get(url)
+        .query(&[
+            ("username", format!("eq.{username}")),
+            ("device_id", format!("eq.{device_id}"))
+        ])
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    let data = res.json::<Vec<Revoked_Devices_Table>>().await?;
+    Ok(data)
+}
+
+
+
+pub async fn upsert_device_link(link: Json<Device_Links_Table>) -> Result< (), reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "DEVICE_LINKS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+    headers.insert("Prefer", "resolution=merge-duplicates".parse().unwrap());
+
+
+    let client = Client::new();
+    let res = client
+        .post(url)
+        .json(&link)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    Ok(())
+}
+
+
+
+// A DELETE returning the deleted row, so two devices racing for the same link can't both get it
+pub async fn take_device_link(username: &str, link_id: &str) -> Result< Option<Device_Links_Table>, reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "DEVICE_LINKS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+    headers.insert("Prefer", "return=representation".parse().unwrap());
+
+
+    let client = Client::new();
+    let res = client
+        .delete(url)
+        .query(&[
+            ("username", format!("eq.{username}")),
+            ("link_id", format!("eq.{link_id}"))
+        ])
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    let data = res.json::<Vec<Device_Links_Table>>().await?;
+    Ok(data.into_iter().next())
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::sync::{broadcast, mpsc::Sender};
+
+use crate::models::notification_data::NotificationData;
+
+use super::notification_broker::{BrokerResult, NotificationBroker};
+
+
+// Same expiry as the Redis lists
+const QUEUE_TTL: Duration = Duration::from_secs(60 * 60 * 24);
+
+
+/// Single-node broker: one tokio broadcast channel per receiving user for realtime delivery,
+/// and in-process lists with a 24h TTL (refreshed on every push) for offline users.
+pub struct InMemoryBroker {
+    channels: Mutex<HashMap<String, broadcast::Sender<NotificationData>>>,
+    queues: Mutex<HashMap<String, (Instant, Vec<String>)>>
+}
+
+impl InMemoryBroker {
+
+    pub fn new() -> Self {
+        Self {
+            channels: Mutex::new(HashMap::new()),
+            queues: Mutex::new(HashMap::new())
+        }
+    }
+
+    fn list_key(notification: &NotificationData) -> String {
+        format!("NOTIFICATIONS<{},{}>", notification.from, notification.to)
+    }
+}
+
+
+#[async_trait]
+impl NotificationBroker for InMemoryBroker {
+
+    async fn publish_notification(&self, notification: NotificationData) -> BrokerResult<i32> {
+        let channels = self.channels.lock().unwrap();
+
+        match channels.get(&notification.to) {
+            Some(channel) => Ok(channel.send(notification).map(|subscribers| subscribers as i32).unwrap_or(0)),
+            None => Ok(0)
+        }
+    }
+
+    async fn queue_notification(&self, notification: NotificationData) -> BrokerResult<()> {
+        let json_payload = serde_json::to_string(&notification).unwrap();
+        let list_key = Self::list_key(&notification);
+
+        let mut queues = self.queues.lock().unwrap();
+        let now = Instant::now();
+
+        // Dropping expired lists
+        queues.retain(|_, (expires_at, _)| *expires_at > now);
+
+        let (expires_at, list) = queues.entry(list_key).or_insert((now + QUEUE_TTL, Vec::new()));
+        list.push(json_payload);
+        *expires_at = now + QUEUE_TTL;
+
+        Ok(())
+    }
+
+    async fn subscribe_to_notifications(&self, username: &str, sender: Sender<NotificationData>) -> BrokerResult<()> {
+        let mut receiver = {
+            let mut channels = self.channels.lock().unwrap();
+            channels.entry(username.to_string())
+                .or_insert_with(|| broadcast::channel(100).0)
+                .subscribe()
+        };
+
+        loop {
+            tokio::select! {
+                // SSE client went away, so stop counting as a live subscriber
+                _ = sender.closed() => break,
+                received = receiver.recv() => match received {
+                    Ok(notification) => {
+                        if sender.send(notification).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        eprintln!("Subscriber {} lagged behind by {} notifications", username, skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break
+                }
+            }
+        }
+
+        drop(receiver);
+        let mut channels = self.channels.lock().unwrap();
+        if channels.get(username).is_some_and(|channel| channel.receiver_count() == 0) {
+            channels.remove(username);
+        }
+
+        Ok(())
+    }
+
+    async fn retrieve_queued_notifications(&self, username: &str) -> BrokerResult<HashMap<String, Vec<String>>> {
+        let mut queues = self.queues.lock().unwrap();
+        let now = Instant::now();
+        let suffix = format!(",{}>", username);
+
+        let keys: Vec<String> = queues.keys()
+            .filter(|key| key.starts_with("NOTIFICATIONS<") && key.ends_with(&suffix))
+            .cloned()
+            .collect();
+
+        let mut result_map = HashMap::new();
+
+        for key in keys {
+            //Deleting key after retrieving all notifications from it
+            if let Some((expires_at, items)) = queues.remove(&key) {
+                if expires_at > now {
+                    result_map.insert(key, items);
+                }
+            }
+        }
+
+        Ok(result_map)
+    }
+
+    async fn keep_alive(&self) -> BrokerResult<()> {
+        Ok(())
+    }
+}
@@ -1,4 +1,12 @@
 pub mod auth_db;
 pub mod rooms_db;
 pub mod dms_db;
-pub mod redis_db;
\ No newline at end of file
+pub mod redis_db;
+pub mod prekeys_db;
+pub mod devices_db;
+pub mod storage;
+pub mod supabase_storage;
+pub mod sqlite_storage;
+pub mod notification_broker;
+pub mod redis_broker;
+pub mod memory_broker;
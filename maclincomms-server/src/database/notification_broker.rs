@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::Sender;
+
+use crate::models::notification_data::NotificationData;
+use crate::secret_store::get_secret;
+
+use super::{memory_broker::InMemoryBroker, redis_broker::RedisBroker};
+
+
+#[derive(Debug)]
+pub enum BrokerError {
+    Redis(redis::RedisError)
+}
+
+impl fmt::Display for BrokerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BrokerError::Redis(err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl From<redis::RedisError> for BrokerError {
+    fn from(err: redis::RedisError) -> Self {
+        BrokerError::Redis(err)
+    }
+}
+
+pub type BrokerResult<T> = Result<T, BrokerError>;
+
+
+/// Realtime delivery and offline queueing of notifications.
+/// Channels and queues are keyed `NOTIFICATIONS<from,to>`; queued lists expire 24h after their last push.
+#[async_trait]
+pub trait NotificationBroker: Send + Sync {
+
+    // Returns the number of live subscribers the notification was delivered to (0 => receiver offline)
+    async fn publish_notification(&self, notification: NotificationData) -> BrokerResult<i32>;
+
+    async fn queue_notification(&self, notification: NotificationData) -> BrokerResult<()>;
+
+    // Forwards every notification addressed to `username` into `sender` until the subscriber goes away
+    async fn subscribe_to_notifications(&self, username: &str, sender: Sender<NotificationData>) -> BrokerResult<()>;
+
+    // Drains all queued lists for `username`, keyed by list name
+    async fn retrieve_queued_notifications(&self, username: &str) -> BrokerResult<HashMap<String, Vec<String>>>;
+
+    async fn keep_alive(&self) -> BrokerResult<()>;
+}
+
+
+/// Picks the broker from the NOTIFICATION_BROKER secret ("redis" by default, or "memory").
+pub fn broker_from_secrets() -> Arc<dyn NotificationBroker> {
+
+    let broker = get_secret("NOTIFICATION_BROKER").unwrap_or("redis".to_string());
+
+    match broker.to_lowercase().as_str() {
+        "memory" => {
+            println!("Using in-memory notification broker");
+            Arc::new(InMemoryBroker::new())
+        }
+        _ => {
+            println!("Using Redis notification broker");
+            Arc::new(RedisBroker)
+        }
+    }
+}
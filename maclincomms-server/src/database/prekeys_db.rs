@@ -0,0 +1,192 @@
+use reqwest::{header::{HeaderMap, AUTHORIZATION}, Client};
+use serde_json::{json, Value};
+use crate::secret_store::get_secret;
+use crate::models::prekey_data::{OneTimePrekey, PrekeysTable};
+
+
+
+// Replaces the user's signed prekey row
+pub async fn upsert_prekeys(prekeys: PrekeysTable) -> Result< (), reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "PREKEYS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+    headers.insert("Prefer", "resolution=merge-duplicates".parse().unwrap());
+
+
+    let client = Client::new();
+    let res = client
+        .post(url)
+        .json(&prekeys)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    Ok(())
+}
+
+
+
+pub async fn get_prekeys(username: &str) -> Result< Vec<PrekeysTable>, reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "PREKEYS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+    let client = Client::new();
+    let res = client
+        .get(url)
+        .query(&[("username", format!("eq.{username}"))])
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    let data = res.json::<Vec<PrekeysTable>>().await?;
+    println!("{:#?}", data);
+    Ok(data)
+}
+
+
+
+pub async fn insert_one_time_prekeys(username: &str, one_time_prekeys: Vec<OneTimePrekey>) -> Result< (), reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "ONE_TIME_PREKEYS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+
+    let body: Vec<Value> = one_time_prekeys.into_iter()
+        .map(|one_time_prekey| json!({
+            "username": username,
+            "key_id": one_time_prekey.key_id,
+            "prekey": one_time_prekey.prekey
+        }))
+        .collect();
+
+
+    let client = Client::new();
+    let res = client
+        .post(url)
+        .json(&body)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    Ok(())
+}
+
+
+
+pub async fn count_one_time_prekeys(username: &str) -> Result< usize, reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "ONE_TIME_PREKEYS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+    let client = Client::new();
+    let res = client
+        .get(url)
+        .query(&[
+            ("select", "key_id"),
+            ("username", &format!("eq.{username}"))
+        ])
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    let data = res.json::<Vec<Value>>().await?;
+    Ok(data.len())
+}
+
+
+
+// Uses the claim_one_time_prekey rpc, which deletes the user's oldest one time prekey and returns it
+pub async fn claim_one_time_prekey(username: &str) -> Result< Option<OneTimePrekey>, reqwest::Error > {
+
+
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "rpc/claim_one_time_prekey",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+
+    let body = json!({
+        "user_name": username
+    });
+
+
+    let client = Client::new();
+    let res = client
+        .post(url)
+        .json(&body)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);
+
+    let data = res.json::<Vec<OneTimePrekey>>().await?;
+    println!("{:#?}", data);
+    Ok(data.into_iter().next())
+}
@@ -0,0 +1,38 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc::Sender;
+
+use crate::models::notification_data::NotificationData;
+
+use super::{notification_broker::{BrokerResult, NotificationBroker}, redis_db};
+
+
+/// Broker backed by Redis pub/sub and lists (Upstash in the hosted deployment).
+/// The connection URL is read from the REDIS_URL secret on every call.
+pub struct RedisBroker;
+
+#[async_trait]
+impl NotificationBroker for RedisBroker {
+
+    async fn publish_notification(&self, notification: NotificationData) -> BrokerResult<i32> {
+        Ok(redis_db::publish_notification(notification).await?)
+    }
+
+    async fn queue_notification(&self, notification: NotificationData) -> BrokerResult<()> {
+        Ok(redis_db::queue_notification(notification).await?)
+    }
+
+    async fn subscribe_to_notifications(&self, username: &str, sender: Sender<NotificationData>) -> BrokerResult<()> {
+        Ok(redis_db::subscribe_to_notifications(username, sender).await?)
+    }
+
+    async fn retrieve_queued_notifications(&self, username: &str) -> BrokerResult<HashMap<String, Vec<String>>> {
+        Ok(redis_db::retrieve_queued_notifications(username).await?)
+    }
+
+    async fn keep_alive(&self) -> BrokerResult<()> {
+        redis_db::keep_alive_upstash().await?;
+        Ok(())
+    }
+}
@@ -57,10 +57,10 @@ pub async fn queue_notification(notification: NotificationData) -> redis::RedisR
     let list_key = format!("NOTIFICATIONS<{},{}>", notification.from, notification.to);
 
     // Push the notification to the list
-    con.rpush(&list_key, json_payload).await?;
+    con.rpush::<_, _, ()>(&list_key, json_payload).await?;
     
     // Set a TTL of 24 hours (86400 seconds) for the list key
-    con.expire(&list_key, 86400).await?;
+    con.expire::<_, ()>(&list_key, 86400).await?;
 
     Ok(())
     
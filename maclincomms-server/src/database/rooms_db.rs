@@ -2,7 +2,7 @@ use actix_web::web::Json;
 use reqwest::{header::{HeaderMap, AUTHORIZATION}, Client};
 use serde_json::json;
 use crate::secret_store::get_secret;
-use crate::models::room_data::Rooms_Table;
+use crate::models::room_data::{Room_Invites_Table, Rooms_Table};
 
 
 pub async fn get_room_data(roomname: &str) -> Result< Vec<Rooms_Table>, reqwest::Error > {
@@ -40,6 +40,45 @@ pub async fn get_room_data(roomname: &str) -> Result< Vec<Rooms_Table>, reqwest:
 
 
 
+pub async fn get_listed_rooms(offset: usize, limit: usize) -> Result< Vec<Rooms_Table>, reqwest::Error > {
+
+    
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "ROOMS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+   
+    let client = Client::new();
+    let res = client
+        .get(url)
+        .query(&[
+            ("settings->>listed", "eq.true".to_owned()),
+            ("order", "room_name.asc".to_owned()),
+            ("offset", offset.to_string()),
+            ("limit", limit.to_string())
+        ])
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);     
+
+    let data = res.json::<Vec<Rooms_Table>>().await?;
+    Ok(data)
+}
+
+
+
+
 pub async fn insert_room_data(room: Json<Rooms_Table>) -> Result< (), reqwest::Error > {
 
     
@@ -149,6 +188,156 @@ pub async fn remove_member_from_room(username: &str, roomname: &str) -> Result<
 }
 
 
+pub async fn update_room_owner(roomname: &str, new_owner: &str) -> Result< (), reqwest::Error > {
+
+    
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "ROOMS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+    let body = json!({
+        "room_owner": new_owner
+    });
+
+   
+    let client = Client::new();
+    let res = client
+        .patch(url)
+        .query(&[("room_name", format!("eq.{roomname}"))])
+        .json(&body)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);     
+
+    Ok(())
+}
+
+
+pub async fn update_room_moderation(roomname: &str, moderators: &[String], banned: &[String], muted: &[String]) -> Result< (), reqwest::Error > {
+
+    
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "ROOMS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+    let body = json!({
+        "moderators": moderators,
+        "banned": banned,
+        "muted": muted
+    });
+
+   
+    let client = Client::new();
+    let res = client
+        .patch(url)
+        .query(&[("room_name", format!("eq.{roomname}"))])
+        .json(&body)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);     
+
+    Ok(())
+}
+
+
+pub async fn update_room_key(roomname: &str, room_key: &str) -> Result< (), reqwest::Error > {
+
+    
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "ROOMS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+    let body = json!({
+        "room_key": room_key
+    });
+
+   
+    let client = Client::new();
+    let res = client
+        .patch(url)
+        .query(&[("room_name", format!("eq.{roomname}"))])
+        .json(&body)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);     
+
+    Ok(())
+}
+
+
+pub async fn update_room_join_requests(roomname: &str, join_requests: &[String]) -> Result< (), reqwest::Error > {
+
+    
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "ROOMS",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+    let body = json!({
+        "join_requests": join_requests
+    });
+
+   
+    let client = Client::new();
+    let res = client
+        .patch(url)
+        .query(&[("room_name", format!("eq.{roomname}"))])
+        .json(&body)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);     
+
+    Ok(())
+}
+
+
 pub async fn delete_room_data(roomname: &str) -> Result< (), reqwest::Error > {
 
     
@@ -181,3 +370,109 @@ pub async fn delete_room_data(roomname: &str) -> Result< (), reqwest::Error > {
 }
 
 
+
+pub async fn insert_room_invite(invite: Json<Room_Invites_Table>) -> Result< (), reqwest::Error > {
+
+    
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "ROOM_INVITES",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+
+   
+    let client = Client::new();
+    let res = client
+        .post(url)
+        .json(&invite)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);     
+
+    Ok(())
+}
+
+
+pub async fn get_room_invite(invite_token: &str) -> Result< Vec<Room_Invites_Table>, reqwest::Error > {
+
+    
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "ROOM_INVITES",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+   
+    let client = Client::new();
+    let res = client
+        .get(url)
+        .query(&[("invite_token", format!("eq.{invite_token}"))])
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);     
+
+    let data = res.json::<Vec<Room_Invites_Table>>().await?;
+    Ok(data)
+}
+
+
+//Only bumps the count if nobody else redeemed since `current_uses` was read, true if this call got the use
+pub async fn increment_room_invite_uses(invite_token: &str, current_uses: i64) -> Result< bool, reqwest::Error > {
+
+    
+    let url = match get_secret("SUPABASE_URL"){
+        Some(url) => url + "ROOM_INVITES",
+        None => "".to_owned()
+    };
+    let api_key = match get_secret("SUPABASE_API_KEY"){
+        Some(key) => key,
+        None => "".to_owned()
+    };
+
+
+    let mut headers = HeaderMap::new();
+
+    headers.insert("apikey", api_key.parse().unwrap());
+    headers.insert(AUTHORIZATION, format!("Bearer {}", api_key).parse().unwrap());
+    headers.insert("Prefer", "return=representation".parse().unwrap());
+
+    let body = json!({
+        "uses": current_uses + 1
+    });
+
+   
+    let client = Client::new();
+    let res = client
+        .patch(url)
+        .query(&[
+            ("invite_token", format!("eq.{invite_token}")),
+            ("uses", format!("eq.{current_uses}"))
+        ])
+        .json(&body)
+        .headers(headers)
+        .send()
+        .await?;
+    println!("{:#?}", res);     
+
+    let updated = res.json::<Vec<Room_Invites_Table>>().await?;
+    Ok(!updated.is_empty())
+}
@@ -0,0 +1,656 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+
+use crate::models::{device_data::{Device_Links_Table, Revoked_Devices_Table, User_Devices_Table}, dms_data::{Blocked_List, ChatData, DmUser_Data, Dms_List}, prekey_data::{OneTimePrekey, PrekeyBundle, UploadPrekeys}, room_data::{Room_Invites_Table, Rooms_Table}, user_auth::User_Auth_Table};
+
+use super::storage::{Storage, StorageResult};
+
+
+/// Embedded SQLite storage so the server can run without any outside database.
+/// Mirrors the Supabase tables; array columns are stored as JSON text.
+pub struct SqliteStorage {
+    conn: Mutex<Connection>
+}
+
+impl SqliteStorage {
+
+    pub fn open(path: &str) -> StorageResult<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> StorageResult<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> StorageResult<Self> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS USER_AUTH (
+                username TEXT PRIMARY KEY,
+                password_hash TEXT NOT NULL,
+                password_salt TEXT NOT NULL,
+                public_identity_key TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS USER_DEVICES (
+                username TEXT NOT NULL,
+                device_id TEXT NOT NULL,
+                device_name TEXT NOT NULL,
+                public_identity_key TEXT NOT NULL,
+                added_at INTEGER NOT NULL,
+                PRIMARY KEY (username, device_id)
+            );
+            CREATE TABLE IF NOT EXISTS REVOKED_DEVICES (
+                username TEXT NOT NULL,
+                device_id TEXT NOT NULL,
+                revoked_at INTEGER NOT NULL,
+                PRIMARY KEY (username, device_id)
+            );
+            CREATE TABLE IF NOT EXISTS DEVICE_LINKS (
+                username TEXT NOT NULL,
+                link_id TEXT NOT NULL,
+                sealed_bundle TEXT NOT NULL,
+                expires_at INTEGER NOT NULL,
+                PRIMARY KEY (username, link_id)
+            );
+            CREATE TABLE IF NOT EXISTS PREKEYS (
+                username TEXT PRIMARY KEY,
+                identity_signing_key TEXT NOT NULL,
+                signed_prekey_id INTEGER NOT NULL,
+                signed_prekey TEXT NOT NULL,
+                signed_prekey_signature TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS ONE_TIME_PREKEYS (
+                username TEXT NOT NULL,
+                key_id INTEGER NOT NULL,
+                prekey TEXT NOT NULL,
+                PRIMARY KEY (username, key_id)
+            );
+            CREATE TABLE IF NOT EXISTS DMS (
+                username TEXT PRIMARY KEY,
+                dms_list TEXT NOT NULL DEFAULT '[]',
+                blocked_list TEXT NOT NULL DEFAULT '[]',
+                chat_history TEXT NOT NULL DEFAULT '[]'
+            );
+            CREATE TABLE IF NOT EXISTS ROOMS (
+                room_name TEXT PRIMARY KEY,
+                room_key TEXT NOT NULL,
+                room_owner TEXT NOT NULL,
+                room_members TEXT NOT NULL DEFAULT '[]',
+                members_keys TEXT NOT NULL DEFAULT '[]',
+                persistent INTEGER NOT NULL DEFAULT 0,
+                moderators TEXT NOT NULL DEFAULT '[]',
+                banned TEXT NOT NULL DEFAULT '[]',
+                muted TEXT NOT NULL DEFAULT '[]',
+                settings TEXT NOT NULL DEFAULT '{}',
+                join_requests TEXT NOT NULL DEFAULT '[]'
+            );
+            CREATE TABLE IF NOT EXISTS ROOM_INVITES (
+                invite_token TEXT PRIMARY KEY,
+                room_name TEXT NOT NULL,
+                room_key TEXT NOT NULL,
+                created_by TEXT NOT NULL,
+                expires_at INTEGER NOT NULL,
+                max_uses INTEGER NOT NULL,
+                uses INTEGER NOT NULL DEFAULT 0
+            );"
+        )?;
+
+        // Databases created before persistent rooms, moderation, room settings and join requests lack these columns, they already exist otherwise
+        for column in [
+            "persistent INTEGER NOT NULL DEFAULT 0",
+            "moderators TEXT NOT NULL DEFAULT '[]'",
+            "banned TEXT NOT NULL DEFAULT '[]'",
+            "muted TEXT NOT NULL DEFAULT '[]'",
+            "settings TEXT NOT NULL DEFAULT '{}'",
+            "join_requests TEXT NOT NULL DEFAULT '[]'"
+        ] {
+            let _ = conn.execute(&format!("ALTER TABLE ROOMS ADD COLUMN {}", column), []);
+        }
+
+        Ok(Self {
+            conn: Mutex::new(conn)
+        })
+    }
+
+    fn get_list(conn: &Connection, table: &str, column: &str, key_column: &str, key: &str) -> StorageResult<Option<Vec<String>>> {
+        let query = format!("SELECT {} FROM {} WHERE {} = ?1", column, table, key_column);
+        let json_list: Option<String> = conn.query_row(&query, params![key], |row| row.get(0)).optional()?;
+
+        match json_list {
+            Some(list) => Ok(Some(serde_json::from_str(&list)?)),
+            None => Ok(None)
+        }
+    }
+
+    fn set_list(conn: &Connection, table: &str, column: &str, key_column: &str, key: &str, list: &Vec<String>) -> StorageResult<()> {
+        let query = format!("UPDATE {} SET {} = ?1 WHERE {} = ?2", table, column, key_column);
+        conn.execute(&query, params![serde_json::to_string(list)?, key])?;
+        Ok(())
+    }
+
+    // Rooms matching an SQL condition, the list columns are stored as JSON text
+    fn select_rooms(conn: &Connection, condition: &str, params: impl rusqlite::Params) -> StorageResult<Vec<Rooms_Table>> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT room_name, room_key, room_owner, room_members, members_keys, persistent, moderators, banned, muted, settings, join_requests FROM ROOMS WHERE {}",
+            condition
+        ))?;
+        let rows = stmt.query_map(params, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, bool>(5)?,
+                row.get::<_, String>(6)?,
+                row.get::<_, String>(7)?,
+                row.get::<_, String>(8)?,
+                row.get::<_, String>(9)?,
+                row.get::<_, String>(10)?
+            ))
+        })?;
+
+        let mut rooms = Vec::new();
+        for row in rows {
+            let (room_name, room_key, room_owner, room_members, members_keys, persistent, moderators, banned, muted, settings, join_requests) = row?;
+            rooms.push(Rooms_Table {
+                room_name,
+                room_key,
+                room_owner,
+                room_members: serde_json::from_str(&room_members)?,
+                members_keys: serde_json::from_str(&members_keys)?,
+                persistent,
+                moderators: serde_json::from_str(&moderators)?,
+                banned: serde_json::from_str(&banned)?,
+                muted: serde_json::from_str(&muted)?,
+                settings: serde_json::from_str(&settings)?,
+                join_requests: serde_json::from_str(&join_requests)?
+            });
+        }
+        Ok(rooms)
+    }
+
+    // Equivalent of postgres array_append on a JSON list column
+    fn append_to_list(&self, table: &str, column: &str, key_column: &str, key: &str, value: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        if let Some(mut list) = Self::get_list(&conn, table, column, key_column, key)? {
+            list.push(value.to_string());
+            Self::set_list(&conn, table, column, key_column, key, &list)?;
+        }
+        Ok(())
+    }
+
+    // Equivalent of postgres array_remove on a JSON list column
+    fn remove_from_list(&self, table: &str, column: &str, key_column: &str, key: &str, value: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        if let Some(mut list) = Self::get_list(&conn, table, column, key_column, key)? {
+            list.retain(|item| item != value);
+            Self::set_list(&conn, table, column, key_column, key, &list)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[async_trait]
+impl Storage for SqliteStorage {
+
+    async fn get_auth_data(&self, username: &str) -> StorageResult<Vec<User_Auth_Table>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT username, password_hash, password_salt, public_identity_key FROM USER_AUTH WHERE username = ?1"
+        )?;
+        let rows = stmt.query_map(params![username], |row| {
+            Ok(User_Auth_Table {
+                username: row.get(0)?,
+                password_hash: row.get(1)?,
+                password_salt: row.get(2)?,
+                public_identity_key: row.get(3)?
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    async fn insert_auth_data(&self, user: User_Auth_Table) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO USER_AUTH (username, password_hash, password_salt, public_identity_key) VALUES (?1, ?2, ?3, ?4)",
+            params![user.username, user.password_hash, user.password_salt, user.public_identity_key]
+        )?;
+        Ok(())
+    }
+
+    async fn update_identity_key(&self, username: &str, public_identity_key: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE USER_AUTH SET public_identity_key = ?2 WHERE username = ?1",
+            params![username, public_identity_key]
+        )?;
+        Ok(())
+    }
+
+    async fn get_user_devices(&self, username: &str) -> StorageResult<Vec<User_Devices_Table>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT username, device_id, device_name, public_identity_key, added_at FROM USER_DEVICES WHERE username = ?1 ORDER BY added_at"
+        )?;
+        let rows = stmt.query_map(params![username], |row| {
+            Ok(User_Devices_Table {
+                username: row.get(0)?,
+                device_id: row.get(1)?,
+                device_name: row.get(2)?,
+                public_identity_key: row.get(3)?,
+                added_at: row.get(4)?
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    async fn insert_user_device(&self, device: User_Devices_Table) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO USER_DEVICES (username, device_id, device_name, public_identity_key, added_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![device.username, device.device_id, device.device_name, device.public_identity_key, device.added_at]
+        )?;
+        Ok(())
+    }
+
+    async fn remove_user_device(&self, username: &str, device_id: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM USER_DEVICES WHERE username = ?1 AND device_id = ?2",
+            params![username, device_id]
+        )?;
+        Ok(())
+    }
+
+    async fn update_device_identity_key(&self, username: &str, device_id: &str, public_identity_key: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE USER_DEVICES SET public_identity_key = ?3 WHERE username = ?1 AND device_id = ?2",
+            params![username, device_id, public_identity_key]
+        )?;
+        Ok(())
+    }
+
+    async fn insert_revoked_device(&self, device: Revoked_Devices_Table) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO REVOKED_DEVICES (username, device_id, revoked_at) VALUES (?1, ?2, ?3)",
+            params![device.username, device.device_id, device.revoked_at]
+        )?;
+        Ok(())
+    }
+
+    async fn is_device_revoked(&self, username: &str, device_id: &str) -> StorageResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let revoked = conn.query_row(
+            "SELECT 1 FROM REVOKED_DEVICES WHERE username = ?1 AND device_id = ?2",
+            params![username, device_id],
+            |_| Ok(())
+        ).optional()?;
+        Ok(revoked.is_some())
+    }
+
+    async fn insert_device_link(&self, link: Device_Links_Table) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO DEVICE_LINKS (username, link_id, sealed_bundle, expires_at) VALUES (?1, ?2, ?3, ?4)",
+            params![link.username, link.link_id, link.sealed_bundle, link.expires_at]
+        )?;
+        Ok(())
+    }
+
+    async fn take_device_link(&self, username: &str, link_id: &str, now: i64) -> StorageResult<Option<Device_Links_Table>> {
+        let conn = self.conn.lock().unwrap();
+        let link = conn.query_row(
+            "DELETE FROM DEVICE_LINKS WHERE username = ?1 AND link_id = ?2 RETURNING username, link_id, sealed_bundle, expires_at",
+            params![username, link_id],
+            |row| Ok(Device_Links_Table {
+                username: row.get(0)?,
+                link_id: row.get(1)?,
+                sealed_bundle: row.get(2)?,
+                expires_at: row.get(3)?
+            })
+        ).optional()?;
+
+        Ok(link.filter(|link| link.expires_at > now))
+    }
+
+    async fn upload_prekeys(&self, username: &str, prekeys: UploadPrekeys) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO PREKEYS (username, identity_signing_key, signed_prekey_id, signed_prekey, signed_prekey_signature) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![username, prekeys.identity_signing_key, prekeys.signed_prekey_id, prekeys.signed_prekey, prekeys.signed_prekey_signature]
+        )?;
+
+        for one_time_prekey in prekeys.one_time_prekeys {
+            conn.execute(
+                "INSERT OR REPLACE INTO ONE_TIME_PREKEYS (username, key_id, prekey) VALUES (?1, ?2, ?3)",
+                params![username, one_time_prekey.key_id, one_time_prekey.prekey]
+            )?;
+        }
+        Ok(())
+    }
+
+    async fn count_one_time_prekeys(&self, username: &str) -> StorageResult<usize> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM ONE_TIME_PREKEYS WHERE username = ?1",
+            params![username],
+            |row| row.get(0)
+        )?;
+        Ok(count as usize)
+    }
+
+    // Like the claim_one_time_prekey rpc, the oldest one time prekey is deleted as it is handed out
+    async fn get_prekey_bundle(&self, username: &str) -> StorageResult<Option<PrekeyBundle>> {
+        let conn = self.conn.lock().unwrap();
+        let bundle = conn.query_row(
+            "SELECT p.username, u.public_identity_key, p.identity_signing_key, p.signed_prekey_id, p.signed_prekey, p.signed_prekey_signature
+             FROM PREKEYS p JOIN USER_AUTH u ON u.username = p.username WHERE p.username = ?1",
+            params![username],
+            |row| {
+                Ok(PrekeyBundle {
+                    username: row.get(0)?,
+                    public_identity_key: row.get(1)?,
+                    identity_signing_key: row.get(2)?,
+                    signed_prekey_id: row.get(3)?,
+                    signed_prekey: row.get(4)?,
+                    signed_prekey_signature: row.get(5)?,
+                    one_time_prekey: None
+                })
+            }
+        ).optional()?;
+
+        let Some(mut bundle) = bundle else {
+            return Ok(None);
+        };
+
+        bundle.one_time_prekey = conn.query_row(
+            "SELECT key_id, prekey FROM ONE_TIME_PREKEYS WHERE username = ?1 ORDER BY rowid LIMIT 1",
+            params![username],
+            |row| Ok(OneTimePrekey { key_id: row.get(0)?, prekey: row.get(1)? })
+        ).optional()?;
+
+        if let Some(one_time_prekey) = &bundle.one_time_prekey {
+            conn.execute(
+                "DELETE FROM ONE_TIME_PREKEYS WHERE username = ?1 AND key_id = ?2",
+                params![username, one_time_prekey.key_id]
+            )?;
+        }
+        Ok(Some(bundle))
+    }
+
+    async fn insert_user_to_dms_table(&self, username: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("INSERT INTO DMS (username) VALUES (?1)", params![username])?;
+        Ok(())
+    }
+
+    // Like the append_dms_list rpc, accepting a request adds both users to each other's DMs list
+    async fn insert_user_to_dms_list(&self, username: &str, user_to_add: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+
+        for (user, contact) in [(username, user_to_add), (user_to_add, username)] {
+            if let Some(mut dms_list) = Self::get_list(&conn, "DMS", "dms_list", "username", user)? {
+                if !dms_list.iter().any(|dm_user| dm_user == contact) {
+                    dms_list.push(contact.to_string());
+                    Self::set_list(&conn, "DMS", "dms_list", "username", user, &dms_list)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_dms_list(&self, user1: &str, user2: &str) -> StorageResult<Vec<Dms_List>> {
+        let conn = self.conn.lock().unwrap();
+        let mut dms_lists = Vec::new();
+
+        for username in [user1, user2] {
+            if let Some(dms_list) = Self::get_list(&conn, "DMS", "dms_list", "username", username)? {
+                dms_lists.push(Dms_List {
+                    username: username.to_string(),
+                    dms_list
+                });
+            }
+        }
+        Ok(dms_lists)
+    }
+
+    async fn get_dms_list_data(&self, username: &str) -> StorageResult<Vec<DmUser_Data>> {
+        let conn = self.conn.lock().unwrap();
+        let dms_list = Self::get_list(&conn, "DMS", "dms_list", "username", username)?.unwrap_or_default();
+        let mut dms_data = Vec::new();
+
+        for dm_user in dms_list {
+            let key: Option<String> = conn.query_row(
+                "SELECT public_identity_key FROM USER_AUTH WHERE username = ?1",
+                params![dm_user],
+                |row| row.get(0)
+            ).optional()?;
+
+            if let Some(public_identity_key) = key {
+                dms_data.push(DmUser_Data {
+                    username: dm_user,
+                    public_identity_key,
+                    devices: Vec::new()
+                });
+            }
+        }
+        Ok(dms_data)
+    }
+
+    async fn get_blocked_list(&self, username: &str) -> StorageResult<Vec<Blocked_List>> {
+        let conn = self.conn.lock().unwrap();
+        match Self::get_list(&conn, "DMS", "blocked_list", "username", username)? {
+            Some(blocked_list) => Ok(vec![Blocked_List { blocked_list }]),
+            None => Ok(Vec::new())
+        }
+    }
+
+    async fn insert_user_to_blocked_list(&self, username: &str, user_to_block: &str) -> StorageResult<()> {
+        self.append_to_list("DMS", "blocked_list", "username", username, user_to_block)
+    }
+
+    async fn remove_user_from_blocked_list(&self, username: &str, user_to_unblock: &str) -> StorageResult<()> {
+        self.remove_from_list("DMS", "blocked_list", "username", username, user_to_unblock)
+    }
+
+    async fn get_dm_chats_backup_data(&self, username: &str) -> StorageResult<ChatData> {
+        let conn = self.conn.lock().unwrap();
+        let chat_history: Option<String> = conn.query_row(
+            "SELECT chat_history FROM DMS WHERE username = ?1",
+            params![username],
+            |row| row.get(0)
+        ).optional()?;
+
+        match chat_history {
+            Some(history) => Ok(serde_json::from_str(&history)?),
+            None => Ok(ChatData(Vec::new()))
+        }
+    }
+
+    async fn upload_dm_chats_backup_data(&self, username: &str, chat_history: ChatData) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE DMS SET chat_history = ?1 WHERE username = ?2",
+            params![serde_json::to_string(&chat_history)?, username]
+        )?;
+        Ok(())
+    }
+
+    async fn get_room_data(&self, roomname: &str) -> StorageResult<Vec<Rooms_Table>> {
+        let conn = self.conn.lock().unwrap();
+        Self::select_rooms(&conn, "room_name = ?1", params![roomname])
+    }
+
+    async fn get_listed_rooms(&self, offset: usize, limit: usize) -> StorageResult<Vec<Rooms_Table>> {
+        let conn = self.conn.lock().unwrap();
+        Self::select_rooms(
+            &conn,
+            "json_extract(settings, '$.listed') = 1 ORDER BY room_name LIMIT ?1 OFFSET ?2",
+            params![limit as i64, offset as i64]
+        )
+    }
+
+    async fn insert_room_data(&self, room: Rooms_Table) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO ROOMS (room_name, room_key, room_owner, room_members, members_keys, persistent, moderators, banned, muted, settings, join_requests) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                room.room_name,
+                room.room_key,
+                room.room_owner,
+                serde_json::to_string(&room.room_members)?,
+                serde_json::to_string(&room.members_keys)?,
+                room.persistent,
+                serde_json::to_string(&room.moderators)?,
+                serde_json::to_string(&room.banned)?,
+                serde_json::to_string(&room.muted)?,
+                serde_json::to_string(&room.settings)?,
+                serde_json::to_string(&room.join_requests)?
+            ]
+        )?;
+        Ok(())
+    }
+
+    // Like the append_members_array rpc, also records the member's public identity key in members_keys
+    async fn insert_member_to_room(&self, username: &str, roomname: &str) -> StorageResult<()> {
+        self.append_to_list("ROOMS", "room_members", "room_name", roomname, username)?;
+
+        let conn = self.conn.lock().unwrap();
+        let key: Option<String> = conn.query_row(
+            "SELECT public_identity_key FROM USER_AUTH WHERE username = ?1",
+            params![username],
+            |row| row.get(0)
+        ).optional()?;
+
+        if let Some(public_identity_key) = key {
+            let members_keys: Option<String> = conn.query_row(
+                "SELECT members_keys FROM ROOMS WHERE room_name = ?1",
+                params![roomname],
+                |row| row.get(0)
+            ).optional()?;
+
+            if let Some(members_keys) = members_keys {
+                let mut members_keys: Vec<Value> = serde_json::from_str(&members_keys)?;
+                members_keys.push(json!({ username: public_identity_key }));
+                conn.execute(
+                    "UPDATE ROOMS SET members_keys = ?1 WHERE room_name = ?2",
+                    params![serde_json::to_string(&members_keys)?, roomname]
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_member_from_room(&self, username: &str, roomname: &str) -> StorageResult<()> {
+        self.remove_from_list("ROOMS", "room_members", "room_name", roomname, username)?;
+
+        let conn = self.conn.lock().unwrap();
+        let members_keys: Option<String> = conn.query_row(
+            "SELECT members_keys FROM ROOMS WHERE room_name = ?1",
+            params![roomname],
+            |row| row.get(0)
+        ).optional()?;
+
+        if let Some(members_keys) = members_keys {
+            let mut members_keys: Vec<Value> = serde_json::from_str(&members_keys)?;
+            members_keys.retain(|entry| entry.get(username).is_none());
+            conn.execute(
+                "UPDATE ROOMS SET members_keys = ?1 WHERE room_name = ?2",
+                params![serde_json::to_string(&members_keys)?, roomname]
+            )?;
+        }
+        Ok(())
+    }
+
+    async fn update_room_owner(&self, roomname: &str, new_owner: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE ROOMS SET room_owner = ?1 WHERE room_name = ?2", params![new_owner, roomname])?;
+        Ok(())
+    }
+
+    async fn update_room_moderation(&self, roomname: &str, moderators: &[String], banned: &[String], muted: &[String]) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE ROOMS SET moderators = ?1, banned = ?2, muted = ?3 WHERE room_name = ?4",
+            params![
+                serde_json::to_string(moderators)?,
+                serde_json::to_string(banned)?,
+                serde_json::to_string(muted)?,
+                roomname
+            ]
+        )?;
+        Ok(())
+    }
+
+    async fn update_room_key(&self, roomname: &str, room_key: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("UPDATE ROOMS SET room_key = ?1 WHERE room_name = ?2", params![room_key, roomname])?;
+        Ok(())
+    }
+
+    async fn update_room_join_requests(&self, roomname: &str, join_requests: &[String]) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE ROOMS SET join_requests = ?1 WHERE room_name = ?2",
+            params![serde_json::to_string(join_requests)?, roomname]
+        )?;
+        Ok(())
+    }
+
+    async fn delete_room_data(&self, roomname: &str) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM ROOMS WHERE room_name = ?1", params![roomname])?;
+        Ok(())
+    }
+
+    async fn insert_room_invite(&self, invite: Room_Invites_Table) -> StorageResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO ROOM_INVITES (invite_token, room_name, room_key, created_by, expires_at, max_uses, uses) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![invite.invite_token, invite.room_name, invite.room_key, invite.created_by, invite.expires_at, invite.max_uses, invite.uses]
+        )?;
+        Ok(())
+    }
+
+    async fn get_room_invite(&self, invite_token: &str) -> StorageResult<Vec<Room_Invites_Table>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT invite_token, room_name, room_key, created_by, expires_at, max_uses, uses FROM ROOM_INVITES WHERE invite_token = ?1"
+        )?;
+        let rows = stmt.query_map(params![invite_token], |row| {
+            Ok(Room_Invites_Table {
+                invite_token: row.get(0)?,
+                room_name: row.get(1)?,
+                room_key: row.get(2)?,
+                created_by: row.get(3)?,
+                expires_at: row.get(4)?,
+                max_uses: row.get(5)?,
+                uses: row.get(6)?
+            })
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    // A single conditional UPDATE, so two members racing for the last use can't both get in
+    async fn redeem_room_invite(&self, invite_token: &str, now: i64) -> StorageResult<bool> {
+        let conn = self.conn.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE ROOM_INVITES SET uses = uses + 1 WHERE invite_token = ?1 AND uses < max_uses AND expires_at > ?2",
+            params![invite_token, now]
+        )?;
+        Ok(updated > 0)
+    }
+
+    async fn keep_alive(&self) -> StorageResult<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,138 @@
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::models::{device_data::{Device_Links_Table, Revoked_Devices_Table, User_Devices_Table}, dms_data::{Blocked_List, ChatData, DmUser_Data, Dms_List}, prekey_data::{PrekeyBundle, UploadPrekeys}, room_data::{Room_Invites_Table, Rooms_Table}, user_auth::User_Auth_Table};
+use crate::secret_store::get_secret;
+
+use super::{sqlite_storage::SqliteStorage, supabase_storage::SupabaseStorage};
+
+
+#[derive(Debug)]
+pub enum StorageError {
+    Request(reqwest::Error),
+    Sqlite(rusqlite::Error),
+    Serialization(serde_json::Error)
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Request(err) => write!(f, "{}", err),
+            StorageError::Sqlite(err) => write!(f, "{}", err),
+            StorageError::Serialization(err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl From<reqwest::Error> for StorageError {
+    fn from(err: reqwest::Error) -> Self {
+        StorageError::Request(err)
+    }
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> Self {
+        StorageError::Sqlite(err)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(err: serde_json::Error) -> Self {
+        StorageError::Serialization(err)
+    }
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+
+/// Persistent data the server keeps: user auth, devices, device links, X3DH prekeys, DMs/blocked lists, chat backups, rooms and room invites.
+/// Handlers receive it as `web::Data<dyn Storage>`.
+#[async_trait]
+pub trait Storage: Send + Sync {
+
+    //USERS
+    async fn get_auth_data(&self, username: &str) -> StorageResult<Vec<User_Auth_Table>>;
+    async fn insert_auth_data(&self, user: User_Auth_Table) -> StorageResult<()>;
+    async fn update_identity_key(&self, username: &str, public_identity_key: &str) -> StorageResult<()>;
+
+    //DEVICES
+    async fn get_user_devices(&self, username: &str) -> StorageResult<Vec<User_Devices_Table>>;
+    async fn insert_user_device(&self, device: User_Devices_Table) -> StorageResult<()>;
+    async fn remove_user_device(&self, username: &str, device_id: &str) -> StorageResult<()>;
+    async fn update_device_identity_key(&self, username: &str, device_id: &str, public_identity_key: &str) -> StorageResult<()>;
+    // Revoked device ids are kept for good, so a revoked device can't sign in and register itself again
+    async fn insert_revoked_device(&self, device: Revoked_Devices_Table) -> StorageResult<()>;
+    async fn is_device_revoked(&self, username: &str, device_id: &str) -> StorageResult<bool>;
+
+    //DEVICE LINKS
+    // Replaces any earlier link of the user under the same id
+    async fn insert_device_link(&self, link: Device_Links_Table) -> StorageResult<()>;
+    // Deletes the link in the same step it is read so it can only be claimed once, None if missing or expired (as of `now`)
+    async fn take_device_link(&self, username: &str, link_id: &str, now: i64) -> StorageResult<Option<Device_Links_Table>>;
+
+    //PREKEYS
+    // Replaces the signed prekey and appends the one time prekeys
+    async fn upload_prekeys(&self, username: &str, prekeys: UploadPrekeys) -> StorageResult<()>;
+    async fn count_one_time_prekeys(&self, username: &str) -> StorageResult<usize>;
+    // Removes the returned one time prekey, so each is handed out once. None if no prekeys were ever uploaded
+    async fn get_prekey_bundle(&self, username: &str) -> StorageResult<Option<PrekeyBundle>>;
+
+    //DMS
+    async fn insert_user_to_dms_table(&self, username: &str) -> StorageResult<()>;
+    async fn insert_user_to_dms_list(&self, username: &str, user_to_add: &str) -> StorageResult<()>;
+    async fn get_dms_list(&self, user1: &str, user2: &str) -> StorageResult<Vec<Dms_List>>;
+    async fn get_dms_list_data(&self, username: &str) -> StorageResult<Vec<DmUser_Data>>;
+
+    //BLOCKED LISTS
+    async fn get_blocked_list(&self, username: &str) -> StorageResult<Vec<Blocked_List>>;
+    async fn insert_user_to_blocked_list(&self, username: &str, user_to_block: &str) -> StorageResult<()>;
+    async fn remove_user_from_blocked_list(&self, username: &str, user_to_unblock: &str) -> StorageResult<()>;
+
+    //CHAT BACKUPS
+    async fn get_dm_chats_backup_data(&self, username: &str) -> StorageResult<ChatData>;
+    async fn upload_dm_chats_backup_data(&self, username: &str, chat_history: ChatData) -> StorageResult<()>;
+
+    //ROOMS
+    async fn get_room_data(&self, roomname: &str) -> StorageResult<Vec<Rooms_Table>>;
+    // Rooms whose owners listed them in the directory, by room name
+    async fn get_listed_rooms(&self, offset: usize, limit: usize) -> StorageResult<Vec<Rooms_Table>>;
+    async fn insert_room_data(&self, room: Rooms_Table) -> StorageResult<()>;
+    async fn insert_member_to_room(&self, username: &str, roomname: &str) -> StorageResult<()>;
+    async fn remove_member_from_room(&self, username: &str, roomname: &str) -> StorageResult<()>;
+    async fn update_room_owner(&self, roomname: &str, new_owner: &str) -> StorageResult<()>;
+    async fn update_room_moderation(&self, roomname: &str, moderators: &[String], banned: &[String], muted: &[String]) -> StorageResult<()>;
+    async fn update_room_key(&self, roomname: &str, room_key: &str) -> StorageResult<()>;
+    async fn update_room_join_requests(&self, roomname: &str, join_requests: &[String]) -> StorageResult<()>;
+    async fn delete_room_data(&self, roomname: &str) -> StorageResult<()>;
+
+    //ROOM INVITES
+    async fn insert_room_invite(&self, invite: Room_Invites_Table) -> StorageResult<()>;
+    async fn get_room_invite(&self, invite_token: &str) -> StorageResult<Vec<Room_Invites_Table>>;
+    // Uses up one redemption of the invite, false if it already expired (as of `now`) or has no uses left
+    async fn redeem_room_invite(&self, invite_token: &str, now: i64) -> StorageResult<bool>;
+
+    // Called periodically so hosted databases aren't paused during long inactivity periods
+    async fn keep_alive(&self) -> StorageResult<()>;
+}
+
+
+/// Picks the storage backend from the STORAGE_BACKEND secret ("supabase" by default, or "sqlite").
+/// The SQLite database file is taken from SQLITE_PATH (default `maclincomms.db`).
+pub fn storage_from_secrets() -> StorageResult<Arc<dyn Storage>> {
+
+    let backend = get_secret("STORAGE_BACKEND").unwrap_or("supabase".to_string());
+
+    match backend.to_lowercase().as_str() {
+        "sqlite" => {
+            let path = get_secret("SQLITE_PATH").unwrap_or("maclincomms.db".to_string());
+            println!("Using SQLite storage at {}", path);
+            Ok(Arc::new(SqliteStorage::open(&path)?))
+        }
+        _ => {
+            println!("Using Supabase storage");
+            Ok(Arc::new(SupabaseStorage))
+        }
+    }
+}
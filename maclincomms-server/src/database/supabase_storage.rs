@@ -0,0 +1,206 @@
+use actix_web::web::Json;
+use async_trait::async_trait;
+
+use crate::models::{device_data::{Device_Links_Table, Revoked_Devices_Table, User_Devices_Table}, dms_data::{Blocked_List, ChatData, DmUser_Data, Dms_List}, prekey_data::{PrekeyBundle, PrekeysTable, UploadPrekeys}, room_data::{Room_Invites_Table, Rooms_Table}, user_auth::User_Auth_Table};
+
+use super::{auth_db, devices_db, dms_db, prekeys_db, rooms_db, storage::{Storage, StorageResult}};
+
+
+//Reads of the uses count an invite redemption gets before it gives up to concurrent ones
+const REDEEM_INVITE_ATTEMPTS: usize = 5;
+
+/// Storage backed by Supabase PostgREST tables and rpc functions.
+/// Connection details are read from the SUPABASE_URL and SUPABASE_API_KEY secrets on every call.
+pub struct SupabaseStorage;
+
+#[async_trait]
+impl Storage for SupabaseStorage {
+
+    async fn get_auth_data(&self, username: &str) -> StorageResult<Vec<User_Auth_Table>> {
+        Ok(auth_db::get_auth_data(username).await?)
+    }
+
+    async fn insert_auth_data(&self, user: User_Auth_Table) -> StorageResult<()> {
+        Ok(auth_db::insert_auth_data(Json(user)).await?)
+    }
+
+    async fn update_identity_key(&self, username: &str, public_identity_key: &str) -> StorageResult<()> {
+        Ok(auth_db::update_identity_key(username, public_identity_key).await?)
+    }
+
+    async fn get_user_devices(&self, username: &str) -> StorageResult<Vec<User_Devices_Table>> {
+        Ok(devices_db::get_user_devices(username).await?)
+    }
+
+    async fn insert_user_device(&self, device: User_Devices_Table) -> StorageResult<()> {
+        Ok(devices_db::insert_user_device(Json(device)).await?)
+    }
+
+    async fn remove_user_device(&self, username: &str, device_id: &str) -> StorageResult<()> {
+        Ok(devices_db::remove_user_device(username, device_id).await?)
+    }
+
+    async fn update_device_identity_key(&self, username: &str, device_id: &str, public_identity_key: &str) -> StorageResult<()> {
+        Ok(devices_db::update_device_identity_key(username, device_id, public_identity_key).await?)
+    }
+
+    async fn insert_revoked_device(&self, device: Revoked_Devices_Table) -> StorageResult<()> {
+        Ok(devices_db::insert_revoked_device(Json(device)).await?)
+    }
+
+    async fn is_device_revoked(&self, username: &str, device_id: &str) -> StorageResult<bool> {
+        Ok(!devices_db::get_revoked_device(username, device_id).await?.is_empty())
+    }
+
+    async fn insert_device_link(&self, link: Device_Links_Table) -> StorageResult<()> {
+        Ok(devices_db::upsert_device_link(Json(link)).await?)
+    }
+
+    async fn take_device_link(&self, username: &str, link_id: &str, now: i64) -> StorageResult<Option<Device_Links_Table>> {
+        let link = devices_db::take_device_link(username, link_id).await?;
+        Ok(link.filter(|link| link.expires_at > now))
+    }
+
+    async fn upload_prekeys(&self, username: &str, prekeys: UploadPrekeys) -> StorageResult<()> {
+        prekeys_db::upsert_prekeys(PrekeysTable {
+            username: username.to_string(),
+            identity_signing_key: prekeys.identity_signing_key,
+            signed_prekey_id: prekeys.signed_prekey_id,
+            signed_prekey: prekeys.signed_prekey,
+            signed_prekey_signature: prekeys.signed_prekey_signature
+        }).await?;
+
+        if !prekeys.one_time_prekeys.is_empty() {
+            prekeys_db::insert_one_time_prekeys(username, prekeys.one_time_prekeys).await?;
+        }
+        Ok(())
+    }
+
+    async fn count_one_time_prekeys(&self, username: &str) -> StorageResult<usize> {
+        Ok(prekeys_db::count_one_time_prekeys(username).await?)
+    }
+
+    async fn get_prekey_bundle(&self, username: &str) -> StorageResult<Option<PrekeyBundle>> {
+        let Some(user) = auth_db::get_auth_data(username).await?.into_iter().next() else {
+            return Ok(None);
+        };
+        let Some(prekeys) = prekeys_db::get_prekeys(username).await?.into_iter().next() else {
+            return Ok(None);
+        };
+
+        Ok(Some(PrekeyBundle {
+            username: prekeys.username,
+            public_identity_key: user.public_identity_key,
+            identity_signing_key: prekeys.identity_signing_key,
+            signed_prekey_id: prekeys.signed_prekey_id,
+            signed_prekey: prekeys.signed_prekey,
+            signed_prekey_signature: prekeys.signed_prekey_signature,
+            one_time_prekey: prekeys_db::claim_one_time_prekey(username).await?
+        }))
+    }
+
+    async fn insert_user_to_dms_table(&self, username: &str) -> StorageResult<()> {
+        Ok(dms_db::insert_user_to_dms_table(username).await?)
+    }
+
+    async fn insert_user_to_dms_list(&self, username: &str, user_to_add: &str) -> StorageResult<()> {
+        Ok(dms_db::insert_user_to_dms_list(username, user_to_add).await?)
+    }
+
+    async fn get_dms_list(&self, user1: &str, user2: &str) -> StorageResult<Vec<Dms_List>> {
+        Ok(dms_db::get_dms_list(user1, user2).await?)
+    }
+
+    async fn get_dms_list_data(&self, username: &str) -> StorageResult<Vec<DmUser_Data>> {
+        Ok(dms_db::get_dms_list_data(username).await?)
+    }
+
+    async fn get_blocked_list(&self, username: &str) -> StorageResult<Vec<Blocked_List>> {
+        Ok(dms_db::get_blocked_list(username).await?)
+    }
+
+    async fn insert_user_to_blocked_list(&self, username: &str, user_to_block: &str) -> StorageResult<()> {
+        Ok(dms_db::insert_user_to_blocked_list(username, user_to_block).await?)
+    }
+
+    async fn remove_user_from_blocked_list(&self, username: &str, user_to_unblock: &str) -> StorageResult<()> {
+        Ok(dms_db::remove_user_from_blocked_list(username, user_to_unblock).await?)
+    }
+
+    async fn get_dm_chats_backup_data(&self, username: &str) -> StorageResult<ChatData> {
+        Ok(dms_db::get_dm_chats_backup_data(username).await?)
+    }
+
+    async fn upload_dm_chats_backup_data(&self, username: &str, chat_history: ChatData) -> StorageResult<()> {
+        Ok(dms_db::upload_dm_chats_backup_data(username, chat_history).await?)
+    }
+
+    async fn get_room_data(&self, roomname: &str) -> StorageResult<Vec<Rooms_Table>> {
+        Ok(rooms_db::get_room_data(roomname).await?)
+    }
+
+    async fn get_listed_rooms(&self, offset: usize, limit: usize) -> StorageResult<Vec<Rooms_Table>> {
+        Ok(rooms_db::get_listed_rooms(offset, limit).await?)
+    }
+
+    async fn insert_room_data(&self, room: Rooms_Table) -> StorageResult<()> {
+        Ok(rooms_db::insert_room_data(Json(room)).await?)
+    }
+
+    async fn insert_member_to_room(&self, username: &str, roomname: &str) -> StorageResult<()> {
+        Ok(rooms_db::insert_member_to_room(username, roomname).await?)
+    }
+
+    async fn remove_member_from_room(&self, username: &str, roomname: &str) -> StorageResult<()> {
+        Ok(rooms_db::remove_member_from_room(username, roomname).await?)
+    }
+
+    async fn update_room_owner(&self, roomname: &str, new_owner: &str) -> StorageResult<()> {
+        Ok(rooms_db::update_room_owner(roomname, new_owner).await?)
+    }
+
+    async fn update_room_moderation(&self, roomname: &str, moderators: &[String], banned: &[String], muted: &[String]) -> StorageResult<()> {
+        Ok(rooms_db::update_room_moderation(roomname, moderators, banned, muted).await?)
+    }
+
+    async fn update_room_key(&self, roomname: &str, room_key: &str) -> StorageResult<()> {
+        Ok(rooms_db::update_room_key(roomname, room_key).await?)
+    }
+
+    async fn update_room_join_requests(&self, roomname: &str, join_requests: &[String]) -> StorageResult<()> {
+        Ok(rooms_db::update_room_join_requests(roomname, join_requests).await?)
+    }
+
+    async fn delete_room_data(&self, roomname: &str) -> StorageResult<()> {
+        Ok(rooms_db::delete_room_data(roomname).await?)
+    }
+
+    async fn insert_room_invite(&self, invite: Room_Invites_Table) -> StorageResult<()> {
+        Ok(rooms_db::insert_room_invite(Json(invite)).await?)
+    }
+
+    async fn get_room_invite(&self, invite_token: &str) -> StorageResult<Vec<Room_Invites_Table>> {
+        Ok(rooms_db::get_room_invite(invite_token).await?)
+    }
+
+    // The PATCH only matches while the uses count is the one read here, so concurrent redemptions can't overshoot max_uses.
+    // Losing that race to another redemption only means reading the count again
+    async fn redeem_room_invite(&self, invite_token: &str, now: i64) -> StorageResult<bool> {
+        for _ in 0..REDEEM_INVITE_ATTEMPTS {
+            let invite = rooms_db::get_room_invite(invite_token).await?;
+            match invite.first() {
+                Some(invite) if invite.uses < invite.max_uses && invite.expires_at > now => {
+                    if rooms_db::increment_room_invite_uses(invite_token, invite.uses).await? {
+                        return Ok(true);
+                    }
+                }
+                _ => return Ok(false)
+            }
+        }
+        Ok(false)
+    }
+
+    async fn keep_alive(&self) -> StorageResult<()> {
+        Ok(auth_db::keep_alive_supabase().await?)
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use actix_web::web::{self, ServiceConfig};
+use actix_ws::Session;
+use database::{notification_broker::NotificationBroker, storage::Storage};
+use uuid::Uuid;
+
+pub mod secret_store;
+pub mod routes;
+pub mod middleware;
+pub mod models;
+pub mod core;
+pub mod database;
+
+
+pub type WorldChatSharedState = Arc<Mutex<HashMap<Uuid, Session>>>;
+
+pub type RoomChatSharedState = Arc<Mutex<HashMap<(String, String), HashMap<String, Session>>>>;
+
+pub type RoomMutedSharedState = Arc<Mutex<HashMap<String, HashSet<String>>>>;
+
+pub type DMChatSharedState = Arc<Mutex<HashMap<(String, String), HashMap<(String, String), Session>>>>;
+
+
+pub fn configure_app(
+    cfg: &mut ServiceConfig,
+    pub_chat_shared_state: WorldChatSharedState,
+    rooms_shared_state: RoomChatSharedState,
+    rooms_muted_shared_state: RoomMutedSharedState,
+    dms_shared_state: DMChatSharedState,
+    storage: Arc<dyn Storage>,
+    broker: Arc<dyn NotificationBroker>
+) {
+    cfg
+    .app_data(web::Data::from(storage))
+    .app_data(web::Data::from(broker))
+    .app_data(web::Data::new(pub_chat_shared_state))
+    .app_data(web::Data::new(rooms_shared_state))
+    .app_data(web::Data::new(rooms_muted_shared_state))
+    .app_data(web::Data::new(dms_shared_state))
+        .service(routes::endpoints::register)
+        .service(routes::endpoints::login)
+        .service(routes::endpoints::authenticate_user)
+        .service(routes::endpoints::request_new_token)
+        .service(routes::endpoints::public_chat)
+        .service(routes::endpoints::create_room)
+        .service(routes::endpoints::join_room)
+        .service(routes::endpoints::create_room_invite)
+        .service(routes::endpoints::respond_join_request)
+        .service(routes::endpoints::retrieve_room_data)
+        .service(routes::endpoints::room_directory)
+        .service(routes::endpoints::private_room_chat)
+        .service(routes::endpoints::add_user)
+        .service(routes::endpoints::accept_user)
+        .service(routes::endpoints::get_dms_data)
+        .service(routes::endpoints::get_dm_chats_data)
+        .service(routes::endpoints::upload_dm_chats_data)
+        .service(routes::endpoints::upload_prekeys)
+        .service(routes::endpoints::get_prekey_bundle)
+        .service(routes::endpoints::private_dm_chat)
+        .service(routes::endpoints::block_user)
+        .service(routes::endpoints::unblock_user)
+        .service(routes::endpoints::get_devices)
+        .service(routes::endpoints::revoke_device)
+        .service(routes::endpoints::create_device_link)
+        .service(routes::endpoints::claim_device_link)
+        .service(routes::endpoints::rotate_identity_key)
+        .service(routes::endpoints::realtime_notifications)
+        .service(routes::endpoints::queued_notifications);
+}
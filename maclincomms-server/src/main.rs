@@ -1,89 +1,143 @@
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+#[cfg(feature = "shuttle")]
 use actix_web::web::ServiceConfig;
-use actix_web::{
-    web, App, HttpServer
-};
-use actix_ws::Session;
-use database::auth_db::keep_alive_supabase;
-use database::redis_db::keep_alive_upstash;
-use uuid::Uuid;
+#[cfg(not(feature = "shuttle"))]
+use actix_web::{App, HttpServer};
 
+#[cfg(feature = "shuttle")]
 use shuttle_actix_web::ShuttleActixWeb;
 
-use secret_store::set_secrets;
-
-mod secret_store;
-mod routes;
-mod middleware;
-mod models;
-mod core;
-mod database;
+use maclincoms_server::{
+    configure_app, secret_store::{self, set_secrets},
+    database::{notification_broker::{broker_from_secrets, NotificationBroker}, storage::{storage_from_secrets, Storage}},
+    DMChatSharedState, RoomChatSharedState, RoomMutedSharedState, WorldChatSharedState
+};
 
 
+#[cfg(feature = "shuttle")]
 #[shuttle_runtime::main]
 async fn main(
     #[shuttle_runtime::Secrets] secrets: shuttle_runtime::SecretStore
 )
  -> ShuttleActixWeb<impl FnOnce(&mut ServiceConfig) + Send + Clone + 'static> {
 
-    type WorldChatSharedState = Arc<Mutex<HashMap<Uuid, Session>>>;
+    let pub_chat_shared_state: WorldChatSharedState = Arc::new(Mutex::new(HashMap::new()));
+
+    let rooms_shared_state: RoomChatSharedState = Arc::new(Mutex::new(HashMap::new()));
+
+    let rooms_muted_shared_state: RoomMutedSharedState = Arc::new(Mutex::new(HashMap::new()));
+
+    let dms_shared_state: DMChatSharedState = Arc::new(Mutex::new(HashMap::new()));
+
+
+    set_secrets(secret_store::from_shuttle_secrets(secrets)); // Store secrets globally
+
+    let storage = storage_from_secrets().expect("Failed to initialise storage backend");
+
+    let broker = broker_from_secrets();
+
+    spawn_keep_alive_task(storage.clone(), broker.clone());
+
+    let config = move |cfg: &mut ServiceConfig| {
+        configure_app(cfg, pub_chat_shared_state, rooms_shared_state, rooms_muted_shared_state, dms_shared_state, storage, broker);
+    };
+
+    Ok(config.into())
+
+}
+
+
+/* Self-hosted entry point, built with `cargo run --no-default-features`.
+   Secrets are read from the TOML file given with `--config <path>` (or MACLINCOMMS_CONFIG),
+   and environment variables override any value from the file. */
+#[cfg(not(feature = "shuttle"))]
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+
+    let args: Vec<String> = std::env::args().collect();
+
+    let config_path = args.iter()
+        .position(|arg| arg == "--config")
+        .and_then(|i| args.get(i + 1).cloned())
+        .or_else(|| std::env::var("MACLINCOMMS_CONFIG").ok());
 
-    type RoomChatSharedState = Arc<Mutex<HashMap<(String, String), HashMap<String, Session>>>>;
+    let secrets = match secret_store::load_self_hosted_secrets(config_path.as_deref()) {
+        Ok(secrets) => secrets,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let uses_sqlite = secrets.get("STORAGE_BACKEND").is_some_and(|backend| backend.eq_ignore_ascii_case("sqlite"));
+
+    let uses_memory_broker = secrets.get("NOTIFICATION_BROKER").is_some_and(|broker| broker.eq_ignore_ascii_case("memory"));
+
+    for key in secret_store::SECRET_KEYS {
+        if uses_sqlite && key.starts_with("SUPABASE") {
+            continue;
+        }
+        if uses_memory_broker && key == "REDIS_URL" {
+            continue;
+        }
+        if !secrets.contains_key(key) {
+            eprintln!("Missing secret: {}", key);
+        }
+    }
+
+    let bind_address = secrets.get("BIND_ADDRESS").cloned().unwrap_or("0.0.0.0:8000".to_string());
 
-    type DMChatSharedState = Arc<Mutex<HashMap<(String, String), HashMap<String, Session>>>>;
+    set_secrets(secrets); // Store secrets globally
 
     let pub_chat_shared_state: WorldChatSharedState = Arc::new(Mutex::new(HashMap::new()));
 
     let rooms_shared_state: RoomChatSharedState = Arc::new(Mutex::new(HashMap::new()));
 
+    let rooms_muted_shared_state: RoomMutedSharedState = Arc::new(Mutex::new(HashMap::new()));
+
     let dms_shared_state: DMChatSharedState = Arc::new(Mutex::new(HashMap::new()));
 
+    let storage = match storage_from_secrets() {
+        Ok(storage) => storage,
+        Err(err) => {
+            eprintln!("Failed to initialise storage backend: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let broker = broker_from_secrets();
 
-    set_secrets(secrets); // Store secrets globally
+    spawn_keep_alive_task(storage.clone(), broker.clone());
+
+    println!("Starting maclincomms server on {}", bind_address);
+
+    HttpServer::new(move || {
+        App::new().configure(|cfg| {
+            configure_app(cfg, pub_chat_shared_state.clone(), rooms_shared_state.clone(), rooms_muted_shared_state.clone(), dms_shared_state.clone(), storage.clone(), broker.clone());
+        })
+    })
+    .bind(bind_address)?
+    .run()
+    .await
+
+}
 
-    //Task to keep Databases alive during long inactivity periods
-    tokio::spawn(async{
+
+//Task to keep Databases alive during long inactivity periods
+fn spawn_keep_alive_task(storage: Arc<dyn Storage>, broker: Arc<dyn NotificationBroker>) {
+    tokio::spawn(async move {
         println!("Running Periodic Keep Alive Thread");
         loop{
-            if let Ok(subs) = keep_alive_upstash().await{
-                println!("Kept Upstash Alive");
+            if let Ok(()) = broker.keep_alive().await{
+                println!("Kept Notification Broker Alive");
             }
-            if let Ok(()) = keep_alive_supabase().await{
-                println!("Kept Supabase Alive")
+            if let Ok(()) = storage.keep_alive().await{
+                println!("Kept Storage Alive")
             }
             // Query again after 24 hours
             tokio::time::sleep(std::time::Duration::from_secs(60 * 60 * 24)).await;
         }
     });
-
-    let config = move |cfg: &mut ServiceConfig| {
-        cfg
-        .app_data(web::Data::new(pub_chat_shared_state.clone()))
-        .app_data(web::Data::new(rooms_shared_state.clone()))
-        .app_data(web::Data::new(dms_shared_state.clone()))
-            .service(routes::endpoints::register)
-            .service(routes::endpoints::login)
-            .service(routes::endpoints::authenticate_user)
-            .service(routes::endpoints::request_new_token)
-            .service(routes::endpoints::public_chat)
-            .service(routes::endpoints::create_room)
-            .service(routes::endpoints::join_room)
-            .service(routes::endpoints::retrieve_room_data)
-            .service(routes::endpoints::private_room_chat)
-            .service(routes::endpoints::add_user)
-            .service(routes::endpoints::accept_user)
-            .service(routes::endpoints::get_dms_data)
-            .service(routes::endpoints::get_dm_chats_data)
-            .service(routes::endpoints::upload_dm_chats_data)
-            .service(routes::endpoints::private_dm_chat)
-            .service(routes::endpoints::block_user)
-            .service(routes::endpoints::unblock_user)
-            .service(routes::endpoints::realtime_notifications)
-            .service(routes::endpoints::queued_notifications);
-    };
-   
-    Ok(config.into())
-
-}
\ No newline at end of file
+}
@@ -2,13 +2,16 @@ use std::future::{ready, Ready};
 
 use actix_web::{
     dev::Payload, 
-    error::{ErrorGatewayTimeout, ErrorUnauthorized}, 
+    error::{ErrorGatewayTimeout, ErrorInternalServerError, ErrorUnauthorized, InternalError}, 
     http::header::HeaderValue, 
     web, 
     Error, 
     FromRequest, 
-    HttpRequest
+    HttpRequest,
+    HttpResponse
 };
+use futures_util::future::LocalBoxFuture;
+use serde_json::json;
 
 use jsonwebtoken::{
     decode, 
@@ -21,6 +24,10 @@ use jsonwebtoken::{
 
 use crate::secret_store::get_secret;
 
+use crate::database::storage::Storage;
+
+use crate::models::status_types::StatusTypes;
+
 
 
 use crate::models::jwt_models::{
@@ -34,7 +41,7 @@ use crate::models::jwt_models::{
 
 impl FromRequest for UserAuthenticationTokenPayload {
     type Error = Error;
-    type Future = Ready<Result<Self, Self::Error>>;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
 	let req = req.clone();
@@ -42,13 +49,13 @@ impl FromRequest for UserAuthenticationTokenPayload {
 	let authorization_header_option: Option<&HeaderValue> = req.headers().get(actix_web::http::header::AUTHORIZATION);
 
 	// No Header was sent
-	if authorization_header_option.is_none() { return ready(Err(ErrorUnauthorized("No authentication token sent!"))); }
+	if authorization_header_option.is_none() { return Box::pin(ready(Err(ErrorUnauthorized("No authentication token sent!")))); }
 
 	let authentication_token: String = authorization_header_option.unwrap().to_str().unwrap_or("").to_string();
 
     
 	// Couldn't convert Header::Authorization to String
-	if authentication_token.is_empty() { return ready(Err(ErrorUnauthorized("Authentication token has unknown data!"))) }
+	if authentication_token.is_empty() { return Box::pin(ready(Err(ErrorUnauthorized("Authentication token has unknown data!")))) }
 
 
     let server_secret = match get_secret("TOKEN_SECRET"){
@@ -65,15 +72,39 @@ impl FromRequest for UserAuthenticationTokenPayload {
 	);
  
 
-	match token_result {
+	let payload = match token_result {
         Ok(token) => {
             // Wrap the decoded token data into your desired struct
-            ready(Ok(UserAuthenticationTokenPayload {
+            UserAuthenticationTokenPayload {
                 username: token.claims.username,
-            }))
+                device_id: token.claims.device_id
+            }
         },
-        Err(_e) => ready(Err(ErrorUnauthorized("Invalid Authentication Token!")))
-    }
+        Err(_e) => return Box::pin(ready(Err(ErrorUnauthorized("Invalid Authentication Token!"))))
+    };
+
+    // Tokens of revoked devices stop working on every route straight away, not once they expire
+    let storage = req.app_data::<web::Data<dyn Storage>>().cloned();
+
+    Box::pin(async move {
+        if payload.device_id.is_empty() {
+            return Ok(payload);
+        }
+        let Some(storage) = storage else {
+            return Err(ErrorInternalServerError("Storage is not configured!"));
+        };
+        match storage.get_user_devices(&payload.username).await {
+            Ok(devices) if devices.iter().any(|device| device.device_id==payload.device_id) => Ok(payload),
+            Ok(_) => Err(InternalError::from_response(
+                "Device revoked!",
+                HttpResponse::Unauthorized().json(json!({
+                    "status_type": StatusTypes::DEVICE_REVOKED,
+                    "message": "This device was revoked"
+                }))
+            ).into()),
+            Err(_) => Err(ErrorInternalServerError("Couldn't check the device!"))
+        }
+    })
 }
 }
 
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+
+//--------------DEVICES (identity keys Base64 encoded)----------------------
+
+// One machine a user is signed in from, each with its own identity key
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct User_Devices_Table{
+    pub username: String,
+    pub device_id: String,
+    pub device_name: String,
+    pub public_identity_key: String,
+    pub added_at: i64
+}
+
+// What contacts get of a device, enough to encrypt DMs to it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeviceKey{
+    pub device_id: String,
+    pub public_identity_key: String
+}
+
+impl From<User_Devices_Table> for DeviceKey {
+    fn from(device: User_Devices_Table) -> Self {
+        Self {
+            device_id: device.device_id,
+            public_identity_key: device.public_identity_key
+        }
+    }
+}
+
+// A device id that was revoked, it can never be registered again
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Revoked_Devices_Table{
+    pub username: String,
+    pub device_id: String,
+    pub revoked_at: i64
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Revoke_Device{
+    pub device_id: String
+}
+
+// Separates a username from a device id, usernames can't contain it
+pub const DEVICE_MAILBOX_SEPARATOR: char = '#';
+
+// Notifications meant for one device only (DM ciphertexts) are published and queued under its mailbox instead of the username
+pub fn device_mailbox(username: &str, device_id: &str) -> String {
+    format!("{}{}{}", username, DEVICE_MAILBOX_SEPARATOR, device_id)
+}
+
+
+//--------------DEVICE LINKS (bundle sealed by the linking device, opaque to the server)----------------------
+
+// How long a new device has to enter the code shown on the linking device
+pub const DEVICE_LINK_TTL_MINUTES: i64 = 10;
+
+// Keys, session keys and the DMs list fit well within this, anything larger isn't a link bundle
+pub const DEVICE_LINK_MAX_BUNDLE_LEN: usize = 512*1024;
+
+// A one-time hand-off of keys from a signed-in device to a new one of the same account
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Device_Links_Table{
+    pub username: String,
+    pub link_id: String,
+    pub sealed_bundle: String,
+    pub expires_at: i64
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Create_Device_Link{
+    pub link_id: String,
+    pub sealed_bundle: String
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claim_Device_Link{
+    pub link_id: String
+}
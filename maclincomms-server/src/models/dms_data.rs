@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use super::{device_data::DeviceKey, prekey_data::X3dhHandshake};
+
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Add_User{
@@ -11,7 +13,8 @@ pub struct Add_User{
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Accept_User{
     pub username_to_accept: String,
-    pub acceptance_status: AcceptanceStatus
+    pub acceptance_status: AcceptanceStatus,
+    pub x3dh_handshake: Option<X3dhHandshake> // None when the user had no prekey bundle (or an older client accepted)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,8 +26,26 @@ pub enum AcceptanceStatus{
 #[derive(Clone, Serialize, Deserialize)]
 pub struct DmSenderMessage {
     pub username: String,
-    pub content: String,
-    pub is_online_offline_msg: bool
+    pub content: String, //For the receiver's account device, and clients without linked devices
+    pub is_online_offline_msg: bool,
+    #[serde(default)]
+    pub device_contents: HashMap<String, String>, //Device Id -> Ciphertext for each of the receiver's devices, emptied before delivery
+    #[serde(default)]
+    pub device_id: String //Sending device, set by the server
+}
+
+
+impl DmSenderMessage {
+    // The message as one of the receiver's devices gets it, with only its ciphertext and the device it came from
+    pub fn for_device(&self, sending_device_id: &str, receiving_device_id: &str) -> Self {
+        Self {
+            username: self.username.clone(),
+            content: self.device_contents.get(receiving_device_id).cloned().unwrap_or(self.content.clone()),
+            is_online_offline_msg: self.is_online_offline_msg,
+            device_contents: HashMap::new(),
+            device_id: sending_device_id.to_string()
+        }
+    }
 }
 
 
@@ -53,6 +74,8 @@ pub struct Dms_List{
 pub struct DmUser_Data{
     pub username: String,
     pub public_identity_key: String,
+    #[serde(default)]
+    pub devices: Vec<DeviceKey>
 }
 
 #[derive(Debug, Serialize, Deserialize)]
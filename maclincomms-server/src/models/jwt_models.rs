@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UserClaims{
     pub username: String,
+    #[serde(default)]
+    pub device_id: String, //Empty in tokens issued before linked devices
     pub exp: i64
  // pub exp: String
 }
@@ -11,6 +13,7 @@ pub struct UserClaims{
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserAuthenticationTokenPayload {
     pub username: String,
+    pub device_id: String
 }
 
 
@@ -4,5 +4,11 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize)]
 pub struct Login_User{
     pub username: String,
-    pub password: String
+    pub password: String,
+    #[serde(default)]
+    pub device_id: String, //Empty from clients without linked devices
+    #[serde(default)]
+    pub device_name: String,
+    #[serde(default)]
+    pub public_identity_key: String //The device's own identity key, registered on its first login
 }
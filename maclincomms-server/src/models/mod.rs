@@ -7,4 +7,7 @@ pub mod jwt_models;
 pub mod room_data;
 pub mod dms_data;
 pub mod notification_data;
-pub mod ack_model;
\ No newline at end of file
+pub mod ack_model;
+pub mod prekey_data;
+pub mod room_moderation;
+pub mod device_data;
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NotificationData {
     pub n_type: NotificationType,
     pub from: String,
@@ -9,9 +9,36 @@ pub struct NotificationData {
     pub content: String
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NotificationType {
     MESSAGE,
     ADD_REQUEST,
-    ACCEPTED
+    ACCEPTED,
+    ROOM_INVITE,
+    ROOM_JOIN_REQUEST, // Content is the name of the room knocked on
+    ROOM_JOIN_APPROVED,
+    IDENTITY_ROTATED // Sent to every DM contact, content is IdentityRotatedNotificationContent
+}
+
+//Content of ROOM_INVITE notifications
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomInviteNotificationContent {
+    pub room_name: String,
+    pub invite_token: String,
+    pub expires_at: i64
+}
+
+//Content of ROOM_JOIN_APPROVED notifications, the token is issued once the owner approves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomJoinApprovedNotificationContent {
+    pub room_name: String,
+    pub room_token: String
+}
+
+//Content of IDENTITY_ROTATED notifications, contacts check the signature against the key they already have
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityRotatedNotificationContent {
+    pub old_public_identity_key: String,
+    pub new_public_identity_key: String,
+    pub signature: String
 }
\ No newline at end of file
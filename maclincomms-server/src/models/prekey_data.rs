@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+
+//--------------X3DH PREKEYS (keys Base64 encoded)----------------------
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UploadPrekeys{
+    pub identity_signing_key: String, // Ed25519 key the signed prekey is signed with
+    pub signed_prekey_id: u32,
+    pub signed_prekey: String,
+    pub signed_prekey_signature: String,
+    pub one_time_prekeys: Vec<OneTimePrekey> // Appended to the ones already stored
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OneTimePrekey{
+    pub key_id: u32,
+    pub prekey: String
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PrekeysTable{
+    pub username: String,
+    pub identity_signing_key: String,
+    pub signed_prekey_id: u32,
+    pub signed_prekey: String,
+    pub signed_prekey_signature: String
+}
+
+// Handed out once per fetch, the one time prekey is removed from the server as it is given out
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrekeyBundle{
+    pub username: String,
+    pub public_identity_key: String,
+    pub identity_signing_key: String,
+    pub signed_prekey_id: u32,
+    pub signed_prekey: String,
+    pub signed_prekey_signature: String,
+    pub one_time_prekey: Option<OneTimePrekey>
+}
+
+
+// Sent by the accepting user along with accept_user, forwarded untouched in the ACCEPTED notification
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct X3dhHandshake{
+    pub ephemeral_key: String,
+    pub signed_prekey_id: u32,
+    pub one_time_prekey_id: Option<u32>
+}
+
+// Content of ACCEPTED notifications
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcceptedNotificationContent{
+    pub message: String,
+    pub x3dh_handshake: Option<X3dhHandshake>
+}
@@ -6,6 +6,10 @@ use serde::{Deserialize, Serialize};
 pub struct Register_User{
     pub username: String,
     pub password: String,
-    pub public_identity_key: String
+    pub public_identity_key: String,
+    #[serde(default)]
+    pub device_id: String, //Empty from clients without linked devices
+    #[serde(default)]
+    pub device_name: String
 }
 
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::models::dms_data::ChatData;
 
-use super::{dms_data::DmUser_Data, room_data::Rooms_Table, status_types::StatusTypes};
+use super::{device_data::User_Devices_Table, dms_data::DmUser_Data, prekey_data::PrekeyBundle, room_data::{Listed_Room, Rooms_Table}, status_types::StatusTypes};
 
 
 #[derive(Serialize, Deserialize)]
@@ -23,6 +23,7 @@ pub struct LoginResponseData{
     pub access_token: String,
     pub refresh_token: String,
     pub exp: i64,
+    pub account_device: bool, //The device signs in with the account's identity key, the one X3DH prekeys are published for
     pub message: String
 }
 
@@ -62,6 +63,58 @@ pub struct RoomDataResponseData{
     pub message: String
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RoomDirectoryResponseData{
+    pub status_type: StatusTypes,
+    pub rooms: Vec<Listed_Room>,
+    pub page: usize,
+    pub has_more: bool,
+    pub message: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DevicesResponseData{
+    pub status_type: StatusTypes,
+    pub devices: Vec<User_Devices_Table>,
+    pub current_device_id: String,
+    pub message: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RevokeDeviceResponseData{
+    pub status_type: StatusTypes,
+    pub message: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeviceLinkResponseData{
+    pub status_type: StatusTypes,
+    pub sealed_bundle: String,
+    pub expires_at: i64,
+    pub message: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RotateIdentityKeyResponseData{
+    pub status_type: StatusTypes,
+    pub notified_contacts: usize,
+    pub message: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RespondJoinRequestResponseData{
+    pub status_type: StatusTypes,
+    pub message: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CreateRoomInviteResponseData{
+    pub status_type: StatusTypes,
+    pub invite_token: String,
+    pub expires_at: i64,
+    pub message: String
+}
+
 
 //-----------------DMS------------------
 
@@ -115,3 +168,20 @@ pub struct QueuedNotificationsReponseData{
     pub data: HashMap<String, Vec<String>>,
     pub message: String
 }
+
+
+//-----------------PREKEYS------------------
+
+#[derive(Serialize, Deserialize)]
+pub struct UploadPrekeysResponseData{
+    pub status_type: StatusTypes,
+    pub one_time_prekeys_count: usize,
+    pub message: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PrekeyBundleResponseData{
+    pub status_type: StatusTypes,
+    pub bundle: Option<PrekeyBundle>,
+    pub message: String
+}
@@ -1,16 +1,26 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use super::dms_data::AcceptanceStatus;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Create_Room{
     pub room_name: String,
     pub owner_key: String,
+    #[serde(default)]
+    pub persistent: bool,
+    #[serde(default)]
+    pub settings: RoomSettings
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Join_Room{
+    #[serde(default)]
     pub room_name: String,
-    pub room_key: String
+    #[serde(default)]
+    pub room_key: String,
+    #[serde(default)]
+    pub invite_token: Option<String> // Redeems an invite instead of the room key, the invite decides the room
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -19,7 +29,127 @@ pub struct Rooms_Table{
     pub room_key: String,
     pub room_owner: String,
     pub room_members: Vec<String>,
-    pub members_keys: Vec<Value>
+    pub members_keys: Vec<Value>,
+    #[serde(default)]
+    pub persistent: bool, // Persistent rooms outlive their owner's session, members rejoin via /join_room
+    #[serde(default)]
+    pub moderators: Vec<String>,
+    #[serde(default)]
+    pub banned: Vec<String>,
+    #[serde(default)]
+    pub muted: Vec<String>,
+    #[serde(default)]
+    pub settings: RoomSettings,
+    #[serde(default)]
+    pub join_requests: Vec<String> // Members who knocked on a join approval room, waiting on the owner
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Respond_Join_Request{
+    pub room_name: String,
+    pub username: String,
+    pub acceptance_status: AcceptanceStatus
+}
+
+//Chosen by the owner at creation, enforced by /join_room and the room socket
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RoomSettings{
+    pub max_members: i64, // 0 leaves the room uncapped, the owner counts as a member
+    pub whispers_allowed: bool,
+    pub join_approval: bool, // Joining with the room key alone isn't enough, invites still let people in
+    pub slow_mode_seconds: i64, // Minimum gap between a member's messages, 0 turns it off
+    pub share_history_count: i64, // Earlier messages members hand to a newcomer, 0 keeps history to those who were there
+    pub listed: bool, // Shown in the room directory and joinable without the room key, join approval rooms still get a knock
+    pub topic: String // Shown next to the room in the directory
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            max_members: 0,
+            whispers_allowed: true,
+            join_approval: false,
+            slow_mode_seconds: 0,
+            share_history_count: 0,
+            listed: false,
+            topic: "".to_owned()
+        }
+    }
+}
+
+impl RoomSettings {
+    pub const MAX_SLOW_MODE_SECONDS: i64 = 3600;
+
+    pub const MAX_SHARE_HISTORY_COUNT: i64 = 50;
+
+    pub const MAX_TOPIC_CHARS: usize = 80;
+
+    //Out of range values from the client are pulled back in rather than refused
+    pub fn sanitized(self) -> Self {
+        Self {
+            max_members: if self.max_members < 2 { 0 } else { self.max_members },
+            slow_mode_seconds: self.slow_mode_seconds.clamp(0, Self::MAX_SLOW_MODE_SECONDS),
+            share_history_count: self.share_history_count.clamp(0, Self::MAX_SHARE_HISTORY_COUNT),
+            topic: self.topic.trim().chars().take(Self::MAX_TOPIC_CHARS).collect(),
+            ..self
+        }
+    }
+}
+
+
+pub const ROOM_DIRECTORY_PAGE_SIZE: usize = 10;
+
+// A listed room as the directory shows it, nothing that would let someone in
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Listed_Room{
+    pub room_name: String,
+    pub topic: String,
+    pub member_count: i64, // Connected right now, the same count max_members caps
+    pub max_members: i64,
+    pub join_approval: bool,
+    pub persistent: bool
+}
+
+impl Listed_Room {
+    // room_members keeps everyone who ever joined a persistent room, so the count comes from the room's live sessions
+    pub fn new(room: Rooms_Table, member_count: usize) -> Self {
+        Self {
+            room_name: room.room_name,
+            topic: room.settings.topic,
+            member_count: member_count as i64,
+            max_members: room.settings.max_members,
+            join_approval: room.settings.join_approval,
+            persistent: room.persistent
+        }
+    }
+}
+
+
+pub const INVITE_TOKEN_PREFIX: &str = "invite-";
+
+// Earlier messages sealed by a member for a newcomer, [0x66][Username Length][Username][Sealed History].
+// The sender names the newcomer, the server swaps in the sender's name on the way
+pub const ROOM_HISTORY_BYTE: u8 = 0x66;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Create_Room_Invite{
+    pub room_name: String,
+    pub max_uses: i64,
+    pub expires_in_minutes: i64,
+    #[serde(default)]
+    pub send_to: Option<String> // DM contact to send the invite to as a notification
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Room_Invites_Table{
+    pub invite_token: String,
+    pub room_name: String,
+    pub room_key: String, // Room key hash at minting time, rotating the key or recreating the room revokes the invite
+    pub created_by: String,
+    pub expires_at: i64,
+    pub max_uses: i64,
+    pub uses: i64
 }
 
 
@@ -0,0 +1,43 @@
+//--------------ROOM MODERATION
+
+// Moderation commands travel over the room socket as [0x55][Action][Target Username].
+// Once applied the server informs the room with the same frame, a refused command comes back to its issuer as [0x55][REJECTED][Action]
+pub const ROOM_MODERATION_BYTE: u8 = 0x55;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModerationAction {
+    KICK,
+    BAN,
+    MUTE,
+    UNMUTE, //Sent by the server only, muting a muted member unmutes them
+    PROMOTE,
+    ROTATE_KEY,
+    REJECTED
+}
+
+impl ModerationAction {
+    pub fn byte(&self) -> u8 {
+        match self {
+            Self::KICK => 0x01,
+            Self::BAN => 0x02,
+            Self::MUTE => 0x03,
+            Self::UNMUTE => 0x04,
+            Self::PROMOTE => 0x05,
+            Self::ROTATE_KEY => 0x06,
+            Self::REJECTED => 0x00
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x01 => Some(Self::KICK),
+            0x02 => Some(Self::BAN),
+            0x03 => Some(Self::MUTE),
+            0x04 => Some(Self::UNMUTE),
+            0x05 => Some(Self::PROMOTE),
+            0x06 => Some(Self::ROTATE_KEY),
+            0x00 => Some(Self::REJECTED),
+            _ => None
+        }
+    }
+}
@@ -26,5 +26,27 @@ pub enum StatusTypes{
     DMS_DATA_UPLOADED,
     USER_ALREADY_EXISTS,
     ROOM_ALREADY_EXISTS,
-    USER_ALREADY_BLOCKED
+    USER_ALREADY_BLOCKED,
+    PREKEYS_UPLOADED,
+    PREKEY_BUNDLE_FETCHED,
+    PREKEY_BUNDLE_NOT_FOUND,
+    BANNED_FROM_ROOM,
+    ROOM_INVITE_CREATED,
+    INVALID_INVITE,
+    ROOM_FULL,
+    JOIN_REQUEST_SENT,
+    JOIN_REQUEST_APPROVED,
+    JOIN_REQUEST_DENIED,
+    ROOM_DIRECTORY_FETCHED,
+    INVALID_USERNAME,
+    DEVICES_FETCHED,
+    DEVICE_REVOKED,
+    DEVICE_NOT_FOUND,
+    CANNOT_REVOKE_CURRENT_DEVICE,
+    DEVICE_LINK_CREATED,
+    DEVICE_LINK_CLAIMED,
+    INVALID_DEVICE_LINK,
+    IDENTITY_KEY_ROTATED,
+    INVALID_IDENTITY_KEY,
+    NOT_ACCOUNT_DEVICE
 }
\ No newline at end of file
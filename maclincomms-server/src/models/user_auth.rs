@@ -8,4 +8,12 @@ pub struct User_Auth_Table{
     pub password_hash: String,
     pub password_salt: String,
     pub public_identity_key: String
+}
+
+//New account identity key, signed with the one it replaces (Keys Base64 encoded)
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Rotate_Identity_Key{
+    pub old_public_identity_key: String,
+    pub new_public_identity_key: String,
+    pub signature: String // XEdDSA signature of the old key over both keys
 }
\ No newline at end of file
@@ -4,12 +4,12 @@ use base64::{Engine as _, engine::general_purpose};
 use chrono::{Duration, Utc};
 use serde_json::{json, Value};
 use crate::{
-    core::{encoding_token::{encode_user_room_token, encode_user_token}, hashing_data::{hash_room_password, verify_room_password, verify_user_password}}, 
+    core::{encoding_token::{encode_user_room_token, encode_user_token}, hashing_data::{hash_room_password, verify_room_password, verify_user_password}, identity_signature::verify_identity_rotation}, 
     database::{
-        auth_db::{get_auth_data, insert_auth_data}, dms_db::{get_blocked_list, get_dm_chats_backup_data, get_dms_list, get_dms_list_data, insert_user_to_blocked_list, insert_user_to_dms_list, insert_user_to_dms_table, remove_user_from_blocked_list, upload_dm_chats_backup_data}, redis_db::{publish_notification, queue_notification, retrieve_queued_notifications, subscribe_to_notifications}, rooms_db::{delete_room_data, get_room_data, insert_member_to_room, insert_room_data, remove_member_from_room}
+        notification_broker::NotificationBroker, storage::{Storage, StorageResult}
     }, 
     models::{
-    ack_model::AckType, dms_data:: {Accept_User, AcceptanceStatus, Add_User, Block_User, ChatData, DmSenderMessage, Dms_Table, Unblock_User}, jwt_models::{RoomRoles, UserAuthenticationTokenPayload, UserClaims, UserRoomAuthenticationTokenPayload, UserRoomClaims}, login_model:: Login_User, notification_data::{NotificationData, NotificationType}, register_model:: Register_User, response_data::{AcceptUserResponseData, AddUserResponseData, BlockUserResponseData, CreateRoomResponseData, GetDmChatsResponseData, GetDmsResponseData, JoinRoomResponseData, LoginResponseData, QueuedNotificationsReponseData, RegisterResponseData, RequestNewTokenResponseData, RoomDataResponseData, UnblockUserResponseData, UploadDmChatsResponseData}, room_data:: {Create_Room, Join_Room, RoomReceiverMessage, RoomSenderMessage, Rooms_Table, WhisperMode}, status_types:: StatusTypes, user_auth::User_Auth_Table
+    ack_model::AckType, device_data::{device_mailbox, Claim_Device_Link, Create_Device_Link, DeviceKey, Device_Links_Table, Revoke_Device, Revoked_Devices_Table, User_Devices_Table, DEVICE_LINK_MAX_BUNDLE_LEN, DEVICE_LINK_TTL_MINUTES, DEVICE_MAILBOX_SEPARATOR}, dms_data:: {Accept_User, AcceptanceStatus, Add_User, Block_User, ChatData, DmSenderMessage, Dms_Table, Unblock_User}, jwt_models::{RoomRoles, UserAuthenticationTokenPayload, UserClaims, UserRoomAuthenticationTokenPayload, UserRoomClaims}, login_model:: Login_User, notification_data::{IdentityRotatedNotificationContent, NotificationData, NotificationType, RoomInviteNotificationContent, RoomJoinApprovedNotificationContent}, prekey_data::{AcceptedNotificationContent, UploadPrekeys}, register_model:: Register_User, response_data::{AcceptUserResponseData, AddUserResponseData, BlockUserResponseData, CreateRoomInviteResponseData, CreateRoomResponseData, DeviceLinkResponseData, DevicesResponseData, GetDmChatsResponseData, GetDmsResponseData, JoinRoomResponseData, LoginResponseData, RespondJoinRequestResponseData, PrekeyBundleResponseData, QueuedNotificationsReponseData, RegisterResponseData, RequestNewTokenResponseData, RevokeDeviceResponseData, RotateIdentityKeyResponseData, RoomDataResponseData, RoomDirectoryResponseData, UnblockUserResponseData, UploadDmChatsResponseData, UploadPrekeysResponseData}, room_data:: {Create_Room, Create_Room_Invite, Join_Room, Listed_Room, Respond_Join_Request, ROOM_DIRECTORY_PAGE_SIZE, Room_Invites_Table, RoomSettings, INVITE_TOKEN_PREFIX, ROOM_HISTORY_BYTE, RoomReceiverMessage, RoomSenderMessage, Rooms_Table, WhisperMode}, room_moderation::{ModerationAction, ROOM_MODERATION_BYTE}, status_types:: StatusTypes, user_auth::{Rotate_Identity_Key, User_Auth_Table}
  }
 };
 use tokio::{sync::mpsc, task::futures};
@@ -19,8 +19,9 @@ use crate::core::hashing_data::hash_user_password;
 use actix_ws::{CloseCode, CloseReason, Message, Session};
 use futures_util::{stream, Stream, StreamExt};
 
-use std::{collections::{HashMap}};
+use std::{collections::{HashMap, HashSet}};
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use uuid::Uuid;
 use passwords::PasswordGenerator;
 
@@ -36,23 +37,37 @@ pub async fn greet(user_name: web::Path<String>) -> impl Responder {
 
 #[actix_web::post("/register_user")]
 pub async fn register(
-   user: web::Json<Register_User> 
+   user: web::Json<Register_User>,
+   storage: web::Data<dyn Storage>
 ) -> impl Responder {
     
     let passed_user = user.into_inner();
     let user = passed_user.username.clone();
     let identity_key = passed_user.public_identity_key;
+    let device_id = passed_user.device_id;
+
+    //The separator would make the username look like one of another user's device mailboxes
+    if user.contains(DEVICE_MAILBOX_SEPARATOR) {
+        return HttpResponse::BadRequest().json(RegisterResponseData {
+            status_type: StatusTypes::INVALID_USERNAME,
+            exp: 0,
+            access_token: "".to_owned(),
+            refresh_token: "".to_owned(),
+            message: format!("Usernames can't contain '{}'", DEVICE_MAILBOX_SEPARATOR),
+        });
+    }
+
     let (salt_bytes, hash_bytes) = hash_user_password(passed_user.password);
-    let existing_user = get_auth_data(&passed_user.username).await;
+    let existing_user = storage.get_auth_data(&passed_user.username).await;
     match existing_user {
         Ok(data) => match data.len() {
             0 => {
-                if let Err(err) = insert_auth_data(web::Json(User_Auth_Table {
+                if let Err(err) = storage.insert_auth_data(User_Auth_Table {
                     username: passed_user.username.clone(),
                     password_hash: general_purpose::STANDARD.encode(hash_bytes),
                     password_salt: general_purpose::STANDARD.encode(salt_bytes),
-                    public_identity_key: identity_key
-                }))
+                    public_identity_key: identity_key.clone()
+                })
                 .await
                 {
                     return HttpResponse::InternalServerError().json(RegisterResponseData {
@@ -65,7 +80,7 @@ pub async fn register(
                 }
 
                 //Inserting User to dms table
-                if let Err(err) = insert_user_to_dms_table(&passed_user.username).await{
+                if let Err(err) = storage.insert_user_to_dms_table(&passed_user.username).await{
                     return HttpResponse::InternalServerError().json(RegisterResponseData {
                         status_type: StatusTypes::DATABASE_ERROR,
                         exp: 0,
@@ -75,14 +90,29 @@ pub async fn register(
                     });
                 }
 
+                //The registering device is the account's first
+                if !device_id.is_empty() {
+                    if let Err(err) = register_device(&storage, &user, &device_id, passed_user.device_name, identity_key).await{
+                        return HttpResponse::InternalServerError().json(RegisterResponseData {
+                            status_type: StatusTypes::DATABASE_ERROR,
+                            exp: 0,
+                            access_token: "".to_owned(),
+                            refresh_token: "".to_owned(),
+                            message: format!("Failed to register device: {}", err),
+                        });
+                    }
+                }
+
                 // Assigning a JWT Access Token
                 let access_tok = encode_user_token(UserClaims{
                 username: user.clone(),
+                device_id: device_id.clone(),
                 exp: (Utc::now() + Duration::days(2)).timestamp()
                 });
                 // Assigning a JWT Refresh Token
                 let refresh_tok = encode_user_token(UserClaims {
                 username: user,
+                device_id,
                 exp: (Utc::now() + Duration::days(5)).timestamp(),
                 });
                 
@@ -119,13 +149,14 @@ pub async fn register(
 
 #[actix_web::post("/login_user")]
 pub async fn login(
-   user: web::Json<Login_User> 
+   user: web::Json<Login_User>,
+   storage: web::Data<dyn Storage>
 ) -> impl Responder {
     
     let passed_user = user.into_inner();
     let user = passed_user.username;
 
-    let found_user = get_auth_data(&user).await;
+    let found_user = storage.get_auth_data(&user).await;
 
     match found_user {
         Ok(data) => match data.len() {
@@ -136,6 +167,7 @@ pub async fn login(
                     exp: 0,
                     access_token: "".to_owned(),
                     refresh_token: "".to_owned(),
+                    account_device: false,
                     message: "User not found".to_owned(),
                 })
             },
@@ -151,18 +183,62 @@ pub async fn login(
                         exp: 0,
                         access_token: "".to_owned(),
                         refresh_token: "".to_owned(),
+                        account_device: false,
                         message: "Invalid Credentials".to_owned(),
                     });
                 }
 
+                //Clients without linked devices sign in as the account itself
+                let device_id = passed_user.device_id;
+                let account_device = device_id.is_empty() || passed_user.public_identity_key==data[0].public_identity_key;
+
+                //Revoked devices stay revoked, signing in again doesn't bring them back
+                if !device_id.is_empty() {
+                    match storage.is_device_revoked(&user, &device_id).await {
+                        Ok(false) => {},
+                        Ok(true) => {
+                            return HttpResponse::Unauthorized().json(LoginResponseData {
+                                status_type: StatusTypes::DEVICE_REVOKED,
+                                exp: 0,
+                                access_token: "".to_owned(),
+                                refresh_token: "".to_owned(),
+                                account_device: false,
+                                message: "This device was revoked".to_owned(),
+                            });
+                        }
+                        Err(err) => {
+                            return HttpResponse::InternalServerError().json(LoginResponseData {
+                                status_type: StatusTypes::DATABASE_ERROR,
+                                exp: 0,
+                                access_token: "".to_owned(),
+                                refresh_token: "".to_owned(),
+                                account_device: false,
+                                message: format!("Internal server error because of DB error: {}", err),
+                            });
+                        }
+                    }
+                    if let Err(err) = register_device(&storage, &user, &device_id, passed_user.device_name, passed_user.public_identity_key).await{
+                        return HttpResponse::InternalServerError().json(LoginResponseData {
+                            status_type: StatusTypes::DATABASE_ERROR,
+                            exp: 0,
+                            access_token: "".to_owned(),
+                            refresh_token: "".to_owned(),
+                            account_device: false,
+                            message: format!("Failed to register device: {}", err),
+                        });
+                    }
+                }
+
                 // Assigning a JWT Access Token
                 let access_tok = encode_user_token(UserClaims{
                     username: user.clone(),
+                    device_id: device_id.clone(),
                     exp: (Utc::now() + Duration::days(2)).timestamp()
                 });
                 // Assigning a JWT Refresh Token
                 let refresh_tok = encode_user_token(UserClaims {
                     username: user,
+                    device_id,
                     exp: (Utc::now() + Duration::days(5)).timestamp(),
                 });
 
@@ -171,6 +247,7 @@ pub async fn login(
                     exp: (Utc::now() + Duration::hours(47)).timestamp(),
                     access_token: access_tok,
                     refresh_token: refresh_tok,
+                    account_device,
                     message: "Logged In Successfully".to_owned(),
                 })
             }
@@ -180,12 +257,38 @@ pub async fn login(
             exp: 0,
             access_token: "".to_owned(),
             refresh_token: "".to_owned(),
+            account_device: false,
             message: format!("Internal server error because of DB error: {}", err),
         })
     }
 }
 
 
+//Adds the device to the user's devices the first time it signs in
+async fn register_device(
+    storage: &web::Data<dyn Storage>,
+    username: &str,
+    device_id: &str,
+    device_name: String,
+    public_identity_key: String
+) -> StorageResult<()> {
+
+    let devices = storage.get_user_devices(username).await?;
+
+    if devices.iter().any(|device| device.device_id==device_id) || public_identity_key.is_empty() {
+        return Ok(());
+    }
+
+    storage.insert_user_device(User_Devices_Table {
+        username: username.to_string(),
+        device_id: device_id.to_string(),
+        device_name,
+        public_identity_key,
+        added_at: Utc::now().timestamp()
+    }).await
+}
+
+
 //Authentication endpoint based on token
 #[actix_web::get("/authN_user")]
 pub async fn authenticate_user(
@@ -203,15 +306,18 @@ pub async fn request_new_token(
 ) -> impl Responder {
 
     let user_name = user.username;
+    let device_id = user.device_id;
 
     // Assigning a JWT Access Token
     let access_tok = encode_user_token(UserClaims{
         username: user_name.clone(),
+        device_id: device_id.clone(),
         exp: (Utc::now() + Duration::days(2)).timestamp()
     });
     // Assigning a JWT Refresh Token
     let refresh_tok = encode_user_token(UserClaims {
         username: user_name,
+        device_id,
         exp: (Utc::now() + Duration::days(5)).timestamp(),
     });
         
@@ -307,26 +413,44 @@ pub async fn public_chat(
 // Shared Arc type for managing active rooms with active Websocket Sessions
 type RoomChatSharedState = Arc<Mutex<HashMap<(String, String), HashMap<String, Session>>>>;
 
+// Muted members of each live room, keyed by room name so it survives room key rotation
+type RoomMutedSharedState = Arc<Mutex<HashMap<String, HashSet<String>>>>;
+
+
+fn generate_room_key() -> String {
+    let mut pgi = PasswordGenerator::new().length(9).numbers(true).lowercase_letters(true).uppercase_letters(true).symbols(true).exclude_similar_characters(false).try_iter().unwrap();
+    pgi.next().unwrap()
+}
+
+// Publishes to an online user, or queues for an offline one. False if neither worked
+async fn deliver_notification(broker: &web::Data<dyn NotificationBroker>, notification: NotificationData) -> bool {
+    match broker.publish_notification(notification.clone()).await {
+        Ok(0) => broker.queue_notification(notification).await.is_ok(),
+        Ok(_) => true,
+        Err(_) => false
+    }
+}
+
 
 
 #[actix_web::post("/create_room")]
 pub async fn create_room(
     user: UserAuthenticationTokenPayload,
     body: web::Json<Create_Room>,
-    rooms_shared_state: web::Data<RoomChatSharedState>
+    rooms_shared_state: web::Data<RoomChatSharedState>,
+    rooms_muted_shared_state: web::Data<RoomMutedSharedState>,
+    storage: web::Data<dyn Storage>
 ) -> impl Responder {
     
     let user_name = user.username;
 
     let passed_room = body.into_inner();
 
-    let mut pgi = PasswordGenerator::new().length(9).numbers(true).lowercase_letters(true).uppercase_letters(true).symbols(true).exclude_similar_characters(false).try_iter().unwrap();
-
-    let key = pgi.next().unwrap();
+    let key = generate_room_key();
 
     let key_hash = hash_room_password(key.clone());
 
-    let existing_room = get_room_data(&passed_room.room_name).await;
+    let existing_room = storage.get_room_data(&passed_room.room_name).await;
 
     match existing_room {
         Ok(data) => match data.len() {
@@ -334,13 +458,19 @@ pub async fn create_room(
                 let mut m_keys = Vec::<Value>::new();
                 m_keys.push(json!({ user_name.clone() : passed_room.owner_key }));
 
-                if let Err(err) = insert_room_data(web::Json(Rooms_Table {
+                if let Err(err) = storage.insert_room_data(Rooms_Table {
                     room_name: passed_room.room_name.clone(),
                     room_key: key_hash.clone(),
                     room_owner: user_name.clone(),
                     room_members: vec![user_name.clone()],
-                    members_keys: m_keys
-                }))
+                    members_keys: m_keys,
+                    persistent: passed_room.persistent,
+                    moderators: Vec::new(),
+                    banned: Vec::new(),
+                    muted: Vec::new(),
+                    settings: passed_room.settings.sanitized(),
+                    join_requests: Vec::new()
+                })
                 .await
                 {
                     return HttpResponse::InternalServerError().json(CreateRoomResponseData {
@@ -364,6 +494,8 @@ pub async fn create_room(
                 {
                     let mut rooms = rooms_shared_state.lock().unwrap();
                     rooms.insert((passed_room.room_name.clone(), key_hash.clone()), HashMap::new()); // Insert an empty room
+                    // A new room starts with nobody muted, whatever an earlier room by this name had
+                    rooms_muted_shared_state.lock().unwrap().insert(passed_room.room_name.clone(), HashSet::new());
                 } 
                 // Mutex lock is dropped here
             
@@ -397,17 +529,49 @@ pub async fn create_room(
 #[actix_web::post("/join_room")]
 pub async fn join_room(
     user: UserAuthenticationTokenPayload,
-    body: web::Json<Join_Room>
+    body: web::Json<Join_Room>,
+    rooms_shared_state: web::Data<RoomChatSharedState>,
+    storage: web::Data<dyn Storage>,
+    broker: web::Data<dyn NotificationBroker>
 ) -> impl Responder {
     
     let user_name = user.username;
 
-    let passed_room = body.into_inner();
+    let mut passed_room = body.into_inner();
 
     let room_key = passed_room.room_key.clone();
 
+    //Invites stand in for the room key, and decide which room is joined
+    let invite = match passed_room.invite_token.clone() {
+        Some(invite_token) => match storage.get_room_invite(&invite_token).await {
+            Ok(invites) => match invites.into_iter().next() {
+                Some(invite) => Some(invite),
+                None => {
+                    return HttpResponse::NotFound().json(JoinRoomResponseData {
+                        status_type: StatusTypes::INVALID_INVITE,
+                        room_token: "".to_owned(),
+                        room_name: passed_room.room_name,
+                        message: "Invite could not be found".to_owned(),
+                    });
+                }
+            },
+            Err(err) => {
+                return HttpResponse::InternalServerError().json(JoinRoomResponseData {
+                    status_type: StatusTypes::DATABASE_ERROR,
+                    room_token: "".to_owned(),
+                    room_name: passed_room.room_name,
+                    message: "Couldnt join room because of DB Error".to_owned(),
+                });
+            }
+        },
+        None => None
+    };
+    if let Some(invite) = &invite {
+        passed_room.room_name = invite.room_name.clone();
+    }
+
 
-    let existing_room = get_room_data(&passed_room.room_name).await;
+    let existing_room = storage.get_room_data(&passed_room.room_name).await;
 
     match existing_room {
         Ok(data) => match data.len() {
@@ -420,13 +584,78 @@ pub async fn join_room(
                 })
             },
             non_zero => {
-                //Validating Room Key
+                //Validating Room Key, an invite is only good for the room key it was minted under. Listed rooms let anyone in
                 let room_key_hash = data[0].room_key.clone();
-                let allow = verify_room_password(room_key, room_key_hash.clone());
+                let allow = match &invite {
+                    Some(invite) => invite.room_key==room_key_hash,
+                    None => data[0].settings.listed || verify_room_password(room_key, room_key_hash.clone())
+                };
                 //matching hash
                 if allow == true {
-                    //Inserting member to room
-                    if let Err(err) = insert_member_to_room(&user_name, &passed_room.room_name).await
+                    //Banned users stay out for good
+                    if data[0].banned.contains(&user_name) {
+                        return HttpResponse::Forbidden().json(JoinRoomResponseData {
+                            status_type: StatusTypes::BANNED_FROM_ROOM,
+                            room_token: "".to_owned(),
+                            room_name: passed_room.room_name,
+                            message: "You are banned from this room".to_owned(),
+                        });
+                    }
+                    let is_returning = data[0].room_owner==user_name || data[0].room_members.contains(&user_name);
+                    //The cap counts who is in the room right now, persistent rooms keep past members in room_members
+                    let max_members = data[0].settings.max_members;
+                    let connected = connected_room_members(&rooms_shared_state, &passed_room.room_name);
+                    if max_members > 0 && data[0].room_owner!=user_name && !connected.contains(&user_name) && connected.len() as i64 >= max_members {
+                        return HttpResponse::Forbidden().json(JoinRoomResponseData {
+                            status_type: StatusTypes::ROOM_FULL,
+                            room_token: "".to_owned(),
+                            room_name: passed_room.room_name,
+                            message: "Room is full".to_owned(),
+                        });
+                    }
+                    //Knocking: the room key gets the request to the owner, their approval gets the room token. Their invites count as approval
+                    if data[0].settings.join_approval && invite.is_none() && !is_returning {
+                        if !data[0].join_requests.contains(&user_name) {
+                            let mut join_requests = data[0].join_requests.clone();
+                            join_requests.push(user_name.clone());
+                            if let Err(_) = storage.update_room_join_requests(&passed_room.room_name, &join_requests).await {
+                                return HttpResponse::InternalServerError().json(JoinRoomResponseData {
+                                    status_type: StatusTypes::DATABASE_ERROR,
+                                    room_token: "".to_owned(),
+                                    room_name: passed_room.room_name,
+                                    message: "Couldnt join room because of DB Error".to_owned(),
+                                });
+                            }
+                            let knocked = deliver_notification(&broker, NotificationData {
+                                n_type: NotificationType::ROOM_JOIN_REQUEST,
+                                from: user_name.clone(),
+                                to: data[0].room_owner.clone(),
+                                content: passed_room.room_name.clone()
+                            }).await;
+                            if !knocked {
+                                return HttpResponse::InternalServerError().json(JoinRoomResponseData {
+                                    status_type: StatusTypes::NOTIFICATIONS_ERROR,
+                                    room_token: "".to_owned(),
+                                    room_name: passed_room.room_name,
+                                    message: "Notifications Error due to Redis Error".to_owned(),
+                                });
+                            }
+                        }
+                        return HttpResponse::Accepted().json(JoinRoomResponseData {
+                            status_type: StatusTypes::JOIN_REQUEST_SENT,
+                            room_token: "".to_owned(),
+                            room_name: passed_room.room_name,
+                            message: "Waiting for the owner's approval".to_owned(),
+                        });
+                    }
+                    //Inserting member to room, unless they are still in it (reconnecting to a persistent room).
+                    //Done before an invite use is taken, so a failed insert doesn't burn one
+                    let is_member = data[0].room_members.contains(&user_name);
+                    let inserted = match is_member {
+                        true => Ok(()),
+                        false => storage.insert_member_to_room(&user_name, &passed_room.room_name).await
+                    };
+                    if let Err(err) = inserted
                     {
                         return HttpResponse::InternalServerError().json(JoinRoomResponseData {
                             status_type: StatusTypes::DATABASE_ERROR,
@@ -435,13 +664,41 @@ pub async fn join_room(
                             message: "Couldnt join room because of DB Error".to_owned(),
                         });
                     }
-                    //Assign room token
+                    //Every join through an invite takes up one of its uses
+                    if let Some(invite) = &invite {
+                        let redeemed = storage.redeem_room_invite(&invite.invite_token, Utc::now().timestamp()).await;
+                        //No use left for them, they don't stay on as a member
+                        if !matches!(redeemed, Ok(true)) && !is_member {
+                            let _ = storage.remove_member_from_room(&user_name, &passed_room.room_name).await;
+                        }
+                        match redeemed {
+                            Ok(true) => {},
+                            Ok(false) => {
+                                return HttpResponse::Unauthorized().json(JoinRoomResponseData {
+                                    status_type: StatusTypes::INVALID_INVITE,
+                                    room_token: "".to_owned(),
+                                    room_name: passed_room.room_name,
+                                    message: "Invite has expired or has no uses left".to_owned(),
+                                });
+                            }
+                            Err(err) => {
+                                return HttpResponse::InternalServerError().json(JoinRoomResponseData {
+                                    status_type: StatusTypes::DATABASE_ERROR,
+                                    room_token: "".to_owned(),
+                                    room_name: passed_room.room_name,
+                                    message: "Couldnt join room because of DB Error".to_owned(),
+                                });
+                            }
+                        }
+                    }
+                    //Assign room token, owners coming back to their persistent room rejoin as OWNER
+                    let role = if data[0].room_owner==user_name { RoomRoles::OWNER } else { RoomRoles::MEMBER };
                     let room_access_tok = encode_user_room_token(UserRoomClaims{
                         username: user_name,
                         exp: (Utc::now() + Duration::minutes(10)).timestamp(),
                         room_name: passed_room.room_name.clone(),
                         room_key: room_key_hash,
-                        role: RoomRoles::MEMBER
+                        role: role
                     });
             
                     return HttpResponse::Ok().json(JoinRoomResponseData {
@@ -451,6 +708,14 @@ pub async fn join_room(
                         message: "Room Joined Successfully".to_owned(),
                     });
                 }
+                else if invite.is_some() {
+                    return HttpResponse::Unauthorized().json(JoinRoomResponseData {
+                        status_type: StatusTypes::INVALID_INVITE,
+                        room_token: "".to_owned(),
+                        room_name: passed_room.room_name,
+                        message: "Invite was revoked by a room key change".to_owned(),
+                    });
+                }
                 else {
                     return HttpResponse::Unauthorized().json(JoinRoomResponseData {
                         status_type: StatusTypes::INVALID_CREDENTIALS,
@@ -471,21 +736,309 @@ pub async fn join_room(
     }
 }
 
+#[actix_web::post("/respond_join_request")]
+pub async fn respond_join_request(
+    user: UserAuthenticationTokenPayload,
+    body: web::Json<Respond_Join_Request>,
+    rooms_shared_state: web::Data<RoomChatSharedState>,
+    storage: web::Data<dyn Storage>,
+    broker: web::Data<dyn NotificationBroker>
+) -> impl Responder {
+
+    let user_name = user.username;
+
+    let passed_data = body.into_inner();
+
+    let joiner = passed_data.username;
+
+    let room = match storage.get_room_data(&passed_data.room_name).await {
+        Ok(data) => match data.into_iter().next() {
+            Some(room) => room,
+            None => {
+                return HttpResponse::NotFound().json(RespondJoinRequestResponseData {
+                    status_type: StatusTypes::ROOM_NOT_FOUND,
+                    message: "Room could not be found".to_owned(),
+                });
+            }
+        },
+        Err(err) => {
+            return HttpResponse::InternalServerError().json(RespondJoinRequestResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                message: format!("Internal server error because of DB error: {}", err),
+            });
+        }
+    };
+
+    if room.room_owner!=user_name {
+        return HttpResponse::Forbidden().json(RespondJoinRequestResponseData {
+            status_type: StatusTypes::INVALID_CREDENTIALS,
+            message: "Only the room owner can answer join requests".to_owned(),
+        });
+    }
+
+    if !room.join_requests.contains(&joiner) {
+        return HttpResponse::NotFound().json(RespondJoinRequestResponseData {
+            status_type: StatusTypes::USER_NOT_FOUND,
+            message: "No join request from that user".to_owned(),
+        });
+    }
+
+    let join_requests: Vec<String> = room.join_requests.iter().filter(|u| **u!=joiner).cloned().collect();
+
+    match passed_data.acceptance_status {
+        AcceptanceStatus::ACCEPTED if room.banned.contains(&joiner) => {
+            //Banned after knocking, the request goes with the ban
+            let _ = storage.update_room_join_requests(&room.room_name, &join_requests).await;
+            HttpResponse::Forbidden().json(RespondJoinRequestResponseData {
+                status_type: StatusTypes::BANNED_FROM_ROOM,
+                message: format!("{} is banned from this room", joiner),
+            })
+        }
+        AcceptanceStatus::ACCEPTED => {
+            //The room may have filled up while they waited, their request stays for later
+            let connected = connected_room_members(&rooms_shared_state, &room.room_name);
+            if room.settings.max_members > 0 && !connected.contains(&joiner) && connected.len() as i64 >= room.settings.max_members {
+                return HttpResponse::Forbidden().json(RespondJoinRequestResponseData {
+                    status_type: StatusTypes::ROOM_FULL,
+                    message: "Room is full".to_owned(),
+                });
+            }
+
+            let updated = match storage.update_room_join_requests(&room.room_name, &join_requests).await {
+                Ok(()) => storage.insert_member_to_room(&joiner, &room.room_name).await,
+                Err(err) => Err(err)
+            };
+            if let Err(err) = updated {
+                return HttpResponse::InternalServerError().json(RespondJoinRequestResponseData {
+                    status_type: StatusTypes::DATABASE_ERROR,
+                    message: format!("Internal server error because of DB error: {}", err),
+                });
+            }
+
+            //Issued only now, the joiner was never handed one for knocking
+            let room_access_tok = encode_user_room_token(UserRoomClaims{
+                username: joiner.clone(),
+                exp: (Utc::now() + Duration::minutes(10)).timestamp(),
+                room_name: room.room_name.clone(),
+                room_key: room.room_key,
+                role: RoomRoles::MEMBER
+            });
+
+            let notified = deliver_notification(&broker, NotificationData {
+                n_type: NotificationType::ROOM_JOIN_APPROVED,
+                from: user_name,
+                to: joiner.clone(),
+                content: serde_json::to_string(&RoomJoinApprovedNotificationContent {
+                    room_name: room.room_name,
+                    room_token: room_access_tok
+                }).unwrap()
+            }).await;
+
+            //Now a member, they can still come in with the room key
+            if !notified {
+                return HttpResponse::Ok().json(RespondJoinRequestResponseData {
+                    status_type: StatusTypes::NOTIFICATIONS_ERROR,
+                    message: format!("{} was approved but couldnt be notified", joiner),
+                });
+            }
+
+            HttpResponse::Ok().json(RespondJoinRequestResponseData {
+                status_type: StatusTypes::JOIN_REQUEST_APPROVED,
+                message: format!("{} can join the room", joiner),
+            })
+        }
+        AcceptanceStatus::IGNORED => {
+            if let Err(err) = storage.update_room_join_requests(&room.room_name, &join_requests).await {
+                return HttpResponse::InternalServerError().json(RespondJoinRequestResponseData {
+                    status_type: StatusTypes::DATABASE_ERROR,
+                    message: format!("Internal server error because of DB error: {}", err),
+                });
+            }
+
+            HttpResponse::Ok().json(RespondJoinRequestResponseData {
+                status_type: StatusTypes::JOIN_REQUEST_DENIED,
+                message: format!("{}'s join request denied", joiner),
+            })
+        }
+    }
+}
+
+#[actix_web::post("/create_room_invite")]
+pub async fn create_room_invite(
+    user: UserAuthenticationTokenPayload,
+    body: web::Json<Create_Room_Invite>,
+    storage: web::Data<dyn Storage>,
+    broker: web::Data<dyn NotificationBroker>
+) -> impl Responder {
+
+    let user_name = user.username;
+
+    let passed_invite = body.into_inner();
+
+    //At least one use, and at most a week before it expires
+    if passed_invite.max_uses < 1 || passed_invite.expires_in_minutes < 1 || passed_invite.expires_in_minutes > 7*24*60 {
+        return HttpResponse::BadRequest().json(CreateRoomInviteResponseData {
+            status_type: StatusTypes::INVALID_INVITE,
+            invite_token: "".to_owned(),
+            expires_at: 0,
+            message: "Invites need at least one use and must expire within 7 days".to_owned(),
+        });
+    }
+
+    let room = match storage.get_room_data(&passed_invite.room_name).await {
+        Ok(data) => match data.into_iter().next() {
+            Some(room) => room,
+            None => {
+                return HttpResponse::NotFound().json(CreateRoomInviteResponseData {
+                    status_type: StatusTypes::ROOM_NOT_FOUND,
+                    invite_token: "".to_owned(),
+                    expires_at: 0,
+                    message: "Room could not be found".to_owned(),
+                });
+            }
+        },
+        Err(err) => {
+            return HttpResponse::InternalServerError().json(CreateRoomInviteResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                invite_token: "".to_owned(),
+                expires_at: 0,
+                message: format!("Internal server error because of DB error: {}", err),
+            });
+        }
+    };
+
+    if room.room_owner!=user_name {
+        return HttpResponse::Forbidden().json(CreateRoomInviteResponseData {
+            status_type: StatusTypes::INVALID_CREDENTIALS,
+            invite_token: "".to_owned(),
+            expires_at: 0,
+            message: "Only the room owner can create invites".to_owned(),
+        });
+    }
+
+    //Invites only go to DM contacts who haven't blocked the owner
+    if let Some(send_to) = &passed_invite.send_to {
+        let is_contact = matches!(
+            storage.get_dms_list(&user_name, send_to).await,
+            Ok(lists) if lists.iter().any(|list| list.username==user_name && list.dms_list.contains(send_to))
+        );
+        let is_blocked = matches!(
+            storage.get_blocked_list(send_to).await,
+            Ok(data) if data.first().is_some_and(|b| b.blocked_list.contains(&user_name))
+        );
+        if !is_contact || is_blocked {
+            return HttpResponse::NotFound().json(CreateRoomInviteResponseData {
+                status_type: StatusTypes::USER_NOT_FOUND,
+                invite_token: "".to_owned(),
+                expires_at: 0,
+                message: "User not found in your DMs".to_owned(),
+            });
+        }
+    }
+
+    let invite = Room_Invites_Table {
+        invite_token: format!("{}{}", INVITE_TOKEN_PREFIX, Uuid::new_v4().simple()),
+        room_name: room.room_name,
+        room_key: room.room_key,
+        created_by: user_name.clone(),
+        expires_at: (Utc::now() + Duration::minutes(passed_invite.expires_in_minutes)).timestamp(),
+        max_uses: passed_invite.max_uses,
+        uses: 0
+    };
+
+    if let Err(err) = storage.insert_room_invite(invite.clone()).await {
+        return HttpResponse::InternalServerError().json(CreateRoomInviteResponseData {
+            status_type: StatusTypes::DATABASE_ERROR,
+            invite_token: "".to_owned(),
+            expires_at: 0,
+            message: format!("Internal server error because of DB error: {}", err),
+        });
+    }
+
+    if let Some(send_to) = passed_invite.send_to {
+        let notification = NotificationData {
+            n_type: NotificationType::ROOM_INVITE,
+            from: user_name,
+            to: send_to,
+            content: serde_json::to_string(&RoomInviteNotificationContent {
+                room_name: invite.room_name.clone(),
+                invite_token: invite.invite_token.clone(),
+                expires_at: invite.expires_at
+            }).unwrap()
+        };
+
+        if !deliver_notification(&broker, notification).await {
+            return HttpResponse::InternalServerError().json(CreateRoomInviteResponseData {
+                status_type: StatusTypes::NOTIFICATIONS_ERROR,
+                invite_token: invite.invite_token,
+                expires_at: invite.expires_at,
+                message: "Invite created, but couldnt be sent because of a Notifications Error".to_owned(),
+            });
+        }
+    }
+
+    HttpResponse::Ok().json(CreateRoomInviteResponseData {
+        status_type: StatusTypes::ROOM_INVITE_CREATED,
+        invite_token: invite.invite_token,
+        expires_at: invite.expires_at,
+        message: "Room invite created".to_owned(),
+    })
+}
+
+#[actix_web::get("/room_directory")]
+pub async fn room_directory(
+    user: UserAuthenticationTokenPayload, // Only signed in users browse the directory
+    query: web::Query<HashMap<String, String>>,
+    rooms_shared_state: web::Data<RoomChatSharedState>,
+    storage: web::Data<dyn Storage>
+) -> impl Responder {
+
+    let page = query.get("page").and_then(|page| page.parse::<usize>().ok()).unwrap_or(0);
+
+    //One extra room tells whether there is another page
+    match storage.get_listed_rooms(page * ROOM_DIRECTORY_PAGE_SIZE, ROOM_DIRECTORY_PAGE_SIZE + 1).await {
+        Ok(mut rooms) => {
+            let has_more = rooms.len() > ROOM_DIRECTORY_PAGE_SIZE;
+            rooms.truncate(ROOM_DIRECTORY_PAGE_SIZE);
+            HttpResponse::Ok().json(RoomDirectoryResponseData {
+                status_type: StatusTypes::ROOM_DIRECTORY_FETCHED,
+                rooms: rooms.into_iter()
+                    .map(|room| {
+                        let member_count = connected_room_members(&rooms_shared_state, &room.room_name).len();
+                        Listed_Room::new(room, member_count)
+                    })
+                    .collect(),
+                page,
+                has_more,
+                message: "Room directory fetched".to_owned(),
+            })
+        }
+        Err(err) => HttpResponse::InternalServerError().json(RoomDirectoryResponseData {
+            status_type: StatusTypes::DATABASE_ERROR,
+            rooms: Vec::new(),
+            page,
+            has_more: false,
+            message: format!("Couldnt retrieve data from DB: {}", err),
+        })
+    }
+}
+
 #[actix_web::get("/room_data")]
 pub async fn retrieve_room_data(
-    user: UserRoomAuthenticationTokenPayload
+    user: UserRoomAuthenticationTokenPayload,
+    storage: web::Data<dyn Storage>
 ) -> impl Responder {
 
     let room_name = user.room_name;
 
-    let existing_room = get_room_data(&room_name).await;
+    let existing_room = storage.get_room_data(&room_name).await;
 
     match existing_room {
         Ok(data) => match data.len() {
             0 => {
                 HttpResponse::NotFound().json(RoomDataResponseData {
                     status_type: StatusTypes::ROOM_NOT_FOUND,
-                    room_data: Rooms_Table { room_name: "".to_string(), room_key: "".to_string(), room_owner: "".to_string(), room_members: Vec::new(), members_keys: Vec::new() },
+                    room_data: Rooms_Table { room_name: "".to_string(), room_key: "".to_string(), room_owner: "".to_string(), room_members: Vec::new(), members_keys: Vec::new(), persistent: false, moderators: Vec::new(), banned: Vec::new(), muted: Vec::new(), settings: RoomSettings::default(), join_requests: Vec::new() },
                     message: "Room could not be found".to_owned(),
                 })
             },
@@ -499,7 +1052,7 @@ pub async fn retrieve_room_data(
         }
         Err(err) => HttpResponse::InternalServerError().json(RoomDataResponseData {
             status_type: StatusTypes::DATABASE_ERROR,
-            room_data: Rooms_Table { room_name: "".to_string(), room_key: "".to_string(), room_owner: "".to_string(), room_members: Vec::new(), members_keys: Vec::new() },
+            room_data: Rooms_Table { room_name: "".to_string(), room_key: "".to_string(), room_owner: "".to_string(), room_members: Vec::new(), members_keys: Vec::new(), persistent: false, moderators: Vec::new(), banned: Vec::new(), muted: Vec::new(), settings: RoomSettings::default(), join_requests: Vec::new() },
             message: "Couldnt retrieve data from DB".to_owned(),
         })
     }
@@ -512,24 +1065,51 @@ pub async fn private_room_chat(
     req: HttpRequest,
     body: web::Payload,
     rooms_shared_state: web::Data<RoomChatSharedState>, // Inject shared state
+    rooms_muted_shared_state: web::Data<RoomMutedSharedState>,
+    storage: web::Data<dyn Storage>
 ) -> actix_web::Result<impl Responder> {
 
     // Initialize WebSocket connection
     let (response, mut session, mut msg_stream) = actix_ws::handle(&req, body)?;
 
     let room_name = user.room_name;
-    let room_key = user.room_key;
+    let mut room_key = user.room_key;
     let username = user.username;
     let role = user.role;
 
     let shared_state = rooms_shared_state.clone();
 
+    let muted_state = rooms_muted_shared_state.clone();
+
+    let room_data = storage.get_room_data(&room_name).await.ok().and_then(|data| data.into_iter().next());
+
+    // Banned members are turned away, even with a room token issued before the ban
+    if room_data.as_ref().is_some_and(|room| room.banned.contains(&username)) {
+        return Err(actix_web::error::ErrorForbidden("Banned from room"));
+    }
+
+    let settings = room_data.as_ref().map(|room| room.settings.clone()).unwrap_or_default();
+
+    // The muted list is read from the ROOMS table once, moderation keeps it current from then on
+    if let Some(room) = &room_data {
+        muted_state.lock().unwrap().entry(room_name.clone()).or_insert_with(|| room.muted.iter().cloned().collect());
+    }
+
+    // Persistent rooms outlive the shared state (e.g. a server restart), bring them back from the ROOMS table
+    if room_data.is_some_and(|room| room.persistent && room.room_key==room_key) {
+        shared_state.lock().unwrap().entry((room_name.clone(), room_key.clone())).or_default();
+    }
+
     // Add this session to the shared state
     {
         let mut rooms = shared_state.lock().unwrap();
 
         // Inserting user sesssion into an existing room
         if let Some(room_sessions) = rooms.get_mut(&(room_name.clone(), room_key.clone())){
+            // Room tokens outlive the /join_room check, so the cap is held again on the live sessions
+            if settings.max_members > 0 && !room_sessions.contains_key(&username) && room_sessions.len() as i64 >= settings.max_members {
+                return Err(actix_web::error::ErrorForbidden("Room is full"));
+            }
             room_sessions.insert(username.clone(), session.clone());
         }
         else{
@@ -540,7 +1120,17 @@ pub async fn private_room_chat(
 
     // Spawn an asynchronous task to handle WebSocket messages
     actix_web::rt::spawn(async move {
+        //Whether the member sent their own leave message before disconnecting
+        let mut leave_announced = false;
+        //When this member's last message went through, for slow mode
+        let mut last_message_at: Option<Instant> = None;
+
         while let Some(Ok(msg)) = msg_stream.next().await {
+            //Kicked or banned, the moderator's command already took this session out of the room
+            if !locate_room_session(&shared_state, &room_name, &username, &mut room_key) {
+                break;
+            }
+
             match msg {
                 Message::Ping(bytes) => {
                     if session.pong(&bytes).await.is_err() {
@@ -549,6 +1139,8 @@ pub async fn private_room_chat(
                 }
                 Message::Text(text) => {
 
+                    let is_muted = muted_state.lock().unwrap().get(&room_name).is_some_and(|muted| muted.contains(&username));
+
                     // Broadcast to all connected sessions in a room
                     let rooms = shared_state.lock().unwrap();
                     if let Some(room_sessions) = rooms.get(&(room_name.clone(), room_key.clone())){
@@ -563,6 +1155,25 @@ pub async fn private_room_chat(
                             Err(e) => RoomSenderMessage { username: "".to_string(), content: "".to_string(), users: Vec::new(), whisper_mode: WhisperMode::NONE, is_join_leave_msg: false }
                         };
 
+                        //Muted members are only heard joining and leaving, no ack either so their chain key stays put
+                        if is_muted && !(sender_msg.is_join_leave_msg && sender_msg.content!="  ") {
+                            continue;
+                        }
+
+                        //Whispers and the rotate informers that follow them are dropped alike when the room turns them off
+                        if !settings.whispers_allowed && !matches!(sender_msg.whisper_mode, WhisperMode::NONE) {
+                            continue;
+                        }
+
+                        //Slow mode paces messages only, joining, leaving and rotate informers go through
+                        if !sender_msg.is_join_leave_msg && sender_msg.content!="  " {
+                            let too_soon = last_message_at.is_some_and(|at| (at.elapsed().as_secs() as i64) < settings.slow_mode_seconds);
+                            if too_soon {
+                                continue;
+                            }
+                            last_message_at = Some(Instant::now());
+                        }
+
                         //Send server acknowledgment to sender only for normal messages
                         if sender_msg.is_join_leave_msg==false{
                             if sender_session.unwrap().clone().binary(AckType::ServerAck.byte()).await.is_err(){
@@ -571,6 +1182,10 @@ pub async fn private_room_chat(
                         }
 
                         
+                        if sender_msg.is_join_leave_msg && sender_msg.content.split_whitespace().nth(1)==Some("left"){
+                            leave_announced = true;
+                        }
+
                         //Parse list of users to send or hide from
                         let users_list = sender_msg.users;
                         
@@ -674,18 +1289,73 @@ pub async fn private_room_chat(
                             let receiving_username_bytes = &bytes[113..];
                             let receiving_username_res = String::from_utf8(receiving_username_bytes.to_vec());
                             if let Ok(receiving_username) = receiving_username_res{
-                                let receiving_user_session = room_sessions.get(&receiving_username);
-                                //Modify receiving username at end with sending username
-                                let sending_username_bytes = username.as_bytes();
-                                let mut modified_bytes = bytes[..113].to_vec(); // take first 113 bytes
-                                modified_bytes.extend_from_slice(sending_username_bytes); // append sending username
-                                //Send sender_key to user
-                                if receiving_user_session.unwrap().clone().binary(modified_bytes).await.is_err(){
-                                    println!("Failed to send sender key to user.");
+                                //Receiver may have left the room meanwhile
+                                if let Some(receiving_user_session) = room_sessions.get(&receiving_username){
+                                    //Modify receiving username at end with sending username
+                                    let sending_username_bytes = username.as_bytes();
+                                    let mut modified_bytes = bytes[..113].to_vec(); // take first 113 bytes
+                                    modified_bytes.extend_from_slice(sending_username_bytes); // append sending username
+                                    //Send sender_key to user
+                                    if receiving_user_session.clone().binary(modified_bytes).await.is_err(){
+                                        println!("Failed to send sender key to user.");
+                                    }
                                 }
                             }
                         }
                     }
+                    //Checking for history shared with a newcomer, only in rooms that opted in
+                    else if bytes[0] == ROOM_HISTORY_BYTE && bytes.len() >= 2 {
+                        let receiving_username_end = 2 + bytes[1] as usize;
+                        if settings.share_history_count == 0 || bytes.len() <= receiving_username_end {
+                            continue;
+                        }
+                        let rooms = shared_state.lock().unwrap();
+                        if let Some(room_sessions) = rooms.get(&(room_name.clone(), room_key.clone())){
+                            if let Ok(receiving_username) = String::from_utf8(bytes[2..receiving_username_end].to_vec()){
+                                if let Some(receiving_user_session) = room_sessions.get(&receiving_username){
+                                    let mut modified_bytes = vec![ROOM_HISTORY_BYTE, username.len() as u8];
+                                    modified_bytes.extend_from_slice(username.as_bytes());
+                                    modified_bytes.extend_from_slice(&bytes[receiving_username_end..]);
+                                    if receiving_user_session.clone().binary(modified_bytes).await.is_err(){
+                                        println!("Failed to send room history to user.");
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    //Checking for ownership transfer [0x44][New Owner Username]
+                    else if bytes[0] == 0x44 {
+                        if let Ok(new_owner) = String::from_utf8(bytes[1..].to_vec()){
+                            //Only the current owner can hand the room over, and only to a member connected right now
+                            let is_owner = matches!(
+                                storage.get_room_data(&room_name).await,
+                                Ok(data) if data.first().is_some_and(|room| room.room_owner==username)
+                            );
+                            let is_connected = new_owner!=username && shared_state.lock().unwrap()
+                                .get(&(room_name.clone(), room_key.clone()))
+                                .is_some_and(|room_sessions| room_sessions.contains_key(&new_owner));
+
+                            if is_owner && is_connected && storage.update_room_owner(&room_name, &new_owner).await.is_ok(){
+                                //Informing everyone in the room, including the old owner
+                                let mut informer: Vec<u8> = [0x44].to_vec();
+                                informer.extend_from_slice(new_owner.as_bytes());
+                                let rooms = shared_state.lock().unwrap();
+                                if let Some(room_sessions) = rooms.get(&(room_name.clone(), room_key.clone())){
+                                    for (_, s) in room_sessions.iter() {
+                                        if s.clone().binary(informer.clone()).await.is_err() {
+                                            println!("Failed to send ownership transfer to a client.");
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    //Checking for moderation commands [0x55][Action][Target Username]
+                    else if bytes[0] == ROOM_MODERATION_BYTE && bytes.len() >= 2 {
+                        if let (Some(action), Ok(target)) = (ModerationAction::from_byte(bytes[1]), String::from_utf8(bytes[2..].to_vec())){
+                            moderate_room(&storage, &shared_state, &muted_state, &room_name, &mut room_key, &username, action, target).await;
+                        }
+                    }
                 }
                 Message::Close(_) => {
                     break;
@@ -694,11 +1364,18 @@ pub async fn private_room_chat(
             }
         }
 
+        let still_in_room = locate_room_session(&shared_state, &room_name, &username, &mut room_key);
+
         // Remove user session or Hashmap of Sessions for a Room from shared state when connection closes
         // Remove and update the ROOMS table too 
-        match role {
-            RoomRoles::OWNER => {    //IF OWNER DISCONNECTS, DELETE ROOM
-                let __ = delete_room_data(&room_name).await;
+        // Ownership may have been transferred during the session, so the ROOMS table decides who the owner is
+        let (is_owner, persistent) = match storage.get_room_data(&room_name).await {
+            Ok(data) if !data.is_empty() => (data[0].room_owner==username, data[0].persistent),
+            _ => (matches!(role, RoomRoles::OWNER), false)
+        };
+        match is_owner && !persistent {
+            true => {    //IF OWNER OF AN EPHEMERAL ROOM DISCONNECTS, DELETE ROOM
+                let __ = storage.delete_room_data(&room_name).await;
 
                 {
                     let mut rooms = shared_state.lock().unwrap();
@@ -711,16 +1388,27 @@ pub async fn private_room_chat(
                         //Removing ROOM
                         rooms.remove(&(room_name.clone(), room_key));
                     }
+                    muted_state.lock().unwrap().remove(&room_name);
                 }
             }
-            RoomRoles::MEMBER => {  //FOR MEMBERS
+            false if still_in_room => {  //FOR MEMBERS, AND OWNERS OF PERSISTENT ROOMS
+                let __ = storage.remove_member_from_room(&username, &room_name).await;
                 {
                     let mut rooms = shared_state.lock().unwrap();
                     if let Some(room_sessions) = rooms.get_mut(&(room_name.clone(), room_key)) {
                         room_sessions.remove(&username);
+
+                        //Members that dropped without a leave message are announced here
+                        if !leave_announced {
+                            announce_leave(room_sessions, &username).await;
+                        }
                     }
                 }
-                let __ = remove_member_from_room(&username, &room_name).await;
+
+                // Closing user session if any
+                let _ = session.close(None).await;
+            }
+            false => {  //KICKED OR BANNED, ALREADY REMOVED AND ANNOUNCED
 
                 // Closing user session if any
                 let _ = session.close(None).await;
@@ -734,20 +1422,205 @@ pub async fn private_room_chat(
 }
 
 
+// Finds the room holding this session, following the room key if the owner rotated it.
+// False once the session is no longer in the room
+fn locate_room_session(shared_state: &RoomChatSharedState, room_name: &str, username: &str, room_key: &mut String) -> bool {
+    let rooms = shared_state.lock().unwrap();
+    match rooms.iter().find(|((name, _), room_sessions)| name==room_name && room_sessions.contains_key(username)) {
+        Some(((_, current_key), _)) => {
+            *room_key = current_key.clone();
+            true
+        }
+        None => false
+    }
+}
+
+
+// Members connected to a room right now, under whichever room key it currently has
+fn connected_room_members(shared_state: &RoomChatSharedState, room_name: &str) -> HashSet<String> {
+    let rooms = shared_state.lock().unwrap();
+    rooms.iter()
+        .filter(|((name, _), _)| name==room_name)
+        .flat_map(|(_, room_sessions)| room_sessions.keys().cloned())
+        .collect()
+}
+
+
+// Announces a member leaving on their behalf, so the rest of the room still rotates away the sender keys they held
+async fn announce_leave(room_sessions: &HashMap<String, Session>, username: &str) {
+    let leave_msg = serde_json::to_string(&RoomReceiverMessage{
+        username: username.to_string(),
+        content: format!("{} left", username),
+        is_join_leave_msg: true
+    }).unwrap();
+    for (_, s) in room_sessions.iter() {
+        if s.clone().text(leave_msg.clone()).await.is_err() {
+            println!("Failed to send leave message to a client.");
+        }
+    }
+}
+
+
+// Applies a moderation command from `username`, checked against the ROOMS table.
+// Owner and moderators can kick, ban and mute, only the owner can promote and rotate the room key.
+// Moderators can't act on the owner or each other, and nobody acts on themselves.
+async fn moderate_room(
+    storage: &web::Data<dyn Storage>,
+    shared_state: &RoomChatSharedState,
+    muted_state: &RoomMutedSharedState,
+    room_name: &str,
+    room_key: &mut String,
+    username: &str,
+    action: ModerationAction,
+    target: String
+) {
+    let room = match storage.get_room_data(room_name).await {
+        Ok(data) if !data.is_empty() => data[0].clone(),
+        _ => return
+    };
+
+    let is_owner = room.room_owner==username;
+    let is_moderator = is_owner || room.moderators.iter().any(|m| m==username);
+    let can_moderate_target = target!=username && target!=room.room_owner && (is_owner || !room.moderators.contains(&target));
+
+    let allowed = match action {
+        ModerationAction::KICK | ModerationAction::MUTE => is_moderator && can_moderate_target && room.room_members.contains(&target),
+        ModerationAction::BAN => is_moderator && can_moderate_target && !target.is_empty(),
+        ModerationAction::PROMOTE => is_owner && target!=username && room.room_members.contains(&target) && !room.moderators.contains(&target),
+        ModerationAction::ROTATE_KEY => is_owner,
+        _ => false
+    };
+
+    let mut moderators = room.moderators;
+    let mut banned = room.banned;
+    let mut muted = room.muted;
+    let mut applied = action;
+
+    let stored = match action {
+        _ if !allowed => false,
+        ModerationAction::BAN => {
+            if !banned.contains(&target) {
+                banned.push(target.clone());
+            }
+            moderators.retain(|m| *m!=target);
+            muted.retain(|m| *m!=target);
+            //A knock still waiting can't be approved anymore
+            let updated = storage.update_room_moderation(room_name, &moderators, &banned, &muted).await;
+            match updated {
+                Ok(()) if room.join_requests.contains(&target) => {
+                    let join_requests: Vec<String> = room.join_requests.iter().filter(|u| **u!=target).cloned().collect();
+                    storage.update_room_join_requests(room_name, &join_requests).await.is_ok()
+                }
+                Ok(()) => true,
+                Err(_) => false
+            }
+        }
+        ModerationAction::MUTE => {
+            if muted.contains(&target) {
+                muted.retain(|m| *m!=target);
+                applied = ModerationAction::UNMUTE;
+            } else {
+                muted.push(target.clone());
+            }
+            storage.update_room_moderation(room_name, &moderators, &banned, &muted).await.is_ok()
+        }
+        ModerationAction::PROMOTE => {
+            moderators.push(target.clone());
+            muted.retain(|m| *m!=target);
+            storage.update_room_moderation(room_name, &moderators, &banned, &muted).await.is_ok()
+        }
+        ModerationAction::ROTATE_KEY => {
+            let new_key = generate_room_key();
+            let new_key_hash = hash_room_password(new_key.clone());
+            if storage.update_room_key(room_name, &new_key_hash).await.is_ok() {
+                //Tokens for the old key stop working, the room and its sessions move under the new key
+                let room_sessions = {
+                    let mut rooms = shared_state.lock().unwrap();
+                    let room_sessions = rooms.remove(&(room_name.to_string(), room_key.clone()));
+                    if let Some(room_sessions) = &room_sessions {
+                        rooms.insert((room_name.to_string(), new_key_hash.clone()), room_sessions.clone());
+                    }
+                    room_sessions
+                };
+                *room_key = new_key_hash;
+
+                //Only the owner gets the new key to share, the rest are told it changed
+                for (id, s) in room_sessions.unwrap_or_default() {
+                    let informer = match id==username {
+                        true => [vec![ROOM_MODERATION_BYTE, applied.byte()], new_key.as_bytes().to_vec()].concat(),
+                        false => vec![ROOM_MODERATION_BYTE, applied.byte()]
+                    };
+                    if s.clone().binary(informer).await.is_err() {
+                        println!("Failed to send new room key to a client.");
+                    }
+                }
+                return;
+            }
+            false
+        }
+        _ => true //KICK changes nothing stored
+    };
+
+    if stored {
+        muted_state.lock().unwrap().insert(room_name.to_string(), muted.iter().cloned().collect());
+    }
+
+    //Sessions are copied out of the shared state first, so the lock is never held across an await below
+    let mut room_sessions = {
+        let mut rooms = shared_state.lock().unwrap();
+        let Some(room_sessions) = rooms.get_mut(&(room_name.to_string(), room_key.clone())) else {
+            return;
+        };
+        let sessions = room_sessions.clone();
+        //Whoever was kicked or banned is taken out of the room right away, without waiting on their client
+        if stored && matches!(applied, ModerationAction::KICK | ModerationAction::BAN) {
+            room_sessions.remove(&target);
+        }
+        sessions
+    };
+
+    if !stored {
+        if let Some(s) = room_sessions.get(username) {
+            if s.clone().binary(vec![ROOM_MODERATION_BYTE, ModerationAction::REJECTED.byte(), action.byte()]).await.is_err() {
+                println!("Failed to send rejection to moderator.");
+            }
+        }
+        return;
+    }
+
+    let informer = [vec![ROOM_MODERATION_BYTE, applied.byte()], target.as_bytes().to_vec()].concat();
+    for (_, s) in room_sessions.iter() {
+        if s.clone().binary(informer.clone()).await.is_err() {
+            println!("Failed to send moderation informer to a client.");
+        }
+    }
+
+    if matches!(applied, ModerationAction::KICK | ModerationAction::BAN) {
+        if let Some(s) = room_sessions.remove(&target) {
+            let _ = s.close(Some(CloseReason { code: CloseCode::Policy, description: Some("Removed from room".to_string()) })).await;
+            announce_leave(&room_sessions, &target).await;
+            let _ = storage.remove_member_from_room(&target, room_name).await;
+        }
+    }
+}
+
+
 
 
 //------------------------------------DM CHATS--------------------------------------------------
 
 
 
-// Shared Arc type for managing active dms with 2 active Websocket Sessions
-type DMChatSharedState = Arc<Mutex<HashMap<(String, String), HashMap<String, Session>>>>;
+// Shared Arc type for managing active dms with their Websocket Sessions, keyed by (username, device id)
+type DMChatSharedState = Arc<Mutex<HashMap<(String, String), HashMap<(String, String), Session>>>>;
 
 
 #[actix_web::post("/add_user")]
 pub async fn add_user(
     user: UserAuthenticationTokenPayload,
-    body: web::Json<Add_User>
+    body: web::Json<Add_User>,
+    storage: web::Data<dyn Storage>,
+    broker: web::Data<dyn NotificationBroker>
 ) -> impl Responder {
 
     let user_name = user.username;
@@ -758,7 +1631,7 @@ pub async fn add_user(
 
     let message = passed_data.message;
 
-    let found_user = get_auth_data(&add_username).await;
+    let found_user = storage.get_auth_data(&add_username).await;
 
     match found_user {
         Ok(data) => match data.len() {
@@ -770,7 +1643,7 @@ pub async fn add_user(
                 })
             },
             non_zero => {
-                let blocked_list = get_blocked_list(&add_username).await;
+                let blocked_list = storage.get_blocked_list(&add_username).await;
                 let mut is_blocked = false;
                 match blocked_list {
                     Ok(data) => {
@@ -788,7 +1661,7 @@ pub async fn add_user(
                             }
                             false => {
                                 //PUB TO PUB/SUB CHANNEL FOR ONLINE USER
-                                let pub_res = publish_notification(NotificationData{
+                                let pub_res = broker.publish_notification(NotificationData{
                                     n_type: NotificationType::ADD_REQUEST,
                                     from: user_name.clone(),
                                     to: add_username.clone(),
@@ -799,7 +1672,7 @@ pub async fn add_user(
                                     Ok(subscribers) => {
                                         if subscribers==0 { //USER IS OFFLINE
                                             //QUEUE TO LIST FOR OFFLINE USER
-                                            if let Err(err) = queue_notification(NotificationData{
+                                            if let Err(err) = broker.queue_notification(NotificationData{
                                                 n_type: NotificationType::ADD_REQUEST,
                                                 from: user_name.clone(),
                                                 to: add_username,
@@ -856,7 +1729,9 @@ pub async fn add_user(
 #[actix_web::post("/accept_user")]
 pub async fn accept_user(
     user: UserAuthenticationTokenPayload,
-    body: web::Json<Accept_User>
+    body: web::Json<Accept_User>,
+    storage: web::Data<dyn Storage>,
+    broker: web::Data<dyn NotificationBroker>
 ) -> impl Responder {
 
     let user_name = user.username;
@@ -867,29 +1742,35 @@ pub async fn accept_user(
 
     let status = passed_data.acceptance_status;
 
+    //X3DH handshake (if any) travels to the requesting user with the ACCEPTED notification
+    let accepted_content = serde_json::to_string(&AcceptedNotificationContent{
+        message: format!("{} accepted your add request", user_name.clone()),
+        x3dh_handshake: passed_data.x3dh_handshake
+    }).unwrap();
+
     match status {
         AcceptanceStatus::ACCEPTED => {
             //PUB TO PUB/SUB CHANNEL FOR ONLINE USER
-            let pub_res = publish_notification(NotificationData{
+            let pub_res = broker.publish_notification(NotificationData{
                 n_type: NotificationType::ACCEPTED,
                 from: user_name.clone(),
                 to: user_to_add.clone(),
-                content: format!("{} accepted your add request", user_name.clone())
+                content: accepted_content.clone()
             }).await;
 
             match pub_res {
                 Ok(subscribers) => {
                     if subscribers==0 { //USER IS OFFLINE
                         //QUEUE TO LIST FOR OFFLINE USER
-                        if let Err(err) = queue_notification(NotificationData{
+                        if let Err(err) = broker.queue_notification(NotificationData{
                             n_type: NotificationType::ACCEPTED,
                             from: user_name.clone(),
                             to: user_to_add.clone(),
-                            content: format!("{} accepted your add request", user_name.clone())
+                            content: accepted_content
                         })
                         .await
                         {
-                            if let Err(err) = insert_user_to_dms_list(&user_name, &user_to_add).await{
+                            if let Err(err) = storage.insert_user_to_dms_list(&user_name, &user_to_add).await{
                                 return HttpResponse::InternalServerError().json(AcceptUserResponseData {
                                     status_type: StatusTypes::DATABASE_ERROR,
                                     message: format!("Internal server error because of DB error: {}", err),
@@ -903,7 +1784,7 @@ pub async fn accept_user(
                         }
                     }
                     else { //NON ZERO NUMBER OF SUBS MEANS USER IS SUBSCRIBED CURRENTLY
-                        if let Err(err) = insert_user_to_dms_list(&user_name, &user_to_add).await{
+                        if let Err(err) = storage.insert_user_to_dms_list(&user_name, &user_to_add).await{
                             return HttpResponse::InternalServerError().json(AcceptUserResponseData {
                                 status_type: StatusTypes::DATABASE_ERROR,
                                 message: format!("Internal server error because of DB error: {}", err),
@@ -916,7 +1797,7 @@ pub async fn accept_user(
                         });
                     }
 
-                    if let Err(err) = insert_user_to_dms_list(&user_name, &user_to_add).await{
+                    if let Err(err) = storage.insert_user_to_dms_list(&user_name, &user_to_add).await{
                         return HttpResponse::InternalServerError().json(AcceptUserResponseData {
                             status_type: StatusTypes::DATABASE_ERROR,
                             message: format!("Internal server error because of DB error: {}", err),
@@ -948,15 +1829,31 @@ pub async fn accept_user(
 
 #[actix_web::get("/get_dms")]
 pub async fn get_dms_data(
-    user: UserAuthenticationTokenPayload
+    user: UserAuthenticationTokenPayload,
+    storage: web::Data<dyn Storage>
 ) -> impl Responder {
 
     let username = user.username;
 
-    let get_dms_res = get_dms_list_data(&username).await;
+    let get_dms_res = storage.get_dms_list_data(&username).await;
 
     match get_dms_res{
-        Ok(dms_data) => {
+        Ok(mut dms_data) => {
+            //Every device of a contact gets its own copy of each DM
+            for dm_user in dms_data.iter_mut() {
+                match storage.get_user_devices(&dm_user.username).await{
+                    Ok(devices) => {
+                        dm_user.devices = devices.into_iter().map(DeviceKey::from).collect();
+                    }
+                    Err(err) => {
+                        return HttpResponse::InternalServerError().json(GetDmsResponseData {
+                            status_type: StatusTypes::DATABASE_ERROR,
+                            data: Vec::new(),
+                            message: format!("DB Error: {}", err),
+                        });
+                    }
+                }
+            }
             HttpResponse::Ok().json(GetDmsResponseData {
                 status_type: StatusTypes::DMS_DATA_FETCHED,
                 data: dms_data,
@@ -976,12 +1873,13 @@ pub async fn get_dms_data(
 
 #[actix_web::get("/get_dm_chats")]
 pub async fn get_dm_chats_data(
-    user: UserAuthenticationTokenPayload
+    user: UserAuthenticationTokenPayload,
+    storage: web::Data<dyn Storage>
 ) -> impl Responder {
 
     let username = user.username;
 
-    let get_dm_chats_res = get_dm_chats_backup_data(&username).await;
+    let get_dm_chats_res = storage.get_dm_chats_backup_data(&username).await;
 
     match get_dm_chats_res{
         Ok(dms_data) => {
@@ -1005,12 +1903,13 @@ pub async fn get_dm_chats_data(
 #[actix_web::post("/upload_dm_chats")]
 pub async fn upload_dm_chats_data(
     user: UserAuthenticationTokenPayload,
-    body: web::Json<ChatData>
+    body: web::Json<ChatData>,
+    storage: web::Data<dyn Storage>
 ) -> impl Responder {
 
     let username = user.username;
     let chats_data = body.into_inner();
-    let upload_chats_result = upload_dm_chats_backup_data(&username, chats_data).await;
+    let upload_chats_result = storage.upload_dm_chats_backup_data(&username, chats_data).await;
     match upload_chats_result{
         Ok(()) => {
             HttpResponse::Ok().json(UploadDmChatsResponseData {
@@ -1029,6 +1928,120 @@ pub async fn upload_dm_chats_data(
 
 
 
+//Most one time prekeys kept per user
+const MAX_ONE_TIME_PREKEYS: usize = 200;
+
+#[actix_web::post("/upload_prekeys")]
+pub async fn upload_prekeys(
+    user: UserAuthenticationTokenPayload,
+    body: web::Json<UploadPrekeys>,
+    storage: web::Data<dyn Storage>
+) -> impl Responder {
+
+    let username = user.username;
+    let mut prekeys = body.into_inner();
+
+    let stored_count = match storage.count_one_time_prekeys(&username).await{
+        Ok(count) => count,
+        Err(err) => {
+            return HttpResponse::InternalServerError().json(UploadPrekeysResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                one_time_prekeys_count: 0,
+                message: format!("DB Error: {}", err),
+            });
+        }
+    };
+    prekeys.one_time_prekeys.truncate(MAX_ONE_TIME_PREKEYS.saturating_sub(stored_count));
+    let uploaded_count = prekeys.one_time_prekeys.len();
+
+    match storage.upload_prekeys(&username, prekeys).await{
+        Ok(()) => {
+            HttpResponse::Ok().json(UploadPrekeysResponseData {
+                status_type: StatusTypes::PREKEYS_UPLOADED,
+                one_time_prekeys_count: stored_count + uploaded_count,
+                message: "Prekeys Uploaded".to_string()
+            })
+        }
+        Err(err) => {
+            HttpResponse::InternalServerError().json(UploadPrekeysResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                one_time_prekeys_count: stored_count,
+                message: format!("DB Error: {}", err),
+            })
+        }
+    }
+}
+
+
+#[actix_web::get("/prekey_bundle")]
+pub async fn get_prekey_bundle(
+    user: UserAuthenticationTokenPayload,
+    target: web::Query<HashMap<String, String>>,
+    storage: web::Data<dyn Storage>
+) -> impl Responder {
+
+    let username = user.username;
+
+    let Some(bundle_user) = target.get("username") else {
+        return HttpResponse::BadRequest().json(PrekeyBundleResponseData {
+            status_type: StatusTypes::USER_NOT_FOUND,
+            bundle: None,
+            message: "No username provided".to_string(),
+        });
+    };
+
+    //Users who blocked the requester don't hand out their prekeys
+    match storage.get_blocked_list(bundle_user).await{
+        Ok(data) => {
+            let is_blocked = data
+                .get(0)
+                .map(|b| b.blocked_list.contains(&username))
+                .unwrap_or(false);
+
+            if is_blocked {
+                return HttpResponse::Ok().json(PrekeyBundleResponseData {
+                    status_type: StatusTypes::PREKEY_BUNDLE_NOT_FOUND,
+                    bundle: None,
+                    message: "No prekey bundle for this user".to_string(),
+                });
+            }
+        }
+        Err(err) => {
+            return HttpResponse::InternalServerError().json(PrekeyBundleResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                bundle: None,
+                message: format!("DB Error: {}", err),
+            });
+        }
+    }
+
+    match storage.get_prekey_bundle(bundle_user).await{
+        Ok(Some(bundle)) => {
+            HttpResponse::Ok().json(PrekeyBundleResponseData {
+                status_type: StatusTypes::PREKEY_BUNDLE_FETCHED,
+                bundle: Some(bundle),
+                message: "Fetched Prekey Bundle Successfully".to_string(),
+            })
+        }
+        Ok(None) => {
+            HttpResponse::Ok().json(PrekeyBundleResponseData {
+                status_type: StatusTypes::PREKEY_BUNDLE_NOT_FOUND,
+                bundle: None,
+                message: "No prekey bundle for this user".to_string(),
+            })
+        }
+        Err(err) => {
+            HttpResponse::InternalServerError().json(PrekeyBundleResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                bundle: None,
+                message: format!("DB Error: {}", err),
+            })
+        }
+    }
+}
+
+
+
 #[actix_web::get("/dm_chat")]
 pub async fn private_dm_chat(
     user: UserAuthenticationTokenPayload, // Extractor/Kindda Middleware for JWT validation
@@ -1036,9 +2049,12 @@ pub async fn private_dm_chat(
     body: web::Payload,
     target: web::Query<HashMap<String, String>>,
     dms_shared_state: web::Data<DMChatSharedState>, // Inject shared state
+    storage: web::Data<dyn Storage>,
+    broker: web::Data<dyn NotificationBroker>
 ) -> actix_web::Result<impl Responder> {
 
     let from_username = user.username;
+    let from_device = user.device_id;
     println!("{:?}", from_username.clone());
 
     let mut to_username = "".to_string();
@@ -1053,7 +2069,7 @@ pub async fn private_dm_chat(
     }
 
 
-    let dms_lists_res = get_dms_list(&from_username, &to_username).await;
+    let dms_lists_res = storage.get_dms_list(&from_username, &to_username).await;
 
     match dms_lists_res{
         Ok(data) => {
@@ -1093,17 +2109,20 @@ pub async fn private_dm_chat(
     //Unique Tuple for every DM
     let dm_id = (users[0].clone(), users[1].clone());
 
+    //Each device of a user has its own session in the DM
+    let session_id = (from_username.clone(), from_device.clone());
+
     // Add this session to the shared state
     {
         let mut dms = shared_state.lock().unwrap();
 
         // Inserting user sesssion into an existing dm, if not creating new hashmap of sessions
         if let Some(dm_sessions) = dms.get_mut(&(dm_id)){
-            dm_sessions.insert(from_username.clone(), session.clone());
+            dm_sessions.insert(session_id.clone(), session.clone());
         }
         else{
             let mut session_map = HashMap::new();
-            session_map.insert(from_username.clone(), session.clone());
+            session_map.insert(session_id.clone(), session.clone());
             dms.insert(dm_id.clone(), session_map);
         }
          
@@ -1120,63 +2139,103 @@ pub async fn private_dm_chat(
                 }
                 Message::Text(text) => {
 
-                    let dms = shared_state.lock().unwrap();
-                    if let Some(dm_sessions) = dms.get(&(dm_id)){
-                        let receiver_session = dm_sessions.get(&to_username);
-                        let sender_session = dm_sessions.get(&from_username);
+                    //Sender's session and the receiver's devices connected to this DM
+                    let dm_sessions = {
+                        let dms = shared_state.lock().unwrap();
+                        dms.get(&(dm_id)).map(|dm_sessions| (
+                            dm_sessions.get(&session_id).cloned(),
+                            dm_sessions.iter()
+                                .filter(|((username, _), _)| *username==to_username)
+                                .map(|((_, device_id), session)| (device_id.clone(), session.clone()))
+                                .collect::<Vec<(String, Session)>>()
+                        ))
+                    };
+
+                    if let Some((sender_session, receiver_sessions)) = dm_sessions{
 
                         //Parse to Struct
                         let sender_msg_res = serde_json::from_str::<DmSenderMessage>(&text);
 
-                        let is_online_offline_msg = match sender_msg_res{
+                        let is_online_offline_msg = match &sender_msg_res{
                             Ok(m) => m.is_online_offline_msg,
                             Err(e) => true //Treating as online/offline message during error to avoid sending any notification and ack
                         };
 
+                        //Each receiving device only gets its own ciphertext
+                        let device_text = |device_id: &str| -> String {
+                            match &sender_msg_res {
+                                Ok(m) => serde_json::to_string(&m.for_device(&from_device, device_id)).unwrap_or(text.to_string()),
+                                Err(_) => text.to_string()
+                            }
+                        };
+
                         //Send server acknowledgment to sender only for normal messages
                         if is_online_offline_msg==false{
-                            if sender_session.unwrap().clone().binary(AckType::ServerAck.byte()).await.is_err(){
-                                println!("Failed to send server ack to sender.");
+                            if let Some(mut sender_session) = sender_session{
+                                if sender_session.binary(AckType::ServerAck.byte()).await.is_err(){
+                                    println!("Failed to send server ack to sender.");
+                                }
                             }
                         }
                         
-                        //receiver is connected to ws
-                        if dm_sessions.len()==2 {
-                            if receiver_session.unwrap().clone().text(text).await.is_err(){
+                        //receiver's devices connected to ws
+                        for (device_id, receiver_session) in &receiver_sessions {
+                            if receiver_session.clone().text(device_text(device_id)).await.is_err(){
                                 println!("Failed to send message to a client.");
                             }
                         }
-                        //receiver has disconnected from ws
-                        else if dm_sessions.len()==1 && is_online_offline_msg==false{
-                            //PUB TO PUB/SUB CHANNEL FOR ONLINE RECEIVER
-                            let pub_res = publish_notification(NotificationData{ 
-                                n_type: NotificationType::MESSAGE, 
-                                from: from_username.clone(), 
-                                to: to_username.clone(), 
-                                content: text.to_string()
-                            }).await;
 
-                            match pub_res{
-                                Ok(subscribers) => {
-                                    if subscribers==0 { //RECEIVER IS OFFLINE
-                                        //QUEUE TO LIST FOR OFFLINE RECEIVER
-                                        if let Err(err) = queue_notification(NotificationData{ 
-                                            n_type: NotificationType::MESSAGE, 
-                                            from: from_username.clone(), 
-                                            to: to_username.clone(), 
-                                            content: text.to_string()
-                                        })
-                                        .await
-                                        {
-                                            println!("Couldnt queue messg notif: {}", err);
+                        //receiver's devices disconnected from ws
+                        if is_online_offline_msg==false{
+                            let receiver_devices = match storage.get_user_devices(&to_username).await{
+                                Ok(devices) => devices,
+                                Err(err) => {
+                                    println!("DB error in messg notif: {}", err);
+                                    Vec::new()
+                                }
+                            };
+
+                            //Every device not in the DM gets the message in its own mailbox
+                            let mut mailboxes: Vec<(String, String)> = receiver_devices.iter()
+                                .filter(|device| !receiver_sessions.iter().any(|(device_id, _)| *device_id==device.device_id))
+                                .map(|device| (device_mailbox(&to_username, &device.device_id), device_text(&device.device_id)))
+                                .collect();
+
+                            //Receivers without linked devices get it under their username, as long as they aren't in the DM
+                            if receiver_devices.is_empty() && receiver_sessions.is_empty() {
+                                mailboxes.push((to_username.clone(), device_text("")));
+                            }
+
+                            for (mailbox, content) in mailboxes {
+                                //PUB TO PUB/SUB CHANNEL FOR ONLINE RECEIVER
+                                let pub_res = broker.publish_notification(NotificationData{ 
+                                    n_type: NotificationType::MESSAGE, 
+                                    from: from_username.clone(), 
+                                    to: mailbox.clone(), 
+                                    content: content.clone()
+                                }).await;
+
+                                match pub_res{
+                                    Ok(subscribers) => {
+                                        if subscribers==0 { //RECEIVER IS OFFLINE
+                                            //QUEUE TO LIST FOR OFFLINE RECEIVER
+                                            if let Err(err) = broker.queue_notification(NotificationData{ 
+                                                n_type: NotificationType::MESSAGE, 
+                                                from: from_username.clone(), 
+                                                to: mailbox, 
+                                                content
+                                            })
+                                            .await
+                                            {
+                                                println!("Couldnt queue messg notif: {}", err);
+                                            }
                                         }
                                     }
-                                }
-                                Err(err) => {
-                                    println!("Redis error in messg notif: {}", err);
+                                    Err(err) => {
+                                        println!("Redis error in messg notif: {}", err);
+                                    }
                                 }
                             }
-                            
                         }
                         
                     }
@@ -1186,11 +2245,19 @@ pub async fn private_dm_chat(
                 }
                 Message::Binary(bytes) => {
                     if bytes.as_ref()==AckType::ReceiverAck.byte(){
-                        let dms = shared_state.lock().unwrap();
-                        if let Some(dm_sessions) = dms.get(&(dm_id)){
-                            let sender_session = dm_sessions.get(&to_username);
-                            //Send receiver acknowledgment from receiver to sender
-                            if sender_session.unwrap().clone().binary(AckType::ReceiverAck.byte()).await.is_err(){
+                        //The other user's devices in the DM
+                        let sender_sessions: Vec<Session> = {
+                            let dms = shared_state.lock().unwrap();
+                            dms.get(&(dm_id))
+                                .map(|dm_sessions| dm_sessions.iter()
+                                    .filter(|((username, _), _)| *username==to_username)
+                                    .map(|(_, session)| session.clone())
+                                    .collect())
+                                .unwrap_or_default()
+                        };
+                        //Send receiver acknowledgment from receiver to sender
+                        for mut sender_session in sender_sessions {
+                            if sender_session.binary(AckType::ReceiverAck.byte()).await.is_err(){
                                 println!("Failed to send receiver ack to sender.");
                             }
                         }
@@ -1207,7 +2274,7 @@ pub async fn private_dm_chat(
         {
             let mut dms = dms_shared_state.lock().unwrap();
             if let Some(dm_sessions) = dms.get_mut(&(dm_id)) {
-                dm_sessions.remove(&from_username);
+                dm_sessions.remove(&session_id);
             }
         }
 
@@ -1227,7 +2294,8 @@ pub async fn private_dm_chat(
 pub async fn block_user(
     user: UserAuthenticationTokenPayload,
     body: web::Json<Block_User>,
-    dms_shared_state: web::Data<DMChatSharedState>
+    dms_shared_state: web::Data<DMChatSharedState>,
+    storage: web::Data<dyn Storage>
 ) -> impl Responder {
 
     let user_name = user.username;
@@ -1236,7 +2304,7 @@ pub async fn block_user(
 
     let user_to_block = passed_data.username_to_block;
 
-    let b_list_res = get_blocked_list(&user_name).await;
+    let b_list_res = storage.get_blocked_list(&user_name).await;
 
     match b_list_res {
         Ok(data) => {
@@ -1253,7 +2321,7 @@ pub async fn block_user(
                     }); 
                 }
                 false => {
-                    if let Err(err) = insert_user_to_blocked_list(&user_name, &user_to_block).await{
+                    if let Err(err) = storage.insert_user_to_blocked_list(&user_name, &user_to_block).await{
                         return HttpResponse::InternalServerError().json(BlockUserResponseData {
                             status_type: StatusTypes::DATABASE_ERROR,
                             message: format!("Internal server error because of DB error: {}", err),
@@ -1296,7 +2364,8 @@ pub async fn block_user(
 #[actix_web::post("/unblock_user")]
 pub async fn unblock_user(
     user: UserAuthenticationTokenPayload,
-    body: web::Json<Unblock_User>
+    body: web::Json<Unblock_User>,
+    storage: web::Data<dyn Storage>
 ) -> impl Responder {
 
     let user_name = user.username;
@@ -1305,7 +2374,7 @@ pub async fn unblock_user(
 
     let user_to_unblock = passed_data.username_to_unblock;
 
-    let b_list_res = get_blocked_list(&user_name).await;
+    let b_list_res = storage.get_blocked_list(&user_name).await;
 
     match b_list_res {
         Ok(data) => {
@@ -1316,7 +2385,7 @@ pub async fn unblock_user(
 
             match is_blocked {
                 true => {
-                    if let Err(err) = remove_user_from_blocked_list(&user_name, &user_to_unblock).await{
+                    if let Err(err) = storage.remove_user_from_blocked_list(&user_name, &user_to_unblock).await{
                         return HttpResponse::InternalServerError().json(UnblockUserResponseData {
                             status_type: StatusTypes::DATABASE_ERROR,
                             message: format!("Internal server error because of DB error: {}", err),
@@ -1356,6 +2425,329 @@ pub async fn unblock_user(
 
 
 
+//______________DEVICES_________________________________________!
+
+
+#[actix_web::get("/devices")]
+pub async fn get_devices(
+    user: UserAuthenticationTokenPayload,
+    storage: web::Data<dyn Storage>
+) -> impl Responder {
+
+    let username = user.username;
+
+    match storage.get_user_devices(&username).await{
+        Ok(devices) => {
+            HttpResponse::Ok().json(DevicesResponseData {
+                status_type: StatusTypes::DEVICES_FETCHED,
+                devices,
+                current_device_id: user.device_id,
+                message: "Fetched Devices Successfully".to_string(),
+            })
+        }
+        Err(err) => {
+            HttpResponse::InternalServerError().json(DevicesResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                devices: Vec::new(),
+                current_device_id: user.device_id,
+                message: format!("DB Error: {}", err),
+            })
+        }
+    }
+}
+
+
+
+#[actix_web::post("/revoke_device")]
+pub async fn revoke_device(
+    user: UserAuthenticationTokenPayload,
+    body: web::Json<Revoke_Device>,
+    dms_shared_state: web::Data<DMChatSharedState>,
+    storage: web::Data<dyn Storage>
+) -> impl Responder {
+
+    let user_name = user.username;
+
+    let device_id = body.into_inner().device_id;
+
+    //Signing out is how a device leaves, so it can't cut itself off
+    if device_id==user.device_id {
+        return HttpResponse::BadRequest().json(RevokeDeviceResponseData {
+            status_type: StatusTypes::CANNOT_REVOKE_CURRENT_DEVICE,
+            message: "Can't revoke the device you are using".to_string(),
+        });
+    }
+
+    let is_registered = match storage.get_user_devices(&user_name).await{
+        Ok(devices) => devices.iter().any(|device| device.device_id==device_id),
+        Err(err) => {
+            return HttpResponse::InternalServerError().json(RevokeDeviceResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                message: format!("DB Error: {}", err),
+            });
+        }
+    };
+    if !is_registered {
+        return HttpResponse::NotFound().json(RevokeDeviceResponseData {
+            status_type: StatusTypes::DEVICE_NOT_FOUND,
+            message: "No such device".to_string(),
+        });
+    }
+
+    //Tombstoned before it is removed, so the device can't sign in and register itself again in between
+    let revoked = match storage.insert_revoked_device(Revoked_Devices_Table {
+        username: user_name.clone(),
+        device_id: device_id.clone(),
+        revoked_at: Utc::now().timestamp()
+    }).await{
+        Ok(()) => storage.remove_user_device(&user_name, &device_id).await,
+        Err(err) => Err(err)
+    };
+    if let Err(err) = revoked{
+        return HttpResponse::InternalServerError().json(RevokeDeviceResponseData {
+            status_type: StatusTypes::DATABASE_ERROR,
+            message: format!("DB Error: {}", err),
+        });
+    }
+
+    //Dropping the revoked device from every DM it has open
+    let revoked_sessions: Vec<Session> = {
+        let mut dms = dms_shared_state.lock().unwrap();
+        dms.values_mut()
+            .filter_map(|dm_sessions| dm_sessions.remove(&(user_name.clone(), device_id.clone())))
+            .collect()
+    };
+    for session in revoked_sessions {
+        let _ = session.close(Some(CloseReason { code: CloseCode::Policy, description: Some("Device revoked".to_string()) })).await;
+    }
+
+    HttpResponse::Ok().json(RevokeDeviceResponseData {
+        status_type: StatusTypes::DEVICE_REVOKED,
+        message: "Device revoked".to_string(),
+    })
+}
+
+
+
+
+#[actix_web::post("/create_device_link")]
+pub async fn create_device_link(
+    user: UserAuthenticationTokenPayload,
+    body: web::Json<Create_Device_Link>,
+    storage: web::Data<dyn Storage>
+) -> impl Responder {
+
+    let passed_link = body.into_inner();
+
+    //The bundle is sealed with the secret half of the code, the server only ever holds ciphertext
+    if passed_link.link_id.is_empty() || passed_link.sealed_bundle.is_empty() || passed_link.sealed_bundle.len() > DEVICE_LINK_MAX_BUNDLE_LEN {
+        return HttpResponse::BadRequest().json(DeviceLinkResponseData {
+            status_type: StatusTypes::INVALID_DEVICE_LINK,
+            sealed_bundle: "".to_owned(),
+            expires_at: 0,
+            message: "Invalid device link".to_string(),
+        });
+    }
+
+    let link = Device_Links_Table {
+        username: user.username,
+        link_id: passed_link.link_id,
+        sealed_bundle: passed_link.sealed_bundle,
+        expires_at: (Utc::now() + Duration::minutes(DEVICE_LINK_TTL_MINUTES)).timestamp()
+    };
+    let expires_at = link.expires_at;
+
+    match storage.insert_device_link(link).await {
+        Ok(_) => {
+            HttpResponse::Ok().json(DeviceLinkResponseData {
+                status_type: StatusTypes::DEVICE_LINK_CREATED,
+                sealed_bundle: "".to_owned(),
+                expires_at,
+                message: "Device link created".to_string(),
+            })
+        }
+        Err(err) => {
+            HttpResponse::InternalServerError().json(DeviceLinkResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                sealed_bundle: "".to_owned(),
+                expires_at: 0,
+                message: format!("DB Error: {}", err),
+            })
+        }
+    }
+}
+
+
+
+#[actix_web::post("/claim_device_link")]
+pub async fn claim_device_link(
+    user: UserAuthenticationTokenPayload,
+    body: web::Json<Claim_Device_Link>,
+    storage: web::Data<dyn Storage>
+) -> impl Responder {
+
+    let link_id = body.into_inner().link_id;
+
+    //Links are looked up under the claimer's own account, so a code is useless to anyone else
+    match storage.take_device_link(&user.username, &link_id, Utc::now().timestamp()).await {
+        Ok(Some(link)) => {
+            HttpResponse::Ok().json(DeviceLinkResponseData {
+                status_type: StatusTypes::DEVICE_LINK_CLAIMED,
+                sealed_bundle: link.sealed_bundle,
+                expires_at: link.expires_at,
+                message: "Device link claimed".to_string(),
+            })
+        }
+        Ok(None) => {
+            HttpResponse::NotFound().json(DeviceLinkResponseData {
+                status_type: StatusTypes::INVALID_DEVICE_LINK,
+                sealed_bundle: "".to_owned(),
+                expires_at: 0,
+                message: "Device link is invalid, used or expired".to_string(),
+            })
+        }
+        Err(err) => {
+            HttpResponse::InternalServerError().json(DeviceLinkResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                sealed_bundle: "".to_owned(),
+                expires_at: 0,
+                message: format!("DB Error: {}", err),
+            })
+        }
+    }
+}
+
+
+
+#[actix_web::post("/rotate_identity_key")]
+pub async fn rotate_identity_key(
+    user: UserAuthenticationTokenPayload,
+    body: web::Json<Rotate_Identity_Key>,
+    storage: web::Data<dyn Storage>,
+    broker: web::Data<dyn NotificationBroker>
+) -> impl Responder {
+
+    let user_name = user.username;
+
+    let rotation = body.into_inner();
+
+    let decode_key = |key: &str| -> Option<[u8;32]> { general_purpose::STANDARD.decode(key).ok()?.try_into().ok() };
+    let signature = general_purpose::STANDARD.decode(&rotation.signature).unwrap_or_default();
+
+    //Only whoever holds the old key can hand the account over to a new one
+    let is_signed = match (decode_key(&rotation.old_public_identity_key), decode_key(&rotation.new_public_identity_key)) {
+        (Some(old_key), Some(new_key)) => old_key!=new_key && verify_identity_rotation(old_key, new_key, &signature),
+        _ => false
+    };
+    if !is_signed {
+        return HttpResponse::BadRequest().json(RotateIdentityKeyResponseData {
+            status_type: StatusTypes::INVALID_IDENTITY_KEY,
+            notified_contacts: 0,
+            message: "New identity key isn't signed by the old one".to_string(),
+        });
+    }
+
+    //Signed by the key the account has now, not one it rotated away from
+    let current_key = match storage.get_auth_data(&user_name).await {
+        Ok(data) => data.into_iter().next().map(|user_auth| user_auth.public_identity_key).unwrap_or_default(),
+        Err(err) => {
+            return HttpResponse::InternalServerError().json(RotateIdentityKeyResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                notified_contacts: 0,
+                message: format!("DB Error: {}", err),
+            });
+        }
+    };
+    if current_key!=rotation.old_public_identity_key {
+        return HttpResponse::Conflict().json(RotateIdentityKeyResponseData {
+            status_type: StatusTypes::INVALID_IDENTITY_KEY,
+            notified_contacts: 0,
+            message: "Old identity key isn't the account's current one".to_string(),
+        });
+    }
+
+    //The account device holds the account key, clients without linked devices sign in as the account itself
+    let account_device = match storage.get_user_devices(&user_name).await {
+        Ok(devices) => devices.into_iter().find(|device| device.device_id==user.device_id),
+        Err(err) => {
+            return HttpResponse::InternalServerError().json(RotateIdentityKeyResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                notified_contacts: 0,
+                message: format!("DB Error: {}", err),
+            });
+        }
+    };
+    if !user.device_id.is_empty() && !account_device.as_ref().is_some_and(|device| device.public_identity_key==current_key) {
+        return HttpResponse::Forbidden().json(RotateIdentityKeyResponseData {
+            status_type: StatusTypes::NOT_ACCOUNT_DEVICE,
+            notified_contacts: 0,
+            message: "Only the account device can rotate the identity key".to_string(),
+        });
+    }
+
+    let updated = match account_device {
+        Some(device) => match storage.update_identity_key(&user_name, &rotation.new_public_identity_key).await {
+            Ok(_) => storage.update_device_identity_key(&user_name, &device.device_id, &rotation.new_public_identity_key).await,
+            Err(err) => Err(err)
+        },
+        None => storage.update_identity_key(&user_name, &rotation.new_public_identity_key).await
+    };
+    if let Err(err) = updated {
+        return HttpResponse::InternalServerError().json(RotateIdentityKeyResponseData {
+            status_type: StatusTypes::DATABASE_ERROR,
+            notified_contacts: 0,
+            message: format!("DB Error: {}", err),
+        });
+    }
+
+    //Every DM contact re-derives its root keys with the new key, online ones right away
+    let contacts = match storage.get_dms_list_data(&user_name).await {
+        Ok(contacts) => contacts,
+        Err(err) => {
+            return HttpResponse::InternalServerError().json(RotateIdentityKeyResponseData {
+                status_type: StatusTypes::DATABASE_ERROR,
+                notified_contacts: 0,
+                message: format!("Identity key rotated, but contacts couldn't be read: {}", err),
+            });
+        }
+    };
+    let rotated_content = serde_json::to_string(&IdentityRotatedNotificationContent {
+        old_public_identity_key: rotation.old_public_identity_key,
+        new_public_identity_key: rotation.new_public_identity_key,
+        signature: rotation.signature
+    }).unwrap();
+
+    let mut notified_contacts = 0;
+    for contact in contacts {
+        let notified = deliver_notification(&broker, NotificationData {
+            n_type: NotificationType::IDENTITY_ROTATED,
+            from: user_name.clone(),
+            to: contact.username,
+            content: rotated_content.clone()
+        }).await;
+        if notified {
+            notified_contacts += 1;
+        }
+    }
+
+    HttpResponse::Ok().json(RotateIdentityKeyResponseData {
+        status_type: StatusTypes::IDENTITY_KEY_ROTATED,
+        notified_contacts,
+        message: "Identity key rotated".to_string(),
+    })
+}
+
+
+
+
+
+
+
+
+
+
+
+
 //______________NOTIFICATIONS_________________________________________!
 
 
@@ -1363,6 +2755,7 @@ pub async fn unblock_user(
 pub async fn realtime_notifications(
     user: UserAuthenticationTokenPayload,
     req: HttpRequest,
+    broker: web::Data<dyn NotificationBroker>
 ) -> impl Responder {
 
     let username = user.username;
@@ -1370,8 +2763,19 @@ pub async fn realtime_notifications(
     //Channel for broadcasting
     let (task_sender, mut task_receiver) = mpsc::channel::<NotificationData>(100);
 
+    //Devices also listen on their own mailbox, for DMs encrypted to them
+    if !user.device_id.is_empty() {
+        let (mailbox_broker, mailbox_sender) = (broker.clone(), task_sender.clone());
+        let mailbox = device_mailbox(&username, &user.device_id);
+        tokio::spawn(async move {
+            if let Err(err) = mailbox_broker.subscribe_to_notifications(&mailbox, mailbox_sender).await {
+                eprintln!("Device mailbox subscription for {} failed: {}", mailbox, err);
+            }
+        });
+    }
+
     tokio::spawn(async move {
-        let s = subscribe_to_notifications(&username, task_sender).await;
+        let s = broker.subscribe_to_notifications(&username, task_sender).await;
         println!("{:?}", s);
     });
 
@@ -1400,10 +2804,21 @@ pub async fn realtime_notifications(
 pub async fn queued_notifications(
     user: UserAuthenticationTokenPayload,
     req: HttpRequest,
+    broker: web::Data<dyn NotificationBroker>
 ) -> impl Responder {
 
     let username = user.username;
-    let queued_notifications = retrieve_queued_notifications(&username).await;
+    let mut queued_notifications = broker.retrieve_queued_notifications(&username).await;
+
+    //Along with the ones queued in the device's own mailbox
+    if !user.device_id.is_empty() {
+        if let Ok(n_data) = queued_notifications.as_mut() {
+            match broker.retrieve_queued_notifications(&device_mailbox(&username, &user.device_id)).await {
+                Ok(mailbox_data) => n_data.extend(mailbox_data),
+                Err(err) => queued_notifications = Err(err)
+            }
+        }
+    }
 
     match queued_notifications {
         Ok(n_data) => {
@@ -1,10 +1,14 @@
-use shuttle_runtime::SecretStore;
 use once_cell::sync::Lazy;
+use std::collections::HashMap;
 use std::sync::Mutex;
 
-static SECRETS: Lazy<Mutex<Option<SecretStore>>> = Lazy::new(|| Mutex::new(None));
+/// Keys the self-hosted server expects to find in its config file or environment.
+#[cfg(not(feature = "shuttle"))]
+pub const SECRET_KEYS: [&str; 4] = ["REDIS_URL", "TOKEN_SECRET", "SUPABASE_URL", "SUPABASE_API_KEY"];
 
-pub fn set_secrets(secret_store: SecretStore) {
+static SECRETS: Lazy<Mutex<Option<HashMap<String, String>>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn set_secrets(secret_store: HashMap<String, String>) {
     let mut secrets = SECRETS.lock().unwrap();
     *secrets = Some(secret_store);
 }
@@ -13,3 +17,41 @@ pub fn get_secret(key: &str) -> Option<String> {
     let secrets = SECRETS.lock().unwrap();
     secrets.as_ref()?.get(key).map(|s| s.to_string())
 }
+
+#[cfg(feature = "shuttle")]
+pub fn from_shuttle_secrets(secret_store: shuttle_runtime::SecretStore) -> HashMap<String, String> {
+    secret_store.into_iter().collect()
+}
+
+/// Loads secrets for the self-hosted server.
+/// Values come from a flat TOML file (same layout as Shuttle's `Secrets.toml`),
+/// and environment variables with the same names take precedence over it.
+#[cfg(not(feature = "shuttle"))]
+pub fn load_self_hosted_secrets(config_path: Option<&str>) -> Result<HashMap<String, String>, String> {
+
+    let mut secrets: HashMap<String, String> = HashMap::new();
+
+    if let Some(path) = config_path {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| format!("Could not read config file {}: {}", path, err))?;
+
+        let table: toml::Table = contents.parse()
+            .map_err(|err| format!("Could not parse config file {}: {}", path, err))?;
+
+        for (key, value) in table {
+            let value = match value {
+                toml::Value::String(s) => s,
+                other => other.to_string()
+            };
+            secrets.insert(key, value);
+        }
+    }
+
+    for key in SECRET_KEYS.iter().chain(["BIND_ADDRESS", "STORAGE_BACKEND", "SQLITE_PATH", "NOTIFICATION_BROKER"].iter()) {
+        if let Ok(value) = std::env::var(key) {
+            secrets.insert(key.to_string(), value);
+        }
+    }
+
+    Ok(secrets)
+}
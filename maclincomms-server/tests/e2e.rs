@@ -0,0 +1,1397 @@
+//! End-to-end tests: boots the actix app on a random local port with in-memory storage and
+//! notification broker, then drives scripted clients built from the client crate's
+//! `network_jobs` and `crypto` modules over real HTTP and websocket connections.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::{App, HttpServer};
+use base64::{engine::general_purpose, Engine as _};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{connect_async, tungstenite::{ClientRequestBuilder, Message}, MaybeTlsStream, WebSocketStream};
+
+use maclincoms_server::{
+    configure_app,
+    database::{memory_broker::InMemoryBroker, sqlite_storage::SqliteStorage},
+    secret_store::set_secrets
+};
+
+use maclincomms::{
+    crypto::{
+        decrypt_msg::{decrypt_room_message, decrypt_senderkey_message},
+        device_link::{generate_link_code, open_link_bundle, parse_link_code, seal_link_bundle},
+        dm_envelope::{DmEnvelope, DM_ENVELOPE_VERSION},
+        double_ratchet::DoubleRatchet,
+        identity_pair::{sign_identity_rotation, verify_identity_rotation},
+        dm_keys::{derive_message_key, generate_dh_keypair, generate_receiver_chainkey, generate_sender_chainkey, generate_shared_rootkey},
+        encrypt_msg::{encrypt_room_message, encrypt_senderkey_message, sign_room_ciphertext, verify_room_ciphertext},
+        room_history::{compose_room_history_frame, open_room_history, parse_room_history_frame, seal_room_history},
+        room_keys::{compose_sender_key, derive_roommessage_key, generate_roomchain_key},
+        room_whisper::{decrypt_room_whisper, encrypt_room_whisper, is_room_whisper},
+        signature_keypair::generate_signature_keypair,
+        x3dh::{verify_prekey_signature, x3dh_initiate, x3dh_respond}
+    },
+    endpoints::{Endpoints, ServerUrl},
+    network_jobs::{
+        accept_user::{accept_user, AcceptUserResponseResult},
+        add_user::{add_user, AddUserResponseResult},
+        authN_user::authenticate_user,
+        claim_device_link::{claim_device_link, ClaimDeviceLinkResponseResult},
+        create_room::{create_room, CreateRoomResponseResult},
+        create_device_link::{create_device_link, CreateDeviceLinkResponseResult},
+        create_room_invite::{create_room_invite, CreateRoomInviteResponseResult},
+        get_devices::{get_devices, DevicesResponseResult},
+        get_dms::{get_dms, GetDmsResponseResult},
+        get_prekey_bundle::{get_prekey_bundle, GetPrekeyBundleResponseResult},
+        get_room_directory::{get_room_directory, RoomDirectoryResponseResult},
+        get_roomdata::get_room_data,
+        join_room::{join_room, JoinRoomResponseResult},
+        login::login_account,
+        register::register_account,
+        request_token::request_new_token,
+        respond_join_request::{respond_join_request, RespondJoinRequestResponseResult},
+        revoke_device::{revoke_device, RevokeDeviceResponseResult},
+        rotate_identity::{rotate_identity_key, RotateIdentityKeyResponseResult},
+        upload_prekeys::{upload_prekeys, UploadPrekeysResponseResult}
+    },
+    user_model::{AcceptanceStatus, AcceptedNotificationContent, DeviceLinkBundle, IdentityRotatedNotificationContent, DmE2EEncryption_Data, ListedRoom, DmMessage, DmSessionEncryption_Key, UserIdentityKeys, VerifiedContacts, StatusTypes, ModerationAction, RoomInviteNotificationContent, RoomJoinApprovedNotificationContent, RoomHistoryMessage, RoomReceiverMessage, RoomSenderMessage, RoomSettings, UserLoginPayload, UserPrekeys, UserRegisterPayload, WhisperMode, X3dhHandshake, ROOM_MODERATION_BYTE}
+};
+
+
+const SERVER_ACK: u8 = 0x01;
+const RECEIVER_ACK: u8 = 0x02;
+const SENDER_KEY_DESCRIPTOR: u8 = 0x11;
+const ROTATE_CHAIN_KEY_INFORMER: u8 = 0x33;
+const OWNERSHIP_TRANSFER: u8 = 0x44;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+
+struct TestUser {
+    username: String,
+    token: String,
+    identity_pub: [u8;32],
+    identity_priv: [u8;32]
+}
+
+
+async fn spawn_server() -> Endpoints {
+    set_secrets(HashMap::from([("TOKEN_SECRET".to_string(), "e2e-test-secret".to_string())]));
+
+    let storage = Arc::new(SqliteStorage::open_in_memory().unwrap());
+    let broker = Arc::new(InMemoryBroker::new());
+    let pub_chat_shared_state = Arc::new(Mutex::new(HashMap::new()));
+    let rooms_shared_state = Arc::new(Mutex::new(HashMap::new()));
+    let rooms_muted_shared_state = Arc::new(Mutex::new(HashMap::new()));
+    let dms_shared_state = Arc::new(Mutex::new(HashMap::new()));
+
+    let server = HttpServer::new(move || {
+        let (storage, broker) = (storage.clone(), broker.clone());
+        let (pub_chat, rooms, rooms_muted, dms) = (pub_chat_shared_state.clone(), rooms_shared_state.clone(), rooms_muted_shared_state.clone(), dms_shared_state.clone());
+        App::new().configure(move |cfg| configure_app(cfg, pub_chat, rooms, rooms_muted, dms, storage, broker))
+    })
+    .workers(1)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+
+    let addr = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+
+    Endpoints::from_server_url(&ServerUrl::parse(&format!("http://{}", addr)))
+}
+
+
+// Signs up and in without a device id, the way clients from before linked devices do
+async fn register_and_login(endpoints: &Endpoints, username: &str) -> TestUser {
+    let (identity_pub, identity_priv) = generate_dh_keypair();
+
+    let registered = register_account(UserRegisterPayload {
+        username: username.to_string(),
+        password: "correct horse battery staple".to_string(),
+        public_identity_key: general_purpose::STANDARD.encode(identity_pub),
+        device_id: String::new(),
+        device_name: String::new()
+    }, endpoints.register).await.unwrap();
+    assert!(matches!(registered.status_type, StatusTypes::REGISTRATION_SUCCESSFUL));
+
+    let logged_in = login_account(UserLoginPayload {
+        username: username.to_string(),
+        password: "correct horse battery staple".to_string(),
+        device_id: String::new(),
+        device_name: String::new(),
+        public_identity_key: general_purpose::STANDARD.encode(identity_pub)
+    }, endpoints.login).await.unwrap();
+    assert!(matches!(logged_in.status_type, StatusTypes::LOG_IN_SUCCESSFUL));
+
+    TestUser {
+        username: username.to_string(),
+        token: logged_in.access_token,
+        identity_pub,
+        identity_priv
+    }
+}
+
+
+async fn queued_notifications(endpoints: &Endpoints, user: &TestUser) -> HashMap<String, Vec<Value>> {
+    let res: Value = reqwest::Client::new()
+        .get(endpoints.queued_notifications)
+        .header("Authorization", user.token.clone())
+        .send().await.unwrap()
+        .json().await.unwrap();
+
+    let lists: HashMap<String, Vec<String>> = serde_json::from_value(res["data"].clone()).unwrap();
+    lists.into_iter()
+        .map(|(key, items)| (key, items.iter().map(|item| serde_json::from_str(item).unwrap()).collect()))
+        .collect()
+}
+
+
+async fn connect(url: String, token: &str) -> Socket {
+    let request = ClientRequestBuilder::new(url.parse().unwrap()).with_header("Authorization", token);
+    let (socket, _) = connect_async(request).await.unwrap();
+    socket
+}
+
+// Next text or binary frame, skipping keep-alive frames
+async fn next_frame(socket: &mut Socket) -> Message {
+    loop {
+        let frame = tokio::time::timeout(Duration::from_secs(5), socket.next()).await
+            .expect("timed out waiting for a websocket frame")
+            .expect("websocket closed")
+            .unwrap();
+        match frame {
+            Message::Ping(_) | Message::Pong(_) => continue,
+            other => return other
+        }
+    }
+}
+
+async fn expect_binary(socket: &mut Socket) -> Vec<u8> {
+    match next_frame(socket).await {
+        Message::Binary(bytes) => bytes,
+        other => panic!("expected binary frame, got {:?}", other)
+    }
+}
+
+async fn expect_text(socket: &mut Socket) -> String {
+    match next_frame(socket).await {
+        Message::Text(text) => text,
+        other => panic!("expected text frame, got {:?}", other)
+    }
+}
+
+// Closes the socket and waits for the server to drop the session
+async fn disconnect(mut socket: Socket) {
+    socket.send(Message::Close(None)).await.unwrap();
+    while let Ok(Some(_)) = tokio::time::timeout(Duration::from_secs(5), socket.next()).await.unwrap().transpose() {}
+}
+
+
+// Encrypts and sends like the DM chat input, the ratchet advances its sending chain on every message
+async fn send_dm(socket: &mut Socket, sender: &TestUser, keys: &mut DoubleRatchet, plaintext: &str) {
+    let content = keys.encrypt(plaintext);
+    socket.send(Message::Text(serde_json::to_string(&DmMessage {
+        username: sender.username.clone(),
+        content,
+        is_online_offline_msg: false,
+        device_contents: HashMap::new(),
+        device_id: String::new()
+    }).unwrap())).await.unwrap();
+}
+
+
+#[actix_web::test]
+async fn dm_flow_acks_queued_notifications_and_e2ee() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+
+    // Add request is queued while bob has no realtime subscription
+    let added = add_user(alice.token.clone(), bob.username.clone(), "hey bob".to_string(), endpoints.add_user).await;
+    assert!(matches!(added, AddUserResponseResult::ADD_REQUEST_SENT));
+
+    let bob_queue = queued_notifications(&endpoints, &bob).await;
+    let requests = &bob_queue["NOTIFICATIONS<alice,bob>"];
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["n_type"], "ADD_REQUEST");
+    assert_eq!(requests[0]["content"], "hey bob");
+    assert!(queued_notifications(&endpoints, &bob).await.is_empty(), "queue is drained on retrieval");
+
+    let accepted = accept_user(bob.token.clone(), alice.username.clone(), AcceptanceStatus::ACCEPTED, None, endpoints.accept_user).await;
+    assert!(matches!(accepted, AcceptUserResponseResult::USER_ADDED));
+
+    let alice_queue = queued_notifications(&endpoints, &alice).await;
+    assert_eq!(alice_queue["NOTIFICATIONS<bob,alice>"][0]["n_type"], "ACCEPTED");
+
+    // Both sides see each other with their identity keys
+    let GetDmsResponseResult::DMS_DATA_FETCHED(alice_dms) = get_dms(alice.token.clone(), endpoints.get_dms_data).await else {
+        panic!("alice could not fetch her DMs list");
+    };
+    assert_eq!(alice_dms[0].username, "bob");
+    assert_eq!(alice_dms[0].public_identity_key, general_purpose::STANDARD.encode(bob.identity_pub));
+
+    let GetDmsResponseResult::DMS_DATA_FETCHED(bob_dms) = get_dms(bob.token.clone(), endpoints.get_dms_data).await else {
+        panic!("bob could not fetch his DMs list");
+    };
+    let alice_identity_pub: [u8;32] = general_purpose::STANDARD.decode(&bob_dms[0].public_identity_key).unwrap().try_into().unwrap();
+
+    // Each side starts its ratchet from the other's identity key, as acceptuser_thread and the ACCEPTED notification do
+    let mut alice_keys = DoubleRatchet::new(general_purpose::STANDARD.decode(&alice_dms[0].public_identity_key).unwrap().try_into().unwrap());
+    let mut bob_keys = DoubleRatchet::new(alice_identity_pub);
+
+    let mut alice_socket = connect(format!("{}?target=bob", endpoints.dm_chat), &alice.token).await;
+    let mut bob_socket = connect(format!("{}?target=alice", endpoints.dm_chat), &bob.token).await;
+
+    // Online message: server ack to sender, ciphertext forwarded to receiver
+    send_dm(&mut bob_socket, &bob, &mut bob_keys, "hello alice").await;
+    assert_eq!(expect_binary(&mut bob_socket).await, vec![SERVER_ACK]);
+
+    let received: DmMessage = serde_json::from_str(&expect_text(&mut alice_socket).await).unwrap();
+    assert_eq!(received.username, "bob");
+    let envelope = DmEnvelope::parse(&received.content).unwrap();
+    assert_eq!(envelope.version, DM_ENVELOPE_VERSION);
+    assert_eq!(envelope.counter, 0);
+    assert_eq!(alice_keys.decrypt(&envelope, alice.identity_priv).as_deref(), Some("hello alice"));
+
+    // Receiver ack is relayed back to the sender
+    alice_socket.send(Message::Binary(vec![RECEIVER_ACK])).await.unwrap();
+    assert_eq!(expect_binary(&mut bob_socket).await, vec![RECEIVER_ACK]);
+
+    // Reply on alice's ratcheted chain, before bob has received anything
+    send_dm(&mut alice_socket, &alice, &mut alice_keys, "hi bob").await;
+    assert_eq!(expect_binary(&mut alice_socket).await, vec![SERVER_ACK]);
+
+    let received: DmMessage = serde_json::from_str(&expect_text(&mut bob_socket).await).unwrap();
+    let envelope = DmEnvelope::parse(&received.content).unwrap();
+    assert_eq!(bob_keys.decrypt(&envelope, bob.identity_priv).as_deref(), Some("hi bob"));
+
+    // Offline messages: still server-acked, then queued as MESSAGE notifications
+    disconnect(alice_socket).await;
+
+    send_dm(&mut bob_socket, &bob, &mut bob_keys, "are you there?").await;
+    assert_eq!(expect_binary(&mut bob_socket).await, vec![SERVER_ACK]);
+    send_dm(&mut bob_socket, &bob, &mut bob_keys, "still around?").await;
+    assert_eq!(expect_binary(&mut bob_socket).await, vec![SERVER_ACK]);
+
+    let alice_queue = queued_notifications(&endpoints, &alice).await;
+    let messages = &alice_queue["NOTIFICATIONS<bob,alice>"];
+    assert_eq!(messages.len(), 2);
+    assert!(messages.iter().all(|message| message["n_type"] == "MESSAGE"));
+
+    let envelopes: Vec<DmEnvelope> = messages.iter()
+        .map(|message| serde_json::from_str::<DmMessage>(message["content"].as_str().unwrap()).unwrap())
+        .map(|queued| DmEnvelope::parse(&queued.content).unwrap())
+        .collect();
+    assert_eq!((envelopes[0].previous_counter, envelopes[0].counter), (1, 0));
+    assert_eq!((envelopes[1].previous_counter, envelopes[1].counter), (1, 1));
+
+    // Out of order: the later message skips ahead, the earlier one uses the stored skipped key
+    assert_eq!(alice_keys.decrypt(&envelopes[1], alice.identity_priv).as_deref(), Some("still around?"));
+    assert_eq!(alice_keys.skipped_message_keys.len(), 1);
+    assert_eq!(alice_keys.decrypt(&envelopes[0], alice.identity_priv).as_deref(), Some("are you there?"));
+    assert!(alice_keys.skipped_message_keys.is_empty());
+
+    // Replayed message is rejected without touching the ratchet
+    let keys_before = alice_keys.clone();
+    assert_eq!(alice_keys.decrypt(&envelopes[0], alice.identity_priv), None);
+    assert_eq!(alice_keys, keys_before);
+
+    disconnect(bob_socket).await;
+}
+
+
+#[actix_web::test]
+async fn x3dh_session_starts_while_requester_is_offline() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+
+    // Alice publishes her signed prekey and two one time prekeys, as start_uploadprekeys_thread does after login
+    let mut alice_prekeys = UserPrekeys::new(alice.identity_pub);
+    let one_time_prekeys = alice_prekeys.generate_one_time_prekeys(2);
+    let uploaded = upload_prekeys(alice.token.clone(), endpoints.upload_prekeys, alice_prekeys.upload_payload(one_time_prekeys)).await;
+    assert!(matches!(uploaded, UploadPrekeysResponseResult::PREKEYS_UPLOADED(2)));
+
+    let missing = get_prekey_bundle(bob.token.clone(), "carol".to_string(), endpoints.prekey_bundle).await;
+    assert!(matches!(missing, GetPrekeyBundleResponseResult::PREKEY_BUNDLE_NOT_FOUND));
+
+    let added = add_user(alice.token.clone(), bob.username.clone(), "hey bob".to_string(), endpoints.add_user).await;
+    assert!(matches!(added, AddUserResponseResult::ADD_REQUEST_SENT));
+
+    // Each fetch hands out one of the one time prekeys, never the same one twice
+    let GetPrekeyBundleResponseResult::PREKEY_BUNDLE_FETCHED(bundle) = get_prekey_bundle(bob.token.clone(), alice.username.clone(), endpoints.prekey_bundle).await else {
+        panic!("bob could not fetch alice's prekey bundle");
+    };
+    let GetPrekeyBundleResponseResult::PREKEY_BUNDLE_FETCHED(second_bundle) = get_prekey_bundle(bob.token.clone(), alice.username.clone(), endpoints.prekey_bundle).await else {
+        panic!("bob could not fetch alice's prekey bundle");
+    };
+    let one_time_prekey = bundle.one_time_prekey.clone().unwrap();
+    assert_ne!(one_time_prekey.key_id, second_bundle.one_time_prekey.unwrap().key_id);
+
+    let reuploaded = upload_prekeys(alice.token.clone(), endpoints.upload_prekeys, alice_prekeys.upload_payload(Vec::new())).await;
+    assert!(matches!(reuploaded, UploadPrekeysResponseResult::PREKEYS_UPLOADED(0)));
+
+    // Bob verifies the bundle and initiates, as initiate_x3dh_handshake does
+    let decode = |key: &str| -> [u8;32] { general_purpose::STANDARD.decode(key).unwrap().try_into().unwrap() };
+    assert_eq!(decode(&bundle.public_identity_key), alice.identity_pub);
+    let signed_prekey = decode(&bundle.signed_prekey);
+    let signature = general_purpose::STANDARD.decode(&bundle.signed_prekey_signature).unwrap();
+    assert!(verify_prekey_signature(decode(&bundle.identity_signing_key), alice.identity_pub, signed_prekey, &signature));
+
+    let (ephemeral_pub, ephemeral_priv) = generate_dh_keypair();
+    let shared_key = x3dh_initiate(bob.identity_priv, ephemeral_priv, alice.identity_pub, signed_prekey, Some(decode(&one_time_prekey.prekey)));
+    let mut bob_keys = DoubleRatchet::from_x3dh(shared_key, signed_prekey, ephemeral_priv);
+
+    let handshake = X3dhHandshake {
+        ephemeral_key: general_purpose::STANDARD.encode(ephemeral_pub),
+        signed_prekey_id: bundle.signed_prekey_id,
+        one_time_prekey_id: Some(one_time_prekey.key_id)
+    };
+    let accepted = accept_user(bob.token.clone(), alice.username.clone(), AcceptanceStatus::ACCEPTED, Some(handshake), endpoints.accept_user).await;
+    assert!(matches!(accepted, AcceptUserResponseResult::USER_ADDED));
+
+    // Bob writes before alice has even seen the acceptance
+    let mut bob_socket = connect(format!("{}?target=alice", endpoints.dm_chat), &bob.token).await;
+    send_dm(&mut bob_socket, &bob, &mut bob_keys, "welcome aboard").await;
+    assert_eq!(expect_binary(&mut bob_socket).await, vec![SERVER_ACK]);
+
+    // ACCEPTED carries the handshake, followed by the queued message
+    let alice_queue = queued_notifications(&endpoints, &alice).await;
+    let notifications = &alice_queue["NOTIFICATIONS<bob,alice>"];
+    assert_eq!(notifications[0]["n_type"], "ACCEPTED");
+    assert_eq!(notifications[1]["n_type"], "MESSAGE");
+
+    let content = AcceptedNotificationContent::parse(notifications[0]["content"].as_str().unwrap());
+    assert_eq!(content.message, "bob accepted your add request");
+    let handshake = content.x3dh_handshake.expect("ACCEPTED notification without X3DH handshake");
+
+    // Alice responds, as accept_x3dh_handshake does, using up the one time prekey
+    let one_time_prekey_priv = alice_prekeys.take_one_time_prekey(handshake.one_time_prekey_id.unwrap());
+    assert!(one_time_prekey_priv.is_some());
+    assert!(alice_prekeys.take_one_time_prekey(handshake.one_time_prekey_id.unwrap()).is_none());
+
+    let their_ephemeral_key = decode(&handshake.ephemeral_key);
+    let shared_key = x3dh_respond(alice.identity_priv, alice_prekeys.signed_prekey_priv, one_time_prekey_priv, bob.identity_pub, their_ephemeral_key);
+    let mut alice_keys = DoubleRatchet::from_x3dh(shared_key, their_ephemeral_key, alice_prekeys.signed_prekey_priv);
+
+    let queued: DmMessage = serde_json::from_str(notifications[1]["content"].as_str().unwrap()).unwrap();
+    let envelope = DmEnvelope::parse(&queued.content).unwrap();
+    assert_eq!(alice_keys.decrypt(&envelope, alice.identity_priv).as_deref(), Some("welcome aboard"));
+
+    disconnect(bob_socket).await;
+}
+
+
+#[actix_web::test]
+async fn room_flow_sender_keys_acks_and_whisper_informer() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+
+    let CreateRoomResponseResult::ROOM(room) = create_room(
+        alice.token.clone(),
+        "rustaceans".to_string(),
+        general_purpose::STANDARD.encode(alice.identity_pub),
+        false,
+        RoomSettings::default(),
+        endpoints.create_room
+    ).await else {
+        panic!("alice could not create the room");
+    };
+    let mut alice_socket = connect(endpoints.room_chat.to_string(), &room.room_token).await;
+
+    let JoinRoomResponseResult::ROOM(joined) = join_room(bob.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await else {
+        panic!("bob could not join the room");
+    };
+    let mut bob_socket = connect(endpoints.room_chat.to_string(), &joined.room_token).await;
+
+    // Join messages are broadcast without a server ack
+    bob_socket.send(Message::Text(serde_json::to_string(&RoomSenderMessage {
+        username: bob.username.clone(),
+        content: "joined".to_string(),
+        users: Vec::new(),
+        whisper_mode: WhisperMode::NONE,
+        is_join_leave_msg: true
+    }).unwrap())).await.unwrap();
+
+    let join: RoomReceiverMessage = serde_json::from_str(&expect_text(&mut alice_socket).await).unwrap();
+    assert_eq!(join.username, "bob");
+    assert!(join.is_join_leave_msg);
+
+    // Owner looks up bob's identity key and sends him her sender key
+    let room_data = get_room_data(room.room_token.clone(), endpoints.get_room_data).await.unwrap();
+    let bob_identity_pub: [u8;32] = room_data.members_keys.iter()
+        .find_map(|entry| entry.get("bob")?.as_str())
+        .map(|key| general_purpose::STANDARD.decode(key).unwrap().try_into().unwrap())
+        .expect("bob's identity key missing from members_keys");
+    assert_eq!(bob_identity_pub, bob.identity_pub);
+
+    let alice_chain_key = generate_roomchain_key();
+    let (alice_sig_pub, alice_sig_priv) = generate_signature_keypair();
+    let (dh_pub, dh_priv) = generate_dh_keypair();
+    let sending_chain_key = generate_sender_chainkey(generate_shared_rootkey(bob_identity_pub, dh_priv));
+    let encrypted_sender_key = encrypt_senderkey_message(sending_chain_key, &compose_sender_key(alice_chain_key, alice_sig_pub));
+
+    let descriptor = [vec![SENDER_KEY_DESCRIPTOR], encrypted_sender_key, dh_pub.to_vec(), b"bob".to_vec()].concat();
+    alice_socket.send(Message::Binary(descriptor)).await.unwrap();
+
+    // Server swaps the trailing target username for the sender's
+    let forwarded = expect_binary(&mut bob_socket).await;
+    assert_eq!(forwarded[0], SENDER_KEY_DESCRIPTOR);
+    assert_eq!(&forwarded[113..], b"alice");
+
+    let their_dh_pub: [u8;32] = forwarded[81..113].try_into().unwrap();
+    let receiving_chain_key = generate_receiver_chainkey(generate_shared_rootkey(their_dh_pub, bob.identity_priv));
+    let sender_key = decrypt_senderkey_message(derive_message_key(receiving_chain_key), &forwarded[1..81]);
+    assert_eq!(&sender_key[..32], &alice_chain_key);
+    assert_eq!(&sender_key[32..], &alice_sig_pub);
+
+    // Signed room message: server ack to sender, ciphertext to the rest of the room
+    let ciphertext = encrypt_room_message(alice_chain_key, "welcome bob");
+    let signature = sign_room_ciphertext(alice_sig_priv, &ciphertext);
+    alice_socket.send(Message::Text(serde_json::to_string(&RoomSenderMessage {
+        username: alice.username.clone(),
+        content: ciphertext + "." + &signature,
+        users: Vec::new(),
+        whisper_mode: WhisperMode::NONE,
+        is_join_leave_msg: false
+    }).unwrap())).await.unwrap();
+
+    assert_eq!(expect_binary(&mut alice_socket).await, vec![SERVER_ACK]);
+
+    let message: RoomReceiverMessage = serde_json::from_str(&expect_text(&mut bob_socket).await).unwrap();
+    let (ciphertext, signature) = message.content.split_once('.').unwrap();
+    assert!(verify_room_ciphertext(sender_key[32..].try_into().unwrap(), ciphertext, signature).is_ok());
+    assert_eq!(decrypt_room_message(derive_roommessage_key(sender_key[..32].try_into().unwrap()), ciphertext), "welcome bob");
+
+    // Whisper sealed for bob alone, the server only routes it
+    let (_, carol_priv) = generate_dh_keypair();
+    let whisper = encrypt_room_whisper(alice.identity_priv, &[("bob".to_string(), bob.identity_pub)], "just for you");
+    let signature = sign_room_ciphertext(alice_sig_priv, &whisper);
+    alice_socket.send(Message::Text(serde_json::to_string(&RoomSenderMessage {
+        username: alice.username.clone(),
+        content: whisper + "." + &signature,
+        users: vec!["bob".to_string()],
+        whisper_mode: WhisperMode::SHARE_WITH,
+        is_join_leave_msg: false
+    }).unwrap())).await.unwrap();
+
+    assert_eq!(expect_binary(&mut alice_socket).await, vec![SERVER_ACK]);
+
+    let message: RoomReceiverMessage = serde_json::from_str(&expect_text(&mut bob_socket).await).unwrap();
+    let (whisper, signature) = message.content.split_once('.').unwrap();
+    assert!(verify_room_ciphertext(alice_sig_pub, whisper, signature).is_ok());
+    assert!(is_room_whisper(whisper));
+    assert_eq!(decrypt_room_whisper(bob.identity_priv, "bob", alice.identity_pub, whisper).as_deref(), Some("just for you"));
+    // A member it was hidden from can't read the frame even if the server leaks it
+    assert_eq!(decrypt_room_whisper(carol_priv, "carol", alice.identity_pub, whisper), None);
+
+    // Whisper key-rotation informer reaches only the whisper recipients
+    alice_socket.send(Message::Text(serde_json::to_string(&RoomSenderMessage {
+        username: alice.username.clone(),
+        content: "  ".to_string(),
+        users: vec!["bob".to_string()],
+        whisper_mode: WhisperMode::SHARE_WITH,
+        is_join_leave_msg: false
+    }).unwrap())).await.unwrap();
+
+    assert_eq!(expect_binary(&mut alice_socket).await, vec![SERVER_ACK]);
+    assert_eq!(expect_binary(&mut bob_socket).await, [vec![ROTATE_CHAIN_KEY_INFORMER], b"alice".to_vec()].concat());
+
+    // Owner leaving closes the room for everyone
+    disconnect(alice_socket).await;
+    assert!(matches!(next_frame(&mut bob_socket).await, Message::Close(_)));
+}
+
+
+#[actix_web::test]
+async fn dropped_member_is_announced_so_the_room_rotates_sender_keys() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+    let carol = register_and_login(&endpoints, "carol").await;
+
+    let CreateRoomResponseResult::ROOM(room) = create_room(
+        alice.token.clone(),
+        "rustaceans".to_string(),
+        general_purpose::STANDARD.encode(alice.identity_pub),
+        false,
+        RoomSettings::default(),
+        endpoints.create_room
+    ).await else {
+        panic!("alice could not create the room");
+    };
+    let mut alice_socket = connect(endpoints.room_chat.to_string(), &room.room_token).await;
+
+    let mut member_sockets = Vec::new();
+    for member in [&bob, &carol] {
+        let JoinRoomResponseResult::ROOM(joined) = join_room(member.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await else {
+            panic!("{} could not join the room", member.username);
+        };
+        member_sockets.push((connect(endpoints.room_chat.to_string(), &joined.room_token).await, joined.room_token));
+    }
+    let (mut carol_socket, carol_room_token) = member_sockets.pop().unwrap();
+    let (bob_socket, _) = member_sockets.pop().unwrap();
+
+    // Bob drops without sending "bob left"
+    disconnect(bob_socket).await;
+
+    // The server announces it for him, after taking him off the room
+    for socket in [&mut alice_socket, &mut carol_socket] {
+        let leave: RoomReceiverMessage = serde_json::from_str(&expect_text(socket).await).unwrap();
+        assert_eq!(leave.username, "bob");
+        assert_eq!(leave.content, "bob left");
+        assert!(leave.is_join_leave_msg);
+    }
+    let room_data = get_room_data(carol_room_token, endpoints.get_room_data).await.unwrap();
+    assert!(!room_data.room_members.contains(&"bob".to_string()));
+
+    // Fresh sender keys go out over the 0x11 path, a descriptor still addressed to bob is dropped
+    let (alice_sig_pub, _) = generate_signature_keypair();
+    let new_chain_key = generate_roomchain_key();
+    for (target, target_identity_pub) in [("bob", bob.identity_pub), ("carol", carol.identity_pub)] {
+        let (dh_pub, dh_priv) = generate_dh_keypair();
+        let sending_chain_key = generate_sender_chainkey(generate_shared_rootkey(target_identity_pub, dh_priv));
+        let encrypted_sender_key = encrypt_senderkey_message(sending_chain_key, &compose_sender_key(new_chain_key, alice_sig_pub));
+        let descriptor = [vec![SENDER_KEY_DESCRIPTOR], encrypted_sender_key, dh_pub.to_vec(), target.as_bytes().to_vec()].concat();
+        alice_socket.send(Message::Binary(descriptor)).await.unwrap();
+    }
+
+    let forwarded = expect_binary(&mut carol_socket).await;
+    assert_eq!(&forwarded[113..], b"alice");
+    let their_dh_pub: [u8;32] = forwarded[81..113].try_into().unwrap();
+    let receiving_chain_key = generate_receiver_chainkey(generate_shared_rootkey(their_dh_pub, carol.identity_priv));
+    let sender_key = decrypt_senderkey_message(derive_message_key(receiving_chain_key), &forwarded[1..81]);
+    assert_eq!(&sender_key[..32], &new_chain_key);
+
+    disconnect(carol_socket).await;
+    disconnect(alice_socket).await;
+}
+
+
+#[actix_web::test]
+async fn persistent_room_survives_its_owner_and_changes_hands() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+
+    let CreateRoomResponseResult::ROOM(room) = create_room(
+        alice.token.clone(),
+        "lounge".to_string(),
+        general_purpose::STANDARD.encode(alice.identity_pub),
+        true,
+        RoomSettings::default(),
+        endpoints.create_room
+    ).await else {
+        panic!("alice could not create the room");
+    };
+    let mut alice_socket = connect(endpoints.room_chat.to_string(), &room.room_token).await;
+
+    let JoinRoomResponseResult::ROOM(bob_joined) = join_room(bob.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await else {
+        panic!("bob could not join the room");
+    };
+    let mut bob_socket = connect(endpoints.room_chat.to_string(), &bob_joined.room_token).await;
+
+    // Handing the room to someone who isn't in it is ignored, to bob it goes through for everyone
+    alice_socket.send(Message::Binary([vec![OWNERSHIP_TRANSFER], b"carol".to_vec()].concat())).await.unwrap();
+    alice_socket.send(Message::Binary([vec![OWNERSHIP_TRANSFER], b"bob".to_vec()].concat())).await.unwrap();
+    for socket in [&mut alice_socket, &mut bob_socket] {
+        assert_eq!(expect_binary(socket).await, [vec![OWNERSHIP_TRANSFER], b"bob".to_vec()].concat());
+    }
+
+    // Refused without an informer: bob handing it to himself, alice no longer owning it
+    bob_socket.send(Message::Binary([vec![OWNERSHIP_TRANSFER], b"bob".to_vec()].concat())).await.unwrap();
+    alice_socket.send(Message::Binary([vec![OWNERSHIP_TRANSFER], b"alice".to_vec()].concat())).await.unwrap();
+
+    // The old owner leaving no longer closes the room
+    disconnect(alice_socket).await;
+    let leave: RoomReceiverMessage = serde_json::from_str(&expect_text(&mut bob_socket).await).unwrap();
+    assert_eq!(leave.content, "alice left");
+
+    let room_data = get_room_data(bob_joined.room_token.clone(), endpoints.get_room_data).await.unwrap();
+    assert_eq!(room_data.room_owner, "bob");
+    assert!(room_data.persistent);
+    assert_eq!(room_data.room_members, vec!["bob".to_string()]);
+
+    // Neither does the new owner leaving, the room is persistent
+    disconnect(bob_socket).await;
+
+    // Alice comes back later with a fresh room token from /join_room
+    let JoinRoomResponseResult::ROOM(alice_rejoined) = join_room(alice.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await else {
+        panic!("alice could not rejoin the persistent room");
+    };
+    let alice_socket = connect(endpoints.room_chat.to_string(), &alice_rejoined.room_token).await;
+
+    let room_data = get_room_data(alice_rejoined.room_token, endpoints.get_room_data).await.unwrap();
+    assert_eq!(room_data.room_owner, "bob");
+    assert_eq!(room_data.room_members, vec!["alice".to_string()]);
+
+    disconnect(alice_socket).await;
+}
+
+
+fn moderation_frame(action: ModerationAction, payload: &str) -> Vec<u8> {
+    [vec![ROOM_MODERATION_BYTE, action.byte()], payload.as_bytes().to_vec()].concat()
+}
+
+async fn send_room_text(socket: &mut Socket, username: &str, content: &str) {
+    socket.send(Message::Text(serde_json::to_string(&RoomSenderMessage {
+        username: username.to_string(),
+        content: content.to_string(),
+        users: Vec::new(),
+        whisper_mode: WhisperMode::NONE,
+        is_join_leave_msg: false
+    }).unwrap())).await.unwrap();
+}
+
+#[actix_web::test]
+async fn room_moderation_is_enforced_by_the_server() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+    let carol = register_and_login(&endpoints, "carol").await;
+    let dave = register_and_login(&endpoints, "dave").await;
+
+    let CreateRoomResponseResult::ROOM(room) = create_room(
+        alice.token.clone(),
+        "moderated".to_string(),
+        general_purpose::STANDARD.encode(alice.identity_pub),
+        false,
+        RoomSettings::default(),
+        endpoints.create_room
+    ).await else {
+        panic!("alice could not create the room");
+    };
+    let mut alice_socket = connect(endpoints.room_chat.to_string(), &room.room_token).await;
+
+    let mut sockets = Vec::new();
+    for member in [&bob, &carol, &dave] {
+        let JoinRoomResponseResult::ROOM(joined) = join_room(member.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await else {
+            panic!("{} could not join the room", member.username);
+        };
+        sockets.push(connect(endpoints.room_chat.to_string(), &joined.room_token).await);
+    }
+    let mut dave_socket = sockets.pop().unwrap();
+    let mut carol_socket = sockets.pop().unwrap();
+    let mut bob_socket = sockets.pop().unwrap();
+
+    // A plain member can't kick, only the issuer hears about it
+    bob_socket.send(Message::Binary(moderation_frame(ModerationAction::KICK, "carol"))).await.unwrap();
+    assert_eq!(expect_binary(&mut bob_socket).await, vec![ROOM_MODERATION_BYTE, ModerationAction::REJECTED.byte(), ModerationAction::KICK.byte()]);
+
+    // Promoted by the owner, bob mutes carol
+    alice_socket.send(Message::Binary(moderation_frame(ModerationAction::PROMOTE, "bob"))).await.unwrap();
+    bob_socket.send(Message::Binary(moderation_frame(ModerationAction::MUTE, "carol"))).await.unwrap();
+    for socket in [&mut alice_socket, &mut bob_socket, &mut carol_socket, &mut dave_socket] {
+        assert_eq!(expect_binary(socket).await, moderation_frame(ModerationAction::PROMOTE, "bob"));
+        assert_eq!(expect_binary(socket).await, moderation_frame(ModerationAction::MUTE, "carol"));
+    }
+
+    // Carol's messages are dropped without an ack, the next thing dave hears is alice
+    send_room_text(&mut carol_socket, "carol", "can anyone hear me").await;
+    send_room_text(&mut alice_socket, "alice", "order please").await;
+    assert_eq!(expect_binary(&mut alice_socket).await, vec![SERVER_ACK]);
+    for socket in [&mut bob_socket, &mut carol_socket, &mut dave_socket] {
+        let message: RoomReceiverMessage = serde_json::from_str(&expect_text(socket).await).unwrap();
+        assert_eq!(message.username, "alice");
+    }
+
+    // Moderators can't touch the owner
+    bob_socket.send(Message::Binary(moderation_frame(ModerationAction::BAN, "alice"))).await.unwrap();
+    assert_eq!(expect_binary(&mut bob_socket).await, vec![ROOM_MODERATION_BYTE, ModerationAction::REJECTED.byte(), ModerationAction::BAN.byte()]);
+
+    // Banning carol closes her session, the room hears her leave and she can't come back
+    bob_socket.send(Message::Binary(moderation_frame(ModerationAction::BAN, "carol"))).await.unwrap();
+    assert_eq!(expect_binary(&mut carol_socket).await, moderation_frame(ModerationAction::BAN, "carol"));
+    assert!(matches!(next_frame(&mut carol_socket).await, Message::Close(_)));
+    for socket in [&mut alice_socket, &mut bob_socket, &mut dave_socket] {
+        assert_eq!(expect_binary(socket).await, moderation_frame(ModerationAction::BAN, "carol"));
+        let leave: RoomReceiverMessage = serde_json::from_str(&expect_text(socket).await).unwrap();
+        assert_eq!(leave.content, "carol left");
+    }
+    assert!(matches!(
+        join_room(carol.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::BANNED_FROM_ROOM
+    ));
+
+    // Rotating the key: the owner gets the new one, the old one stops letting people in
+    alice_socket.send(Message::Binary(moderation_frame(ModerationAction::ROTATE_KEY, ""))).await.unwrap();
+    let rotated = expect_binary(&mut alice_socket).await;
+    assert_eq!(rotated[..2], [ROOM_MODERATION_BYTE, ModerationAction::ROTATE_KEY.byte()]);
+    let new_key = String::from_utf8(rotated[2..].to_vec()).unwrap();
+    for socket in [&mut bob_socket, &mut dave_socket] {
+        assert_eq!(expect_binary(socket).await, moderation_frame(ModerationAction::ROTATE_KEY, ""));
+    }
+    assert!(matches!(
+        join_room(carol.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::INVALID_CREDENTIALS
+    ));
+
+    // Sessions already in the room carry on under the new key
+    send_room_text(&mut bob_socket, "bob", "still here").await;
+    assert_eq!(expect_binary(&mut bob_socket).await, vec![SERVER_ACK]);
+    let message: RoomReceiverMessage = serde_json::from_str(&expect_text(&mut alice_socket).await).unwrap();
+    assert_eq!(message.content, "still here");
+    let _ = expect_text(&mut dave_socket).await;
+
+    // Kicked dave can rejoin with the new key
+    alice_socket.send(Message::Binary(moderation_frame(ModerationAction::KICK, "dave"))).await.unwrap();
+    assert_eq!(expect_binary(&mut dave_socket).await, moderation_frame(ModerationAction::KICK, "dave"));
+    assert!(matches!(next_frame(&mut dave_socket).await, Message::Close(_)));
+    for socket in [&mut alice_socket, &mut bob_socket] {
+        assert_eq!(expect_binary(socket).await, moderation_frame(ModerationAction::KICK, "dave"));
+        let leave: RoomReceiverMessage = serde_json::from_str(&expect_text(socket).await).unwrap();
+        assert_eq!(leave.content, "dave left");
+    }
+    let JoinRoomResponseResult::ROOM(rejoined) = join_room(dave.token.clone(), room.room_name.clone(), new_key, endpoints.join_room).await else {
+        panic!("dave could not rejoin with the new key");
+    };
+    let dave_socket = connect(endpoints.room_chat.to_string(), &rejoined.room_token).await;
+
+    disconnect(dave_socket).await;
+    disconnect(bob_socket).await;
+    disconnect(alice_socket).await;
+}
+
+
+#[actix_web::test]
+async fn room_invites_are_owner_only_limited_and_revoked_by_key_rotation() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+    let carol = register_and_login(&endpoints, "carol").await;
+    let dave = register_and_login(&endpoints, "dave").await;
+    let eve = register_and_login(&endpoints, "eve").await;
+
+    let CreateRoomResponseResult::ROOM(room) = create_room(
+        alice.token.clone(),
+        "by-invite".to_string(),
+        general_purpose::STANDARD.encode(alice.identity_pub),
+        false,
+        RoomSettings::default(),
+        endpoints.create_room
+    ).await else {
+        panic!("alice could not create the room");
+    };
+
+    // Only the owner mints invites, and only with sane limits
+    assert!(matches!(
+        create_room_invite(carol.token.clone(), room.room_name.clone(), 1, 60, None, endpoints.create_room_invite).await,
+        CreateRoomInviteResponseResult::NOT_ROOM_OWNER
+    ));
+    assert!(matches!(
+        create_room_invite(alice.token.clone(), room.room_name.clone(), 0, 60, None, endpoints.create_room_invite).await,
+        CreateRoomInviteResponseResult::INVALID_INVITE
+    ));
+
+    // Two uses let in two people, the invite names the room so it can be left empty
+    let CreateRoomInviteResponseResult::INVITE(invite) = create_room_invite(alice.token.clone(), room.room_name.clone(), 2, 60, None, endpoints.create_room_invite).await else {
+        panic!("alice could not create an invite");
+    };
+    for member in [&carol, &dave] {
+        let JoinRoomResponseResult::ROOM(joined) = join_room(member.token.clone(), "".to_string(), invite.invite_token.clone(), endpoints.join_room).await else {
+            panic!("{} could not join with the invite", member.username);
+        };
+        assert_eq!(joined.room_name, "by-invite");
+    }
+    assert!(matches!(
+        join_room(eve.token.clone(), "".to_string(), invite.invite_token.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::INVALID_INVITE
+    ));
+
+    // Invites go out as notifications to DM contacts only
+    assert!(matches!(
+        create_room_invite(alice.token.clone(), room.room_name.clone(), 1, 60, Some(bob.username.clone()), endpoints.create_room_invite).await,
+        CreateRoomInviteResponseResult::USER_NOT_FOUND
+    ));
+    add_user(alice.token.clone(), bob.username.clone(), "hi bob".to_string(), endpoints.add_user).await;
+    accept_user(bob.token.clone(), alice.username.clone(), AcceptanceStatus::ACCEPTED, None, endpoints.accept_user).await;
+    queued_notifications(&endpoints, &bob).await;
+
+    let CreateRoomInviteResponseResult::INVITE(sent) = create_room_invite(alice.token.clone(), room.room_name.clone(), 1, 60, Some(bob.username.clone()), endpoints.create_room_invite).await else {
+        panic!("alice could not send bob an invite");
+    };
+    let bob_queue = queued_notifications(&endpoints, &bob).await;
+    let invites = &bob_queue["NOTIFICATIONS<alice,bob>"];
+    assert_eq!(invites[0]["n_type"], "ROOM_INVITE");
+    let content = RoomInviteNotificationContent::parse(invites[0]["content"].as_str().unwrap()).unwrap();
+    assert_eq!(content.room_name, "by-invite");
+    assert_eq!(content.invite_token, sent.invite_token);
+    assert!(matches!(
+        join_room(bob.token.clone(), content.room_name, content.invite_token, endpoints.join_room).await,
+        JoinRoomResponseResult::ROOM(_)
+    ));
+
+    // Rotating the room key revokes invites minted under the old one
+    let CreateRoomInviteResponseResult::INVITE(stale) = create_room_invite(alice.token.clone(), room.room_name.clone(), 5, 60, None, endpoints.create_room_invite).await else {
+        panic!("alice could not create an invite");
+    };
+    let mut alice_socket = connect(endpoints.room_chat.to_string(), &room.room_token).await;
+    alice_socket.send(Message::Binary(moderation_frame(ModerationAction::ROTATE_KEY, ""))).await.unwrap();
+    assert_eq!(expect_binary(&mut alice_socket).await[..2], [ROOM_MODERATION_BYTE, ModerationAction::ROTATE_KEY.byte()]);
+    assert!(matches!(
+        join_room(eve.token.clone(), "".to_string(), stale.invite_token, endpoints.join_room).await,
+        JoinRoomResponseResult::INVALID_INVITE
+    ));
+
+    disconnect(alice_socket).await;
+}
+
+
+#[actix_web::test]
+async fn room_settings_cap_members_and_pace_messages() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+    let carol = register_and_login(&endpoints, "carol").await;
+    let dave = register_and_login(&endpoints, "dave").await;
+
+    let CreateRoomResponseResult::ROOM(room) = create_room(
+        alice.token.clone(),
+        "capped".to_string(),
+        general_purpose::STANDARD.encode(alice.identity_pub),
+        false,
+        RoomSettings { max_members: 3, whispers_allowed: false, slow_mode_seconds: 60, ..RoomSettings::default() },
+        endpoints.create_room
+    ).await else {
+        panic!("alice could not create the room");
+    };
+    let mut alice_socket = connect(endpoints.room_chat.to_string(), &room.room_token).await;
+
+    // The owner and two connected members fill the room
+    let JoinRoomResponseResult::ROOM(bob_joined) = join_room(bob.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await else {
+        panic!("bob could not join the room");
+    };
+    let mut bob_socket = connect(endpoints.room_chat.to_string(), &bob_joined.room_token).await;
+    let JoinRoomResponseResult::ROOM(carol_joined) = join_room(carol.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await else {
+        panic!("carol could not join the room");
+    };
+    let carol_socket = connect(endpoints.room_chat.to_string(), &carol_joined.room_token).await;
+    assert!(matches!(
+        join_room(dave.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::ROOM_FULL
+    ));
+
+    // Whispers are dropped without an ack, plain messages go through once per slow mode interval
+    bob_socket.send(Message::Text(serde_json::to_string(&RoomSenderMessage {
+        username: "bob".to_string(),
+        content: "just between us".to_string(),
+        users: vec!["alice".to_string()],
+        whisper_mode: WhisperMode::SHARE_WITH,
+        is_join_leave_msg: false
+    }).unwrap())).await.unwrap();
+    send_room_text(&mut bob_socket, "bob", "first").await;
+    assert_eq!(expect_binary(&mut bob_socket).await, vec![SERVER_ACK]);
+    send_room_text(&mut bob_socket, "bob", "too soon").await;
+
+    let message: RoomReceiverMessage = serde_json::from_str(&expect_text(&mut alice_socket).await).unwrap();
+    assert_eq!(message.content, "first");
+
+    // Had "too soon" been acked, bob would see that before alice's message
+    send_room_text(&mut alice_socket, "alice", "slow down").await;
+    assert_eq!(expect_binary(&mut alice_socket).await, vec![SERVER_ACK]);
+    let message: RoomReceiverMessage = serde_json::from_str(&expect_text(&mut bob_socket).await).unwrap();
+    assert_eq!(message.content, "slow down");
+
+    // The cap counts who is connected, a seat frees up once someone leaves
+    disconnect(carol_socket).await;
+    assert!(matches!(
+        join_room(dave.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::ROOM(_)
+    ));
+
+    // Rooms needing approval turn the room key into a knock, returning members and invites walk in
+    let CreateRoomResponseResult::ROOM(gated) = create_room(
+        carol.token.clone(),
+        "gated".to_string(),
+        general_purpose::STANDARD.encode(carol.identity_pub),
+        true,
+        RoomSettings { join_approval: true, ..RoomSettings::default() },
+        endpoints.create_room
+    ).await else {
+        panic!("carol could not create the room");
+    };
+    assert!(matches!(
+        join_room(dave.token.clone(), gated.room_name.clone(), gated.room_key.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::JOIN_REQUEST_SENT
+    ));
+    let CreateRoomInviteResponseResult::INVITE(invite) = create_room_invite(carol.token.clone(), gated.room_name.clone(), 1, 60, None, endpoints.create_room_invite).await else {
+        panic!("carol could not create an invite");
+    };
+    assert!(matches!(
+        join_room(dave.token.clone(), "".to_string(), invite.invite_token, endpoints.join_room).await,
+        JoinRoomResponseResult::ROOM(_)
+    ));
+    assert!(matches!(
+        join_room(carol.token.clone(), gated.room_name.clone(), gated.room_key.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::ROOM(_)
+    ));
+
+    disconnect(bob_socket).await;
+    disconnect(alice_socket).await;
+}
+
+
+#[actix_web::test]
+async fn knocking_waits_for_the_owner_to_approve_or_deny() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+    let carol = register_and_login(&endpoints, "carol").await;
+    let dave = register_and_login(&endpoints, "dave").await;
+
+    let CreateRoomResponseResult::ROOM(room) = create_room(
+        alice.token.clone(),
+        "knock-knock".to_string(),
+        general_purpose::STANDARD.encode(alice.identity_pub),
+        true,
+        RoomSettings { join_approval: true, ..RoomSettings::default() },
+        endpoints.create_room
+    ).await else {
+        panic!("alice could not create the room");
+    };
+
+    // The room key only knocks, knocking twice doesn't notify the owner twice
+    for _ in 0..2 {
+        assert!(matches!(
+            join_room(bob.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await,
+            JoinRoomResponseResult::JOIN_REQUEST_SENT
+        ));
+    }
+    assert!(matches!(
+        join_room(carol.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::JOIN_REQUEST_SENT
+    ));
+    let alice_queue = queued_notifications(&endpoints, &alice).await;
+    let knocks = &alice_queue["NOTIFICATIONS<bob,alice>"];
+    assert_eq!(knocks.len(), 1);
+    assert_eq!(knocks[0]["n_type"], "ROOM_JOIN_REQUEST");
+    assert_eq!(knocks[0]["content"], "knock-knock");
+    assert_eq!(alice_queue["NOTIFICATIONS<carol,alice>"][0]["n_type"], "ROOM_JOIN_REQUEST");
+
+    // Only the owner answers, and only requests that were made
+    assert!(matches!(
+        respond_join_request(carol.token.clone(), room.room_name.clone(), bob.username.clone(), AcceptanceStatus::ACCEPTED, endpoints.respond_join_request).await,
+        RespondJoinRequestResponseResult::NOT_ROOM_OWNER
+    ));
+    assert!(matches!(
+        respond_join_request(alice.token.clone(), room.room_name.clone(), "mallory".to_string(), AcceptanceStatus::ACCEPTED, endpoints.respond_join_request).await,
+        RespondJoinRequestResponseResult::REQUEST_NOT_FOUND
+    ));
+
+    // Approval hands bob a room token in a notification
+    assert!(matches!(
+        respond_join_request(alice.token.clone(), room.room_name.clone(), bob.username.clone(), AcceptanceStatus::ACCEPTED, endpoints.respond_join_request).await,
+        RespondJoinRequestResponseResult::JOIN_REQUEST_APPROVED
+    ));
+    let bob_queue = queued_notifications(&endpoints, &bob).await;
+    let approvals = &bob_queue["NOTIFICATIONS<alice,bob>"];
+    assert_eq!(approvals[0]["n_type"], "ROOM_JOIN_APPROVED");
+    let approved = RoomJoinApprovedNotificationContent::parse(approvals[0]["content"].as_str().unwrap()).unwrap();
+    assert_eq!(approved.room_name, "knock-knock");
+
+    let mut alice_socket = connect(endpoints.room_chat.to_string(), &room.room_token).await;
+    let mut bob_socket = connect(endpoints.room_chat.to_string(), &approved.room_token).await;
+
+    // Now a member, bob comes back with the room key alone
+    assert!(matches!(
+        join_room(bob.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::ROOM(_)
+    ));
+
+    // Denied knocks are dropped, carol can't be approved afterwards
+    assert!(matches!(
+        respond_join_request(alice.token.clone(), room.room_name.clone(), carol.username.clone(), AcceptanceStatus::IGNORED, endpoints.respond_join_request).await,
+        RespondJoinRequestResponseResult::JOIN_REQUEST_DENIED
+    ));
+    assert!(matches!(
+        respond_join_request(alice.token.clone(), room.room_name.clone(), carol.username.clone(), AcceptanceStatus::ACCEPTED, endpoints.respond_join_request).await,
+        RespondJoinRequestResponseResult::REQUEST_NOT_FOUND
+    ));
+    assert!(queued_notifications(&endpoints, &carol).await.is_empty());
+
+    // Banning a knocker drops the knock, there is nothing left to approve
+    assert!(matches!(
+        join_room(dave.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::JOIN_REQUEST_SENT
+    ));
+    alice_socket.send(Message::Binary(moderation_frame(ModerationAction::BAN, "dave"))).await.unwrap();
+    for socket in [&mut alice_socket, &mut bob_socket] {
+        assert_eq!(expect_binary(socket).await, moderation_frame(ModerationAction::BAN, "dave"));
+    }
+    assert!(matches!(
+        respond_join_request(alice.token.clone(), room.room_name.clone(), dave.username.clone(), AcceptanceStatus::ACCEPTED, endpoints.respond_join_request).await,
+        RespondJoinRequestResponseResult::REQUEST_NOT_FOUND
+    ));
+    assert!(matches!(
+        join_room(dave.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await,
+        JoinRoomResponseResult::BANNED_FROM_ROOM
+    ));
+
+    disconnect(bob_socket).await;
+    disconnect(alice_socket).await;
+}
+
+
+#[actix_web::test]
+async fn history_reaches_the_newcomer_only_in_rooms_sharing_it() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+    let carol = register_and_login(&endpoints, "carol").await;
+
+    let history = vec![RoomHistoryMessage { username: "alice".to_string(), content: "before you came".to_string(), time: "12:00".to_string() }];
+    let (alice_sigpub, alice_sigpriv) = generate_signature_keypair();
+
+    for (room_name, share_history_count) in [("shared", 10), ("private", 0)] {
+        let CreateRoomResponseResult::ROOM(room) = create_room(
+            alice.token.clone(),
+            room_name.to_string(),
+            general_purpose::STANDARD.encode(alice.identity_pub),
+            false,
+            RoomSettings { share_history_count, ..RoomSettings::default() },
+            endpoints.create_room
+        ).await else {
+            panic!("alice could not create the room");
+        };
+        let mut alice_socket = connect(endpoints.room_chat.to_string(), &room.room_token).await;
+        let mut joined_sockets = Vec::new();
+        for member in [&bob, &carol] {
+            let JoinRoomResponseResult::ROOM(joined) = join_room(member.token.clone(), room.room_name.clone(), room.room_key.clone(), endpoints.join_room).await else {
+                panic!("{} could not join the room", member.username);
+            };
+            joined_sockets.push(connect(endpoints.room_chat.to_string(), &joined.room_token).await);
+        }
+        let mut carol_socket = joined_sockets.pop().unwrap();
+        let mut bob_socket = joined_sockets.pop().unwrap();
+
+        // Sealed for bob, signed by alice and routed to bob alone, under alice's name
+        let sealed = seal_room_history(bob.identity_pub, alice_sigpriv, &history);
+        alice_socket.send(Message::Binary(compose_room_history_frame("bob", sealed))).await.unwrap();
+        send_room_text(&mut alice_socket, "alice", "hello").await;
+        assert_eq!(expect_binary(&mut alice_socket).await, vec![SERVER_ACK]);
+
+        if share_history_count > 0 {
+            let (shared_by, sealed) = parse_room_history_frame(&expect_binary(&mut bob_socket).await).unwrap();
+            assert_eq!(shared_by, "alice");
+            assert_eq!(open_room_history(bob.identity_priv, alice_sigpub, &sealed), Some(history.clone()));
+        }
+        let message: RoomReceiverMessage = serde_json::from_str(&expect_text(&mut bob_socket).await).unwrap();
+        assert_eq!(message.content, "hello");
+        let message: RoomReceiverMessage = serde_json::from_str(&expect_text(&mut carol_socket).await).unwrap();
+        assert_eq!(message.content, "hello");
+
+        disconnect(carol_socket).await;
+        disconnect(bob_socket).await;
+        disconnect(alice_socket).await;
+    }
+}
+
+
+#[actix_web::test]
+async fn listed_rooms_show_up_in_the_directory_and_open_without_a_key() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+
+    for (room_name, listed, join_approval) in [("lounge", true, false), ("hideout", false, false), ("porch", true, true)] {
+        let CreateRoomResponseResult::ROOM(_) = create_room(
+            alice.token.clone(),
+            room_name.to_string(),
+            general_purpose::STANDARD.encode(alice.identity_pub),
+            true,
+            RoomSettings { listed, join_approval, topic: format!("  all about the {}  ", room_name), ..RoomSettings::default() },
+            endpoints.create_room
+        ).await else {
+            panic!("alice could not create {}", room_name);
+        };
+    }
+
+    // Unlisted rooms stay out of the directory, topics come back trimmed
+    let RoomDirectoryResponseResult::ROOM_DIRECTORY_FETCHED(rooms, has_more) = get_room_directory(bob.token.clone(), 0, endpoints.room_directory).await else {
+        panic!("bob could not fetch the directory");
+    };
+    assert!(!has_more);
+    let names: Vec<&str> = rooms.iter().map(|room| room.room_name.as_str()).collect();
+    assert_eq!(names, vec!["lounge", "porch"]);
+    assert_eq!(rooms[0].topic, "all about the lounge");
+    assert!(rooms[1].join_approval);
+
+    let RoomDirectoryResponseResult::ROOM_DIRECTORY_FETCHED(rooms, _) = get_room_directory(bob.token.clone(), 1, endpoints.room_directory).await else {
+        panic!("bob could not fetch the second page");
+    };
+    assert!(rooms.is_empty());
+
+    // Listed rooms open without the room key, approval still turns it into a knock
+    let JoinRoomResponseResult::ROOM(lounge) = join_room(bob.token.clone(), "lounge".to_string(), "".to_string(), endpoints.join_room).await else {
+        panic!("bob could not open the lounge");
+    };
+
+    // The directory counts who is in the room now, not everyone who ever joined it
+    let lounge_members = |rooms: Vec<ListedRoom>| rooms.into_iter().find(|room| room.room_name=="lounge").unwrap().member_count;
+    let RoomDirectoryResponseResult::ROOM_DIRECTORY_FETCHED(rooms, _) = get_room_directory(bob.token.clone(), 0, endpoints.room_directory).await else {
+        panic!("bob could not fetch the directory");
+    };
+    assert_eq!(lounge_members(rooms), 0);
+    let bob_socket = connect(endpoints.room_chat.to_string(), &lounge.room_token).await;
+    let RoomDirectoryResponseResult::ROOM_DIRECTORY_FETCHED(rooms, _) = get_room_directory(bob.token.clone(), 0, endpoints.room_directory).await else {
+        panic!("bob could not fetch the directory");
+    };
+    assert_eq!(lounge_members(rooms), 1);
+    disconnect(bob_socket).await;
+
+    assert!(matches!(
+        join_room(bob.token.clone(), "porch".to_string(), "".to_string(), endpoints.join_room).await,
+        JoinRoomResponseResult::JOIN_REQUEST_SENT
+    ));
+    assert!(!matches!(
+        join_room(bob.token.clone(), "hideout".to_string(), "".to_string(), endpoints.join_room).await,
+        JoinRoomResponseResult::ROOM(_)
+    ));
+}
+
+
+// Logs an existing user in from another device, returning its access and refresh tokens and whether it holds the account key
+async fn login_device(endpoints: &Endpoints, username: &str, device_id: &str, identity_pub: [u8;32]) -> (String, String, bool) {
+    let logged_in = login_account(device_login_payload(username, device_id, identity_pub), endpoints.login).await.unwrap();
+    assert!(matches!(logged_in.status_type, StatusTypes::LOG_IN_SUCCESSFUL));
+
+    (logged_in.access_token, logged_in.refresh_token, logged_in.account_device)
+}
+
+fn device_login_payload(username: &str, device_id: &str, identity_pub: [u8;32]) -> UserLoginPayload {
+    UserLoginPayload {
+        username: username.to_string(),
+        password: "correct horse battery staple".to_string(),
+        device_id: device_id.to_string(),
+        device_name: format!("{} test device", device_id),
+        public_identity_key: general_purpose::STANDARD.encode(identity_pub)
+    }
+}
+
+
+#[actix_web::test]
+async fn each_linked_device_gets_its_own_ciphertext_until_revoked() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+
+    let added = add_user(alice.token.clone(), bob.username.clone(), "hey bob".to_string(), endpoints.add_user).await;
+    assert!(matches!(added, AddUserResponseResult::ADD_REQUEST_SENT));
+    let accepted = accept_user(bob.token.clone(), alice.username.clone(), AcceptanceStatus::ACCEPTED, None, endpoints.accept_user).await;
+    assert!(matches!(accepted, AcceptUserResponseResult::USER_ADDED));
+
+    // Bob's laptop holds the account key, his phone brings a key of its own
+    let (laptop_token, _, laptop_is_account_device) = login_device(&endpoints, "bob", "laptop", bob.identity_pub).await;
+    assert!(laptop_is_account_device);
+    let (phone_pub, phone_priv) = generate_dh_keypair();
+    let (phone_token, phone_refresh_token, phone_is_account_device) = login_device(&endpoints, "bob", "phone", phone_pub).await;
+    assert!(!phone_is_account_device);
+
+    // Usernames can't be mistaken for a device mailbox
+    let rejected = register_account(UserRegisterPayload {
+        username: "bob#phone".to_string(),
+        password: "correct horse battery staple".to_string(),
+        public_identity_key: general_purpose::STANDARD.encode(phone_pub),
+        device_id: String::new(),
+        device_name: String::new()
+    }, endpoints.register).await.unwrap();
+    assert!(matches!(rejected.status_type, StatusTypes::INVALID_USERNAME));
+
+    // Alice sees both of bob's devices with their keys
+    let GetDmsResponseResult::DMS_DATA_FETCHED(alice_dms) = get_dms(alice.token.clone(), endpoints.get_dms_data).await else {
+        panic!("alice could not fetch her DMs list");
+    };
+    let bob_contact = alice_dms[0].clone();
+    assert_eq!(bob_contact.devices.len(), 2);
+    let slots: Vec<String> = bob_contact.devices.iter().map(|device| bob_contact.ratchet_slot(device)).collect();
+    assert!(slots.contains(&"bob".to_string()) && slots.contains(&"bob#phone".to_string()));
+
+    // Alice's account slot is there from accepting, the phone's ratchet starts from its own key
+    let mut alice_keys = DmE2EEncryption_Data { dms: HashMap::from([("bob".to_string(), DoubleRatchet::new(bob.identity_pub))]) };
+    let mut laptop_keys = DoubleRatchet::new(alice.identity_pub);
+    let mut phone_keys = DoubleRatchet::new(alice.identity_pub);
+
+    let mut alice_socket = connect(format!("{}?target=bob", endpoints.dm_chat), &alice.token).await;
+    let mut laptop_socket = connect(format!("{}?target=alice", endpoints.dm_chat), &laptop_token).await;
+
+    let (content, device_contents) = alice_keys.encrypt_for_devices(&bob_contact, "hi bob, on every device");
+    assert_eq!(device_contents.len(), 2);
+    alice_socket.send(Message::Text(serde_json::to_string(&DmMessage {
+        username: alice.username.clone(),
+        content,
+        is_online_offline_msg: false,
+        device_contents,
+        device_id: String::new()
+    }).unwrap())).await.unwrap();
+    assert_eq!(expect_binary(&mut alice_socket).await, vec![SERVER_ACK]);
+
+    // The laptop in the DM gets only its own ciphertext
+    let received: DmMessage = serde_json::from_str(&expect_text(&mut laptop_socket).await).unwrap();
+    assert!(received.device_contents.is_empty());
+    let envelope = DmEnvelope::parse(&received.content).unwrap();
+    assert_eq!(laptop_keys.decrypt(&envelope, bob.identity_priv).as_deref(), Some("hi bob, on every device"));
+
+    // The phone, away from the DM, finds its ciphertext in its own mailbox
+    let phone_user = TestUser { username: "bob".to_string(), token: phone_token.clone(), identity_pub: phone_pub, identity_priv: phone_priv };
+    let phone_queue = queued_notifications(&endpoints, &phone_user).await;
+    let messages = &phone_queue["NOTIFICATIONS<alice,bob#phone>"];
+    assert_eq!(messages.len(), 1);
+    let queued: DmMessage = serde_json::from_str(messages[0]["content"].as_str().unwrap()).unwrap();
+    let envelope = DmEnvelope::parse(&queued.content).unwrap();
+    assert_eq!(phone_keys.decrypt(&envelope, phone_priv).as_deref(), Some("hi bob, on every device"));
+
+    // The laptop lists both devices and revokes the phone, but not itself
+    let DevicesResponseResult::DEVICES_FETCHED(devices, current_device_id) = get_devices(laptop_token.clone(), endpoints.devices).await else {
+        panic!("bob could not fetch his devices");
+    };
+    assert_eq!(devices.len(), 2);
+    assert_eq!(current_device_id, "laptop");
+
+    let revoked_self = revoke_device(laptop_token.clone(), "laptop".to_string(), endpoints.revoke_device).await;
+    assert!(matches!(revoked_self, RevokeDeviceResponseResult::CANNOT_REVOKE_CURRENT_DEVICE));
+    let revoked = revoke_device(laptop_token.clone(), "phone".to_string(), endpoints.revoke_device).await;
+    assert!(matches!(revoked, RevokeDeviceResponseResult::DEVICE_REVOKED));
+
+    // The revoked phone gets no new tokens, and its current one no longer opens DMs
+    let refreshed = request_new_token(phone_refresh_token, endpoints.new_token).await.unwrap();
+    assert!(matches!(refreshed.status_type, StatusTypes::DEVICE_REVOKED));
+    let request = ClientRequestBuilder::new(format!("{}?target=alice", endpoints.dm_chat).parse().unwrap()).with_header("Authorization", phone_token.clone());
+    assert!(connect_async(request).await.is_err());
+
+    // Every other route turns it away too, the chat backup included
+    let authenticated = authenticate_user(phone_token.clone(), endpoints.authN).await.unwrap();
+    assert_eq!(authenticated.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let backup = reqwest::Client::new().get(endpoints.get_dm_chats_data).header("Authorization", phone_token).send().await.unwrap();
+    assert_eq!(backup.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // Signing in with the password again doesn't bring the revoked device back
+    let relogin = login_account(device_login_payload("bob", "phone", phone_pub), endpoints.login).await.unwrap();
+    assert!(matches!(relogin.status_type, StatusTypes::DEVICE_REVOKED));
+
+    disconnect(alice_socket).await;
+    disconnect(laptop_socket).await;
+}
+
+
+#[actix_web::test]
+async fn a_link_code_hands_sealed_keys_to_one_new_device_of_the_same_account() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let eve = register_and_login(&endpoints, "eve").await;
+
+    let (laptop_token, _, _) = login_device(&endpoints, "alice", "laptop", alice.identity_pub).await;
+    let (phone_pub, _) = generate_dh_keypair();
+    let (phone_token, _, _) = login_device(&endpoints, "alice", "phone", phone_pub).await;
+
+    // The laptop seals the account key and the chat backup session keys under a fresh code
+    let account_private_key = general_purpose::STANDARD.encode(alice.identity_priv);
+    let bundle = DeviceLinkBundle {
+        account_identity_keys: UserIdentityKeys {
+            public_identity_key: general_purpose::STANDARD.encode(alice.identity_pub),
+            private_identity_key: account_private_key.clone()
+        },
+        dms_session_keys: HashMap::from([("bob".to_string(), DmSessionEncryption_Key { key: [9u8;32], timestamp: "2026-01-01 00:00:00+0000".to_string(), nonce: [4u8;12] })]),
+        dms_list: Vec::new(),
+        verified_contacts: VerifiedContacts::default()
+    };
+    let link_code = generate_link_code();
+    let (link_id, secret) = parse_link_code(&link_code).unwrap();
+    let sealed_bundle = seal_link_bundle("alice", &link_id, &secret, &bundle);
+    assert!(!sealed_bundle.contains(&account_private_key));
+
+    let created = create_device_link(laptop_token.clone(), link_id.clone(), sealed_bundle.clone(), endpoints.create_device_link).await;
+    assert!(matches!(created, CreateDeviceLinkResponseResult::DEVICE_LINK_CREATED(_)));
+
+    // Links are looked up under the claimer's account, eve gets nothing (and doesn't use it up)
+    let stolen = claim_device_link(eve.token.clone(), link_id.clone(), endpoints.claim_device_link).await;
+    assert!(matches!(stolen, ClaimDeviceLinkResponseResult::INVALID_DEVICE_LINK));
+
+    // The phone gets back exactly the ciphertext, only the code opens it
+    let ClaimDeviceLinkResponseResult::DEVICE_LINK_CLAIMED(claimed_bundle) = claim_device_link(phone_token.clone(), link_id.clone(), endpoints.claim_device_link).await else {
+        panic!("the phone could not claim the link");
+    };
+    assert_eq!(claimed_bundle, sealed_bundle);
+    let opened = open_link_bundle("alice", &link_id, &secret, &claimed_bundle).unwrap();
+    assert_eq!(opened.account_identity_keys.private_identity_key, account_private_key);
+    assert_eq!(opened.dms_session_keys["bob"].key, [9u8;32]);
+
+    // One use only
+    let reused = claim_device_link(phone_token.clone(), link_id.clone(), endpoints.claim_device_link).await;
+    assert!(matches!(reused, ClaimDeviceLinkResponseResult::INVALID_DEVICE_LINK));
+
+    // Empty bundles aren't links
+    let empty = create_device_link(laptop_token, link_id, "".to_string(), endpoints.create_device_link).await;
+    assert!(matches!(empty, CreateDeviceLinkResponseResult::INVALID_DEVICE_LINK));
+}
+
+
+#[actix_web::test]
+async fn a_rotated_identity_key_is_signed_by_the_old_one_and_announced_to_contacts() {
+    let endpoints = spawn_server().await;
+
+    let alice = register_and_login(&endpoints, "alice").await;
+    let bob = register_and_login(&endpoints, "bob").await;
+
+    let added = add_user(alice.token.clone(), bob.username.clone(), "hey bob".to_string(), endpoints.add_user).await;
+    assert!(matches!(added, AddUserResponseResult::ADD_REQUEST_SENT));
+    let accepted = accept_user(bob.token.clone(), alice.username.clone(), AcceptanceStatus::ACCEPTED, None, endpoints.accept_user).await;
+    assert!(matches!(accepted, AcceptUserResponseResult::USER_ADDED));
+    queued_notifications(&endpoints, &bob).await;
+
+    let encode = |key: [u8;32]| general_purpose::STANDARD.encode(key);
+    let (new_pub, _) = generate_dh_keypair();
+    let signature = general_purpose::STANDARD.encode(sign_identity_rotation(alice.identity_priv, new_pub));
+
+    // A key alice's old one didn't sign for is turned away
+    let (forged_pub, _) = generate_dh_keypair();
+    let forged = rotate_identity_key(alice.token.clone(), encode(alice.identity_pub), encode(forged_pub), signature.clone(), endpoints.rotate_identity_key).await;
+    assert!(matches!(forged, RotateIdentityKeyResponseResult::INVALID_IDENTITY_KEY));
+
+    // A device with a key of its own can't hand the account over
+    let (phone_pub, _) = generate_dh_keypair();
+    let (phone_token, _, _) = login_device(&endpoints, "alice", "phone", phone_pub).await;
+    let from_phone = rotate_identity_key(phone_token, encode(alice.identity_pub), encode(new_pub), signature.clone(), endpoints.rotate_identity_key).await;
+    assert!(matches!(from_phone, RotateIdentityKeyResponseResult::NOT_ACCOUNT_DEVICE));
+
+    let rotated = rotate_identity_key(alice.token.clone(), encode(alice.identity_pub), encode(new_pub), signature.clone(), endpoints.rotate_identity_key).await;
+    assert!(matches!(rotated, RotateIdentityKeyResponseResult::IDENTITY_KEY_ROTATED(1)));
+
+    // Bob is told, and checks the signature himself
+    let bob_queue = queued_notifications(&endpoints, &bob).await;
+    let rotation_notification = bob_queue["NOTIFICATIONS<alice,bob>"].iter()
+        .find(|notification| notification["n_type"]=="IDENTITY_ROTATED")
+        .expect("bob wasn't told about the new key");
+    let rotation = IdentityRotatedNotificationContent::parse(rotation_notification["content"].as_str().unwrap()).unwrap();
+    assert_eq!(rotation.old_public_identity_key, encode(alice.identity_pub));
+    assert_eq!(rotation.new_public_identity_key, encode(new_pub));
+    let signature_bytes = general_purpose::STANDARD.decode(&rotation.signature).unwrap();
+    assert!(verify_identity_rotation(alice.identity_pub, new_pub, &signature_bytes));
+    assert!(!verify_identity_rotation(alice.identity_pub, forged_pub, &signature_bytes));
+
+    // The DMs list hands out the new key from now on
+    let GetDmsResponseResult::DMS_DATA_FETCHED(bob_dms) = get_dms(bob.token.clone(), endpoints.get_dms_data).await else {
+        panic!("bob could not fetch his DMs list");
+    };
+    assert_eq!(bob_dms[0].public_identity_key, encode(new_pub));
+
+    // The old signature can't be replayed once the account moved on
+    let replayed = rotate_identity_key(alice.token.clone(), encode(alice.identity_pub), encode(new_pub), signature, endpoints.rotate_identity_key).await;
+    assert!(matches!(replayed, RotateIdentityKeyResponseResult::INVALID_IDENTITY_KEY));
+}